@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// Builds a 10k element list from chunks of 100 fresh pairs, joining each
+/// chunk onto the end with the procedure `join`.
+const BUILD: &str = "
+(define (chunk start)
+  (do ((i 99 (- i 1)) (acc '() (cons (+ start i) acc))) ((< i 0) acc)))
+(define (build join)
+  (do ((n 0 (+ n 100)) (acc '() (join acc (chunk n)))) ((= n 10000) acc)))
+";
+
+fn append_benchmark(c: &mut Criterion) {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(BUILD, true).unwrap();
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap();
+
+    // `append` copies the list built so far on every join, while `append!`
+    // only walks it to the last pair.
+    for join in ["append", "append!"] {
+        let expr = scheme_engine::parse(&format!("(length (build {join}))"), true).unwrap();
+        let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+        assert_eq!(
+            scheme_engine::eval(closure.clone()).unwrap(),
+            scheme_engine::Expr::Number(10_000.0)
+        );
+
+        c.bench_function(&format!("build 10k list in chunks with {join}"), |b| {
+            b.iter(|| scheme_engine::eval(closure.clone()).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, append_benchmark);
+criterion_main!(benches);
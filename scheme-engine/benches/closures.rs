@@ -0,0 +1,27 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use scheme_engine::Expr;
+
+fn closures_benchmark(c: &mut Criterion) {
+    let source = r"
+    (define (make-adder n) (lambda (x) (+ x n)))
+    (define (sum-adders count)
+      (do ((i 0 (+ i 1))
+           (total 0 ((make-adder i) total)))
+          ((= i count) total)))
+    ";
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(source, true).unwrap();
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap();
+    let sum_adders = match env.borrow().lookup_var("sum-adders") {
+        Some(Expr::Closure(closure)) => closure.clone(),
+        other => panic!("expected sum-adders closure, found {other:?}"),
+    };
+
+    let args: Vec<Expr> = vec![Expr::Number(1000.0)];
+    c.bench_function("create and call 1000 closures", |b| {
+        b.iter(|| scheme_engine::call(black_box(sum_adders.clone()), black_box(&args)))
+    });
+}
+
+criterion_group!(benches, closures_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// Generate a program with the given number of top-level definitions,
+/// each referring to the previous one.
+fn generate_definitions(count: usize) -> String {
+    let mut source = String::from("(define v0 0)\n");
+    for index in 1..count {
+        source.push_str(&format!("(define v{index} (+ v{} 1))\n", index - 1));
+    }
+    source
+}
+
+fn compile_benchmark(c: &mut Criterion) {
+    let source = generate_definitions(2000);
+    let expr = scheme_engine::parse(&source, true).unwrap();
+
+    c.bench_function("compile 2000 definitions", |b| {
+        b.iter(|| {
+            let env = scheme_engine::new_env().unwrap();
+            scheme_engine::compile(env, black_box(&expr)).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, compile_benchmark);
+criterion_main!(benches);
@@ -4,19 +4,8 @@ use scheme_engine::Expr;
 fn fibonacci_benchmark(c: &mut Criterion) {
     let source = include_str!("fibonacci.scm");
     let env = scheme_engine::new_env().unwrap();
-    let expr = scheme_engine::parse(source, true).unwrap();
-    let program = scheme_engine::compile(env.clone(), &expr).unwrap();
-
-    // Run program to define variables.
-    scheme_engine::eval(program).expect("evaluating top-level fibonacci program");
-
-    let fibonacci = env
-        .borrow()
-        .lookup_var("fib")
-        .expect("variable 'fib' not found")
-        .as_closure()
-        .expect("variable is not a closure")
-        .clone();
+    let fibonacci =
+        scheme_engine::define_procedure(&env, "fib", source).expect("defining fibonacci procedure");
 
     let args: Vec<Expr> = vec![Expr::Number(10.0)];
     c.bench_function("fib 10", |b| {
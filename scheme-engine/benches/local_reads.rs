@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use scheme_engine::Expr;
+
+/// Number of elements, or characters, in each value.
+const LEN: usize = 100_000;
+
+/// Read a local holding the value many times, so each read's copy dominates.
+fn local_reads_benchmark(c: &mut Criterion) {
+    let env = scheme_engine::new_env().unwrap();
+    {
+        let mut env = env.clone();
+        let env = &mut *env.borrow_mut();
+        let values = [
+            (
+                "numbers",
+                Expr::List((0..LEN).map(|n| Expr::Number(n as f64)).collect()),
+            ),
+            ("vector", Expr::from(vec![Expr::Number(0.0); LEN])),
+            ("text", Expr::from("x".repeat(LEN))),
+        ];
+        for (name, value) in values {
+            let symbol = env.intern_var(name).unwrap();
+            env.set_var(symbol, value).unwrap();
+        }
+    }
+
+    let source = "
+    (define (read-often value)
+      (do ((i 0 (+ i 1))) ((= i 1000) value) value))
+    ";
+    let expr = scheme_engine::parse(source, true).unwrap();
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap();
+
+    for (name, description) in [
+        ("numbers", "list"),
+        ("vector", "vector"),
+        ("text", "string"),
+    ] {
+        let expr = scheme_engine::parse(&format!("(read-often {name})"), true).unwrap();
+        let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+        c.bench_function(
+            &format!("read a local {description} of 100k 1000 times"),
+            |b| b.iter(|| scheme_engine::eval(closure.clone()).unwrap()),
+        );
+    }
+}
+
+criterion_group!(benches, local_reads_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use scheme_engine::Expr;
+
+fn map_benchmark(c: &mut Criterion) {
+    let env = scheme_engine::new_env().unwrap();
+    {
+        let mut env = env.clone();
+        let env = &mut *env.borrow_mut();
+        let symbol = env.intern_var("numbers").unwrap();
+        let numbers = (0..100_000).map(|n| Expr::Number(n as f64)).collect();
+        env.set_var(symbol, Expr::List(numbers)).unwrap();
+    }
+
+    let expr = scheme_engine::parse("(map (lambda (x) (+ x 1)) numbers)", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+
+    c.bench_function("map closure over 100k elements", |b| {
+        b.iter(|| scheme_engine::eval(closure.clone()).unwrap())
+    });
+}
+
+criterion_group!(benches, map_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use scheme_engine::Env;
+
+fn new_env_benchmark(c: &mut Criterion) {
+    c.bench_function("new_env from core template", |b| {
+        b.iter(|| scheme_engine::new_env().unwrap())
+    });
+
+    // What new_env did before the template was cached.
+    c.bench_function("bind core library", |b| {
+        b.iter(|| {
+            let mut env = Env::new();
+            scheme_engine::init_core(&mut env).unwrap();
+            env
+        })
+    });
+}
+
+criterion_group!(benches, new_env_benchmark);
+criterion_main!(benches);
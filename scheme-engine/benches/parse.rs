@@ -0,0 +1,36 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use scheme_engine::{parse_program, parse_program_in, ParseArena};
+
+/// Generate a program with the given number of procedure definitions,
+/// with nested lists, strings and longer identifiers.
+fn generate_program(count: usize) -> String {
+    let mut source = String::new();
+    for index in 0..count {
+        source.push_str(&format!(
+            "(define (procedure-number-{index} first-argument rest)\n  \
+             (if (> first-argument {index})\n    \
+             (display \"greater than {index}\")\n    \
+             (cons 'below (vector rest #\\x 1.5 '(a b . c)))))\n"
+        ));
+    }
+    source
+}
+
+fn parse_benchmark(c: &mut Criterion) {
+    let source = generate_program(2000);
+
+    c.bench_function("parse 2000 definitions", |b| {
+        b.iter(|| parse_program(black_box(&source)).unwrap())
+    });
+
+    let mut arena = ParseArena::new();
+    c.bench_function("parse 2000 definitions into arena", |b| {
+        b.iter(|| {
+            arena.clear();
+            parse_program_in(&arena, black_box(&source)).unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, parse_benchmark);
+criterion_main!(benches);
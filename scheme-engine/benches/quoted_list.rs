@@ -0,0 +1,28 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn quoted_list_benchmark(c: &mut Criterion) {
+    let env = scheme_engine::new_env().unwrap();
+
+    let numbers = (0..10_000)
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let indices = (0..1000)
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let source = format!(
+        "(define numbers '({numbers}))
+         (for-each (lambda (i) (length numbers)) '({indices}))"
+    );
+
+    let expr = scheme_engine::parse(&source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+
+    c.bench_function("pass 10k element quoted list 1000 times", |b| {
+        b.iter(|| scheme_engine::eval(closure.clone()).unwrap())
+    });
+}
+
+criterion_group!(benches, quoted_list_benchmark);
+criterion_main!(benches);
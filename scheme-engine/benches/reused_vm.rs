@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use scheme_engine::{Vm, VmConfig};
+
+/// Number of small evaluations per iteration.
+const EVALS: usize = 10_000;
+
+fn reused_vm_benchmark(c: &mut Criterion) {
+    let env = scheme_engine::new_env().unwrap();
+    let source = "(define (add3 a b c) (+ a (+ b c))) (add3 1 2 (add3 3 4 5))";
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+
+    c.bench_function("10k small evals on fresh machines", |b| {
+        b.iter(|| {
+            for _ in 0..EVALS {
+                scheme_engine::eval(closure.clone()).unwrap();
+            }
+        })
+    });
+
+    // Sized from the high-water marks of one run, so it never reallocates.
+    let mut vm = Vm::new();
+    vm.eval(closure.clone()).unwrap();
+    let stats = vm.stats();
+    let mut vm = Vm::with_config(VmConfig {
+        initial_operand_capacity: stats.peak_operand_depth,
+        initial_frame_capacity: stats.peak_call_depth,
+    });
+
+    c.bench_function("10k small evals on one pre-sized machine", |b| {
+        b.iter(|| {
+            for _ in 0..EVALS {
+                vm.eval(closure.clone()).unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, reused_vm_benchmark);
+criterion_main!(benches);
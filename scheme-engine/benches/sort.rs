@@ -0,0 +1,37 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use scheme_engine::Expr;
+
+/// Pseudo-random numbers from a xorshift generator, the same on every run.
+fn random_numbers(count: usize) -> Vec<Expr> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            Expr::Number((state % 1_000_000) as f64)
+        })
+        .collect()
+}
+
+fn sort_benchmark(c: &mut Criterion) {
+    let env = scheme_engine::new_env().unwrap();
+    {
+        let mut env = env.clone();
+        let env = &mut *env.borrow_mut();
+        let symbol = env.intern_var("numbers").unwrap();
+        env.set_var(symbol, Expr::List(random_numbers(50_000).into()))
+            .unwrap();
+    }
+
+    // Every comparison calls a closure, so this mostly measures calls from natives.
+    let expr = scheme_engine::parse("(sort numbers (lambda (a b) (< a b)))", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+
+    c.bench_function("sort 50k numbers with a closure", |b| {
+        b.iter(|| scheme_engine::eval(closure.clone()).unwrap())
+    });
+}
+
+criterion_group!(benches, sort_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+//! Running an untrusted script in a sandboxed environment.
+//!
+//! The environment has the core library only, without file access, and
+//! caps on how far the script can grow it and how long each form may run.
+//!
+//! ```sh
+//! cargo run --example sandbox -- script.scm
+//! ```
+use std::time::Duration;
+
+use scheme_engine::{run_script, EnvLimits, FormResult, RunLimits};
+
+const DEFAULT_SCRIPT: &str = "
+(define (square x) (* x x))
+(square 12)
+(define (spin) (spin))
+(spin)
+(square 3)
+";
+
+fn main() -> scheme_engine::Result<()> {
+    let source = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => DEFAULT_SCRIPT.to_string(),
+    };
+
+    // Not loaded with `init_file_io`, so the script can't touch files.
+    let env = scheme_engine::new_env()?;
+    env.clone().borrow_mut().set_limits(EnvLimits {
+        max_symbols: Some(10_000),
+        max_procedures: Some(10_000),
+    });
+
+    let limits = RunLimits {
+        fuel_per_form: Some(1_000_000),
+        wall_clock_per_form: Some(Duration::from_millis(100)),
+        max_total_forms: Some(1_000),
+        abort_on_limit: false,
+    };
+
+    for (index, result) in run_script(&env, &source, &limits)?.iter().enumerate() {
+        let number = index + 1;
+        match result {
+            FormResult::Value(value) => println!("{number}: {}", value.write_repr()),
+            FormResult::Error(err) => println!("{number}: error: {err}"),
+            FormResult::Limited(limit) => println!("{number}: stopped: {limit}"),
+            FormResult::Skipped => println!("{number}: skipped"),
+        }
+    }
+    Ok(())
+}
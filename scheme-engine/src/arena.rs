@@ -0,0 +1,456 @@
+//! Parse trees allocated in an arena.
+//!
+//! Tools like editors parse the same file again on every change, look at
+//! the tree, and throw it away. A regular parse allocates each list and
+//! long identifier on its own, and frees them all again when the tree is
+//! dropped. Parsing into a [`ParseArena`] instead puts the tree in a few
+//! large blocks, which are reused after [`ParseArena::clear`]:
+//!
+//! ```
+//! use scheme_engine::{parse_program_in, ArenaExpr, ParseArena};
+//!
+//! let mut arena = ParseArena::new();
+//! for source in ["(define x 1)", "(define y 2) (+ x y)"] {
+//!     arena.clear();
+//!     let program = parse_program_in(&arena, source)?;
+//!     let ArenaExpr::Sequence(forms) = program else { unreachable!() };
+//!     assert!(matches!(forms[0], ArenaExpr::List([ArenaExpr::Ident("define"), ..])));
+//! }
+//! # Ok::<(), scheme_engine::Error>(())
+//! ```
+//!
+//! The tree borrows from the arena, so it's an [`ArenaExpr`] rather than
+//! an [`Expr`]. [`ArenaExpr::to_owned`] makes the regular tree, to compile
+//! it or keep it after the arena is cleared.
+//!
+//! Data with datum labels, like `#0=(a . #0#)`, can refer to themselves,
+//! so they're read as regular values.
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::slice;
+
+use crate::error::{Error, Result};
+use crate::escape;
+use crate::expr::{Expr, Keyword};
+use crate::handle::Handle;
+use crate::parser::{
+    check_source_len, datum_label, is_closed, parse_labelled, read_atom, read_bytes, Atom,
+    ParseOptions, TokenStream,
+};
+use crate::token::TokenKind;
+
+/// Size of the first block of each kind of value in an arena.
+const FIRST_CHUNK_BYTES: usize = 4096;
+
+/// Memory for parse trees, reused by each parse after it's cleared.
+///
+/// See [`parse_program_in`].
+pub struct ParseArena {
+    /// The nodes of the trees. Their references into the arena are only
+    /// handed out for as long as the arena is borrowed, so they're kept
+    /// here without that lifetime.
+    exprs: Chunks<ArenaExpr<'static>>,
+    /// The text of identifiers and strings.
+    text: Chunks<u8>,
+}
+
+impl ParseArena {
+    pub fn new() -> Self {
+        Self {
+            exprs: Chunks::new(),
+            text: Chunks::new(),
+        }
+    }
+
+    /// Drop the trees parsed into the arena, keeping its largest blocks
+    /// for the next parse.
+    pub fn clear(&mut self) {
+        self.exprs.clear();
+        self.text.clear();
+    }
+
+    /// Number of bytes in the arena's blocks, used or not.
+    pub fn capacity_bytes(&self) -> usize {
+        self.exprs.capacity_bytes() + self.text.capacity_bytes()
+    }
+
+    fn alloc<'a>(&'a self, expr: ArenaExpr<'a>) -> &'a ArenaExpr<'a> {
+        &self.alloc_exprs([expr].into_iter())[0]
+    }
+
+    fn alloc_exprs<'a>(
+        &'a self,
+        exprs: impl ExactSizeIterator<Item = ArenaExpr<'a>>,
+    ) -> &'a [ArenaExpr<'a>] {
+        // SAFETY: Only the lifetime changes. The references in the nodes
+        //         are to this arena, and the nodes are only reached through
+        //         the returned slice, borrowing the arena for `'a`, so they
+        //         can't outlive what they refer to. Clearing needs the arena
+        //         mutably, so it can't happen while they're borrowed, and
+        //         dropping a node doesn't follow its references.
+        let exprs = exprs.map(|expr| unsafe { mem::transmute::<_, ArenaExpr<'static>>(expr) });
+        self.exprs.alloc_iter(exprs)
+    }
+
+    fn alloc_str(&self, text: &str) -> &str {
+        let bytes = self.text.alloc_copy(text.as_bytes());
+        // SAFETY: The bytes were copied from a string.
+        unsafe { std::str::from_utf8_unchecked(bytes) }
+    }
+}
+
+impl Default for ParseArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ParseArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParseArena")
+            .field("capacity_bytes", &self.capacity_bytes())
+            .finish()
+    }
+}
+
+/// Blocks of values that never move once allocated, so references to
+/// them can be handed out while more are added.
+///
+/// Each block is only filled up to its capacity, and a bigger one is
+/// started when the next values don't fit.
+struct Chunks<T> {
+    chunks: RefCell<Vec<Vec<T>>>,
+}
+
+impl<T> Chunks<T> {
+    fn new() -> Self {
+        Self {
+            chunks: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The block to add `len` values to, with room for them.
+    fn reserve(chunks: &mut Vec<Vec<T>>, len: usize) -> &mut Vec<T> {
+        let full = chunks
+            .last()
+            .is_none_or(|chunk| chunk.capacity() - chunk.len() < len);
+        if full {
+            let first = FIRST_CHUNK_BYTES / mem::size_of::<T>().max(1);
+            let capacity = chunks
+                .last()
+                .map_or(first, |chunk| chunk.capacity() * 2)
+                .max(len);
+            chunks.push(Vec::with_capacity(capacity));
+        }
+        chunks.last_mut().expect("a block was just added")
+    }
+
+    fn alloc_iter(&self, items: impl ExactSizeIterator<Item = T>) -> &[T] {
+        let len = items.len();
+        if len == 0 {
+            return &[];
+        }
+
+        let mut chunks = self.chunks.borrow_mut();
+        let chunk = Self::reserve(&mut chunks, len);
+        let start = chunk.len();
+        // Taking no more than there's room for, so the block isn't moved
+        // by an iterator that miscounts.
+        chunk.extend(items.take(len));
+
+        let values = &chunk[start..];
+        // SAFETY: The block's buffer is never reallocated, since values are
+        //         only added within its capacity, and it isn't freed or
+        //         cleared while the arena is borrowed.
+        unsafe { slice::from_raw_parts(values.as_ptr(), values.len()) }
+    }
+
+    fn clear(&mut self) {
+        // The last block is the biggest.
+        let chunks = self.chunks.get_mut();
+        if let Some(mut last) = chunks.pop() {
+            last.clear();
+            chunks.clear();
+            chunks.push(last);
+        }
+    }
+
+    fn capacity_bytes(&self) -> usize {
+        let chunks = self.chunks.borrow();
+        chunks.iter().map(Vec::capacity).sum::<usize>() * mem::size_of::<T>()
+    }
+}
+
+impl<T: Copy> Chunks<T> {
+    fn alloc_copy(&self, items: &[T]) -> &[T] {
+        if items.is_empty() {
+            return &[];
+        }
+
+        let mut chunks = self.chunks.borrow_mut();
+        let chunk = Self::reserve(&mut chunks, items.len());
+        let start = chunk.len();
+        chunk.extend_from_slice(items);
+
+        let values = &chunk[start..];
+        // SAFETY: See `alloc_iter`.
+        unsafe { slice::from_raw_parts(values.as_ptr(), values.len()) }
+    }
+}
+
+/// A form of source code parsed into a [`ParseArena`].
+///
+/// Mirrors the [`Expr`] variants the parser produces.
+#[derive(Debug, PartialEq)]
+pub enum ArenaExpr<'a> {
+    Void,
+    Bool(bool),
+    Number(f64),
+    Inexact(f64),
+    Char(char),
+    String(&'a str),
+    Ident(&'a str),
+    /// The dot of a dotted list, like [`Keyword::Dot`].
+    Dot,
+    Quote(&'a ArenaExpr<'a>),
+    List(&'a [ArenaExpr<'a>]),
+    Vector(&'a [ArenaExpr<'a>]),
+    Bytevector(&'a [u8]),
+    /// The top-level forms of a program.
+    Sequence(&'a [ArenaExpr<'a>]),
+    /// A datum with labels, read as a regular value since it can
+    /// refer to itself.
+    Datum(Expr),
+}
+
+impl ArenaExpr<'_> {
+    /// The tree as a regular parse would have read it.
+    pub fn to_owned(&self) -> Expr {
+        match *self {
+            ArenaExpr::Void => Expr::Void,
+            ArenaExpr::Bool(value) => Expr::Bool(value),
+            ArenaExpr::Number(number) => Expr::Number(number),
+            ArenaExpr::Inexact(number) => Expr::Inexact(number),
+            ArenaExpr::Char(ch) => Expr::Char(ch),
+            ArenaExpr::String(text) => Expr::String(text.into()),
+            ArenaExpr::Ident(name) => Expr::Ident(name.into()),
+            ArenaExpr::Dot => Expr::Keyword(Keyword::Dot),
+            ArenaExpr::Quote(quoted) => Expr::Quote(Box::new(quoted.to_owned())),
+            ArenaExpr::List(elements) => {
+                Expr::List(elements.iter().map(ArenaExpr::to_owned).collect())
+            }
+            ArenaExpr::Vector(elements) => Expr::Vector(Handle::new(
+                elements.iter().map(ArenaExpr::to_owned).collect(),
+            )),
+            ArenaExpr::Bytevector(bytes) => Expr::from(bytes.to_vec()),
+            ArenaExpr::Sequence(forms) => {
+                Expr::Sequence(forms.iter().map(ArenaExpr::to_owned).collect())
+            }
+            ArenaExpr::Datum(ref datum) => datum.clone(),
+        }
+    }
+}
+
+/// Parse the source of a whole program into the arena, like [`parse_program`].
+///
+/// The result is an [`ArenaExpr::Sequence`] of the top-level forms.
+///
+/// [`parse_program`]: crate::parse_program
+pub fn parse_program_in<'a>(arena: &'a ParseArena, source: &str) -> Result<&'a ArenaExpr<'a>> {
+    check_source_len(source)?;
+
+    let mut parser = ArenaParser {
+        arena,
+        tokens: TokenStream::new(source, &ParseOptions::default()),
+        stack: Vec::new(),
+    };
+    parser.parse_sequence()
+}
+
+/// Parser producing an [`ArenaExpr`], the counterpart of the functions
+/// in [`crate::parser`].
+struct ArenaParser<'a, 's> {
+    arena: &'a ParseArena,
+    tokens: TokenStream<'s>,
+    /// Elements of the lists being parsed, moved into the arena when
+    /// their list ends, so lists don't each need a vector.
+    stack: Vec<ArenaExpr<'a>>,
+}
+
+impl<'a> ArenaParser<'a, '_> {
+    /// Move the values on the stack from `start` into the arena.
+    fn pop_slice(&mut self, start: usize) -> &'a [ArenaExpr<'a>] {
+        self.arena.alloc_exprs(self.stack.drain(start..))
+    }
+
+    fn parse_sequence(&mut self) -> Result<&'a ArenaExpr<'a>> {
+        let start = self.stack.len();
+
+        while self.tokens.peek().kind != TokenKind::EOF {
+            // Datum labels are only visible within their outermost datum.
+            self.tokens.labels.clear();
+            let expr = self.parse_expr()?;
+            self.stack.push(expr);
+        }
+
+        // Block comments are skipped by the lexer, even when unclosed.
+        if self.tokens.lexer.unclosed_comment() {
+            let token = self.tokens.next();
+            return Err(self.tokens.incomplete(token.span.low(), "expression"));
+        }
+
+        let forms = self.pop_slice(start);
+        Ok(self.arena.alloc(ArenaExpr::Sequence(forms)))
+    }
+
+    fn parse_expr(&mut self) -> Result<ArenaExpr<'a>> {
+        self.tokens.enter()?;
+        let expr = self.parse_datum();
+        self.tokens.leave();
+        expr
+    }
+
+    fn parse_datum(&mut self) -> Result<ArenaExpr<'a>> {
+        let token = self.tokens.next();
+
+        match token.kind {
+            TokenKind::LeftParen => self.parse_list(),
+            TokenKind::BytevectorOpen => {
+                let bytes = read_bytes(&mut self.tokens)?;
+                Ok(ArenaExpr::Bytevector(self.arena.text.alloc_copy(&bytes)))
+            }
+            TokenKind::VectorOpen => self.parse_vector(),
+            TokenKind::EOF => Err(self.tokens.incomplete(token.span.low(), "expression")),
+            TokenKind::RightParen => Err(self.tokens.unexpected(&token, "expression")),
+            TokenKind::QuoteMark => {
+                let quoted = self.parse_expr()?;
+                Ok(ArenaExpr::Quote(self.arena.alloc(quoted)))
+            }
+            TokenKind::String => {
+                let fragment = self.tokens.fragment(&token)?;
+                if !is_closed(fragment, '"') {
+                    return Err(self.tokens.incomplete(token.span.high(), "'\"'"));
+                }
+                // Text without escapes is copied as it is.
+                let text = match fragment.contains('\\') {
+                    false => self.arena.alloc_str(&fragment[1..fragment.len() - 1]),
+                    true => self.arena.alloc_str(&escape::decode_string(fragment)?),
+                };
+                Ok(ArenaExpr::String(text))
+            }
+            TokenKind::Char => Ok(ArenaExpr::Char(escape::decode_char(
+                self.tokens.fragment(&token)?,
+            )?)),
+            TokenKind::Atom => {
+                let fragment = self.tokens.fragment(&token)?;
+                if fragment.starts_with('|') && !is_closed(fragment, '|') {
+                    return Err(self.tokens.incomplete(token.span.high(), "'|'"));
+                }
+                match datum_label(fragment) {
+                    Some((number, true)) => {
+                        parse_labelled(&mut self.tokens, number).map(ArenaExpr::Datum)
+                    }
+                    Some((number, false)) => self
+                        .tokens
+                        .labels
+                        .get(&number)
+                        .cloned()
+                        .map(ArenaExpr::Datum)
+                        .ok_or_else(|| Error::Reason(format!("undefined datum label: {fragment}"))),
+                    None => match read_atom(&self.tokens, &token, fragment)? {
+                        Atom::Ident(name) => Ok(ArenaExpr::Ident(self.arena.alloc_str(&name))),
+                        Atom::Value(Expr::Bool(value)) => Ok(ArenaExpr::Bool(value)),
+                        Atom::Value(Expr::Number(number)) => Ok(ArenaExpr::Number(number)),
+                        Atom::Value(Expr::Inexact(number)) => Ok(ArenaExpr::Inexact(number)),
+                        Atom::Value(Expr::Void) => Ok(ArenaExpr::Void),
+                        Atom::Value(value) => Ok(ArenaExpr::Datum(value)),
+                    },
+                }
+            }
+            TokenKind::LineComment | TokenKind::BlockComment | TokenKind::Whitespace => {
+                unreachable!("trivia is skipped by the token stream")
+            }
+        }
+    }
+
+    /// See [`crate::parser`]'s `parse_list`.
+    fn parse_list(&mut self) -> Result<ArenaExpr<'a>> {
+        let start = self.stack.len();
+
+        loop {
+            let kind = self.tokens.peek().kind;
+            match kind {
+                TokenKind::RightParen => break,
+                TokenKind::EOF => {
+                    let token = self.tokens.next();
+                    return Err(self.tokens.incomplete(token.span.low(), "')'"));
+                }
+                _ if self.tokens.peek_is_dot() => {
+                    self.parse_dotted_tail(start)?;
+                    break;
+                }
+                _ => {
+                    let expr = self.parse_expr()?;
+                    self.stack.push(expr);
+                }
+            }
+        }
+
+        self.tokens.expect(TokenKind::RightParen)?;
+
+        Ok(ArenaExpr::List(self.pop_slice(start)))
+    }
+
+    /// See [`crate::parser`]'s `parse_dotted_tail`.
+    fn parse_dotted_tail(&mut self, start: usize) -> Result<()> {
+        let dot = self.tokens.next();
+        if self.stack.len() == start {
+            return Err(self.tokens.unexpected(&dot, "datum before '.'"));
+        }
+
+        self.stack.push(ArenaExpr::Dot);
+        if self.tokens.peek().kind == TokenKind::RightParen || self.tokens.peek_is_dot() {
+            let token = self.tokens.next();
+            return Err(self.tokens.unexpected(&token, "datum after '.'"));
+        }
+        let tail = self.parse_expr()?;
+        self.stack.push(tail);
+
+        match self.tokens.peek().kind {
+            TokenKind::RightParen => Ok(()),
+            TokenKind::EOF => {
+                let token = self.tokens.next();
+                Err(self.tokens.incomplete(token.span.low(), "')'"))
+            }
+            _ => {
+                let token = self.tokens.next();
+                Err(self
+                    .tokens
+                    .unexpected(&token, "')' after the tail of a dotted list"))
+            }
+        }
+    }
+
+    fn parse_vector(&mut self) -> Result<ArenaExpr<'a>> {
+        let start = self.stack.len();
+
+        loop {
+            match self.tokens.peek().kind {
+                TokenKind::RightParen => break,
+                TokenKind::EOF => {
+                    let token = self.tokens.next();
+                    return Err(self.tokens.incomplete(token.span.low(), "')'"));
+                }
+                _ => {
+                    let expr = self.parse_expr()?;
+                    self.stack.push(expr);
+                }
+            }
+        }
+
+        self.tokens.expect(TokenKind::RightParen)?;
+
+        Ok(ArenaExpr::Vector(self.pop_slice(start)))
+    }
+}
@@ -0,0 +1,184 @@
+//! Reuse of compiled programs when the same source is loaded again.
+//!
+//! A host that loads the same library into an environment over and over,
+//! like a prelude run before each request, can keep a [`CompilationCache`]
+//! and skip parsing and compiling the source after the first time.
+//!
+//! # Same environment only
+//!
+//! Compiled code refers to global variables by the [`SymbolId`] they have
+//! in the environment it was compiled against, and the procedures and
+//! shared constants it creates are stored in that environment. So an entry
+//! is only reused for the environment it was compiled for. Loading the
+//! source into another environment compiles it again, and keeps both.
+//!
+//! Making the code relocatable, by linking variables by name when it's
+//! loaded, would let environments share entries. But every load would then
+//! pay for the linking, which is most of what the cache saves for a small
+//! prelude.
+//!
+//! Entries are keyed by a hash of the source text and [`COMPILER_VERSION`],
+//! and keep the text to compare on a hit, so a collision compiles again
+//! instead of running the wrong program.
+//!
+//! [`SymbolId`]: crate::SymbolId
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::compiler::{compile_with_options, CompileOptions};
+use crate::env::Env;
+use crate::error::Result;
+use crate::expr::{Closure, Expr, Proc};
+use crate::handle::{Handle, HandleCell, RcWeak};
+use crate::parser::parse_program;
+use crate::vm::eval;
+
+/// Version of the bytecode the compiler produces, part of each cache key.
+///
+/// Bump it when the compiled form of a program changes, so caches that
+/// outlive a compiler change can't hand out stale code.
+pub const COMPILER_VERSION: u32 = 1;
+
+/// Compiled programs, reused when the same source is loaded into the
+/// same environment again.
+///
+/// Checks done while compiling, like [`Redefinition::Forbid`], only happen
+/// on a miss, and a hit produces no warnings.
+///
+/// ```
+/// use scheme_engine::{CompilationCache, Expr};
+///
+/// let env = scheme_engine::new_env()?;
+/// let mut cache = CompilationCache::new();
+/// let prelude = "(define (twice x) (* x 2)) (twice 21)";
+///
+/// assert_eq!(cache.load(&env, prelude)?, Expr::Number(42.0));
+/// assert_eq!(cache.load(&env, prelude)?, Expr::Number(42.0));
+/// assert_eq!((cache.misses(), cache.hits()), (1, 1));
+/// # Ok::<(), scheme_engine::Error>(())
+/// ```
+///
+/// [`Redefinition::Forbid`]: crate::Redefinition::Forbid
+#[derive(Default)]
+pub struct CompilationCache {
+    options: CompileOptions,
+    entries: HashMap<CacheKey, Entry>,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    /// Address of the environment the program was compiled against.
+    env: usize,
+    /// Hash of the source text and compiler version.
+    source: u64,
+}
+
+struct Entry {
+    source: Box<str>,
+    /// Keeps the address in the key from going to another environment.
+    env: RcWeak<HandleCell<Env>>,
+    proc: Rc<Proc>,
+}
+
+impl CompilationCache {
+    /// Create an empty cache, compiling with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty cache, compiling with the given options.
+    pub fn with_options(options: CompileOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Parse and compile the program against the environment, or reuse
+    /// the result of an earlier call with the same source and environment.
+    ///
+    /// Programs that fail to parse or compile aren't cached.
+    pub fn compile(&mut self, env: &Handle<Env>, source: &str) -> Result<Handle<Closure>> {
+        let weak = env.downgrade();
+        let key = CacheKey {
+            env: RcWeak::as_ptr(&weak) as *const () as usize,
+            source: source_hash(source),
+        };
+
+        if let Some(entry) = self.entries.get(&key) {
+            if &*entry.source == source && RcWeak::ptr_eq(&entry.env, &weak) {
+                self.hits += 1;
+                return Ok(Handle::new(Closure::new(entry.proc.clone())));
+            }
+        }
+
+        self.misses += 1;
+        let program = parse_program(source)?;
+        let closure = compile_with_options(env.clone(), &program, &self.options)?;
+
+        // Entries of dropped environments can't be hit again.
+        self.entries.retain(|_, entry| entry.env.strong_count() > 0);
+        self.entries.insert(
+            key,
+            Entry {
+                source: source.into(),
+                env: weak,
+                proc: closure.borrow().procedure_rc(),
+            },
+        );
+        Ok(closure)
+    }
+
+    /// Compile the program like [`CompilationCache::compile`], then
+    /// evaluate it.
+    pub fn load(&mut self, env: &Handle<Env>, source: &str) -> Result<Expr> {
+        eval(self.compile(env, source)?)
+    }
+
+    /// Number of calls that reused a compiled program.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Number of calls that had to compile the program.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Number of compiled programs in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop every compiled program, keeping the counters.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl fmt::Debug for CompilationCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompilationCache")
+            .field("options", &self.options)
+            .field("len", &self.entries.len())
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .finish()
+    }
+}
+
+fn source_hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    COMPILER_VERSION.hash(&mut hasher);
+    source.hash(&mut hasher);
+    hasher.finish()
+}
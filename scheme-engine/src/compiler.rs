@@ -1,27 +1,201 @@
+//! Compiling expressions into bytecode.
+//!
+//! # Determinism
+//!
+//! Compiling the same source into environments in the same state gives
+//! the same bytecode, constant tables and procedures, so compiled programs
+//! can be compared, cached and serialized. Anything the compiler numbers
+//! is numbered in the order it's first met while walking the program:
+//!
+//! - global variables are interned by their first occurrence,
+//! - constants take the slot of their first occurrence, see
+//!   [`intern_constant`],
+//! - procedures are stored in the order their definitions are compiled,
+//! - local variables and up-values are numbered in order of declaration
+//!   and capture.
+//!
+//! A lookup table, like a `HashMap` from names to ids, is fine for finding
+//! ids, but ids must never be handed out by iterating one.
+use std::fmt;
 use std::mem;
 use std::rc::Rc;
 
 use smol_str::SmolStr;
 
 use crate::declare_id;
-use crate::env::{ConstantId, Env, LocalId, UpValueId};
+use crate::disasm::disassemble;
+use crate::env::{intern_constant, ConstantId, DefSite, Env, LocalId, UpValueId};
 use crate::error::{Error, Result};
-use crate::expr::{Closure, Expr, Keyword, Proc, Signature};
+use crate::expand::expand;
+use crate::expr::{Closure, Constants, Expr, Keyword, NativeFunc, Proc, Signature};
 use crate::handle::Handle;
 use crate::limits::*;
 use crate::opcode::{JumpAddr, Op, UpValueOrigin};
 use crate::symbol::SymbolId;
+use crate::validate::validate_tree;
+
+/// Options controlling how a program is compiled.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Store constants in the environment's shared constant pool,
+    /// instead of a separate table per procedure.
+    ///
+    /// Programs with many small procedures referencing the same literals
+    /// store each literal once. The pool outlives the compilation unit,
+    /// so later compilations against the same environment reuse its slots.
+    pub shared_constants: bool,
+
+    /// Compile a pure expression, which cannot have side effects.
+    ///
+    /// `define`, `set!`, `define-syntax` and `define-test` are errors, as is
+    /// any reference to a variable the environment considers effectful,
+    /// like `display` or a closure that calls it. See [`Env::is_effectful`].
+    ///
+    /// Evaluating a pure expression never changes the values of the
+    /// environment's variables, so it's suited to user-supplied formulas.
+    pub pure: bool,
+
+    /// Don't warn when a definition, parameter or loop variable has the
+    /// same name as a procedure of the core library.
+    ///
+    /// See [`compile_with_warnings`].
+    pub allow_shadowing: bool,
+
+    /// What a top-level `define` of an already defined variable does.
+    pub redefinition: Redefinition,
+
+    /// Name of the source being compiled, like a file path. Definitions
+    /// remember it, so redefinitions can say where the first one was.
+    pub source_name: Option<String>,
+
+    /// Assume the procedures of the core library keep their bindings,
+    /// so calls to them can be compiled into instructions.
+    ///
+    /// A test of `if` or `cond` that calls `not`, `and` or `or` becomes
+    /// jumps, without calling the procedure: `(if (not x) a b)` costs
+    /// the same as `(if x b a)`. Like the syntax of R7RS, `and` and `or`
+    /// in a test stop evaluating their arguments at the first that
+    /// decides the result.
+    ///
+    /// A name bound by a local variable, or whose variable no longer
+    /// holds the core procedure when the program is compiled, is called
+    /// as usual. Redefining it later, including further on in the same
+    /// program, isn't seen by code compiled with this option.
+    pub assume_core_procedures: bool,
+}
+
+/// Policy for a top-level `define` of a variable that an earlier
+/// program already defined in the same environment.
+///
+/// Variables bound by native functions aren't definitions, so
+/// redefining them is covered by [`CompileOptions::allow_shadowing`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Redefinition {
+    /// The new definition replaces the old one.
+    #[default]
+    Allow,
+    /// Allowed, with a warning. See [`compile_with_warnings`].
+    Warn,
+    /// A compile error.
+    Forbid,
+}
+
+/// A problem in a program that doesn't stop it from compiling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
 
 /// Compiles the given top-level expression into bytecode.
 ///
 /// The given environment will be used as the environment
 /// of the created procedure.
+///
+/// The expression is usually the output of the parser. One built some
+/// other way must have the same shape: a [`Expr::Sequence`] only as the
+/// program itself, [`Keyword::Dot`] only before the tail of a list, and
+/// no values that can't be written, like procedures and ports. Otherwise
+/// compiling it fails with [`Error::MalformedTree`].
 pub fn compile(env: Handle<Env>, expr: &Expr) -> Result<Handle<Closure>> {
+    compile_with_options(env, expr, &CompileOptions::default())
+}
+
+/// Compiles the given top-level expression into bytecode, using the given options.
+///
+/// See [`compile`].
+pub fn compile_with_options(
+    env: Handle<Env>,
+    expr: &Expr,
+    options: &CompileOptions,
+) -> Result<Handle<Closure>> {
+    compile_with_warnings(env, expr, options).map(|(closure, _)| closure)
+}
+
+/// Compiles the given top-level expression into bytecode, also returning
+/// warnings about code that is legal but likely a mistake.
+///
+/// Currently this warns when a name bound by the program shadows a
+/// procedure of the core library, like `(define car ...)`, unless
+/// [`CompileOptions::allow_shadowing`] is set.
+///
+/// The program is first expanded with [`expand`], then compiled
+/// with [`compile_core`].
+pub fn compile_with_warnings(
+    env: Handle<Env>,
+    expr: &Expr,
+    options: &CompileOptions,
+) -> Result<(Handle<Closure>, Vec<Warning>)> {
+    validate_tree(expr)?;
+    let expanded = match expr {
+        // Errors name the top-level form, like the compiler's.
+        Expr::Sequence(forms) if forms.len() > 1 => {
+            let env = env.borrow();
+            let forms = forms
+                .iter()
+                .enumerate()
+                .map(|(index, form)| {
+                    expand(&env, form).map_err(|err| error_in_form(err, index + 1, form))
+                })
+                .collect::<Result<_>>()?;
+            Expr::Sequence(forms)
+        }
+        _ => expand(&env.borrow(), expr)?,
+    };
+
+    compile_validated(env, &expanded, options)
+}
+
+/// Compiles a program that only contains core forms, without expanding it.
+///
+/// Derived forms like `let` are errors. See [`expand`] for the
+/// core forms, and how derived forms are expanded.
+pub fn compile_core(
+    env: Handle<Env>,
+    expr: &Expr,
+    options: &CompileOptions,
+) -> Result<(Handle<Closure>, Vec<Warning>)> {
+    validate_tree(expr)?;
+    compile_validated(env, expr, options)
+}
+
+/// Compiles a program of core forms that is known to be well formed.
+fn compile_validated(
+    env: Handle<Env>,
+    expr: &Expr,
+    options: &CompileOptions,
+) -> Result<(Handle<Closure>, Vec<Warning>)> {
     // Create a new procedure to act as the top level execution context.
     let proc = ProcState::new();
 
     let mut compiler = Compiler {
         env,
+        options: options.clone(),
         proc,
         proc_stack: Vec::new(),
         // Compilation starts at the top level of a program.
@@ -29,22 +203,61 @@ pub fn compile(env: Handle<Env>, expr: &Expr) -> Result<Handle<Closure>> {
         depth: 0,
         stack_offset: 0,
         stack_offsets: Vec::new(),
+        definitions: Vec::new(),
+        lambda_name: None,
+        self_name: None,
+        assigned: Vec::new(),
+        form: None,
+        warnings: Vec::new(),
     };
 
-    compiler.compile_expr(expr)?;
+    compiler.compile_program(expr)?;
     compiler.compile_end()?;
 
+    let warnings = mem::take(&mut compiler.warnings);
     let (_env, proc) = compiler.take_procedure()?;
 
     // debug dump the generated bytecode
-    println!("bytecode:");
-    for (index, op) in proc.code.iter().enumerate() {
-        println!("  {index:>6} : {op:?}");
-    }
+    trace!("bytecode:\n{}", disassemble(&proc, None));
 
     let closure = Closure::new(Rc::new(proc));
 
-    Ok(Handle::new(closure))
+    Ok((Handle::new(closure), warnings))
+}
+
+/// Longest form text shown in errors, in characters.
+const FORM_PREVIEW_LEN: usize = 60;
+
+/// Prefix an error with the number and the start of the text
+/// of the top-level form it happened in.
+fn error_in_form(err: Error, number: usize, form: &Expr) -> Error {
+    let text = form.write_repr().to_string();
+    let form = if text.chars().count() > FORM_PREVIEW_LEN {
+        let preview: String = text.chars().take(FORM_PREVIEW_LEN).collect();
+        format!("form {number} {preview}...")
+    } else {
+        format!("form {number} {text}")
+    };
+
+    match err {
+        Error::Compile {
+            message,
+            definitions,
+            location,
+            ..
+        } => Error::Compile {
+            message,
+            definitions,
+            location,
+            form: Some(form),
+        },
+        err => Error::Compile {
+            message: err.to_string(),
+            definitions: Vec::new(),
+            location: None,
+            form: Some(form),
+        },
+    }
 }
 
 macro_rules! error_unbound_variable {
@@ -53,6 +266,39 @@ macro_rules! error_unbound_variable {
     };
 }
 
+macro_rules! error_definition_in_expression {
+    ($form:expr) => {
+        Error::Reason(format!(
+            "definitions are not allowed in expression context: {}",
+            $form
+        ))
+    };
+}
+
+macro_rules! error_derived_form {
+    ($name:expr) => {
+        Error::Reason(format!(
+            "{} is a derived form, expand the program before compiling it",
+            $name
+        ))
+    };
+}
+
+macro_rules! error_not_pure {
+    ($name:expr) => {
+        Error::Reason(format!(
+            "{:?} is not allowed in pure expression mode",
+            $name
+        ))
+    };
+}
+
+macro_rules! error_macros_not_implemented {
+    ($name:expr) => {
+        Error::Reason(format!("{} is not implemented yet", $name))
+    };
+}
+
 macro_rules! error_ill_special_form {
     ($name:expr) => {
         Error::Reason(format!("ill-formed special form {:?}", $name))
@@ -65,6 +311,8 @@ struct Compiler {
     /// TODO: Once the environment stack is figured out, we could change this to a borrow.
     env: Handle<Env>,
 
+    options: CompileOptions,
+
     /// The current procedure being compiled.
     proc: ProcState,
 
@@ -90,12 +338,40 @@ struct Compiler {
     /// this position.
     stack_offset: usize,
     stack_offsets: Vec<usize>,
+
+    /// Names of the definitions enclosing the expression being compiled,
+    /// starting at the outermost, for error messages. Anonymous
+    /// procedures have no name.
+    definitions: Vec<Option<SmolStr>>,
+
+    /// Name for the `lambda` that is the value of the `define` being compiled.
+    lambda_name: Option<SmolStr>,
+
+    /// Name of the local variable that the `lambda` being compiled is bound
+    /// to, when the procedure can refer to itself without capturing it.
+    /// See [`Op::LoadSelf`].
+    self_name: Option<SmolStr>,
+
+    /// Names assigned with `set!` anywhere in the innermost body being compiled.
+    assigned: Vec<SmolStr>,
+
+    /// Number of the top-level form being compiled, counting from 1,
+    /// when the program has more than one.
+    form: Option<usize>,
+
+    warnings: Vec<Warning>,
 }
 
 impl Compiler {
     /// Consume the compiler and take the last procedure as the top-level program.
     fn take_procedure(self) -> Result<(Handle<Env>, Proc)> {
-        let Self { env, proc, .. } = self;
+        let Self {
+            env,
+            options,
+            mut proc,
+            ..
+        } = self;
+        let constants = proc.constants_for(&options);
 
         // Convert the procedure state to an immutable procedure definition
         // suitable for the virtual machine.
@@ -103,13 +379,15 @@ impl Compiler {
             code: proc.code.into_boxed_slice(),
             // Top-level procedures never take arguments.
             sig: Signature::empty(),
-            constants: proc.constants.into_boxed_slice(),
-            // Top-level procedure doesn't have local variables.
-            // Rather, variables are declared as global in the paired environment.
-            local_count: 0,
+            constants,
+            // Top-level variables are declared as global in the paired
+            // environment, so this is normally zero.
+            local_count: proc.max_locals,
             // Top-level procedure doesn't close over anything, because
             // there are no outer scopes.
             up_value_count: 0,
+            effectful: proc.effectful,
+            forms: proc.forms.into_boxed_slice(),
             // By storing the procedure in the environment
             // we've created a circular reference.
             env: env.downgrade(),
@@ -131,6 +409,115 @@ impl Compiler {
         result
     }
 
+    /// Fail if the compiler is in pure expression mode, where the
+    /// named form or variable is not allowed.
+    fn require_impure(&self, name: &str) -> Result<()> {
+        if self.options.pure {
+            Err(error_not_pure!(name))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Mark the current procedure as having side effects, which is
+    /// an error in pure expression mode.
+    fn effect(&mut self, name: &str) -> Result<()> {
+        self.require_impure(name)?;
+        self.proc.effectful = true;
+        Ok(())
+    }
+
+    /// Warn when a name bound by the program hides a core procedure.
+    ///
+    /// The `binding` describes the kind of name, like `parameter`.
+    fn check_shadowing(&mut self, binding: &str, name: &str) {
+        if self.options.allow_shadowing {
+            return;
+        }
+
+        let env = self.env.borrow();
+        if let Some(symbol) = env.resolve_var(name) {
+            if env.is_core_binding(symbol) {
+                self.warnings.push(Warning {
+                    message: format!("{binding} '{name}' shadows a core procedure"),
+                });
+            }
+        }
+    }
+
+    /// Apply the redefinition policy to a top-level `define`, and
+    /// remember where this definition is.
+    fn check_redefinition(&mut self, name: &str, symbol: SymbolId) -> Result<()> {
+        let previous = self.env.borrow().def_site(symbol).cloned();
+        if let Some(previous) = previous {
+            let message = match &previous.source {
+                Some(source) => format!("'{name}' is already defined (previously in {source})"),
+                None => format!("'{name}' is already defined"),
+            };
+            match self.options.redefinition {
+                Redefinition::Allow => {}
+                Redefinition::Warn => self.warnings.push(Warning { message }),
+                Redefinition::Forbid => return Err(Error::Reason(message)),
+            }
+        }
+
+        let site = DefSite {
+            source: self.options.source_name.clone(),
+        };
+        self.env.borrow_mut().set_def_site(symbol, site);
+        Ok(())
+    }
+
+    /// Compile the body of a definition, so errors raised inside
+    /// it mention the definition's name.
+    ///
+    /// Only the innermost definition wraps the error, since it
+    /// already names all the enclosing ones. Errors in anonymous
+    /// procedures outside of any named definition aren't wrapped.
+    fn definition<T, F>(&mut self, name: Option<SmolStr>, block: F) -> Result<T>
+    where
+        F: FnOnce(&mut Compiler) -> Result<T>,
+    {
+        self.definitions.push(name);
+        let result = block(self).map_err(|err| match err {
+            Error::Compile { .. } => err,
+            err if self.definitions.iter().all(Option::is_none) => err,
+            err => Error::Compile {
+                message: err.to_string(),
+                definitions: self
+                    .definitions
+                    .iter()
+                    .map(|name| match name {
+                        Some(name) => format!("'{name}'"),
+                        None => "lambda".to_string(),
+                    })
+                    .collect(),
+                location: self.locate_definition(),
+                form: None,
+            },
+        });
+        self.definitions.pop();
+
+        result
+    }
+
+    /// Line and column of the innermost named definition being compiled,
+    /// when the program was parsed from a source registered in the
+    /// environment under [`CompileOptions::source_name`].
+    fn locate_definition(&self) -> Option<(usize, usize)> {
+        let source_name = self.options.source_name.as_deref()?;
+        let names: Vec<&str> = self
+            .definitions
+            .iter()
+            .flatten()
+            .map(SmolStr::as_str)
+            .collect();
+        let env = self.env.borrow();
+        let sources = env.sources();
+        let form = self.form.map_or(0, |number| number - 1);
+        sources.locate_definition(sources.find(source_name)?, form, &names)
+    }
+
     /// Create a new lexical scope, storing the current scoping
     /// depth and replacing it with the given scope.
     ///
@@ -164,16 +551,45 @@ impl Compiler {
     where
         F: FnOnce(&mut Compiler) -> Result<T>,
     {
-        println!("start procedure");
+        trace!("start procedure");
         let prev_proc = mem::replace(&mut self.proc, ProcState::new());
         self.proc_stack.push(prev_proc);
         let result = self.scope(|compiler| Ok(block(compiler)))?;
         let new_proc = mem::replace(&mut self.proc, self.proc_stack.pop().unwrap());
-        println!("end procedure");
+        trace!("end procedure");
 
         result.map(|r| (r, new_proc))
     }
 
+    /// Compile the top-level expression of a program.
+    ///
+    /// When the program is a sequence of forms, errors are prefixed with
+    /// the number and text of the form they happened in, and the code
+    /// remembers which form each instruction came from, so runtime errors
+    /// can be located too.
+    fn compile_program(&mut self, expr: &Expr) -> Result<()> {
+        let forms = match expr {
+            Expr::Sequence(forms) if forms.len() > 1 => forms,
+            _ => return self.compile_expr(expr),
+        };
+
+        for (index, form) in forms.iter().enumerate() {
+            let number = index + 1;
+            if index > 0 {
+                // Discard the result values of the preceding forms.
+                self.proc.emit_op(Op::Pop);
+            }
+
+            self.form = Some(number);
+            self.proc.forms.push((self.proc.code.len(), number));
+            self.compile_expr(form)
+                .map_err(|err| error_in_form(err, number, form))?;
+        }
+        self.form = None;
+
+        Ok(())
+    }
+
     /// Compile a sequence of expressions.
     ///
     /// The given expression in the `expr` argument must be a list.
@@ -200,17 +616,28 @@ impl Compiler {
             }
 
             self.compile_expr(last)?;
+        } else {
+            // Like every expression, an empty sequence leaves one value.
+            self.proc.emit_op(Op::PushVoid);
         }
 
         Ok(())
     }
 
+    /// Compile an expression whose result is used as a value by
+    /// the enclosing form, such as a call argument or an `if` test.
+    ///
+    /// Definitions are not allowed here.
+    fn compile_value(&mut self, expr: &Expr) -> Result<()> {
+        self.context(Context::Expression, |compiler| compiler.compile_expr(expr))
+    }
+
     /// Compile a single expression.
     ///
     /// Returns the number of resulting values the expression's
     /// evaluation would leave on the operand stack during runtime.
     fn compile_expr(&mut self, expr: &Expr) -> Result<()> {
-        println!("compiler::compile_expr({expr:?})");
+        trace!("compiler::compile_expr({expr:?})");
 
         match expr {
             // Nil literal
@@ -221,11 +648,15 @@ impl Compiler {
             Expr::Void => {
                 self.proc.emit_op(Op::PushVoid);
             }
-            // Number literal
-            Expr::Number(_) => {
-                let constant_id = self.add_constant(expr.clone());
+            // Number and string literals
+            Expr::Number(_) | Expr::Inexact(_) | Expr::String(_) | Expr::Char(_) => {
+                let constant_id = self.add_constant(expr.clone())?;
                 self.proc.emit_op(Op::PushConstant(constant_id));
             }
+            // Bytevector and vector literals evaluate to themselves, like quoted data.
+            Expr::Bytevector(_) | Expr::Vector(_) => {
+                self.compile_quote_form(expr)?;
+            }
             // Boolean literal
             Expr::Bool(boolean) => {
                 let op = if *boolean {
@@ -240,23 +671,42 @@ impl Compiler {
                 self.compile_access(ident.as_str())?;
             }
             Expr::Quote(value) => {
-                self.compile_quote_form(&*value)?;
+                self.compile_quote_form(value)?;
             }
             Expr::List(list) => {
-                self.compile_form(list.as_slice())?;
+                self.compile_form(list)?;
+            }
+            // A form read with a datum label, like `#0=(f x)`.
+            Expr::Pair(_) if expr.is_cyclic() => {
+                return Err(Error::Reason(format!(
+                    "cannot compile a circular form: {}",
+                    expr.write_repr()
+                )));
+            }
+            Expr::Pair(_) => {
+                let list = expr.iter_list()?.collect::<Result<Vec<_>>>()?;
+                self.compile_form(&list)?;
             }
             Expr::Sequence(_) => {
                 self.compile_sequence(expr)?;
             }
+            Expr::Keyword(Keyword::Dot) => {
+                return Err(Error::Reason(
+                    "unexpected '.' outside of quoted data".to_string(),
+                ));
+            }
             _ => todo!("compile_expr: {expr:?}"),
         }
 
         Ok(())
     }
 
+    /// End a procedure by returning the value on top of the stack.
+    ///
+    /// The top-level program is a procedure like any other, so its
+    /// frame is unwound by the same `Return`.
     fn compile_end(&mut self) -> Result<()> {
         self.proc.emit_op(Op::Return);
-        self.proc.emit_op(Op::End);
         Ok(())
     }
 
@@ -266,7 +716,15 @@ impl Compiler {
     ///
     /// Returns the symbol for the location where the variable is stored.
     fn compile_access(&mut self, name: &str) -> Result<()> {
-        match self.resolve_variable_mut(name) {
+        // The procedure refers to itself, unless a local of its own hides the name.
+        if self.proc.self_name.as_deref() == Some(name)
+            && resolve_local(&mut self.proc, name).is_none()
+        {
+            self.proc.emit_op(Op::LoadSelf);
+            return Ok(());
+        }
+
+        match self.resolve_variable_mut(name)? {
             Some(Variable::Local(local_id)) => {
                 self.proc.emit_op(Op::LoadLocalVar(local_id));
                 Ok(())
@@ -280,6 +738,9 @@ impl Compiler {
                 Ok(())
             }
             Some(Variable::Global(symbol)) => {
+                if self.env.borrow().is_effectful(symbol) {
+                    self.effect(name)?;
+                }
                 self.proc.emit_op(Op::LoadEnvVar(symbol));
                 Ok(())
             }
@@ -313,23 +774,25 @@ impl Compiler {
 
         if let Some((Expr::Ident(operator), rest)) = list.split_first() {
             match operator.as_str() {
+                "define" | "define-values" | "define-syntax"
+                    if self.context == Context::Expression =>
+                {
+                    let form = Expr::List(list.into());
+                    Err(error_definition_in_expression!(form.repr()))
+                }
                 "define" => {
                     self.compile_define_form(rest)?;
                     Ok(true)
                 }
+                "define-values" => {
+                    self.compile_define_values_form(rest)?;
+                    Ok(true)
+                }
                 "lambda" => {
                     self.compile_lambda_form(rest)?;
                     Ok(true)
                 }
-                "let" => {
-                    todo!("let form")
-                }
-                "let*" => {
-                    todo!("let* form")
-                }
-                "letrec" => {
-                    todo!("letrec form")
-                }
+                "let" | "let*" | "letrec" => Err(error_derived_form!(operator)),
                 "fluid-let" => {
                     todo!("fluid-let form")
                 }
@@ -337,17 +800,58 @@ impl Compiler {
                     self.compile_if_form(rest, false)?;
                     Ok(true)
                 }
+                "do" => {
+                    self.compile_do_form(rest)?;
+                    Ok(true)
+                }
+                "begin" => {
+                    // Compiled in the enclosing context, so definitions in
+                    // a top-level `begin` are spliced into the program.
+                    self.compile_begin_form(rest)?;
+                    Ok(true)
+                }
+                "when" | "unless" => {
+                    // The body is a sequence of expressions, without definitions.
+                    self.context(Context::Expression, |compiler| {
+                        compiler.compile_when_form(operator, rest)
+                    })?;
+                    Ok(true)
+                }
                 "cond" => {
-                    self.compile_cond_form(rest)?;
+                    // Clauses are expressions, and may not contain definitions.
+                    self.context(Context::Expression, |compiler| {
+                        compiler.compile_cond_form(rest)
+                    })?;
+                    Ok(true)
+                }
+                "case" => {
+                    // Like `cond`, clauses may not contain definitions.
+                    self.context(Context::Expression, |compiler| {
+                        compiler.compile_case_form(rest)
+                    })?;
+                    Ok(true)
+                }
+                "guard" => {
+                    self.compile_guard_form(rest)?;
                     Ok(true)
                 }
                 "set!" => {
-                    todo!("set! form")
+                    self.compile_set_form(rest)?;
+                    Ok(true)
                 }
                 "quote" => {
                     self.compile_quote_form_slice(rest)?;
                     Ok(true)
                 }
+                "define-test" => {
+                    self.compile_define_test_form(rest)?;
+                    Ok(true)
+                }
+                "define-syntax" => {
+                    self.effect("define-syntax")?;
+                    Err(error_macros_not_implemented!(operator))
+                }
+                "let-syntax" | "letrec-syntax" => Err(error_derived_form!(operator)),
                 _ => Ok(false),
             }
         } else {
@@ -355,8 +859,16 @@ impl Compiler {
         }
     }
 
+    /// Compile a procedure call.
+    ///
+    /// # Order of Evaluation
+    ///
+    /// R7RS leaves the order unspecified, but here the operator is evaluated
+    /// first, followed by the arguments from left to right. Programs can rely
+    /// on this, so optimizations must not reorder argument evaluation.
+    /// `tests/language/evaluation_order.scm` pins the guarantee.
     fn compile_call(&mut self, list: &[Expr]) -> Result<()> {
-        println!("compiler::compile_call({list:?})");
+        trace!("compiler::compile_call({list:?})");
 
         if list.is_empty() {
             return Err(Error::Reason("ill-formed expression".to_string()));
@@ -371,18 +883,20 @@ impl Compiler {
                 }
                 // Operator is an expression that must first be evaluated.
                 expr => {
-                    self.compile_expr(expr)?;
+                    self.compile_value(expr)?;
                 }
             }
 
+            // Arguments are evaluated from left to right, see above.
+            //
             // TODO: Variadic procedures take the rest of their arguments as list. We need to store signature information to accomplish this.
             // TODO: Lists need to be changed ti linked-lists.
             for arg in rest {
-                self.compile_expr(arg)?;
+                self.compile_value(arg)?;
             }
 
             self.proc.emit_op(Op::CallNative {
-                arity: rest.len() as u8,
+                arity: arity(rest.len(), "arguments in a call")?,
             });
 
             Ok(())
@@ -406,24 +920,38 @@ impl Compiler {
     ///
     /// Returns the [`SymbolId`] of the defined variable.
     fn compile_define_form(&mut self, rest: &[Expr]) -> Result<Variable> {
+        // Internal definitions only bind locals, which calls can't observe.
+        match self.context {
+            Context::TopLevel => self.effect("define")?,
+            _ => self.require_impure("define")?,
+        }
+
         // TODO: May define create duplicates in top-level but not block level?
         match rest
-            .get(0)
+            .first()
             .ok_or_else(|| Error::Reason("identifier or list expected".to_string()))?
         {
             Expr::Ident(var_name) => {
+                self.check_shadowing("definition of", var_name);
+
                 match self.context {
                     Context::TopLevel => {
-                        // Variables can be redefined
-                        let symbol = self.env.borrow_mut().intern_var(var_name);
+                        // Variables can be redefined, depending on the options.
+                        let symbol = self.env.borrow_mut().intern_var(var_name)?;
+                        self.check_redefinition(var_name, symbol)?;
+
+                        // INVARIANT: The variable is interned before its value is
+                        // compiled, so a lambda can refer to itself by name.
+                        // Self-recursive procedures depend on this order.
+                        debug_assert_eq!(self.env.borrow().resolve_var(var_name), Some(symbol));
 
                         // Define body is an expression and not a block, but may be omitted.
-                        let body = rest.get(1).unwrap_or(&Expr::Void);
+                        let body = rest.get(1).cloned().unwrap_or(Expr::Void);
 
                         // This expression leaves a value on the stack.
-                        self.compile_expr(body)?;
+                        self.compile_definition_value(var_name, &body)?;
 
-                        self.proc.emit_op(Op::StoreEnvVar(symbol));
+                        self.proc.emit_op(Op::DefineEnvVar(symbol));
                         self.proc.emit_op(Op::Pop);
 
                         // Define evaluates to a #!void value.
@@ -437,11 +965,16 @@ impl Compiler {
                     Context::BodyStart => {
                         let local_id = self.declare_local(var_name.as_str())?;
 
+                        // INVARIANT: As at the top-level, the local is declared before
+                        // its value is compiled, so a lambda captures it as an up-value
+                        // and can call itself. The slot is filled before any call.
+                        debug_assert!(resolve_local(&mut self.proc, var_name).is_some());
+
                         // Define body is an expression and not a block, but may be omitted.
-                        let body = rest.get(1).unwrap_or(&Expr::Void);
+                        let body = rest.get(1).cloned().unwrap_or(Expr::Void);
 
                         // This expression leaves a value on the stack.
-                        self.compile_expr(body)?;
+                        self.compile_definition_value(var_name, &body)?;
 
                         self.proc.emit_op(Op::StoreLocalVar(local_id));
                         self.proc.emit_op(Op::Pop);
@@ -457,23 +990,128 @@ impl Compiler {
                         // This form should never appear at the top-level.
                         Ok(Variable::Local(local_id))
                     }
-                    Context::BodyRest => {
-                        return Err(Error::Reason("ill-formed special form: define must appear at top-level or first in body".to_string()));
+                    Context::BodyRest => Err(Error::Reason(
+                        "ill-formed special form: define must appear at top-level or first in body"
+                            .to_string(),
+                    )),
+                    Context::Expression => {
+                        let mut form = vec![Expr::Ident("define".into())];
+                        form.extend(rest.iter().cloned());
+                        Err(error_definition_in_expression!(
+                            Expr::List(form.into()).repr()
+                        ))
                     }
                 }
             }
-            Expr::List(formals) => {
-                // The first formal is the name of the procedure.
-                let (_name, _args) = formals
-                    .split_first()
-                    .ok_or_else(|| Error::Reason("ill-formed special form".to_string()))?;
+            // See `expand`.
+            Expr::List(_) => Err(error_derived_form!("procedure definition")),
+            target => Err(Error::Reason(format!(
+                "expected identifier or list as define target, found {}",
+                describe_expr(target)
+            ))),
+        }
+    }
 
-                // Declare a variable first because the lambda body can
-                // recursively call itself using the variable name.
+    /// Compile the `define-values` special form.
+    ///
+    /// ```scheme
+    /// (define-values <formals> <expression>)
+    /// ```
+    ///
+    /// The formals are like those of `lambda`, and are bound to the values
+    /// the expression returns with `values`. The number of values is
+    /// checked when the expression has been evaluated.
+    fn compile_define_values_form(&mut self, rest: &[Expr]) -> Result<()> {
+        match self.context {
+            Context::TopLevel => self.effect("define-values")?,
+            _ => self.require_impure("define-values")?,
+        }
 
-                todo!("define procedure form")
+        let [formals, init] = rest else {
+            return Err(error_ill_special_form!("define-values"));
+        };
+        let (params, rest_param) = lambda_formals(formals)?;
+        let names: Vec<SmolStr> = params.into_iter().chain(rest_param).collect();
+
+        match self.context {
+            Context::TopLevel => {
+                let mut symbols = Vec::with_capacity(names.len());
+                for name in &names {
+                    self.check_shadowing("definition of", name);
+                    let symbol = self.env.borrow_mut().intern_var(name)?;
+                    self.check_redefinition(name, symbol)?;
+                    symbols.push(symbol);
+                }
+
+                self.compile_value(init)?;
+                let formals_id = self.add_constant(formals.clone())?;
+                self.proc.emit_op(Op::UnpackValues(formals_id));
+
+                // The last value is on top of the stack.
+                for symbol in symbols.into_iter().rev() {
+                    self.proc.emit_op(Op::DefineEnvVar(symbol));
+                    self.proc.emit_op(Op::Pop);
+                }
+
+                // Like define, evaluates to a #!void value.
+                self.proc.emit_op(Op::PushVoid);
+                Ok(())
+            }
+            Context::BodyStart => {
+                let mut locals = Vec::with_capacity(names.len());
+                for name in &names {
+                    self.check_shadowing("definition of", name);
+                    locals.push(self.declare_local(name.as_str())?);
+                }
+
+                self.compile_value(init)?;
+                let formals_id = self.add_constant(formals.clone())?;
+                self.proc.emit_op(Op::UnpackValues(formals_id));
+
+                // Like define, leaves nothing on the stack in a body.
+                for local_id in locals.into_iter().rev() {
+                    self.proc.emit_op(Op::StoreLocalVar(local_id));
+                    self.proc.emit_op(Op::Pop);
+                }
+                Ok(())
             }
-            _ => Err(Error::Reason("ill-formed special form".to_string())),
+            Context::BodyRest => Err(Error::Reason(
+                "ill-formed special form: define-values must appear at top-level or first in body"
+                    .to_string(),
+            )),
+            Context::Expression => {
+                let mut form = vec![Expr::Ident("define-values".into())];
+                form.extend(rest.iter().cloned());
+                Err(error_definition_in_expression!(
+                    Expr::List(form.into()).repr()
+                ))
+            }
+        }
+    }
+
+    /// Compile the value of a `define`.
+    ///
+    /// A `lambda` value takes the variable's name, so errors in its
+    /// body mention it. Other values are compiled inside a definition
+    /// of the name themselves.
+    fn compile_definition_value(&mut self, name: &SmolStr, body: &Expr) -> Result<()> {
+        let is_lambda = matches!(
+            body.as_slice(),
+            Some([Expr::Ident(operator), ..]) if operator == "lambda"
+        );
+
+        if is_lambda {
+            self.lambda_name = Some(name.clone());
+
+            // A local that is never assigned always holds this procedure,
+            // so the procedure's references to it can use its own closure.
+            if self.context == Context::BodyStart && !self.assigned.contains(name) {
+                self.self_name = Some(name.clone());
+            }
+
+            self.compile_value(body)
+        } else {
+            self.definition(Some(name.clone()), |compiler| compiler.compile_value(body))
         }
     }
 
@@ -485,87 +1123,41 @@ impl Compiler {
     /// (lambda (<formals> . <rest>) <body>)
     /// ```
     fn compile_lambda_form(&mut self, rest: &[Expr]) -> Result<()> {
+        let name = self.lambda_name.take();
+        let self_name = self.self_name.take();
+
+        self.definition(name, |compiler| compiler.compile_lambda(rest, self_name))
+    }
+
+    fn compile_lambda(&mut self, rest: &[Expr], self_name: Option<SmolStr>) -> Result<()> {
         if let Some((formals, rest)) = rest.split_first() {
+            let (params, rest_param) = lambda_formals(formals)?;
             let (_, proc_state) = self.proc_scope(|compiler| {
-                match formals {
-                    // If the formals are a single identifier, then that
-                    // identifier is the "rest" variadic parameter.
-                    //
-                    // The lambda is completely variadic and all arguments will be
-                    // passed as a list bound to this formal.
-                    Expr::Ident(_name) => {
-                        todo!()
-                    }
-                    // The formal parameter list is a list of identifiers
-                    // to which the call arguments will be bound.
-                    //
-                    // The lambda's arity is determined by the number of
-                    // identifiers in this list.
-                    //
-                    // The parameters may be followed by a dot(.) keyword, after
-                    // which the "rest" parameters will be bound as a list.
-                    Expr::List(list) => {
-                        compiler.proc.sig.arity = 0;
-                        let mut variadic: bool = false;
-
-                        for param in list {
-                            match param {
-                                Expr::Ident(name) => {
-                                    // Declare bindings in this scope so the
-                                    // arguments can be referenced by name
-                                    // in the lambda body.
-                                    compiler.declare_local(name.as_str())?;
-
-                                    if variadic {
-                                        break;
-                                    } else {
-                                        compiler.proc.sig.arity += 1;
-                                    }
-                                }
-                                Expr::Keyword(Keyword::Dot) => {
-                                    // When we encounter a dot(.) the succeeding parameter
-                                    // is the binding to the variadic list.
-                                    variadic = true;
-                                }
-                                _ => {
-                                    return Err(Error::Reason(
-                                        "parameter must be an identifier".to_string(),
-                                    ))
-                                }
-                            }
-                        }
+                compiler.proc.self_name = self_name;
 
-                        compiler.compile_body(rest)?;
-                        compiler.proc.emit_op(Op::Return);
-
-                        Ok(())
-                    }
-                    _ => Err(Error::Reason("parameter must be an identifier".to_string())),
+                // Declare bindings in this scope so the arguments
+                // can be referenced by name in the lambda body.
+                for name in &params {
+                    compiler.check_shadowing("parameter", name);
+                    compiler.declare_local(name.as_str())?;
+                }
+                compiler.proc.sig.arity = arity(params.len(), "parameters of a procedure")?;
+
+                // The arguments after the fixed parameters are passed
+                // as a list, bound to the rest parameter.
+                if let Some(name) = &rest_param {
+                    compiler.check_shadowing("parameter", name);
+                    compiler.declare_local(name.as_str())?;
+                    compiler.proc.sig.variadic = true;
                 }
-            })?;
-
-            println!("procedure compiled:");
-            for (index, op) in proc_state.code.iter().enumerate() {
-                println!("  {index:>6} : {op:?}");
-            }
-
-            // Reserve an instruction for creating the closure.
-            // The procedure constant is not ready yet.
-            let op_index = self.proc.reserve_op(Op::Bail);
 
-            // Emit arguments that instruction the VM how to capture the up-values
-            // for the closure.
-            for up_value in &proc_state.up_values {
-                self.proc.emit_op(Op::CaptureValue(up_value.origin.clone()));
-            }
+                compiler.compile_body(rest)?;
+                compiler.compile_end()?;
 
-            // Mutable compiler state for the procedure prototype is now discarded.
-            let proc = proc_state.into_procedure(self.env.clone());
+                Ok(())
+            })?;
 
-            // TODO: Store procedure in dedicated environment storage, not constant. In REPL the closure variable can live longer than the constant.
-            // The procedure definition is stored as a constant in the outer environment.
-            let proc_id = self.env.borrow_mut().add_procedure(proc);
-            self.proc.patch_op(op_index, Op::CreateClosure(proc_id));
+            self.compile_closure(proc_state)?;
 
             Ok(())
         } else {
@@ -576,6 +1168,277 @@ impl Compiler {
         }
     }
 
+    /// Emit the instructions that create a closure from a compiled procedure.
+    fn compile_closure(&mut self, mut proc_state: ProcState) -> Result<()> {
+        // All of a nested procedure's code comes from the same top-level form.
+        if let Some(number) = self.form {
+            proc_state.forms = vec![(0, number)];
+        }
+
+        // Calling a closure created here has the effects of its body.
+        self.proc.effectful |= proc_state.effectful;
+
+        // Reserve an instruction for creating the closure.
+        // The procedure constant is not ready yet.
+        let op_index = self.proc.reserve_op(Op::Bail);
+
+        // Emit arguments that instruction the VM how to capture the up-values
+        // for the closure.
+        for up_value in &proc_state.up_values {
+            self.proc.emit_op(Op::CaptureValue(up_value.origin.clone()));
+        }
+
+        // Mutable compiler state for the procedure prototype is now discarded.
+        let proc = proc_state.into_procedure(self.env.clone(), &self.options);
+        trace!("procedure compiled:\n{}", disassemble(&proc, None));
+
+        // TODO: Store procedure in dedicated environment storage, not constant. In REPL the closure variable can live longer than the constant.
+        // The procedure definition is stored as a constant in the outer environment.
+        let proc_id = self.env.borrow_mut().add_procedure(proc)?;
+        self.proc.patch_op(op_index, Op::CreateClosure(proc_id));
+        Ok(())
+    }
+
+    /// Compile the `do` special form.
+    ///
+    /// ```scheme
+    /// (do ((<variable> <init> <step>) ...)
+    ///     (<test> <expression> ...)
+    ///   <command> ...)
+    /// ```
+    ///
+    /// The `<init>` expressions are evaluated and bound to the variables.
+    /// Each iteration first evaluates `<test>`. If it's true, then the
+    /// `<expression>`s are evaluated and the last one is the result of the
+    /// loop, otherwise the `<command>`s are evaluated for effect and the
+    /// variables updated with their `<step>` expressions.
+    ///
+    /// The loop is compiled as a procedure called immediately with the
+    /// `<init>` values, so the variables are locals of that procedure and
+    /// the iterations are a backward jump within it.
+    ///
+    /// TODO: Closures created in the loop body share the variables, instead
+    ///       of getting fresh bindings per iteration.
+    fn compile_do_form(&mut self, rest: &[Expr]) -> Result<()> {
+        let (specs, exit, commands) = match rest {
+            [Expr::List(specs), Expr::List(exit), commands @ ..] => (specs, exit, commands),
+            [..] => return Err(error_ill_special_form!("do")),
+        };
+        let (test, results) = exit
+            .split_first()
+            .ok_or_else(|| error_ill_special_form!("do"))?;
+
+        let mut variables = vec![];
+        for spec in specs.iter() {
+            match spec.as_slice() {
+                Some([Expr::Ident(name), init]) => variables.push((name, init, None)),
+                Some([Expr::Ident(name), init, step]) => variables.push((name, init, Some(step))),
+                _ => return Err(error_ill_special_form!("do")),
+            }
+        }
+
+        let (_, proc_state) = self.proc_scope(|compiler| {
+            compiler.context(Context::Expression, |compiler| {
+                compiler.proc.sig.arity = arity(variables.len(), "loop variables")?;
+                let mut locals = vec![];
+                for (name, _, _) in &variables {
+                    compiler.check_shadowing("loop variable", name);
+                    locals.push(compiler.declare_local(name.as_str())?);
+                }
+
+                // <test>
+                let loop_addr = compiler.proc.next_op_addr();
+                compiler.compile_expr(test)?;
+                let test_jump_index = compiler.proc.reserve_op(Op::JumpFalse(JumpAddr::zero()));
+                compiler.proc.emit_op(Op::Pop); // <test> result
+
+                // <expression> ...
+                if results.is_empty() {
+                    compiler.proc.emit_op(Op::PushVoid);
+                } else {
+                    compiler.compile_sequence_slice(results)?;
+                }
+                compiler.proc.emit_op(Op::Return);
+
+                // <command> ...
+                let body_addr = compiler.proc.next_op_addr();
+                compiler
+                    .proc
+                    .patch_op(test_jump_index, Op::JumpFalse(body_addr));
+                compiler.proc.emit_op(Op::Pop); // <test> result
+
+                for command in commands {
+                    compiler.compile_expr(command)?;
+                    compiler.proc.emit_op(Op::Pop);
+                }
+
+                // All the <step> expressions are evaluated before any
+                // variable is updated.
+                for (_, _, step) in &variables {
+                    if let Some(step) = step {
+                        compiler.compile_expr(step)?;
+                    }
+                }
+                for ((_, _, step), local_id) in variables.iter().zip(locals).rev() {
+                    if step.is_some() {
+                        compiler.proc.emit_op(Op::StoreLocalVar(local_id));
+                        compiler.proc.emit_op(Op::Pop);
+                    }
+                }
+
+                compiler.proc.emit_op(Op::Jump(loop_addr));
+
+                Ok(())
+            })
+        })?;
+
+        self.compile_closure(proc_state)?;
+
+        // The <init> expressions are evaluated outside the loop's scope.
+        for (_, init, _) in &variables {
+            self.compile_value(init)?;
+        }
+
+        self.proc.emit_op(Op::CallNative {
+            arity: arity(variables.len(), "loop variables")?,
+        });
+
+        Ok(())
+    }
+
+    /// Compile the `define-test` special form.
+    ///
+    /// Registers the body as a test, to be run later by `(run-tests)`.
+    ///
+    /// ```scheme
+    /// (define-test <name> <body>)
+    /// ```
+    ///
+    /// The body is wrapped in a thunk, so this is equivalent to:
+    ///
+    /// ```scheme
+    /// (register-test <name> (lambda () <body>))
+    /// ```
+    fn compile_define_test_form(&mut self, rest: &[Expr]) -> Result<()> {
+        self.effect("define-test")?;
+
+        match rest.split_first() {
+            Some((name @ Expr::String(_), body)) if !body.is_empty() => {
+                let mut thunk = vec![Expr::Ident("lambda".into()), Expr::List(Rc::new([]))];
+                thunk.extend(body.iter().cloned());
+
+                self.compile_call(&[
+                    Expr::Ident("register-test".into()),
+                    name.clone(),
+                    Expr::List(thunk.into()),
+                ])
+            }
+            _ => Err(error_ill_special_form!("define-test")),
+        }
+    }
+
+    /// Compile the test of a conditional, followed by jumps that are taken
+    /// when its value is false, or true when `jump_if_true` is set. Returns
+    /// the indices of the jumps, to be patched with their target.
+    ///
+    /// Both ways leave the test's value on the stack. Calls to the core
+    /// `not`, `and` and `or` become jumps instead, when the options allow
+    /// it. See [`CompileOptions::assume_core_procedures`].
+    fn compile_test(&mut self, test: &Expr, jump_if_true: bool) -> Result<Vec<usize>> {
+        use crate::core::{boolean_and, boolean_not, boolean_or};
+
+        if let Expr::List(list) = test {
+            match &list[..] {
+                [Expr::Ident(operator), arg]
+                    if operator == "not" && self.is_core_procedure("not", boolean_not) =>
+                {
+                    return self.compile_test(arg, !jump_if_true);
+                }
+                // `and` jumps when an argument is false, and `or` when one is true.
+                [Expr::Ident(operator), args @ ..]
+                    if operator == "and"
+                        && !args.is_empty()
+                        && self.is_core_procedure("and", boolean_and) =>
+                {
+                    return self.compile_test_chain(args, false, jump_if_true);
+                }
+                [Expr::Ident(operator), args @ ..]
+                    if operator == "or"
+                        && !args.is_empty()
+                        && self.is_core_procedure("or", boolean_or) =>
+                {
+                    return self.compile_test_chain(args, true, jump_if_true);
+                }
+                _ => {}
+            }
+        }
+
+        self.compile_value(test)?;
+        let jump = match jump_if_true {
+            true => Op::JumpTrue(JumpAddr::zero()),
+            false => Op::JumpFalse(JumpAddr::zero()),
+        };
+        Ok(vec![self.proc.reserve_op(jump)])
+    }
+
+    /// The arguments of `and` or `or` as a test. See [`Compiler::compile_test`].
+    ///
+    /// An argument whose value decides the result, false for `and` and
+    /// true for `or`, skips the arguments after it.
+    fn compile_test_chain(
+        &mut self,
+        args: &[Expr],
+        decided_if_true: bool,
+        jump_if_true: bool,
+    ) -> Result<Vec<usize>> {
+        let (last, preceding) = args.split_last().expect("test chain has arguments");
+        let mut jumps = vec![];
+        let mut to_end = vec![];
+
+        for arg in preceding {
+            let decided = self.compile_test(arg, decided_if_true)?;
+            if decided_if_true == jump_if_true {
+                jumps.extend(decided);
+            } else {
+                // The result is decided the other way, so the last
+                // argument's test falls through with it.
+                to_end.extend(decided);
+            }
+            self.proc.emit_op(Op::Pop); // <test> result
+        }
+
+        jumps.extend(self.compile_test(last, jump_if_true)?);
+        let end = self.proc.next_op_addr();
+        for index in to_end {
+            self.proc.patch_jump(index, &end);
+        }
+        Ok(jumps)
+    }
+
+    /// Whether the name refers to the given native of the core library,
+    /// and calls to it may be compiled into instructions.
+    fn is_core_procedure(&self, name: &str, native: NativeFunc) -> bool {
+        if !self.options.assume_core_procedures {
+            return false;
+        }
+
+        // Bound by a local variable of this or an enclosing procedure.
+        let is_lexical = std::iter::once(&self.proc)
+            .chain(&self.proc_stack)
+            .any(|proc| {
+                proc.self_name.as_deref() == Some(name)
+                    || proc.locals.iter().any(|local| local.name == name)
+                    || proc.up_values.iter().any(|up_value| up_value.name == name)
+            });
+        if is_lexical {
+            return false;
+        }
+
+        let env = self.env.borrow();
+        let value = env.resolve_var(name).and_then(|symbol| env.get_var(symbol));
+        matches!(value, Some(Expr::NativeFunc(func)) if std::ptr::fn_addr_eq(*func, native))
+    }
+
     /// Compile the `if` special form.
     ///
     /// First the `<test>` expression is evaluated. If the result is truthy,
@@ -596,20 +1459,19 @@ impl Compiler {
     /// # Tail Calls
     ///
     /// TODO: Handle proper tail calls for branches.
-    fn compile_if_form(&mut self, expressions: &[Expr], is_last: bool) -> Result<()> {
+    fn compile_if_form(&mut self, expressions: &[Expr], _is_last: bool) -> Result<()> {
         match expressions.split_first() {
             Some((test_expr, rest)) => {
                 // <test>
-                self.compile_expr(test_expr)?;
-
+                //
                 // The start of the <alternate> bytecode can only be determined
                 // when the <consequent> is completely emitted.
-                let test_jump_index = self.proc.reserve_op(Op::JumpFalse(JumpAddr::zero()));
+                let test_jumps = self.compile_test(test_expr, false)?;
                 self.proc.emit_op(Op::Pop); // <test> result
 
                 // <consequent>
-                let consequent = rest.get(0).ok_or_else(|| error_ill_special_form!("if"))?;
-                self.compile_expr(consequent)?;
+                let consequent = rest.first().ok_or_else(|| error_ill_special_form!("if"))?;
+                self.compile_value(consequent)?;
 
                 // Jump over the <alternate>.
                 // TODO: Tail calls for conditionals.
@@ -617,13 +1479,14 @@ impl Compiler {
 
                 // <alternate>
                 let alternate_addr = self.proc.next_op_addr();
-                self.proc
-                    .patch_op(test_jump_index, Op::JumpFalse(alternate_addr));
+                for index in test_jumps {
+                    self.proc.patch_jump(index, &alternate_addr);
+                }
                 self.proc.emit_op(Op::Pop); // <test> result
 
                 match rest.get(1) {
                     Some(alternate) => {
-                        self.compile_expr(alternate)?;
+                        self.compile_value(alternate)?;
                     }
                     // When there is no explicit <alternate> then the `if` result is unspecified.
                     //
@@ -644,6 +1507,58 @@ impl Compiler {
         }
     }
 
+    /// Compile the `begin` special form.
+    ///
+    /// ```scheme
+    /// (begin <expression₁> <expression₂> ...)
+    /// (begin <definition> ...)
+    /// ```
+    ///
+    /// The expressions are evaluated in order, and the value of the last is
+    /// the value of the `begin`. At the top level it may also contain
+    /// definitions, which define global variables as if they were written
+    /// in place of the `begin`, and may then be empty.
+    fn compile_begin_form(&mut self, rest: &[Expr]) -> Result<()> {
+        if rest.is_empty() && self.context != Context::TopLevel {
+            return Err(error_ill_special_form!("begin"));
+        }
+
+        self.compile_sequence_slice(rest)
+    }
+
+    /// Compile the `when` or `unless` special form.
+    ///
+    /// ```scheme
+    /// (when <test> <expression₁> <expression₂> ...)
+    /// (unless <test> <expression₁> <expression₂> ...)
+    /// ```
+    ///
+    /// A one-armed `if` whose consequent is a sequence. `when` evaluates the
+    /// expressions if the test is true, and `unless` if it's false. Either
+    /// is the value of the last expression, or `#!void` when they're skipped.
+    fn compile_when_form(&mut self, keyword: &str, rest: &[Expr]) -> Result<()> {
+        let Some((test, body @ [_, ..])) = rest.split_first() else {
+            return Err(error_ill_special_form!(keyword));
+        };
+
+        // Skip the body when the test is false for `when`, or true for `unless`.
+        let skip_jumps = self.compile_test(test, keyword == "unless")?;
+        self.proc.emit_op(Op::Pop); // <test> result
+        self.compile_sequence_slice(body)?;
+        let end_jump = self.proc.reserve_op(Op::Jump(JumpAddr::zero()));
+
+        let skip_addr = self.proc.next_op_addr();
+        for index in skip_jumps {
+            self.proc.patch_jump(index, &skip_addr);
+        }
+        self.proc.emit_op(Op::Pop); // <test> result
+        self.proc.emit_op(Op::PushVoid);
+
+        let end_addr = self.proc.next_op_addr();
+        self.proc.patch_op(end_jump, Op::Jump(end_addr));
+        Ok(())
+    }
+
     /// Compile the `cond` special form.
     ///
     /// ```scheme
@@ -660,13 +1575,13 @@ impl Compiler {
     ///
     /// Note that for the `cond` form the `else` clause does not have a `=>` variant.
     fn compile_cond_form(&mut self, clauses: &[Expr]) -> Result<()> {
-        println!("compile::compile_cond_form({clauses:?})");
+        trace!("compile::compile_cond_form({clauses:?})");
 
         if clauses.is_empty() {
             return Err(error_ill_special_form!("cond"));
         }
 
-        let mut next: Option<usize> = None;
+        let mut next: Vec<usize> = vec![];
         let mut ends = vec![];
         let mut is_last = false;
 
@@ -676,9 +1591,11 @@ impl Compiler {
             }
 
             // The previous clause falls through when it evaluates to false.
-            if let Some(op_index) = next.take() {
+            if !next.is_empty() {
                 let addr = self.proc.next_op_addr();
-                self.proc.patch_op(op_index, Op::JumpFalse(addr));
+                for op_index in next.drain(..) {
+                    self.proc.patch_jump(op_index, &addr);
+                }
 
                 // Remove the result of the previous test.
                 self.proc.emit_op(Op::Pop); // #f
@@ -693,15 +1610,10 @@ impl Compiler {
                     // Ensure else clause is last
                     is_last = true;
 
-                    // Skip over `else`
-                    match sequence.get(1..) {
-                        Some(expressions) => {
-                            self.compile_sequence_slice(expressions)?;
-                        }
-                        // It's an error for `else` to be empty.
-                        None => {
-                            return Err(error_ill_special_form!("cond"));
-                        }
+                    // Skip over `else`. It's an error for `else` to be empty.
+                    match &sequence[1..] {
+                        [] => return Err(error_ill_special_form!("cond")),
+                        expressions => self.compile_sequence_slice(expressions)?,
                     }
 
                     // Skip over the void return.
@@ -712,41 +1624,46 @@ impl Compiler {
                         .split_first()
                         .ok_or_else(|| error_ill_special_form!("cond"))?;
 
-                    // <test>
-                    self.compile_expr(test)?;
-
-                    // Jump to the next clause if this <test> fails.
-                    next = Some(self.proc.reserve_op(Op::JumpFalse(JumpAddr::zero())));
-
-                    match rest.get(1) {
+                    match rest {
                         // The `=>` alternate form takes one expression as a procedure,
                         // and calls it with the result of the <test> expression.
-                        Some(Expr::Ident(arrow)) if arrow == "=>" => {
-                            let callable =
-                                rest.get(1).ok_or_else(|| error_ill_special_form!("cond"))?;
-                            self.compile_expr(callable)?;
+                        [Expr::Ident(arrow), callable] if arrow == "=>" => {
+                            // The value of the <test> is kept as the argument.
+                            self.compile_expr(test)?;
+                            next.push(self.proc.reserve_op(Op::JumpFalse(JumpAddr::zero())));
 
-                            // This form only allows for one expression.
-                            if !(&rest[2..]).is_empty() {
-                                return Err(error_ill_special_form!("cond"));
-                            }
+                            // The procedure is only evaluated once the <test>
+                            // passed, so it goes under its argument afterwards.
+                            self.compile_expr(callable)?;
+                            self.proc.emit_op(Op::Swap);
+                            self.proc.emit_op(Op::CallNative { arity: 1 });
+                        }
+                        // This form only allows for one expression.
+                        [Expr::Ident(arrow), ..] if arrow == "=>" => {
+                            return Err(error_ill_special_form!("cond"));
+                        }
+                        // Without expressions, the value of the <test> is the result.
+                        [] => {
+                            self.compile_expr(test)?;
+                            next.push(self.proc.reserve_op(Op::JumpFalse(JumpAddr::zero())));
                         }
                         // The default form is simply a sequence of expressions.
                         _ => {
+                            // Jump to the next clause if this <test> fails.
+                            next = self.compile_test(test, false)?;
+
                             // Discard the result of <test>
                             self.proc.emit_op(Op::Pop);
 
                             self.compile_sequence_slice(rest)?;
-
-                            // `cond` evaluation stops with the first clause that is true.
-                            //
-                            // Prevent the clause from falling through to the next test
-                            // by jumping to the end of the `cond` block.
-                            ends.push(self.proc.reserve_op(Op::Jump(JumpAddr::zero())));
-
-                            continue;
                         }
                     }
+
+                    // `cond` evaluation stops with the first clause that is true.
+                    //
+                    // Prevent the clause from falling through to the next test
+                    // by jumping to the end of the `cond` block.
+                    ends.push(self.proc.reserve_op(Op::Jump(JumpAddr::zero())));
                 }
                 // <clause> must have at least one expression
                 None => {
@@ -760,9 +1677,11 @@ impl Compiler {
         //
         // When `cond` ends with `else` there is no `next`,
         // because `else` has no test.
-        if let Some(op_index) = next.take() {
+        if !next.is_empty() {
             let addr = self.proc.next_op_addr();
-            self.proc.patch_op(op_index, Op::JumpFalse(addr));
+            for op_index in next.drain(..) {
+                self.proc.patch_jump(op_index, &addr);
+            }
             self.proc.emit_op(Op::Pop); // #f
             self.proc.emit_op(Op::PushVoid);
         }
@@ -773,22 +1692,184 @@ impl Compiler {
             self.proc.patch_op(op_index, Op::Jump(end_addr.clone()));
         }
 
-        assert!(next.is_none());
+        assert!(next.is_empty());
 
         Ok(())
     }
 
+    /// Compile the `guard` special form.
+    ///
+    /// ```scheme
+    /// (guard (<variable> <cond clause₁> <cond clause₂> ...) <body>)
+    /// ```
+    ///
+    /// The body runs as a procedure of no arguments, so its frame can be
+    /// unwound, with an error handler installed. The handler is a procedure
+    /// of the variable, testing the clauses like `cond`, and raising what
+    /// was caught again when none of them match:
+    ///
+    /// ```scheme
+    /// (lambda (<variable>) (cond <clause> ... (else (raise <variable>))))
+    /// ```
+    fn compile_guard_form(&mut self, rest: &[Expr]) -> Result<()> {
+        let Some((Some([variable @ Expr::Ident(_), clauses @ ..]), body)) = rest
+            .split_first()
+            .map(|(handler, body)| (handler.as_slice(), body))
+        else {
+            return Err(error_ill_special_form!("guard"));
+        };
+        let list = |elements: Vec<Expr>| Expr::List(elements.into());
+        let ident = |name: &str| Expr::Ident(name.into());
+
+        let mut cond = vec![ident("cond")];
+        cond.extend(clauses.iter().cloned());
+        let has_else = clauses.last().and_then(Expr::as_slice).is_some_and(
+            |clause| matches!(clause.first(), Some(Expr::Ident(name)) if name == "else"),
+        );
+        if !has_else {
+            let reraise = list(vec![ident("raise"), variable.clone()]);
+            cond.push(list(vec![ident("else"), reraise]));
+        }
+        let handler = list(vec![
+            ident("lambda"),
+            list(vec![variable.clone()]),
+            list(cond),
+        ]);
+        let mut thunk = vec![ident("lambda"), Expr::NIL];
+        thunk.extend(body.iter().cloned());
+
+        // The handler procedure waits under the body's frame.
+        self.compile_value(&handler)?;
+        let push_handler = self.proc.reserve_op(Op::PushHandler(JumpAddr::zero()));
+        self.compile_value(&list(vec![list(thunk)]))?;
+        self.proc.emit_op(Op::PopHandler);
+        let end_jump = self.proc.reserve_op(Op::Jump(JumpAddr::zero()));
+
+        // Unwinding pushes what was raised as the handler's argument.
+        let handler_addr = self.proc.next_op_addr();
+        self.proc
+            .patch_op(push_handler, Op::PushHandler(handler_addr));
+        self.proc.emit_op(Op::CallNative { arity: 1 });
+
+        let end_addr = self.proc.next_op_addr();
+        self.proc.patch_op(end_jump, Op::Jump(end_addr));
+        Ok(())
+    }
+
     /// Compile the `case` special form.
     ///
     /// ```scheme
     /// (case <key> <clause₁> <clause₂> ...)
     /// ```
+    ///
+    /// Each clause is `((<datum> ...) <expression> ...)`, and the last may
+    /// be an `else` clause. The key is evaluated once, and stays on the
+    /// stack while it's compared to the datums with [`Op::MatchConstant`].
+    /// The first datum that's `eqv?` to the key jumps to the expressions of
+    /// its clause. Like in `cond`, `=>` calls a procedure with the key
+    /// instead.
     fn compile_case_form(&mut self, rest: &[Expr]) -> Result<()> {
-        todo!()
+        let (key, clauses) = rest
+            .split_first()
+            .ok_or_else(|| error_ill_special_form!("case"))?;
+        self.compile_expr(key)?;
+
+        let mut ends = vec![];
+        let mut has_else = false;
+
+        for clause in clauses {
+            if has_else {
+                return Err(Error::Reason("else clause must be last".to_string()));
+            }
+
+            let Some((selector, body @ [_, ..])) =
+                clause.as_slice().and_then(<[Expr]>::split_first)
+            else {
+                return Err(error_ill_special_form!("case"));
+            };
+
+            let datums = match selector {
+                Expr::Ident(ident) if ident == "else" => {
+                    has_else = true;
+                    self.compile_case_body(body)?;
+                    continue;
+                }
+                // An empty list of datums never matches.
+                Expr::Nil => &[][..],
+                Expr::List(datums) => &datums[..],
+                _ => return Err(error_ill_special_form!("case")),
+            };
+
+            let mut matched = vec![];
+            for datum in datums {
+                let constant_id = self.add_constant(literal_datum(datum)?)?;
+                self.proc.emit_op(Op::MatchConstant(constant_id));
+                matched.push(self.proc.reserve_op(Op::JumpTrue(JumpAddr::zero())));
+                self.proc.emit_op(Op::Pop); // #f
+            }
+            let next = self.proc.reserve_op(Op::Jump(JumpAddr::zero()));
+
+            let body_addr = self.proc.next_op_addr();
+            for op_index in matched {
+                self.proc.patch_jump(op_index, &body_addr);
+            }
+            self.proc.emit_op(Op::Pop); // #t
+            self.compile_case_body(body)?;
+
+            // Only the first matching clause is evaluated.
+            ends.push(self.proc.reserve_op(Op::Jump(JumpAddr::zero())));
+
+            let next_addr = self.proc.next_op_addr();
+            self.proc.patch_op(next, Op::Jump(next_addr));
+        }
+
+        // Without an `else`, a key that matches no clause is unspecified.
+        if !has_else {
+            self.proc.emit_op(Op::Pop); // <key>
+            self.proc.emit_op(Op::PushVoid);
+        }
+
+        let end_addr = self.proc.next_op_addr();
+        for op_index in ends {
+            self.proc.patch_op(op_index, Op::Jump(end_addr.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Compile the expressions of a matched `case` clause, with the key on
+    /// top of the stack.
+    fn compile_case_body(&mut self, body: &[Expr]) -> Result<()> {
+        match body {
+            // The procedure is called with the key.
+            [Expr::Ident(arrow), callable] if arrow == "=>" => {
+                self.compile_expr(callable)?;
+                self.proc.emit_op(Op::Swap);
+                self.proc.emit_op(Op::CallNative { arity: 1 });
+            }
+            [Expr::Ident(arrow), ..] if arrow == "=>" => {
+                return Err(error_ill_special_form!("case"));
+            }
+            _ => {
+                self.proc.emit_op(Op::Pop); // <key>
+                self.compile_sequence_slice(body)?;
+            }
+        }
+        Ok(())
     }
 
     /// Compile the body of a `define`, `lambda`, `let`, etc...
     fn compile_body(&mut self, rest: &[Expr]) -> Result<()> {
+        let mut assigned = Vec::new();
+        assigned_names(rest, &mut assigned);
+        let outer_assigned = mem::replace(&mut self.assigned, assigned);
+        let result = self.compile_body_forms(rest);
+        self.assigned = outer_assigned;
+
+        result
+    }
+
+    fn compile_body_forms(&mut self, rest: &[Expr]) -> Result<()> {
         self.context(Context::BodyStart, |compiler| {
             // Compile the start of a body.
             //
@@ -796,16 +1877,21 @@ impl Compiler {
             // non-definition expression is encountered.
             let mut body_expressions = rest;
 
-            for expr in rest {
+            for (index, expr) in rest.iter().enumerate() {
                 if let Expr::List(list) = expr {
                     if let Some((Expr::Ident(name), def_rest)) = list.split_first() {
                         match name.as_str() {
                             "define" => {
                                 compiler.compile_define_form(def_rest)?;
-                                body_expressions = &rest[1..];
+                                body_expressions = &rest[index + 1..];
+                            }
+                            "define-values" => {
+                                compiler.compile_define_values_form(def_rest)?;
+                                body_expressions = &rest[index + 1..];
                             }
                             "define-syntax" => {
-                                todo!("define-syntax")
+                                compiler.effect("define-syntax")?;
+                                return Err(error_macros_not_implemented!(name));
                             }
                             _ => break,
                         }
@@ -846,35 +1932,83 @@ impl Compiler {
         })
     }
 
+    /// Compile the `set!` special form.
+    ///
+    /// Assigns a new value to a variable that is already bound, whether it's
+    /// a local, a local captured from an enclosing procedure, or a variable
+    /// in the environment.
+    ///
+    /// ```scheme
+    /// (set! <variable> <expression>)
+    /// ```
+    fn compile_set_form(&mut self, rest: &[Expr]) -> Result<()> {
+        self.effect("set!")?;
+
+        match rest {
+            [Expr::Ident(name), value] => {
+                let variable = self
+                    .resolve_variable_mut(name)?
+                    .ok_or_else(|| error_unbound_variable!(name))?;
+
+                // This expression leaves a value on the stack.
+                self.compile_value(value)?;
+
+                let op = match variable {
+                    Variable::Local(local_id) => Op::StoreLocalVar(local_id),
+                    Variable::NonLocal(up_value_id) => Op::StoreUpValue(up_value_id),
+                    Variable::Global(symbol) => Op::StoreEnvVar(symbol),
+                };
+                self.proc.emit_op(op);
+                self.proc.emit_op(Op::Pop);
+
+                // Assignment evaluates to a #!void value.
+                self.proc.emit_op(Op::PushVoid);
+
+                Ok(())
+            }
+            [..] => Err(error_ill_special_form!("set!")),
+        }
+    }
+
     /// Compile an expression as a constant value.
     fn compile_quote_form_slice(&mut self, expressions: &[Expr]) -> Result<ConstantId> {
-        println!("compiler::compile_quote_form_slice({expressions:?})");
+        trace!("compiler::compile_quote_form_slice({expressions:?})");
         match expressions {
             [value] => self.compile_quote_form(value),
             [..] => Err(error_ill_special_form!("quote")),
         }
     }
 
+    /// Compile quoted data into a constant.
+    ///
+    /// Lists are immutable, so they are shared by every evaluation.
+    /// Constants with mutable parts, like dotted pairs and bytevectors,
+    /// are copied each time instead, so mutating the value of one
+    /// evaluation can't change the literal seen by the next.
     fn compile_quote_form(&mut self, value: &Expr) -> Result<ConstantId> {
-        println!("compiler::compile_quote_form({value:?})");
-        let constant_id = self.add_constant(value.clone());
-        self.proc.emit_op(Op::PushConstant(constant_id));
+        trace!("compiler::compile_quote_form({value:?})");
+        let value = literal_datum(value)?;
+        let op = if value.has_mutable_parts() {
+            Op::PushConstantCopy
+        } else {
+            Op::PushConstant
+        };
+        let constant_id = self.add_constant(value)?;
+        self.proc.emit_op(op(constant_id));
         Ok(constant_id)
     }
 
-    /// Add a constant value to the current environment.
+    /// Add a constant value to the current procedure, or to the
+    /// environment's shared pool when enabled in the options.
     ///
     /// Returns the [`ConstantId`] identifying its location.
     ///
     /// Does not emit a load operation.
-    fn add_constant(&mut self, value: Expr) -> ConstantId {
-        match self.proc.constants.iter().position(|el| el == &value) {
-            Some(index) => ConstantId::new(index as u16),
-            None => {
-                let next_index = self.proc.constants.len();
-                self.proc.constants.push(value);
-                ConstantId::new(next_index as u16)
-            }
+    fn add_constant(&mut self, value: Expr) -> Result<ConstantId> {
+        if self.options.shared_constants {
+            self.env.borrow_mut().add_constant(value)
+        } else {
+            intern_constant(&mut self.proc.constants, value)
         }
     }
 
@@ -920,7 +2054,9 @@ impl Compiler {
         }
 
         let local_id = LocalId::new(index as u8);
-        println!("declare local {local_id:?}:{name:?}");
+        trace!("declare local {local_id:?}:{name:?}");
+
+        self.proc.max_locals = self.proc.max_locals.max(index + 1);
 
         let stack_offset = StackPos::new(self.proc.locals.len());
         self.proc.locals.push(Local {
@@ -939,21 +2075,18 @@ impl Compiler {
     ///
     /// If the scan leaves the local procedure boundary, and finds
     /// the variable in an outer scope, that variable must be marked
-    /// as captured.
-    fn resolve_variable_mut(&mut self, name: &str) -> Option<Variable> {
-        println!("compiler::resolve_variable_mut({name:?})");
+    /// as captured, which fails when a procedure captures too many.
+    fn resolve_variable_mut(&mut self, name: &str) -> Result<Option<Variable>> {
+        trace!("compiler::resolve_variable_mut({name:?})");
 
-        if let Some(variable) = resolve_non_env_mut(&mut self.proc, &mut self.proc_stack, name) {
-            return Some(variable);
+        if let Some(variable) = resolve_non_env_mut(&mut self.proc, &mut self.proc_stack, name)? {
+            return Ok(Some(variable));
         }
 
-        println!("compiler::resolve_variable_mut(...), resolving env var");
+        trace!("compiler::resolve_variable_mut(...), resolving env var");
         // If the variable cannot be found in the locals of the lexical scopes,
         // then we fall back onto the enclosing environment.
-        self.env
-            .borrow()
-            .resolve_var(name)
-            .map(|symbol| Variable::Global(symbol))
+        Ok(self.env.borrow().resolve_var(name).map(Variable::Global))
     }
 }
 
@@ -962,48 +2095,109 @@ fn resolve_non_env_mut(
     proc: &mut ProcState,
     stack: &mut [ProcState],
     name: &str,
-) -> Option<Variable> {
-    println!("compiler::resolve_non_env_mut({proc:?}, {stack:?}, {name:?})");
+) -> Result<Option<Variable>> {
+    trace!("compiler::resolve_non_env_mut({proc:?}, {stack:?}, {name:?})");
 
     // First attempt to resolve the variable in a local scope,
     // then in an outer scope, then the enclosing environment.
     if let Some(local) = resolve_local(proc, name) {
-        return Some(Variable::Local(local.id.clone()));
+        return Ok(Some(Variable::Local(local.id)));
     }
 
     // If a local variable can't be found, we scan the parent scope
     // for a local variable we could use as an up-value.
-    if let Some(up_value) = find_up_value_mut(proc, stack, name) {
-        return Some(Variable::NonLocal(up_value.clone()));
+    Ok(find_up_value_mut(proc, stack, name)?.map(Variable::NonLocal))
+}
+
+/// The fixed parameters of a lambda, and its rest parameter if any.
+///
+/// The formals are a list of identifiers, which may end with a dot
+/// followed by the rest parameter, or a single identifier taking all
+/// the arguments.
+fn lambda_formals(formals: &Expr) -> Result<(Vec<SmolStr>, Option<SmolStr>)> {
+    let parameter = |expr: &Expr| match expr {
+        Expr::Ident(name) => Ok(name.clone()),
+        _ => Err(Error::Reason(format!(
+            "expected identifier in formals list, found {}",
+            describe_expr(expr)
+        ))),
+    };
+
+    match formals {
+        Expr::Nil => Ok((Vec::new(), None)),
+        Expr::Ident(name) => Ok((Vec::new(), Some(name.clone()))),
+        Expr::List(list) => {
+            let (fixed, rest) = match list
+                .iter()
+                .position(|expr| matches!(expr, Expr::Keyword(Keyword::Dot)))
+            {
+                Some(dot) => match &list[dot + 1..] {
+                    [rest] => (&list[..dot], Some(parameter(rest)?)),
+                    _ => return Err(error_ill_special_form!("lambda")),
+                },
+                None => (&list[..], None),
+            };
+            let params = fixed.iter().map(parameter).collect::<Result<_>>()?;
+            Ok((params, rest))
+        }
+        _ => Err(Error::Reason(format!(
+            "expected formals list or identifier, found {}",
+            describe_expr(formals)
+        ))),
+    }
+}
+
+/// Collect the names assigned by `set!` forms anywhere in the expressions,
+/// including nested procedures.
+fn assigned_names(expressions: &[Expr], names: &mut Vec<SmolStr>) {
+    for expr in expressions {
+        let list = match expr {
+            Expr::List(list) => &list[..],
+            Expr::Sequence(list) => &list[..],
+            _ => continue,
+        };
+        if let [Expr::Ident(operator), Expr::Ident(name), ..] = list {
+            if operator == "set!" {
+                names.push(name.clone());
+            }
+        }
+        assigned_names(list, names);
     }
+}
+
+/// The arity of a call or procedure, which must fit in an instruction.
+fn arity(count: usize, what: &str) -> Result<u8> {
+    if count > MAX_ARITY {
+        return Err(Error::Reason(format!(
+            "number of {what} exceeds maximum of {MAX_ARITY}"
+        )));
+    }
+    Ok(count as u8)
+}
 
-    None
+/// An expression found where the syntax expected something else, like `number 1`.
+fn describe_expr(expr: &Expr) -> String {
+    format!("{} {}", expr.type_name(), expr.write_repr())
 }
 
 /// Resolve a local variable in the current procedure, without scanning for up-values.
 fn resolve_local<'a>(proc: &'a mut ProcState, name: &str) -> Option<&'a Local> {
-    println!("compiler::resolve_local({proc:?}, {name:?})");
+    trace!("compiler::resolve_local({proc:?}, {name:?})");
 
-    for local in proc.locals.iter().rev() {
-        if name == local.name {
-            return Some(local);
-        }
-    }
-
-    None
+    proc.locals.iter().rev().find(|local| name == local.name)
 }
 
 fn find_up_value_mut(
     proc: &mut ProcState,
     stack: &mut [ProcState],
     name: &str,
-) -> Option<UpValueId> {
-    println!("compiler::find_up_value_mut({proc:?}, {stack:?}, {name:?})");
+) -> Result<Option<UpValueId>> {
+    trace!("compiler::find_up_value_mut({proc:?}, {stack:?}, {name:?})");
 
     for up_value in &proc.up_values {
         if name == up_value.name {
             // The up-value has previously been captured.
-            return Some(up_value.id);
+            return Ok(Some(up_value.id));
         }
     }
 
@@ -1012,29 +2206,31 @@ fn find_up_value_mut(
     // Scan the procedure stack in reverse looking at their local variables
     // and up-values.
     if let Some((parent, rest)) = stack.split_last_mut() {
-        println!("compiler::find_up_value_mut(...), parent -> {parent:?}");
+        trace!("compiler::find_up_value_mut(...), parent -> {parent:?}");
 
-        match resolve_non_env_mut(parent, rest, name) {
+        match resolve_non_env_mut(parent, rest, name)? {
             // A local variable was found in the parent scope.
             Some(Variable::Local(local_id)) => {
-                println!("compiler::find_up_value_mut(...), local -> {local_id:?}");
-                Some(proc.insert_up_value(name, UpValueOrigin::Parent(local_id)))
+                trace!("compiler::find_up_value_mut(...), local -> {local_id:?}");
+                proc.insert_up_value(name, UpValueOrigin::Parent(local_id))
+                    .map(Some)
             }
             // An up-value has been found in a higher scope beyond the parent scope.
             Some(Variable::NonLocal(up_value_id)) => {
-                println!("compiler::find_up_value_mut(...), non-local -> {up_value_id:?}");
+                trace!("compiler::find_up_value_mut(...), non-local -> {up_value_id:?}");
                 // Flatten the closure by copying the up-value into this one.
-                Some(proc.insert_up_value(name, UpValueOrigin::Outer(up_value_id)))
+                proc.insert_up_value(name, UpValueOrigin::Outer(up_value_id))
+                    .map(Some)
             }
             Some(Variable::Global(_)) => {
                 unreachable!("global variables cannot be up-values")
             }
-            None => None,
+            None => Ok(None),
         }
     } else {
-        println!("compiler::find_up_value_mut(...), no parent");
+        trace!("compiler::find_up_value_mut(...), no parent");
 
-        None
+        Ok(None)
     }
 }
 
@@ -1045,9 +2241,18 @@ struct ProcState {
     code: Vec<Op>,
     sig: Signature,
     locals: Vec<Local>,
+    /// High-water mark of the locals declared at the same time,
+    /// including the parameters.
+    max_locals: usize,
     constants: Vec<Expr>,
     /// List of variables in an outer scope.
     up_values: Vec<UpValueInfo>,
+    /// The procedure has side effects, see [`Proc::effectful`].
+    effectful: bool,
+    /// See [`Proc::forms`].
+    forms: Vec<(usize, usize)>,
+    /// See [`Compiler::self_name`].
+    self_name: Option<SmolStr>,
 }
 
 impl ProcState {
@@ -1056,8 +2261,12 @@ impl ProcState {
             code: Vec::new(),
             sig: Signature::empty(),
             locals: Vec::new(),
+            max_locals: 0,
             constants: Vec::new(),
             up_values: Vec::new(),
+            effectful: false,
+            forms: Vec::new(),
+            self_name: None,
         }
     }
 
@@ -1084,38 +2293,64 @@ impl ProcState {
         self.code[index] = op;
     }
 
-    fn into_procedure(self, env: Handle<Env>) -> Proc {
-        println!("compiled procedure: {self:?}");
+    /// Point a previously emitted jump instruction at the address.
+    fn patch_jump(&mut self, index: usize, addr: &JumpAddr) {
+        self.code[index] = match self.code[index] {
+            Op::Jump(_) => Op::Jump(addr.clone()),
+            Op::JumpFalse(_) => Op::JumpFalse(addr.clone()),
+            Op::JumpTrue(_) => Op::JumpTrue(addr.clone()),
+            ref op => panic!("patching {op:?} at {index}, which is not a jump"),
+        };
+    }
+
+    /// The constant table for the finished procedure.
+    fn constants_for(&mut self, options: &CompileOptions) -> Constants {
+        if options.shared_constants {
+            debug_assert!(self.constants.is_empty());
+            Constants::Shared
+        } else {
+            Constants::Local(mem::take(&mut self.constants).into_boxed_slice())
+        }
+    }
+
+    fn into_procedure(mut self, env: Handle<Env>, options: &CompileOptions) -> Proc {
+        let constants = self.constants_for(options);
+
+        trace!("compiled procedure: {self:?}");
 
         let Self {
             code,
             sig,
-            locals,
-            constants,
+            max_locals,
             up_values,
+            effectful,
+            forms,
             ..
         } = self;
 
         Proc {
             code: code.into_boxed_slice(),
             sig,
-            constants: constants.into_boxed_slice(),
-            local_count: locals.len(),
+            constants,
+            local_count: max_locals,
             up_value_count: up_values.len(),
+            effectful,
+            forms: forms.into_boxed_slice(),
             env: env.downgrade(),
         }
     }
 
-    fn push_up_value(&mut self, up_value: UpValueInfo) -> UpValueId {
-        let index = self.up_values.len();
-        let id = UpValueId::new(index as u8);
-        self.up_values.push(UpValueInfo { id, ..up_value });
-        id
-    }
-
     /// Insert up-value without checking if it already exists.
-    fn insert_up_value(&mut self, name: &str, origin: UpValueOrigin) -> UpValueId {
+    ///
+    /// Up-values are numbered apart from the locals, so a procedure
+    /// can capture up to [`MAX_UP_VALUES`] variables of its own.
+    fn insert_up_value(&mut self, name: &str, origin: UpValueOrigin) -> Result<UpValueId> {
         let index = self.up_values.len();
+        if index >= MAX_UP_VALUES {
+            return Err(Error::Reason(format!(
+                "number of variables captured by a procedure exceeds maximum of {MAX_UP_VALUES}"
+            )));
+        }
         let id = UpValueId::new(index as u8);
         self.up_values.push(UpValueInfo {
             id,
@@ -1123,15 +2358,7 @@ impl ProcState {
             stack_pos: StackPos(0),
             origin,
         });
-        id
-    }
-
-    fn intern_up_value(&mut self) -> UpValueInfo {
-        todo!()
-    }
-
-    fn scope(&mut self) {
-        todo!()
+        Ok(id)
     }
 }
 
@@ -1164,11 +2391,13 @@ struct Local {
     ///
     /// It must thus be valid fro both compile time (lexical scope) and runtime (evaluation stack).
     id: LocalId,
+    #[allow(dead_code)]
     stack_offset: StackPos,
     name: SmolStr,
     /// The depth of the scope where the variable was declared.
     depth: usize,
     /// Flag indicating that the variable has been captured by an inner scope.
+    #[allow(dead_code)]
     is_captured: bool,
 }
 
@@ -1179,6 +2408,7 @@ struct UpValueInfo {
     id: UpValueId,
     name: SmolStr,
     /// Index into lexical stack where the local variable is located.
+    #[allow(dead_code)]
     stack_pos: StackPos,
     /// Indicates where the up-value originated from, relative to the
     /// current local scope.
@@ -1231,18 +2461,186 @@ enum Context {
     ///
     /// No further definitions are allowed.
     BodyRest,
+    /// An expression whose value is used by the enclosing form, such as
+    /// a call argument or the test of an `if`.
+    ///
+    /// Definitions are not allowed.
+    Expression,
 }
 
-impl Context {
-    fn is_top_level(&self) -> bool {
-        matches!(self, Self::TopLevel)
+/// Convert data read by the parser into the value of a literal.
+///
+/// Dotted lists become chains of pairs, and quotes nested inside
+/// the data become `(quote x)` lists.
+pub(crate) fn literal_datum(expr: &Expr) -> Result<Expr> {
+    match expr {
+        Expr::List(list) => {
+            let dot = list
+                .iter()
+                .position(|expr| matches!(expr, Expr::Keyword(Keyword::Dot)));
+            match dot {
+                None => Ok(Expr::List(
+                    list.iter().map(literal_datum).collect::<Result<_>>()?,
+                )),
+                Some(dot) => match &list[dot + 1..] {
+                    [tail] if dot > 0 => list[..dot]
+                        .iter()
+                        .try_rfold(literal_datum(tail)?, |tail, head| {
+                            Ok(Expr::Pair(Handle::new((literal_datum(head)?, tail))))
+                        }),
+                    _ => Err(error_ill_special_form!("quote")),
+                },
+            }
+        }
+        Expr::Vector(elements) => Ok(Expr::Vector(Handle::new(
+            elements
+                .borrow()
+                .iter()
+                .map(literal_datum)
+                .collect::<Result<_>>()?,
+        ))),
+        Expr::Quote(quoted) => Ok(Expr::List(Rc::from([
+            Expr::Ident(SmolStr::new_inline("quote")),
+            literal_datum(quoted)?,
+        ]))),
+        _ => Ok(expr.clone()),
     }
+}
 
-    fn is_body_start(&self) -> bool {
-        matches!(self, Self::BodyStart)
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    /// Everything compiled from the source into a fresh environment: its
+    /// procedures, constants and symbols, and the top-level procedure.
+    fn compiled_state(source: &str, options: &CompileOptions) -> String {
+        let env = crate::new_env().expect("create core environment");
+        let expr = parse(source, true).expect("parse");
+        let closure = compile_with_options(env.clone(), &expr, options).expect("compile");
+
+        let env = env.borrow();
+        let symbols: Vec<&str> = env.iter_vars_sorted().map(|(name, _)| name).collect();
+        format!(
+            "{:?}\n{:?}\n{:?}\n{:?}",
+            env.procedures,
+            env.constants,
+            symbols,
+            closure.borrow().procedure()
+        )
     }
 
-    fn is_body_rest(&self) -> bool {
-        matches!(self, Self::BodyRest)
+    #[test]
+    fn test_compile_deterministic() {
+        let scripts = [
+            include_str!("../tests/language/boolean.scm"),
+            include_str!("../tests/language/conditionals.scm"),
+            include_str!("../tests/language/define.scm"),
+            include_str!("../tests/language/lambda.scm"),
+            include_str!("../tests/language/list.scm"),
+            include_str!("../tests/language/memoize.scm"),
+            include_str!("../tests/language/number.scm"),
+            include_str!("../tests/language/numeric_edges.scm"),
+        ];
+        let shared = CompileOptions {
+            shared_constants: true,
+            ..CompileOptions::default()
+        };
+
+        for source in scripts {
+            for options in [&CompileOptions::default(), &shared] {
+                assert_eq!(
+                    compiled_state(source, options),
+                    compiled_state(source, options)
+                );
+            }
+        }
+    }
+
+    /// Total number of constant slots stored for a compiled program,
+    /// across all procedures and the environment's shared pool.
+    fn constant_slots(options: &CompileOptions) -> usize {
+        let mut source = String::new();
+        for index in 0..50 {
+            source.push_str(&format!(
+                "(define f{index} (lambda (x) (if (= x 1) \"error\" (+ x 1))))\n"
+            ));
+        }
+
+        let env = crate::new_env().expect("create core environment");
+        let expr = parse(&source, true).expect("parse");
+        let closure = compile_with_options(env.clone(), &expr, options).expect("compile");
+
+        let env = env.borrow();
+        let mut count = env.constants.len();
+        for proc in env
+            .procedures
+            .iter()
+            .chain([&closure.borrow().procedure_rc()])
+        {
+            if let Constants::Local(constants) = &proc.constants {
+                count += constants.len();
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_shared_constants() {
+        let local = constant_slots(&CompileOptions::default());
+        let shared = constant_slots(&CompileOptions {
+            shared_constants: true,
+            ..CompileOptions::default()
+        });
+
+        // Each lambda stores its own 1 and "error".
+        assert_eq!(local, 100);
+        assert_eq!(shared, 2);
+    }
+
+    fn eval_source(source: &str) -> Expr {
+        let env = crate::new_env().expect("create core environment");
+        let expr = parse(source, true).expect("parse");
+        let closure = compile(env.clone(), &expr).expect("compile");
+        crate::eval(closure).expect("eval")
+    }
+
+    /// A name is visible inside its own definition, in every form of define.
+    ///
+    /// The procedure define shorthand, named `let` and `letrec` are not
+    /// implemented yet, and need a case here when they are.
+    #[test]
+    fn test_self_recursive_definitions() {
+        let top_level = r"
+        (define count (lambda (n) (if (<= n 0) 0 (+ 1 (count (- n 1))))))
+        (count 10)
+        ";
+        assert_eq!(eval_source(top_level), Expr::Number(10.0));
+
+        let internal = r"
+        (define outer (lambda (n)
+          (define count (lambda (k) (if (<= k 0) 0 (+ 1 (count (- k 1))))))
+          (count n)))
+        (outer 10)
+        ";
+        assert_eq!(eval_source(internal), Expr::Number(10.0));
+
+        // The internal procedure keeps calling itself after the
+        // frame that defined it has returned.
+        let escaping = r"
+        (define make (lambda ()
+          (define count (lambda (k) (if (<= k 0) 0 (+ 1 (count (- k 1))))))
+          count))
+        ((make) 10)
+        ";
+        assert_eq!(eval_source(escaping), Expr::Number(10.0));
+
+        // Reached through a nested lambda instead of directly.
+        let nested = r"
+        (define count (lambda (n)
+          ((lambda (k) (if (<= k 0) 0 (+ 1 (count (- k 1))))) n)))
+        (count 10)
+        ";
+        assert_eq!(eval_source(nested), Expr::Number(10.0));
     }
 }
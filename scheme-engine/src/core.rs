@@ -1,72 +1,204 @@
 //! Core standard library.
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
 
+use crate::compiler::literal_datum;
+use crate::datum::Datum;
+use crate::disasm;
 use crate::env::Env;
 use crate::error::{Error, Result};
-use crate::expr::Expr;
+use crate::error_object::{self, ErrorKind, ErrorObject};
+use crate::expr::{number_repr, DatumLabels, Expr, ExprKind, Spine};
+use crate::format;
+use crate::handle::Handle;
+use crate::port::Port;
+use crate::vm;
 
 pub fn init_core(env: &mut Env) -> Result<()> {
+    let first_symbol = env.var_count();
+
     env.bind_native_func("assert", ext_assert)?;
     env.bind_native_func("assert-eq", ext_assert_eq)?;
-    env.bind_native_func("display", display)?;
-    env.bind_native_func("newline", newline)?;
+    env.bind_native_func("assert-close", ext_assert_close)?;
+    env.bind_effectful_func("display", display)?;
+    env.bind_effectful_func("write", write)?;
+    env.bind_effectful_func("write-shared", write_shared)?;
+    env.bind_effectful_func("write-simple", write_simple)?;
+    env.bind_effectful_func("newline", newline)?;
+    env.bind_effectful_func("write-char", write_char)?;
+    env.bind_effectful_func("write-string", write_string)?;
+    env.bind_native_func("with-output-to-string", with_output_to_string)?;
+    env.bind_effectful_func("format", format)?;
 
     env.bind_native_func("number?", number_is_number)?;
+    env.bind_native_func("number->string", number_to_string)?;
+    env.bind_native_func("integer?", number_is_integer)?;
+    env.bind_native_func("exact?", number_is_exact)?;
+    env.bind_native_func("inexact?", number_is_inexact)?;
+    env.bind_native_func("exact-integer?", number_is_exact_integer)?;
+    env.bind_native_func("exact", number_exact)?;
+    env.bind_native_func("inexact", number_inexact)?;
+    env.bind_native_func("inexact->exact", number_exact)?;
+    env.bind_native_func("exact->inexact", number_inexact)?;
     env.bind_native_func("+", number_add)?;
     env.bind_native_func("-", number_sub)?;
     env.bind_native_func("*", number_mul)?;
+    env.bind_native_func("/", number_div)?;
     env.bind_native_func("=", number_eq)?;
     env.bind_native_func("<", number_lt)?;
     env.bind_native_func(">", number_gt)?;
     env.bind_native_func("<=", number_lt_eq)?;
     env.bind_native_func(">=", number_gt_eq)?;
+    env.bind_native_func("max", number_max)?;
+    env.bind_native_func("min", number_min)?;
+    env.bind_native_func("abs", number_abs)?;
+    env.bind_native_func("quotient", number_quotient)?;
+    env.bind_native_func("remainder", number_remainder)?;
+    env.bind_native_func("modulo", number_modulo)?;
+    env.bind_native_func("floor", number_floor)?;
+    env.bind_native_func("ceiling", number_ceiling)?;
+    env.bind_native_func("truncate", number_truncate)?;
+    env.bind_native_func("round", number_round)?;
+    env.bind_native_func("sqrt", number_sqrt)?;
+    env.bind_native_func("expt", number_expt)?;
 
     env.bind_native_func("boolean?", boolean_is_boolean)?;
     env.bind_native_func("not", boolean_not)?;
     env.bind_native_func("and", boolean_and)?;
     env.bind_native_func("or", boolean_or)?;
 
+    env.bind_native_func("eq?", eq)?;
+    env.bind_native_func("eqv?", eqv)?;
+    env.bind_native_func("equal?", equal)?;
+
+    env.bind_native_func("symbol?", symbol_is_symbol)?;
+    env.bind_native_func("symbol->string", symbol_to_string)?;
+    env.bind_native_func("string->symbol", symbol_from_string)?;
+
+    env.bind_native_func("bytevector", bytevector)?;
+    env.bind_native_func("make-bytevector", bytevector_make)?;
+    env.bind_native_func("bytevector-length", bytevector_length)?;
+    env.bind_native_func("bytevector-u8-ref", bytevector_u8_ref)?;
+    env.bind_effectful_func("bytevector-u8-set!", bytevector_u8_set)?;
+    env.bind_native_func("bytevector-copy", bytevector_copy)?;
+    env.bind_native_func("bytevector-append", bytevector_append)?;
+    env.bind_native_func("utf8->string", bytevector_utf8_to_string)?;
+    env.bind_native_func("string->utf8", bytevector_string_to_utf8)?;
+
+    env.bind_native_func("vector?", vector_is_vector)?;
+    env.bind_native_func("vector", vector)?;
+    env.bind_native_func("make-vector", vector_make)?;
+    env.bind_native_func("vector-length", vector_length)?;
+    env.bind_native_func("vector-ref", vector_ref)?;
+    env.bind_effectful_func("vector-set!", vector_set)?;
+    env.bind_native_func("vector->list", vector_to_list)?;
+    env.bind_native_func("list->vector", vector_from_list)?;
+    env.bind_native_func("vector-map", vector_map)?;
+    env.bind_native_func("vector-for-each", vector_for_each)?;
+    env.bind_effectful_func("vector-fill!", vector_fill)?;
+    env.bind_native_func("vector-copy", vector_copy)?;
+    env.bind_effectful_func("vector-copy!", vector_copy_into)?;
+    env.bind_native_func("vector-append", vector_append)?;
+
+    env.bind_native_func("cons", pair_cons)?;
+    env.bind_native_func("car", pair_car)?;
+    env.bind_native_func("cdr", pair_cdr)?;
+    env.bind_effectful_func("set-car!", pair_set_car)?;
+    env.bind_effectful_func("set-cdr!", pair_set_cdr)?;
+    env.bind_native_func("pair?", pair_is_pair)?;
+    env.bind_native_func("null?", list_is_null)?;
+    env.bind_native_func("list?", list_is_list)?;
+    env.bind_native_func("proper-list?", list_is_list)?;
+    env.bind_native_func("length", list_length)?;
+    env.bind_native_func("list-copy", list_copy)?;
+    env.bind_native_func("last-pair", list_last_pair)?;
+    env.bind_native_func("append", list_append)?;
+    env.bind_native_func("reverse", list_reverse)?;
+    env.bind_effectful_func("append!", list_append_in_place)?;
+    env.bind_effectful_func("reverse!", list_reverse_in_place)?;
+    env.bind_native_func("list-tail", list_tail)?;
+    env.bind_native_func("map", list_map)?;
+    env.bind_native_func("for-each", list_for_each)?;
+    env.bind_native_func("filter", list_filter)?;
+    env.bind_native_func("sort", list_sort)?;
+    env.bind_effectful_func("sort!", list_sort_in_place)?;
+    env.bind_native_func("apply", apply)?;
+    env.bind_native_func("values", values)?;
+    env.bind_native_func("call-with-values", call_with_values)?;
+
+    env.bind_native_func("error", error)?;
+    env.bind_native_func("raise", raise)?;
+    env.bind_native_func("error-object?", error_object_is_error_object)?;
+    env.bind_native_func("error-object-message", error_object_message)?;
+    env.bind_native_func("error-object-irritants", error_object_irritants)?;
+    env.bind_native_func("error-kind", error_object_kind)?;
+
+    env.bind_native_func("port?", port_is_port)?;
+    env.bind_native_func("input-port?", port_is_input_port)?;
+    env.bind_native_func("output-port?", port_is_output_port)?;
+    env.bind_effectful_func("read-line", port_read_line)?;
+    env.bind_effectful_func("read-char", port_read_char)?;
+    env.bind_effectful_func("read", port_read)?;
+    env.bind_native_func("open-input-string", port_open_input_string)?;
+    env.bind_native_func("open-output-string", port_open_output_string)?;
+    env.bind_native_func("get-output-string", port_get_output_string)?;
+    env.bind_effectful_func("close-port", port_close)?;
+    env.bind_effectful_func("close-input-port", port_close_input)?;
+    env.bind_effectful_func("close-output-port", port_close_output)?;
+    env.bind_native_func("eof-object", port_eof_object)?;
+    env.bind_native_func("eof-object?", port_is_eof_object)?;
+
+    env.bind_effectful_func("disassemble", disassemble)?;
+    env.bind_native_func("vm-stats", vm_stats)?;
+    env.bind_native_func("environment->alist", environment_to_alist)?;
+    env.bind_effectful_func("breakpoint", breakpoint)?;
+
+    env.bind_effectful_func("register-test", register_test)?;
+    env.bind_effectful_func("run-tests", run_tests)?;
+
+    env.mark_core_bindings(first_symbol);
+
     Ok(())
 }
 
-macro_rules! unexpected_type {
-    ($expected:expr) => {
-        Err(Error::Reason(format!(
-            "expected argument to be a {}",
-            $expected
-        )))
-    };
+/// The standard error for a call with the wrong number of arguments.
+///
+/// A `guard` catches it as an error object of kind `arity-error`.
+pub(crate) fn wrong_arg_count(who: &str, expected: &str, args: &[Expr]) -> Error {
+    error_object::condition(
+        ErrorKind::ArityError,
+        format!(
+            "{who}: wrong number of arguments, expected {expected} but got {}",
+            args.len()
+        ),
+    )
 }
 
-macro_rules! wrong_arg_count {
-    () => {
-        Err(Error::Reason(format!(
-            "[{}:{}] wrong number of arguments passed to procedure",
-            file!(),
-            line!()
-        )))
-    };
+fn args0(who: &str, args: &[Expr]) -> Result<()> {
+    match args {
+        [] => Ok(()),
+        [..] => Err(wrong_arg_count(who, "0", args)),
+    }
 }
 
-fn args1(args: &[Expr]) -> Result<&Expr> {
+fn args1<'a>(who: &str, args: &'a [Expr]) -> Result<&'a Expr> {
     match args {
         [arg1] => Ok(arg1),
-        [..] => wrong_arg_count!(),
+        [..] => Err(wrong_arg_count(who, "1", args)),
     }
 }
 
-fn args2(args: &[Expr]) -> Result<[&Expr; 2]> {
+fn args2<'a>(who: &str, args: &'a [Expr]) -> Result<[&'a Expr; 2]> {
     match args {
         [arg1, arg2] => Ok([arg1, arg2]),
-        [..] => wrong_arg_count!(),
+        [..] => Err(wrong_arg_count(who, "2", args)),
     }
 }
 
-fn args2_numbers(args: &[Expr]) -> Result<[f64; 2]> {
-    // println!("args2_numbers({:?})", args);
-    match args {
-        [Expr::Number(arg1), Expr::Number(arg2)] => Ok([*arg1, *arg2]),
-        [..] => wrong_arg_count!(),
-    }
+fn args2_numbers(who: &str, args: &[Expr]) -> Result<[f64; 2]> {
+    let [arg1, arg2] = args2(who, args)?;
+    Ok([arg1.expect_number(who, 1)?, arg2.expect_number(who, 2)?])
 }
 
 /// There is no assert in Scheme. This is our own extension to assist with unit testing.
@@ -77,20 +209,15 @@ fn args2_numbers(args: &[Expr]) -> Result<[f64; 2]> {
 /// (assert <expr> <message>?)
 /// ```
 fn ext_assert(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let expr = args
-        .get(0)
-        .ok_or_else(|| Error::Reason("expected assertion expression".to_string()))?;
-    let msg = args.get(1); // optional
+    let (expr, msg) = match args {
+        [expr] => (expr, None),
+        [expr, msg] => (expr, Some(msg.expect_str("assert", 2)?)),
+        [..] => return Err(wrong_arg_count("assert", "1 or 2", args)),
+    };
 
     if let Expr::Bool(false) = expr {
         match msg {
-            Some(Expr::String(message)) => {
-                Err(Error::Reason(format!("assertion error: {message}")))
-            }
-            Some(_) => {
-                // TODO: to_string solution that's cogent with Scheme's specification.
-                Err(Error::Reason("invalid assertion message type".to_string()))
-            }
+            Some(message) => Err(Error::Reason(format!("assertion error: {message}"))),
             None => Err(Error::Reason(format!("assertion failed: {expr:?}"))),
         }
     } else {
@@ -99,9 +226,9 @@ fn ext_assert(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
 }
 
 fn ext_assert_eq(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let [arg1, arg2] = args2(args)?;
+    let [arg1, arg2] = args2("assert-eq", args)?;
     if arg1 == arg2 {
-        Ok(Expr::List(vec![arg1.clone(), arg2.clone()]))
+        Ok(Expr::List(Rc::new([arg1.clone(), arg2.clone()])))
     } else {
         Err(Error::Reason(format!(
             "assertion failed: {} == {}",
@@ -111,176 +238,690 @@ fn ext_assert_eq(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
     }
 }
 
-fn display(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let arg0 = args1(args)?;
-    let repr = arg0.repr();
+/// Like `assert-eq` for numbers, but allows them to differ by the tolerance,
+/// which defaults to `1e-9`. NaN is close to NaN.
+///
+/// ```scheme
+/// (assert-close <number> <number> <tolerance>?)
+/// ```
+fn ext_assert_close(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "assert-close";
+    let (arg1, arg2, tolerance) = match args {
+        [arg1, arg2] => (arg1, arg2, 1e-9),
+        [arg1, arg2, tolerance] => (arg1, arg2, tolerance.expect_number(WHO, 3)?),
+        [..] => return Err(wrong_arg_count(WHO, "2 or 3", args)),
+    };
+    let (a, b) = (arg1.expect_number(WHO, 1)?, arg2.expect_number(WHO, 2)?);
+
+    let close = a == b || (a.is_nan() && b.is_nan()) || (a - b).abs() <= tolerance;
+    if close {
+        Ok(Expr::List(Rc::new([arg1.clone(), arg2.clone()])))
+    } else {
+        Err(Error::Reason(format!(
+            "assertion failed: {a} is not within {tolerance} of {b}"
+        )))
+    }
+}
+
+/// ```scheme
+/// (display <obj> <port>?)
+/// ```
+fn display(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let (obj, port) = match args {
+        [obj] => (obj, None),
+        [obj, port] => (obj, Some(port)),
+        [..] => return Err(wrong_arg_count("display", "1 or 2", args)),
+    };
+
+    write_output(env, "display", port, 2, &obj.repr().to_string())?;
+    Ok(Expr::VOID)
+}
+
+/// Write an object the way it's written in source code, so strings
+/// are quoted and characters are `#\` literals.
+///
+/// Pairs that are part of a cycle are written with datum labels,
+/// like `#0=(1 2 . #0#)`, so the output can be read back.
+///
+/// ```scheme
+/// (write <obj> <port>?)
+/// ```
+fn write(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    write_labelled(env, "write", args, DatumLabels::Cycles)
+}
+
+/// Write an object like `write`, with datum labels for every pair
+/// that appears more than once, shared or cyclic.
+///
+/// ```scheme
+/// (write-shared <obj> <port>?)
+/// ```
+fn write_shared(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    write_labelled(env, "write-shared", args, DatumLabels::Shared)
+}
+
+/// Write an object like `write`, without datum labels. Shared pairs
+/// are written out each time they appear.
+///
+/// A cyclic object is an error, as it can't be written in full.
+///
+/// ```scheme
+/// (write-simple <obj> <port>?)
+/// ```
+fn write_simple(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    if args.first().is_some_and(Expr::is_cyclic) {
+        return Err(Error::Reason(
+            "write-simple: cannot write a circular structure".to_string(),
+        ));
+    }
+    write_labelled(env, "write-simple", args, DatumLabels::Never)
+}
+
+fn write_labelled(env: &mut Env, who: &str, args: &[Expr], labels: DatumLabels) -> Result<Expr> {
+    let (obj, port) = match args {
+        [obj] => (obj, None),
+        [obj, port] => (obj, Some(port)),
+        [..] => return Err(wrong_arg_count(who, "1 or 2", args)),
+    };
+
+    let text = obj.write_repr().with_labels(labels).to_string();
+    write_output(env, who, port, 2, &text)?;
+    Ok(Expr::VOID)
+}
+
+/// The most line endings one call to `newline` writes.
+const MAX_NEWLINES: usize = 1 << 16;
+
+/// Write a line ending, or `<count>` of them.
+///
+/// ```scheme
+/// (newline <port>?)
+/// (newline <port>? <count>)
+/// ```
+///
+/// The count is an extension, not part of R7RS, and at most
+/// [`MAX_NEWLINES`] so the line endings fit in memory.
+fn newline(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "newline";
+    let (port, count) = match args {
+        [] => (None, 1),
+        [count] if count.is_number() => (None, expect_index(count, WHO, 1)?),
+        [port] => (Some(port), 1),
+        [port, count] => (Some(port), expect_index(count, WHO, 2)?),
+        [..] => return Err(wrong_arg_count(WHO, "0 to 2", args)),
+    };
+    if count > MAX_NEWLINES {
+        return Err(Error::Reason(format!(
+            "{WHO}: count {count} is more than the limit of {MAX_NEWLINES}"
+        )));
+    }
+
+    write_output(env, WHO, port, 1, &"\n".repeat(count))?;
+    Ok(Expr::VOID)
+}
+
+/// ```scheme
+/// (write-char <char> <port>?)
+/// ```
+fn write_char(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "write-char";
+    let (ch, port) = match args {
+        [ch] => (ch.expect_char(WHO, 1)?, None),
+        [ch, port] => (ch.expect_char(WHO, 1)?, Some(port)),
+        [..] => return Err(wrong_arg_count(WHO, "1 or 2", args)),
+    };
+
+    write_output(env, WHO, port, 2, ch.encode_utf8(&mut [0; 4]))?;
+    Ok(Expr::VOID)
+}
+
+/// Write the characters of a string from `<start>` to `<end>`.
+///
+/// ```scheme
+/// (write-string <string> <port>? <start>? <end>?)
+/// ```
+fn write_string(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "write-string";
+    let (string, port, range) = match args {
+        [string] => (string.expect_str(WHO, 1)?, None, &[][..]),
+        [string, port, range @ ..] if range.len() <= 2 => {
+            (string.expect_str(WHO, 1)?, Some(port), range)
+        }
+        [..] => return Err(wrong_arg_count(WHO, "1 to 4", args)),
+    };
+
+    // The indices count characters, not bytes.
+    let (start, end) = optional_range(WHO, range, 3, string.chars().count())?;
+    let text: String = string.chars().skip(start).take(end - start).collect();
+
+    write_output(env, WHO, port, 2, &text)?;
+    Ok(Expr::VOID)
+}
 
-    print!("{repr}");
+/// Call the thunk, returning the text it wrote to the environment's printer
+/// as a string, instead of printing it.
+///
+/// ```scheme
+/// (with-output-to-string <thunk>)
+/// ```
+fn with_output_to_string(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "with-output-to-string";
+    let thunk = args1(WHO, args)?.expect_callable(WHO, 1)?;
+
+    let output = Rc::new(RefCell::new(String::new()));
+    let sink = output.clone();
+    let previous = mem::replace(
+        &mut env.printer,
+        Box::new(move |text| sink.borrow_mut().push_str(text)),
+    );
+    let result = vm::Caller::new().call_with(env, thunk, |_| {});
+    env.printer = previous;
 
-    Ok(Expr::Void)
+    result?;
+    Ok(Expr::from(output.take()))
+}
+
+/// Write text to an output port argument, or to the environment's printer
+/// when the port was omitted.
+///
+/// The port is argument `position` of procedure `who`.
+fn write_output(
+    env: &mut Env,
+    who: &str,
+    port: Option<&Expr>,
+    position: usize,
+    text: &str,
+) -> Result<()> {
+    match port {
+        Some(port) => {
+            let mut port = expect_output_port(port, who, position)?;
+            let result = port.borrow_mut().write_str(text);
+            result
+        }
+        None => {
+            env.print(text);
+            Ok(())
+        }
+    }
 }
 
-fn newline(_env: &mut Env, _args: &[Expr]) -> Result<Expr> {
-    // TODO: Output port argument
-    println!();
+/// Formatted output.
+///
+/// ```scheme
+/// (format <destination> <control-string> <arg> ...)
+/// ```
+///
+/// When `<destination>` is `#f` the output is returned as a string, and when
+/// it is `#t` the output is written to the environment's printer.
+///
+/// See [`crate::format`] for the supported directives.
+fn format(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let (destination, control, rest) = match args {
+        [destination, control, rest @ ..] => (
+            destination.expect_bool("format", 1)?,
+            control.expect_str("format", 2)?,
+            rest,
+        ),
+        [..] => return Err(wrong_arg_count("format", "at least 2", args)),
+    };
+
+    let output = format::render(control, rest)?;
 
-    Ok(Expr::Void)
+    if destination {
+        env.print(&output);
+        Ok(Expr::VOID)
+    } else {
+        Ok(Expr::from(output))
+    }
 }
 
 // ----------------------------------------------------------------------------
 // Number
 
 fn number_is_number(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let arg0 = args.get(0).ok_or_else(|| {
-        Error::Reason("wrong number of arguments passed to procedure".to_string())
-    })?;
+    let arg0 = args1("number?", args)?;
     Ok(Expr::Bool(arg0.is_number()))
 }
 
-fn number_add(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    // println!("number_add({:?})", args);
+/// Whether the object is a number with no fractional part.
+///
+/// Per R7RS `(integer? 2.0)` is true. Infinities and NaN are not integers.
+fn number_is_integer(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg0 = args1("integer?", args)?;
+    Ok(Expr::Bool(arg0.as_number().is_some_and(is_integral)))
+}
 
-    let mut sum: f64 = 0.0;
+// Numbers are all stored as floats until the integer type lands. A number
+// is exact when it's a finite integer that wasn't made inexact, by a dot
+// or exponent in its literal, by `inexact`, or by arithmetic on another
+// inexact number. Rationals don't exist, so `(inexact->exact 0.5)` is an
+// error, and exact division without an integer result is inexact.
 
-    for (index, arg) in args.iter().enumerate() {
-        match arg {
-            Expr::Number(number) => sum += number,
-            _ => {
-                return Err(Error::Reason(format!(
-                    "expected argument {index} to be a number, but encountered {arg:?}"
-                )))
-            }
-        }
+#[inline]
+fn is_integral(number: f64) -> bool {
+    number.is_finite() && number.fract() == 0.0
+}
+
+fn number_is_exact(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("exact?", args)?;
+    number.expect_number("exact?", 1)?;
+    Ok(Expr::Bool(number.is_exact()))
+}
+
+fn number_is_inexact(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("inexact?", args)?;
+    number.expect_number("inexact?", 1)?;
+    Ok(Expr::Bool(!number.is_exact()))
+}
+
+/// Whether the object is an exact integer. Unlike `exact?` this accepts
+/// any object, like `integer?`.
+fn number_is_exact_integer(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg0 = args1("exact-integer?", args)?;
+    Ok(Expr::Bool(arg0.is_exact()))
+}
+
+/// The exact number closest to the argument.
+///
+/// ```scheme
+/// (exact <number>)
+/// (inexact->exact <number>)
+/// ```
+///
+/// It's an error when the number has no exact representation, which
+/// without rationals is any number with a fractional part, infinities
+/// and NaN. Negative zero becomes zero, which has no sign when exact.
+fn number_exact(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("exact", args)?.expect_number("exact", 1)?;
+    if is_integral(number) {
+        // Adding zero turns -0.0 into 0.0.
+        Ok(Expr::Number(number + 0.0))
+    } else {
+        Err(Error::Reason(format!(
+            "exact: {} has no exact representation",
+            number_repr(number, None)
+        )))
     }
+}
 
-    // println!("number_add -> {sum}");
-    Ok(Expr::Number(sum))
+/// The inexact number closest to the argument.
+///
+/// ```scheme
+/// (inexact <number>)
+/// (exact->inexact <number>)
+/// ```
+fn number_inexact(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("inexact", args)?.expect_number("inexact", 1)?;
+    Ok(Expr::Inexact(number))
 }
 
-fn number_sub(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    // println!("number_sub({:?})", args);
-
-    let mut sum: f64 = args
-        .get(0)
-        .ok_or_else(|| Error::Reason("wrong number of arguments passed to procedure".to_string()))?
-        .as_number()
-        .ok_or_else(|| {
-            Error::Reason(format!(
-                "expected first argument to be a number, but encountered {:?}",
-                &args[0]
-            ))
-        })?;
+/// Convert a number to its textual representation.
+///
+/// ```scheme
+/// (number->string <number>)
+/// (number->string <number> <radix>)
+/// ```
+///
+/// The radix must be one of 2, 8, 10 or 16. Only integers can be
+/// converted using a radix other than 10.
+fn number_to_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "number->string";
+    let (arg, radix) = match args {
+        [number] => (number, 10.0),
+        [number, radix] => (number, radix.expect_number(WHO, 2)?),
+        [..] => return Err(wrong_arg_count(WHO, "1 or 2", args)),
+    };
+    let number = arg.expect_number(WHO, 1)?;
+
+    if radix == 10.0 {
+        return Ok(Expr::from(arg.repr().to_string()));
+    }
 
-    let rest = &args[1..];
+    if number.fract() != 0.0 || !number.is_finite() {
+        return Err(Error::Reason(format!(
+            "number->string: only integers can be converted with radix {radix}"
+        )));
+    }
 
-    for (index, arg) in rest.iter().enumerate() {
-        // println!("arg [{index}] {arg:?}; sum -> {sum}");
-        match arg {
-            Expr::Number(number) => sum -= number,
-            _ => {
-                return Err(Error::Reason(format!(
-                    "expected argument {index} to be a number, but encountered {arg:?}"
-                )))
-            }
-        }
+    let sign = if number < 0.0 { "-" } else { "" };
+    let bits_per_digit = if radix == 2.0 {
+        1
+    } else if radix == 8.0 {
+        3
+    } else if radix == 16.0 {
+        4
+    } else {
+        return Err(Error::Reason(format!(
+            "number->string: expected radix to be one of 2, 8, 10 or 16, but encountered {radix}"
+        )));
+    };
+    let digits = integer_digits(number.abs(), bits_per_digit);
+
+    Ok(Expr::from(format!("{sign}{digits}")))
+}
+
+/// Digits of a non-negative integer in a radix of `2^bits_per_digit`.
+///
+/// Numbers past `u64` are written exactly too, from the bits of the float.
+fn integer_digits(magnitude: f64, bits_per_digit: usize) -> String {
+    if magnitude == 0.0 {
+        return "0".to_string();
+    }
+
+    // An integer is never below 1, so it's a normal float: the mantissa
+    // with its implicit leading bit, shifted by the exponent.
+    let bits = magnitude.to_bits();
+    let mut mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+    let mut shift = ((bits >> 52) & 0x7ff) as i64 - 1075;
+    if shift < 0 {
+        // Only zeros are shifted out of an integer.
+        mantissa >>= -shift;
+        shift = 0;
     }
+    let binary = format!("{mantissa:b}{}", "0".repeat(shift as usize));
 
-    // println!("number_sub -> {sum}");
-    Ok(Expr::Number(sum))
+    // Group the bits into digits from the right.
+    let padding = (bits_per_digit - binary.len() % bits_per_digit) % bits_per_digit;
+    let binary = format!("{}{binary}", "0".repeat(padding));
+    binary
+        .as_bytes()
+        .chunks(bits_per_digit)
+        .map(|chunk| {
+            let digit = chunk
+                .iter()
+                .fold(0, |digit, bit| digit << 1 | u32::from(bit - b'0'));
+            char::from_digit(digit, 1 << bits_per_digit).expect("digit within radix")
+        })
+        .collect()
+}
+
+fn number_add(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    fold_numbers("+", args, Some(0.0), |a| a, |a, b| a + b)
+        .map(|number| arithmetic_result(number, args))
+}
+
+fn number_sub(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    fold_numbers("-", args, None, |a| -a, |a, b| a - b)
+        .map(|number| arithmetic_result(number, args))
 }
 
 fn number_mul(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let mut sum: f64 = 1.0;
+    fold_numbers("*", args, Some(1.0), |a| a, |a, b| a * b)
+        .map(|number| arithmetic_result(number, args))
+}
 
-    for (index, arg) in args.iter().enumerate() {
-        match arg {
-            Expr::Number(number) => sum *= number,
-            _ => {
-                return Err(Error::Reason(format!(
-                    "expected argument {index} to be a number, but encountered {arg:?}"
-                )))
+/// Divide the first argument by the rest, or take the reciprocal of a
+/// single argument.
+///
+/// Dividing exact numbers by an exact zero is an error, like it is for
+/// `quotient`. With any inexact operand the result is an infinity or NaN.
+fn number_div(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let divisors = if args.len() == 1 {
+        args
+    } else {
+        args.get(1..).unwrap_or_default()
+    };
+    if args.iter().all(Expr::is_exact) && divisors.iter().any(|arg| arg.as_number() == Some(0.0)) {
+        return Err(Error::Reason("/: division by zero".to_string()));
+    }
+    fold_numbers("/", args, None, |a| 1.0 / a, |a, b| a / b)
+        .map(|number| arithmetic_result(number, args))
+}
+
+/// Magnitude from which floats no longer hold every integer, so exact
+/// results from there on may have been rounded.
+const INEXACT_MAGNITUDE: f64 = (1u64 << 53) as f64;
+
+/// The result of arithmetic on the operands, which is inexact when any
+/// of them is, or when it's too large to be sure it wasn't rounded.
+fn arithmetic_result(number: f64, operands: &[Expr]) -> Expr {
+    if number.abs() >= INEXACT_MAGNITUDE
+        || operands
+            .iter()
+            .any(|operand| matches!(operand, Expr::Inexact(_)))
+    {
+        Expr::Inexact(number)
+    } else {
+        Expr::Number(number)
+    }
+}
+
+/// Fold the arguments of a variadic arithmetic procedure from left to right.
+///
+/// Without arguments the result is the `identity`, or an arity error when
+/// the operator has none. A single argument is passed to `unary`, which is
+/// how `(- x)` negates and `(/ x)` takes the reciprocal.
+fn fold_numbers(
+    who: &str,
+    args: &[Expr],
+    identity: Option<f64>,
+    unary: fn(f64) -> f64,
+    binary: fn(f64, f64) -> f64,
+) -> Result<f64> {
+    match args {
+        [] => identity.ok_or_else(|| wrong_arg_count(who, "at least 1", args)),
+        [arg] => arg.expect_number(who, 1).map(unary),
+        [first, rest @ ..] => {
+            let mut acc = first.expect_number(who, 1)?;
+            for (index, arg) in rest.iter().enumerate() {
+                acc = binary(acc, arg.expect_number(who, index + 2)?);
             }
+            Ok(acc)
         }
     }
-
-    Ok(Expr::Number(sum))
 }
 
 // TODO: Does this short circuit, or always evaluate all arguments?
 fn number_eq(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    for ab in args.windows(2) {
-        match ab {
-            [] | [_] => break,
-            [Expr::Number(a), Expr::Number(b), ..] => {
-                if a != b {
-                    return Ok(Expr::Bool(false));
-                }
-            }
-            [..] => {
-                return Err(Error::Reason(
-                    "expected argument to be a number".to_string(),
-                ));
-            }
+    let mut result = true;
+
+    for (index, arg) in args.iter().enumerate() {
+        let number = arg.expect_number("=", index + 1)?;
+        if index > 0 && args[index - 1].as_number() != Some(number) {
+            result = false;
         }
     }
 
-    Ok(Expr::Bool(true))
+    Ok(Expr::Bool(result))
 }
 
 fn number_lt(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let [arg1, arg2] = args2_numbers(args)?;
+    let [arg1, arg2] = args2_numbers("<", args)?;
     Ok(Expr::Bool(arg1 < arg2))
 }
 
 fn number_gt(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let [arg1, arg2] = args2_numbers(args)?;
+    let [arg1, arg2] = args2_numbers(">", args)?;
     Ok(Expr::Bool(arg1 > arg2))
 }
 
 fn number_lt_eq(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
     // println!("number_lt_eq({:?})", args);
-    let [arg1, arg2] = args2_numbers(args)?;
+    let [arg1, arg2] = args2_numbers("<=", args)?;
     Ok(Expr::Bool(arg1 <= arg2))
 }
 
 fn number_gt_eq(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let [arg1, arg2] = args2_numbers(args)?;
+    let [arg1, arg2] = args2_numbers(">=", args)?;
     Ok(Expr::Bool(arg1 >= arg2))
 }
 
+/// ```scheme
+/// (max <number> <number>*)
+/// ```
+///
+/// The result is NaN when any argument is NaN.
+fn number_max(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    extremum("max", args, f64::max)
+}
+
+/// ```scheme
+/// (min <number> <number>*)
+/// ```
+///
+/// The result is NaN when any argument is NaN.
+fn number_min(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    extremum("min", args, f64::min)
+}
+
+/// Fold the arguments with `pick`, which on its own would
+/// ignore NaN arguments.
+fn extremum(who: &str, args: &[Expr], pick: fn(f64, f64) -> f64) -> Result<Expr> {
+    if args.is_empty() {
+        return Err(wrong_arg_count(who, "at least 1", args));
+    }
+    let mut acc = args[0].expect_number(who, 1)?;
+    for (index, arg) in args.iter().enumerate().skip(1) {
+        let number = arg.expect_number(who, index + 1)?;
+        acc = if acc.is_nan() || number.is_nan() {
+            f64::NAN
+        } else {
+            pick(acc, number)
+        };
+    }
+    Ok(arithmetic_result(acc, args))
+}
+
+fn number_abs(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("abs", args)?.expect_number("abs", 1)?;
+    Ok(arithmetic_result(number.abs(), args))
+}
+
+/// Integer division, rounding the quotient towards zero.
+///
+/// ```scheme
+/// (quotient <n1> <n2>)
+/// ```
+fn number_quotient(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [n1, n2] = integer_division_args("quotient", args)?;
+    Ok(arithmetic_result((n1 / n2).trunc(), args))
+}
+
+/// The remainder of `quotient`, which has the sign of the dividend.
+///
+/// ```scheme
+/// (remainder <n1> <n2>)
+/// ```
+fn number_remainder(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [n1, n2] = integer_division_args("remainder", args)?;
+    Ok(arithmetic_result(n1 % n2, args))
+}
+
+/// The remainder of division rounding the quotient down, which
+/// has the sign of the divisor.
+///
+/// ```scheme
+/// (modulo <n1> <n2>)
+/// ```
+fn number_modulo(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [n1, n2] = integer_division_args("modulo", args)?;
+    let remainder = n1 % n2;
+    if remainder != 0.0 && (remainder < 0.0) != (n2 < 0.0) {
+        Ok(arithmetic_result(remainder + n2, args))
+    } else {
+        Ok(arithmetic_result(remainder, args))
+    }
+}
+
+/// The operands of the integer division procedures, which must be
+/// integers, with a divisor other than zero.
+fn integer_division_args(who: &str, args: &[Expr]) -> Result<[f64; 2]> {
+    let [n1, n2] = args2_numbers(who, args)?;
+    for (index, number) in [n1, n2].into_iter().enumerate() {
+        if !is_integral(number) {
+            return Err(Error::Reason(format!(
+                "{who}: expected integer as argument {}, got {number}",
+                index + 1
+            )));
+        }
+    }
+    if n2 == 0.0 {
+        return Err(Error::Reason(format!("{who}: division by zero")));
+    }
+    Ok([n1, n2])
+}
+
+fn number_floor(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("floor", args)?.expect_number("floor", 1)?;
+    Ok(arithmetic_result(number.floor(), args))
+}
+
+fn number_ceiling(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("ceiling", args)?.expect_number("ceiling", 1)?;
+    Ok(arithmetic_result(number.ceil(), args))
+}
+
+fn number_truncate(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("truncate", args)?.expect_number("truncate", 1)?;
+    Ok(arithmetic_result(number.trunc(), args))
+}
+
+/// The closest integer, rounding to even when the number is halfway
+/// between two integers, as R7RS requires. `(round 2.5)` is 2.
+fn number_round(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("round", args)?.expect_number("round", 1)?;
+    Ok(arithmetic_result(number.round_ties_even(), args))
+}
+
+/// The principal square root.
+///
+/// There are no complex numbers, so the square root of
+/// a negative number is an error rather than NaN.
+fn number_sqrt(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let number = args1("sqrt", args)?.expect_number("sqrt", 1)?;
+    if number < 0.0 {
+        return Err(Error::Reason(format!(
+            "sqrt: {number} has no real square root"
+        )));
+    }
+    Ok(arithmetic_result(number.sqrt(), args))
+}
+
+/// The base raised to the power of the exponent.
+///
+/// ```scheme
+/// (expt <base> <exponent>)
+/// ```
+///
+/// `(expt z 0)` is 1 for any base, including zero. A negative base with
+/// a fractional exponent would have a complex result, so it's an error,
+/// and so is zero raised to a negative power.
+fn number_expt(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [base, exponent] = args2_numbers("expt", args)?;
+    if base < 0.0 && exponent.is_finite() && exponent.fract() != 0.0 {
+        return Err(Error::Reason(format!(
+            "expt: {base} raised to {exponent} has no real result"
+        )));
+    }
+    if base == 0.0 && exponent < 0.0 {
+        return Err(Error::Reason(format!(
+            "expt: 0 raised to the negative power {exponent}"
+        )));
+    }
+    Ok(arithmetic_result(base.powf(exponent), args))
+}
+
 // ----------------------------------------------------------------------------
 // Boolean
 
 fn boolean_is_boolean(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    let arg0 = args.get(0).ok_or_else(|| {
-        Error::Reason("wrong number of arguments passed to procedure".to_string())
-    })?;
+    let arg0 = args1("boolean?", args)?;
     Ok(Expr::Bool(arg0.is_boolean()))
 }
 
-fn boolean_not(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    if args.len() > 1 {
-        Err(Error::Reason(
-            "wrong number of arguments passed to procedure".to_string(),
-        ))
-    } else {
-        let arg0 = &args[0];
-        if let Expr::Bool(false) = arg0 {
-            Ok(Expr::Bool(true))
-        } else {
-            Ok(Expr::Bool(false))
-        }
-    }
+pub(crate) fn boolean_not(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg0 = args1("not", args)?;
+    Ok(Expr::Bool(matches!(arg0, Expr::Bool(false))))
 }
 
-fn boolean_and(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+pub(crate) fn boolean_and(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
     // Default return value if procedure has no arguments.
-    let mut expr = &Expr::Bool(true);
+    let mut expr = &Expr::TRUE;
 
     for arg in args.iter() {
         if let Expr::Bool(false) = arg {
             // If any #f is encountered, return early.
-            return Ok(Expr::Bool(false));
+            return Ok(Expr::FALSE);
         } else {
             // Storing reference to argument to avoid cloning on each iteration.
             expr = arg;
@@ -290,6 +931,1571 @@ fn boolean_and(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
     Ok(expr.clone())
 }
 
-fn boolean_or(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
-    todo!()
+pub(crate) fn boolean_or(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    for arg in args.iter() {
+        if !matches!(arg, Expr::Bool(false)) {
+            // The first truthy value is the result.
+            return Ok(arg.clone());
+        }
+    }
+
+    // Default return value if procedure has no arguments,
+    // or all arguments are false.
+    Ok(Expr::FALSE)
+}
+
+// ----------------------------------------------------------------------------
+// Equivalence
+
+/// Whether two values are the same object.
+///
+/// ```scheme
+/// (eq? <obj1> <obj2>)
+/// ```
+///
+/// The same as `eqv?`, since numbers and characters aren't boxed.
+fn eq(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [a, b] = args2("eq?", args)?;
+    Ok(Expr::Bool(a.is_eqv(b)))
+}
+
+/// Whether two values are the same object, see [`Expr::is_eqv`].
+///
+/// ```scheme
+/// (eqv? <obj1> <obj2>)
+/// ```
+fn eqv(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [a, b] = args2("eqv?", args)?;
+    Ok(Expr::Bool(a.is_eqv(b)))
+}
+
+/// Whether two values have the same structure and contents.
+///
+/// ```scheme
+/// (equal? <obj1> <obj2>)
+/// ```
+fn equal(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [a, b] = args2("equal?", args)?;
+    Ok(Expr::Bool(a.is_equal(b)))
+}
+
+// ----------------------------------------------------------------------------
+// Symbol
+
+fn symbol_is_symbol(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg = args1("symbol?", args)?;
+    Ok(Expr::Bool(arg.as_symbol().is_some()))
+}
+
+fn symbol_to_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "symbol->string";
+    let name = args1(WHO, args)?.expect_symbol(WHO, 1)?;
+    Ok(Expr::from(name))
+}
+
+/// The symbol named by the string, which may contain any characters.
+///
+/// `write` puts bars around names that wouldn't read back as the same
+/// symbol, like `|hello world|`.
+fn symbol_from_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "string->symbol";
+    let name = args1(WHO, args)?.expect_str(WHO, 1)?;
+    Ok(Expr::symbol(name))
+}
+
+// ----------------------------------------------------------------------------
+// Bytevector
+
+/// Argument `position` as a byte, an exact integer between 0 and 255.
+fn expect_byte(expr: &Expr, who: &str, position: usize) -> Result<u8> {
+    match expr.as_number() {
+        Some(number) if (0.0..=255.0).contains(&number) && number.fract() == 0.0 => {
+            Ok(number as u8)
+        }
+        _ => Err(expr.type_error(who, "byte", position)),
+    }
+}
+
+/// The largest index or length a script can pass, so a huge number isn't
+/// saturated into a length that can't be allocated.
+const MAX_INDEX: f64 = u32::MAX as f64;
+
+/// Argument `position` as an index, an exact non-negative integer.
+fn expect_index(expr: &Expr, who: &str, position: usize) -> Result<usize> {
+    match expr.as_number() {
+        Some(number) if (0.0..=MAX_INDEX).contains(&number) && number.fract() == 0.0 => {
+            Ok(number as usize)
+        }
+        _ => Err(expr.type_error(who, "index", position)),
+    }
+}
+
+/// A new sequence of `len` copies of `fill`, or an error from `who` if
+/// there isn't enough memory for it.
+fn filled<T: Clone>(who: &str, len: usize, fill: T) -> Result<Vec<T>> {
+    let mut elements = Vec::new();
+    elements
+        .try_reserve_exact(len)
+        .map_err(|_| Error::Reason(format!("{who}: length {len} is too large")))?;
+    elements.resize(len, fill);
+    Ok(elements)
+}
+
+/// The optional `<start>` and `<end>` arguments of a procedure operating
+/// on part of a sequence, at argument `position` onwards.
+///
+/// They default to the whole sequence of length `len`.
+fn optional_range(who: &str, args: &[Expr], position: usize, len: usize) -> Result<(usize, usize)> {
+    let start = match args.first() {
+        Some(start) => expect_index(start, who, position)?,
+        None => 0,
+    };
+    let end = match args.get(1) {
+        Some(end) => expect_index(end, who, position + 1)?,
+        None => len,
+    };
+    check_range(who, start, end, len)?;
+    Ok((start, end))
+}
+
+/// Check that `start` to `end` is a range within a sequence of length `len`.
+fn check_range(who: &str, start: usize, end: usize, len: usize) -> Result<()> {
+    if start > end || end > len {
+        return Err(Error::Reason(format!(
+            "{who}: range {start} to {end} is out of bounds for length {len}"
+        )));
+    }
+    Ok(())
+}
+
+/// The standard error for an index past the end of a sequence.
+fn index_out_of_range(who: &str, index: usize, len: usize) -> Error {
+    Error::Reason(format!(
+        "{who}: index {index} is out of range for length {len}"
+    ))
+}
+
+/// A new bytevector holding the arguments.
+///
+/// ```scheme
+/// (bytevector <byte> ...)
+/// ```
+fn bytevector(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let bytes = args
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| expect_byte(arg, "bytevector", index + 1))
+        .collect::<Result<Vec<u8>>>()?;
+    Ok(Expr::from(bytes))
+}
+
+/// A new bytevector of length `k`, filled with `byte` or zero.
+///
+/// ```scheme
+/// (make-bytevector <k>)
+/// (make-bytevector <k> <byte>)
+/// ```
+fn bytevector_make(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "make-bytevector";
+    let (len, fill) = match args {
+        [len] => (expect_index(len, WHO, 1)?, 0),
+        [len, fill] => (expect_index(len, WHO, 1)?, expect_byte(fill, WHO, 2)?),
+        [..] => return Err(wrong_arg_count(WHO, "1 or 2", args)),
+    };
+    Ok(Expr::from(filled(WHO, len, fill)?))
+}
+
+fn bytevector_length(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "bytevector-length";
+    let bytes = args1(WHO, args)?.expect_bytevector(WHO, 1)?;
+    let len = bytes.borrow().len();
+    Ok(Expr::Number(len as f64))
+}
+
+/// ```scheme
+/// (bytevector-u8-ref <bytevector> <k>)
+/// ```
+fn bytevector_u8_ref(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "bytevector-u8-ref";
+    let [bytes, index] = args2(WHO, args)?;
+    let bytes = bytes.expect_bytevector(WHO, 1)?.borrow();
+    let index = expect_index(index, WHO, 2)?;
+
+    bytes
+        .get(index)
+        .map(|byte| Expr::Number(*byte as f64))
+        .ok_or_else(|| index_out_of_range(WHO, index, bytes.len()))
+}
+
+/// Store a byte in the bytevector, visible through every reference to it.
+///
+/// ```scheme
+/// (bytevector-u8-set! <bytevector> <k> <byte>)
+/// ```
+fn bytevector_u8_set(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "bytevector-u8-set!";
+    let (bytes, index, byte) = match args {
+        [bytes, index, byte] => (
+            bytes.expect_bytevector(WHO, 1)?,
+            expect_index(index, WHO, 2)?,
+            expect_byte(byte, WHO, 3)?,
+        ),
+        [..] => return Err(wrong_arg_count(WHO, "3", args)),
+    };
+
+    let mut bytes = bytes.clone();
+    let mut bytes = bytes.borrow_mut();
+    let len = bytes.len();
+    let slot = bytes
+        .get_mut(index)
+        .ok_or_else(|| index_out_of_range(WHO, index, len))?;
+    *slot = byte;
+
+    Ok(Expr::VOID)
+}
+
+/// A new bytevector with the bytes from `start` up to `end`.
+///
+/// ```scheme
+/// (bytevector-copy <bytevector>)
+/// (bytevector-copy <bytevector> <start>)
+/// (bytevector-copy <bytevector> <start> <end>)
+/// ```
+fn bytevector_copy(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "bytevector-copy";
+    let (bytes, range) = match args {
+        [bytes, range @ ..] if range.len() <= 2 => (bytes.expect_bytevector(WHO, 1)?, range),
+        [..] => return Err(wrong_arg_count(WHO, "1 to 3", args)),
+    };
+
+    let bytes = bytes.borrow();
+    let (start, end) = optional_range(WHO, range, 2, bytes.len())?;
+    Ok(Expr::from(&bytes[start..end]))
+}
+
+/// A new bytevector with the bytes of the arguments, in order.
+///
+/// ```scheme
+/// (bytevector-append <bytevector> ...)
+/// ```
+fn bytevector_append(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let mut appended = Vec::new();
+    for (index, arg) in args.iter().enumerate() {
+        let bytes = arg.expect_bytevector("bytevector-append", index + 1)?;
+        appended.extend_from_slice(&bytes.borrow());
+    }
+    Ok(Expr::from(appended))
+}
+
+/// Decode the bytes from `start` up to `end` as UTF-8.
+///
+/// ```scheme
+/// (utf8->string <bytevector>)
+/// (utf8->string <bytevector> <start>)
+/// (utf8->string <bytevector> <start> <end>)
+/// ```
+fn bytevector_utf8_to_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "utf8->string";
+    let (bytes, range) = match args {
+        [bytes, range @ ..] if range.len() <= 2 => (bytes.expect_bytevector(WHO, 1)?, range),
+        [..] => return Err(wrong_arg_count(WHO, "1 to 3", args)),
+    };
+
+    let bytes = bytes.borrow();
+    let (start, end) = optional_range(WHO, range, 2, bytes.len())?;
+    match std::str::from_utf8(&bytes[start..end]) {
+        Ok(string) => Ok(Expr::from(string)),
+        Err(err) => Err(Error::Reason(format!(
+            "{WHO}: invalid UTF-8 at byte {}",
+            start + err.valid_up_to()
+        ))),
+    }
+}
+
+/// Encode the characters from `start` up to `end` as UTF-8.
+///
+/// ```scheme
+/// (string->utf8 <string>)
+/// (string->utf8 <string> <start>)
+/// (string->utf8 <string> <start> <end>)
+/// ```
+fn bytevector_string_to_utf8(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "string->utf8";
+    let (string, range) = match args {
+        [string, range @ ..] if range.len() <= 2 => (string.expect_str(WHO, 1)?, range),
+        [..] => return Err(wrong_arg_count(WHO, "1 to 3", args)),
+    };
+
+    // The range counts characters, not bytes.
+    let (start, end) = optional_range(WHO, range, 2, string.chars().count())?;
+    let encoded: String = string.chars().skip(start).take(end - start).collect();
+    Ok(Expr::from(encoded.into_bytes()))
+}
+
+// ----------------------------------------------------------------------------
+// Vector
+
+fn vector_is_vector(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg = args1("vector?", args)?;
+    Ok(Expr::Bool(matches!(arg, Expr::Vector(_))))
+}
+
+/// A new vector holding the arguments.
+///
+/// ```scheme
+/// (vector <obj> ...)
+/// ```
+fn vector(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    Ok(Expr::from(args.to_vec()))
+}
+
+/// A new vector of length `k`, filled with `fill` or the unspecified value.
+///
+/// ```scheme
+/// (make-vector <k>)
+/// (make-vector <k> <fill>)
+/// ```
+fn vector_make(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "make-vector";
+    let (len, fill) = match args {
+        [len] => (expect_index(len, WHO, 1)?, Expr::VOID),
+        [len, fill] => (expect_index(len, WHO, 1)?, fill.clone()),
+        [..] => return Err(wrong_arg_count(WHO, "1 or 2", args)),
+    };
+    Ok(Expr::from(filled(WHO, len, fill)?))
+}
+
+fn vector_length(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "vector-length";
+    let vector = args1(WHO, args)?.expect_vector(WHO, 1)?;
+    let len = vector.borrow().len();
+    Ok(Expr::Number(len as f64))
+}
+
+/// ```scheme
+/// (vector-ref <vector> <k>)
+/// ```
+fn vector_ref(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "vector-ref";
+    let [vector, index] = args2(WHO, args)?;
+    let vector = vector.expect_vector(WHO, 1)?.borrow();
+    let index = expect_index(index, WHO, 2)?;
+
+    vector
+        .get(index)
+        .cloned()
+        .ok_or_else(|| index_out_of_range(WHO, index, vector.len()))
+}
+
+/// Store a value in the vector, visible through every reference to it.
+///
+/// ```scheme
+/// (vector-set! <vector> <k> <obj>)
+/// ```
+fn vector_set(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "vector-set!";
+    let (vector, index, value) = match args {
+        [vector, index, value] => (
+            vector.expect_vector(WHO, 1)?,
+            expect_index(index, WHO, 2)?,
+            value,
+        ),
+        [..] => return Err(wrong_arg_count(WHO, "3", args)),
+    };
+
+    let mut vector = vector.clone();
+    let mut vector = vector.borrow_mut();
+    let len = vector.len();
+    let slot = vector
+        .get_mut(index)
+        .ok_or_else(|| index_out_of_range(WHO, index, len))?;
+    *slot = value.clone();
+
+    Ok(Expr::VOID)
+}
+
+/// A list of the elements from `start` up to `end`.
+///
+/// ```scheme
+/// (vector->list <vector>)
+/// (vector->list <vector> <start>)
+/// (vector->list <vector> <start> <end>)
+/// ```
+fn vector_to_list(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "vector->list";
+    let (vector, range) = match args {
+        [vector, range @ ..] if range.len() <= 2 => (vector.expect_vector(WHO, 1)?, range),
+        [..] => return Err(wrong_arg_count(WHO, "1 to 3", args)),
+    };
+
+    let vector = vector.borrow();
+    let (start, end) = optional_range(WHO, range, 2, vector.len())?;
+    Ok(Expr::List(vector[start..end].into()))
+}
+
+/// A new vector of the elements of a proper list.
+///
+/// ```scheme
+/// (list->vector <list>)
+/// ```
+fn vector_from_list(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "list->vector";
+    let elements = expect_proper_list(args1(WHO, args)?, WHO, 1)?;
+    Ok(Expr::from(elements))
+}
+
+/// The procedure and vector arguments of a higher-order vector procedure,
+/// such as `(vector-map <procedure> <vector1> <vector2> ...)`.
+///
+/// The elements are copied out of the vectors, so the procedure can
+/// change the vectors without changing the elements it's called with.
+fn procedure_and_vectors<'a>(who: &str, args: &'a [Expr]) -> Result<(&'a Expr, Vec<Vec<Expr>>)> {
+    match args {
+        [procedure, vectors @ ..] if !vectors.is_empty() => {
+            let procedure = procedure.expect_callable(who, 1)?;
+            let vectors = vectors
+                .iter()
+                .enumerate()
+                .map(|(index, vector)| Ok(vector.expect_vector(who, index + 2)?.borrow().clone()))
+                .collect::<Result<Vec<_>>>()?;
+            Ok((procedure, vectors))
+        }
+        [..] => Err(wrong_arg_count(who, "at least 2", args)),
+    }
+}
+
+/// A vector of the results of applying the procedure element-wise to the
+/// vectors, as long as the shortest one.
+///
+/// ```scheme
+/// (vector-map <procedure> <vector1> <vector2> ...)
+/// ```
+fn vector_map(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let (procedure, vectors) = procedure_and_vectors("vector-map", args)?;
+    let mut results = Vec::new();
+    call_across(env, procedure, &vectors, |value| results.push(value))?;
+    Ok(Expr::from(results))
+}
+
+/// Apply the procedure element-wise to the vectors, in order, for its
+/// side effects.
+///
+/// ```scheme
+/// (vector-for-each <procedure> <vector1> <vector2> ...)
+/// ```
+fn vector_for_each(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let (procedure, vectors) = procedure_and_vectors("vector-for-each", args)?;
+    call_across(env, procedure, &vectors, |_| {})?;
+    Ok(Expr::VOID)
+}
+
+/// Store `fill` in the elements from `start` up to `end`.
+///
+/// ```scheme
+/// (vector-fill! <vector> <fill>)
+/// (vector-fill! <vector> <fill> <start>)
+/// (vector-fill! <vector> <fill> <start> <end>)
+/// ```
+fn vector_fill(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "vector-fill!";
+    let (vector, fill, range) = match args {
+        [vector, fill, range @ ..] if range.len() <= 2 => {
+            (vector.expect_vector(WHO, 1)?, fill, range)
+        }
+        [..] => return Err(wrong_arg_count(WHO, "2 to 4", args)),
+    };
+
+    let mut vector = vector.clone();
+    let mut vector = vector.borrow_mut();
+    let (start, end) = optional_range(WHO, range, 3, vector.len())?;
+    vector[start..end].fill(fill.clone());
+
+    Ok(Expr::VOID)
+}
+
+/// A new vector with the elements from `start` up to `end`.
+///
+/// ```scheme
+/// (vector-copy <vector>)
+/// (vector-copy <vector> <start>)
+/// (vector-copy <vector> <start> <end>)
+/// ```
+fn vector_copy(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "vector-copy";
+    let (vector, range) = match args {
+        [vector, range @ ..] if range.len() <= 2 => (vector.expect_vector(WHO, 1)?, range),
+        [..] => return Err(wrong_arg_count(WHO, "1 to 3", args)),
+    };
+
+    let vector = vector.borrow();
+    let (start, end) = optional_range(WHO, range, 2, vector.len())?;
+    Ok(Expr::from(vector[start..end].to_vec()))
+}
+
+/// Copy the elements of `from`, from `start` up to `end`, into `to`
+/// starting at index `at`.
+///
+/// The vectors may be the same, with overlapping ranges. The elements
+/// end up as if they were copied out to a temporary vector first.
+///
+/// ```scheme
+/// (vector-copy! <to> <at> <from>)
+/// (vector-copy! <to> <at> <from> <start>)
+/// (vector-copy! <to> <at> <from> <start> <end>)
+/// ```
+fn vector_copy_into(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "vector-copy!";
+    let (to, at, from, range) = match args {
+        [to, at, from, range @ ..] if range.len() <= 2 => (
+            to.expect_vector(WHO, 1)?,
+            expect_index(at, WHO, 2)?,
+            from.expect_vector(WHO, 3)?,
+            range,
+        ),
+        [..] => return Err(wrong_arg_count(WHO, "3 to 5", args)),
+    };
+
+    let (start, end) = optional_range(WHO, range, 4, from.borrow().len())?;
+    let count = end - start;
+    check_range(WHO, at, at + count, to.borrow().len())?;
+
+    let mut to = to.clone();
+    if to.ptr_eq(from) {
+        // When the ranges overlap, an element may only be overwritten
+        // after it's been copied: going forwards works for copying towards
+        // the front, and going backwards for copying towards the back.
+        let mut elements = to.borrow_mut();
+        if at <= start {
+            for offset in 0..count {
+                elements[at + offset] = elements[start + offset].clone();
+            }
+        } else {
+            for offset in (0..count).rev() {
+                elements[at + offset] = elements[start + offset].clone();
+            }
+        }
+    } else {
+        to.borrow_mut()[at..at + count].clone_from_slice(&from.borrow()[start..end]);
+    }
+
+    Ok(Expr::VOID)
+}
+
+/// A new vector with the elements of the arguments, in order.
+///
+/// ```scheme
+/// (vector-append <vector> ...)
+/// ```
+fn vector_append(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let mut appended = Vec::new();
+    for (index, arg) in args.iter().enumerate() {
+        let vector = arg.expect_vector("vector-append", index + 1)?;
+        appended.extend_from_slice(&vector.borrow());
+    }
+    Ok(Expr::from(appended))
+}
+
+// ----------------------------------------------------------------------------
+// Pair
+
+/// A new pair.
+///
+/// ```scheme
+/// (cons <head> <tail>)
+/// ```
+fn pair_cons(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [head, tail] = args2("cons", args)?;
+    Ok(Expr::Pair(Handle::new((head.clone(), tail.clone()))))
+}
+
+/// The first element of a pair or non-empty list.
+///
+/// ```scheme
+/// (car <pair>)
+/// ```
+fn pair_car(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match args1("car", args)? {
+        Expr::Pair(pair) => Ok(pair.borrow().0.clone()),
+        Expr::List(list) if !list.is_empty() => Ok(list[0].clone()),
+        other => Err(other.type_error("car", ExprKind::Pair.name(), 1)),
+    }
+}
+
+/// The second element of a pair, or the rest of a non-empty list.
+///
+/// ```scheme
+/// (cdr <pair>)
+/// ```
+fn pair_cdr(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match args1("cdr", args)? {
+        Expr::Pair(pair) => Ok(pair.borrow().1.clone()),
+        Expr::List(list) if !list.is_empty() => Ok(Expr::List(list[1..].into())),
+        other => Err(other.type_error("cdr", ExprKind::Pair.name(), 1)),
+    }
+}
+
+/// Replace the first element of a pair.
+///
+/// ```scheme
+/// (set-car! <pair> <value>)
+/// ```
+fn pair_set_car(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [pair, value] = args2("set-car!", args)?;
+    let mut pair = expect_pair(pair, "set-car!", 1)?.clone();
+    pair.borrow_mut().0 = value.clone();
+    Ok(Expr::VOID)
+}
+
+/// Replace the second element of a pair.
+///
+/// ```scheme
+/// (set-cdr! <pair> <value>)
+/// ```
+fn pair_set_cdr(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [pair, value] = args2("set-cdr!", args)?;
+    let mut pair = expect_pair(pair, "set-cdr!", 1)?.clone();
+    pair.borrow_mut().1 = value.clone();
+    Ok(Expr::VOID)
+}
+
+fn pair_is_pair(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg = args1("pair?", args)?;
+    Ok(Expr::Bool(
+        matches!(arg, Expr::Pair(_)) || matches!(arg, Expr::List(list) if !list.is_empty()),
+    ))
+}
+
+/// Argument `position` as a mutable pair.
+///
+/// Lists written as literals aren't made of pairs, so they can't be mutated.
+fn expect_pair<'a>(expr: &'a Expr, who: &str, position: usize) -> Result<&'a Handle<(Expr, Expr)>> {
+    expr.as_pair()
+        .ok_or_else(|| expr.type_error(who, ExprKind::Pair.name(), position))
+}
+
+// ----------------------------------------------------------------------------
+// List
+
+/// The elements and final tail of a list, which must not be cyclic.
+///
+/// The tail of a proper list is the empty list.
+fn spine_elements(list: &Expr) -> (Vec<Expr>, Expr) {
+    let mut elements = Vec::new();
+    let mut rest = list.clone();
+    loop {
+        rest = match &rest {
+            Expr::Pair(pair) => {
+                let (head, tail) = &*pair.borrow();
+                elements.push(head.clone());
+                tail.clone()
+            }
+            Expr::List(list) => {
+                elements.extend(list.iter().cloned());
+                return (elements, Expr::NIL);
+            }
+            _ => return (elements, rest),
+        }
+    }
+}
+
+/// Argument `position` of procedure `who` as the elements of a proper list.
+///
+/// Improper and cyclic lists are reported with different errors.
+fn expect_proper_list(expr: &Expr, who: &str, position: usize) -> Result<Vec<Expr>> {
+    match expr.iter_list() {
+        Ok(elements) => elements.collect(),
+        Err(_) if expr.spine() == Spine::Cyclic => Err(circular_list(who, position)),
+        Err(_) => Err(expr.type_error(who, "proper list", position)),
+    }
+}
+
+/// The error for a cyclic list, which can't be written in the message.
+fn circular_list(who: &str, position: usize) -> Error {
+    Error::Reason(format!(
+        "{who}: expected proper list as argument {position}, got a circular list"
+    ))
+}
+
+/// Chain the elements together with pairs, ending in the tail.
+fn build_pairs(elements: Vec<Expr>, tail: Expr) -> Expr {
+    elements
+        .into_iter()
+        .rev()
+        .fold(tail, |tail, head| Expr::Pair(Handle::new((head, tail))))
+}
+
+fn list_is_null(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    Ok(Expr::Bool(args1("null?", args)?.is_null()))
+}
+
+/// Whether the argument is a proper list. Returns `#f` for cyclic lists.
+///
+/// ```scheme
+/// (list? <obj>)
+/// ```
+fn list_is_list(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg = args1("list?", args)?;
+    Ok(Expr::Bool(matches!(arg.spine(), Spine::Proper(_))))
+}
+
+/// The number of elements in a proper list.
+///
+/// ```scheme
+/// (length <list>)
+/// ```
+fn list_length(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let list = args1("length", args)?;
+    match list.spine() {
+        Spine::Proper(len) => Ok(Expr::Number(len as f64)),
+        Spine::Improper(_) => Err(list.type_error("length", "proper list", 1)),
+        Spine::Cyclic => Err(circular_list("length", 1)),
+    }
+}
+
+/// A copy of the pairs of a list, sharing the elements and keeping an improper tail.
+///
+/// Anything that isn't a list is returned as is.
+///
+/// ```scheme
+/// (list-copy <obj>)
+/// ```
+fn list_copy(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let list = args1("list-copy", args)?;
+    match list {
+        Expr::Pair(_) => match list.spine() {
+            Spine::Proper(_) => list.iter_list()?.collect(),
+            Spine::Improper(_) => {
+                let (elements, tail) = spine_elements(list);
+                Ok(build_pairs(elements, tail))
+            }
+            Spine::Cyclic => Err(circular_list("list-copy", 1)),
+        },
+        other => Ok(other.clone()),
+    }
+}
+
+/// The final pair of a non-empty list.
+///
+/// ```scheme
+/// (last-pair <list>)
+/// ```
+fn list_last_pair(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "last-pair";
+    let list = args1(WHO, args)?;
+    if list.spine() == Spine::Cyclic {
+        return Err(circular_list(WHO, 1));
+    }
+
+    let mut pair = match list {
+        Expr::Pair(pair) => pair.clone(),
+        Expr::List(elements) if !elements.is_empty() => {
+            return Ok(Expr::List(elements[elements.len() - 1..].into()))
+        }
+        other => return Err(other.type_error(WHO, ExprKind::Pair.name(), 1)),
+    };
+    loop {
+        let next = match &pair.borrow().1 {
+            Expr::Pair(next) => next.clone(),
+            Expr::List(elements) if !elements.is_empty() => {
+                return Ok(Expr::List(elements[elements.len() - 1..].into()))
+            }
+            _ => break,
+        };
+        pair = next;
+    }
+    Ok(Expr::Pair(pair))
+}
+
+/// The elements of the lists in order, ending in the last argument.
+///
+/// ```scheme
+/// (append <list1> ... <obj>)
+/// ```
+fn list_append(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let (last, lists) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(Expr::List(Rc::new([]))),
+    };
+
+    let mut elements = Vec::new();
+    for (index, list) in lists.iter().enumerate() {
+        elements.extend(expect_proper_list(list, "append", index + 1)?);
+    }
+
+    match last {
+        Expr::Nil | Expr::List(_) => {
+            elements.extend(last.expect_list("append", args.len())?.iter().cloned());
+            Ok(Expr::List(elements.into()))
+        }
+        tail => Ok(build_pairs(elements, tail.clone())),
+    }
+}
+
+/// The elements of a proper list in reverse order.
+///
+/// ```scheme
+/// (reverse <list>)
+/// ```
+fn list_reverse(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let mut elements = expect_proper_list(args1("reverse", args)?, "reverse", 1)?;
+    elements.reverse();
+    Ok(Expr::List(elements.into()))
+}
+
+/// Check that argument `position` of procedure `who` is a proper list,
+/// without collecting its elements.
+fn expect_proper_spine(expr: &Expr, who: &str, position: usize) -> Result<()> {
+    match expr.spine() {
+        Spine::Proper(_) => Ok(()),
+        Spine::Improper(_) => Err(expr.type_error(who, "proper list", position)),
+        Spine::Cyclic => Err(circular_list(who, position)),
+    }
+}
+
+/// Join the lists like [`list_append`], by changing the tail of the last
+/// pair of each non-empty list to the list after it.
+///
+/// The result is the first non-empty list, now ending in the others, and
+/// the last argument is shared rather than copied. So changing a pair of
+/// any argument later shows through the result, and the result through
+/// them. Lists written as literals, or the literal rest of a list built
+/// with `cons`, can't be changed, so they're copied into new pairs.
+///
+/// Every argument is checked before any is changed, so an improper or
+/// cyclic list is an error that leaves the others as they were. Passing
+/// the same list twice makes a cycle.
+///
+/// ```scheme
+/// (append! <list1> ... <obj>)
+/// ```
+fn list_append_in_place(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "append!";
+    let (last, lists) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(Expr::List(Rc::new([]))),
+    };
+    for (index, list) in lists.iter().enumerate() {
+        expect_proper_spine(list, WHO, index + 1)?;
+    }
+
+    // Linked from the back, so each list is joined to the finished rest.
+    let mut result = last.clone();
+    for list in lists.iter().rev().filter(|list| !list.is_null()) {
+        result = splice_onto(list, result);
+    }
+    Ok(result)
+}
+
+/// Change the end of the non-empty proper list to the tail, returning the list.
+fn splice_onto(list: &Expr, tail: Expr) -> Expr {
+    let mut pair = match list {
+        Expr::Pair(pair) => pair.clone(),
+        _ => return build_pairs(spine_elements(list).0, tail),
+    };
+    loop {
+        let next = match &pair.borrow().1 {
+            Expr::Pair(next) => next.clone(),
+            _ => break,
+        };
+        pair = next;
+    }
+
+    let mut last = pair.borrow_mut();
+    last.1 = match &last.1 {
+        // The rest is a literal list, which can't be changed.
+        Expr::List(elements) if !elements.is_empty() => build_pairs(elements.to_vec(), tail),
+        _ => tail,
+    };
+    drop(last);
+    list.clone()
+}
+
+/// Reverse the list by turning around the tails of its pairs, returning
+/// the new first pair.
+///
+/// The argument is left as the last pair of the result, a list of one
+/// element. Lists written as literals are reversed into new pairs.
+///
+/// ```scheme
+/// (reverse! <list>)
+/// ```
+fn list_reverse_in_place(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "reverse!";
+    let list = args1(WHO, args)?;
+    expect_proper_spine(list, WHO, 1)?;
+
+    let mut reversed = Expr::List(Rc::new([]));
+    let mut rest = list.clone();
+    loop {
+        rest = match &rest {
+            Expr::Pair(pair) => {
+                let next = mem::replace(&mut pair.clone().borrow_mut().1, reversed);
+                reversed = Expr::Pair(pair.clone());
+                next
+            }
+            Expr::List(elements) => {
+                // The rest is a literal list, which can't be changed.
+                return Ok(elements.iter().fold(reversed, |tail, head| {
+                    Expr::Pair(Handle::new((head.clone(), tail)))
+                }));
+            }
+            _ => return Ok(reversed),
+        }
+    }
+}
+
+/// The list after its first `k` elements.
+///
+/// The pairs are shared with the list, not copied, so changing one shows
+/// through both. The list only needs `k` elements, so it may be improper
+/// or cyclic.
+///
+/// ```scheme
+/// (list-tail <list> <k>)
+/// ```
+fn list_tail(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "list-tail";
+    let [list, k] = args2(WHO, args)?;
+    let k = expect_index(k, WHO, 2)?;
+
+    let mut rest = list.clone();
+    for skipped in 0..k {
+        rest = match &rest {
+            Expr::Pair(pair) => pair.borrow().1.clone(),
+            Expr::List(elements) if k - skipped <= elements.len() => {
+                return Ok(Expr::List(elements[k - skipped..].into()))
+            }
+            Expr::List(elements) => {
+                return Err(index_out_of_range(WHO, k, skipped + elements.len()))
+            }
+            _ => return Err(index_out_of_range(WHO, k, skipped)),
+        };
+    }
+    Ok(rest)
+}
+
+/// The procedure and list arguments of a higher-order list procedure,
+/// such as `(map <procedure> <list1> <list2> ...)`.
+fn procedure_and_lists<'a>(who: &str, args: &'a [Expr]) -> Result<(&'a Expr, Vec<Vec<Expr>>)> {
+    match args {
+        [procedure, lists @ ..] if !lists.is_empty() => {
+            let procedure = procedure.expect_callable(who, 1)?;
+            let lists = lists
+                .iter()
+                .enumerate()
+                .map(|(index, list)| expect_proper_list(list, who, index + 2))
+                .collect::<Result<Vec<_>>>()?;
+            Ok((procedure, lists))
+        }
+        [..] => Err(wrong_arg_count(who, "at least 2", args)),
+    }
+}
+
+/// Call a procedure with the elements at each position of the lists,
+/// stopping at the end of the shortest list.
+fn call_across(
+    env: &mut Env,
+    procedure: &Expr,
+    lists: &[Vec<Expr>],
+    mut each: impl FnMut(Expr),
+) -> Result<()> {
+    let len = lists.iter().map(|list| list.len()).min().unwrap_or(0);
+    let mut caller = vm::Caller::new();
+
+    for index in 0..len {
+        let value = caller.call_with(env, procedure, |operand| {
+            operand.extend(lists.iter().map(|list| list[index].clone()))
+        })?;
+        each(value);
+    }
+
+    Ok(())
+}
+
+/// A list of the results of applying the procedure element-wise to the lists.
+///
+/// ```scheme
+/// (map <procedure> <list1> <list2> ...)
+/// ```
+fn list_map(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let (procedure, lists) = procedure_and_lists("map", args)?;
+    let mut results = Vec::new();
+    call_across(env, procedure, &lists, |value| results.push(value))?;
+    Ok(Expr::List(results.into()))
+}
+
+/// Apply the procedure element-wise to the lists, in order, for its side effects.
+///
+/// ```scheme
+/// (for-each <procedure> <list1> <list2> ...)
+/// ```
+fn list_for_each(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let (procedure, lists) = procedure_and_lists("for-each", args)?;
+    call_across(env, procedure, &lists, |_| {})?;
+    Ok(Expr::VOID)
+}
+
+/// The elements of the list for which the predicate doesn't return `#f`.
+///
+/// ```scheme
+/// (filter <predicate> <list>)
+/// ```
+fn list_filter(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "filter";
+    let [predicate, list] = args2(WHO, args)?;
+    let predicate = predicate.expect_callable(WHO, 1)?;
+    let list = expect_proper_list(list, WHO, 2)?;
+
+    let mut caller = vm::Caller::new();
+    let mut results = Vec::new();
+    for element in list {
+        let keep = caller.call_1(env, predicate, element.clone())?;
+        if keep != Expr::FALSE {
+            results.push(element);
+        }
+    }
+
+    Ok(Expr::List(results.into()))
+}
+
+/// A new list of the elements, sorted by the `less?` procedure.
+///
+/// The sort is stable, so elements that are neither less than each other
+/// keep their order.
+///
+/// ```scheme
+/// (sort <list> <less?>)
+/// ```
+fn list_sort(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [list, less] = args2("sort", args)?;
+    let elements = sort_elements(env, "sort", list, less)?;
+    Ok(Expr::List(elements.into()))
+}
+
+/// Sort the list by the `less?` procedure, reusing its pairs.
+///
+/// The sorted elements are stored back into the cars of the list's pairs,
+/// which is returned. Lists written as literals have no pairs to reuse, so
+/// they're sorted into a new list, like [`list_sort`] does.
+///
+/// ```scheme
+/// (sort! <list> <less?>)
+/// ```
+fn list_sort_in_place(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let [list, less] = args2("sort!", args)?;
+    let elements = sort_elements(env, "sort!", list, less)?;
+
+    let mut pair = match list {
+        Expr::Pair(pair) => pair.clone(),
+        _ => return Ok(Expr::List(elements.into())),
+    };
+    // The comparator may have changed the list while it was being sorted,
+    // so stop wherever it ends now instead of trusting its old length.
+    let mut elements = elements.into_iter();
+    while let Some(element) = elements.next() {
+        let next = {
+            let mut pair = pair.borrow_mut();
+            pair.0 = element;
+            match &pair.1 {
+                Expr::Pair(next) => next.clone(),
+                Expr::List(list) if !list.is_empty() => {
+                    // The rest is a literal list, which can't be changed.
+                    pair.1 = Expr::List(elements.collect());
+                    break;
+                }
+                _ => break,
+            }
+        };
+        pair = next;
+    }
+
+    Ok(list.clone())
+}
+
+/// The elements of the proper list `list`, sorted by calling `less`.
+///
+/// This is a bottom-up merge sort of the indices of the elements, so the
+/// elements are only cloned once, into the result. Merge sort compares
+/// each pair of elements at most once per pass, and always finishes after
+/// `log2(len)` passes, so a comparator that is inconsistent, say by
+/// returning `#t` for both orders, only gives an odd order. An error from
+/// the comparator stops the sort right away.
+fn sort_elements(env: &mut Env, who: &str, list: &Expr, less: &Expr) -> Result<Vec<Expr>> {
+    let elements = expect_proper_list(list, who, 1)?;
+    let less = less.expect_callable(who, 2)?;
+    let len = elements.len();
+
+    let mut caller = vm::Caller::new();
+    let mut is_less = |a: usize, b: usize| -> Result<bool> {
+        let result = caller.call_with(env, less, |operand| {
+            operand.push(elements[a].clone());
+            operand.push(elements[b].clone());
+        })?;
+        Ok(result != Expr::FALSE)
+    };
+
+    let mut order: Vec<usize> = (0..len).collect();
+    let mut merged: Vec<usize> = Vec::with_capacity(len);
+    let mut width = 1;
+    while width < len {
+        merged.clear();
+        for start in (0..len).step_by(2 * width) {
+            let mid = (start + width).min(len);
+            let end = (start + 2 * width).min(len);
+            let (mut left, mut right) = (start, mid);
+            while left < mid && right < end {
+                // Take from the right only when strictly less, which keeps the sort stable.
+                if is_less(order[right], order[left])? {
+                    merged.push(order[right]);
+                    right += 1;
+                } else {
+                    merged.push(order[left]);
+                    left += 1;
+                }
+            }
+            merged.extend_from_slice(&order[left..mid]);
+            merged.extend_from_slice(&order[right..end]);
+        }
+        std::mem::swap(&mut order, &mut merged);
+        width *= 2;
+    }
+
+    Ok(order
+        .into_iter()
+        .map(|index| elements[index].clone())
+        .collect())
+}
+
+/// Call the procedure with the arguments, followed by the elements of the list.
+///
+/// ```scheme
+/// (apply <procedure> <arg> ... <list>)
+/// ```
+fn apply(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "apply";
+    let (procedure, fixed, list) = match args {
+        [procedure, fixed @ .., list] => (procedure.expect_callable(WHO, 1)?, fixed, list),
+        [..] => return Err(wrong_arg_count(WHO, "at least 2", args)),
+    };
+    let list = expect_proper_list(list, WHO, args.len())?;
+
+    vm::Caller::new().call_with(env, procedure, |operand| {
+        operand.extend_from_slice(fixed);
+        operand.extend(list);
+    })
+}
+
+// ----------------------------------------------------------------------------
+// Values
+
+/// Return the arguments as multiple values.
+///
+/// ```scheme
+/// (values <obj> ...)
+/// ```
+///
+/// A single argument is returned as is.
+fn values(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match args {
+        [value] => Ok(value.clone()),
+        _ => Ok(Expr::Values(args.into())),
+    }
+}
+
+/// Call the consumer with the values returned by the producer.
+///
+/// ```scheme
+/// (call-with-values <producer> <consumer>)
+/// ```
+fn call_with_values(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "call-with-values";
+    let [producer, consumer] = args2(WHO, args)?;
+    let producer = producer.expect_callable(WHO, 1)?;
+    let consumer = consumer.expect_callable(WHO, 2)?;
+
+    let mut caller = vm::Caller::new();
+    let produced = caller.call_with(env, producer, |_| {})?;
+    caller.call_with(env, consumer, |operand| match &produced {
+        Expr::Values(values) => operand.extend_from_slice(values),
+        value => operand.push(value.clone()),
+    })
+}
+
+// ----------------------------------------------------------------------------
+// Error
+
+/// Raise a new error object with the message and irritants.
+///
+/// ```scheme
+/// (error <message> <irritant> ...)
+/// ```
+fn error(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "error";
+    let (message, irritants) = args
+        .split_first()
+        .ok_or_else(|| wrong_arg_count(WHO, "at least 1", args))?;
+    let message = message.expect_str(WHO, 1)?;
+
+    let object = ErrorObject::new(ErrorKind::Error, message, irritants.to_vec());
+    raise(env, &[Expr::ErrorObject(Rc::new(object))])
+}
+
+/// Raise the value, to be caught by the nearest `guard`.
+///
+/// ```scheme
+/// (raise <obj>)
+/// ```
+fn raise(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let value = args1("raise", args)?;
+    let err = match value.as_error_object() {
+        Some(object) => object.to_error(),
+        None => Error::Raised {
+            kind: ErrorKind::Raise,
+            message: "raised non-error object".to_string(),
+            irritants: vec![Datum::from_expr(value)],
+        },
+    };
+    env.raised = Some(value.clone());
+    Err(err)
+}
+
+fn error_object_is_error_object(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let value = args1("error-object?", args)?;
+    Ok(Expr::Bool(value.as_error_object().is_some()))
+}
+
+fn error_object_message(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "error-object-message";
+    let object = args1(WHO, args)?.expect_error_object(WHO, 1)?;
+    Ok(Expr::String(object.message().into()))
+}
+
+fn error_object_irritants(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "error-object-irritants";
+    let object = args1(WHO, args)?.expect_error_object(WHO, 1)?;
+    Ok(Expr::List(object.irritants().into()))
+}
+
+/// The condition type of an error object, as a symbol like `type-error`.
+///
+/// ```scheme
+/// (error-kind <error-object>)
+/// ```
+fn error_object_kind(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "error-kind";
+    let object = args1(WHO, args)?.expect_error_object(WHO, 1)?;
+    Ok(Expr::symbol(object.kind().name()))
+}
+
+// ----------------------------------------------------------------------------
+// Port
+
+/// Argument `position` as an input port, returning a handle that can be mutably borrowed.
+fn expect_input_port(expr: &Expr, who: &str, position: usize) -> Result<Handle<Port>> {
+    match expr.as_port() {
+        Some(port) if port.borrow().is_input() => Ok(port.clone()),
+        _ => Err(expr.type_error(who, "input port", position)),
+    }
+}
+
+/// Argument `position` as an output port, returning a handle that can be mutably borrowed.
+fn expect_output_port(expr: &Expr, who: &str, position: usize) -> Result<Handle<Port>> {
+    match expr.as_port() {
+        Some(port) if port.borrow().is_output() => Ok(port.clone()),
+        _ => Err(expr.type_error(who, "output port", position)),
+    }
+}
+
+/// The port to read from, either the optional argument or the current input port.
+///
+/// Without a current input port, `None` means standard input.
+fn input_port_arg(env: &Env, who: &str, args: &[Expr]) -> Result<Option<Handle<Port>>> {
+    match args {
+        [] => Ok(env.input_port.clone()),
+        [port] => expect_input_port(port, who, 1).map(Some),
+        [..] => Err(wrong_arg_count(who, "0 or 1", args)),
+    }
+}
+
+fn port_is_port(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    Ok(Expr::Bool(args1("port?", args)?.as_port().is_some()))
+}
+
+fn port_is_input_port(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg = args1("input-port?", args)?;
+    Ok(Expr::Bool(
+        arg.as_port().is_some_and(|port| port.borrow().is_input()),
+    ))
+}
+
+fn port_is_output_port(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let arg = args1("output-port?", args)?;
+    Ok(Expr::Bool(
+        arg.as_port().is_some_and(|port| port.borrow().is_output()),
+    ))
+}
+
+/// The next line of input, without its line ending, or the end-of-file object.
+///
+/// ```scheme
+/// (read-line <port>?)
+/// ```
+fn port_read_line(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let line = match input_port_arg(env, "read-line", args)? {
+        Some(mut port) => port.borrow_mut().read_line()?,
+        None => {
+            let mut line = String::new();
+            let count = std::io::stdin()
+                .read_line(&mut line)
+                .map_err(|err| Error::Reason(format!("read-line: standard input: {err}")))?;
+            (count > 0).then(|| line.trim_end_matches(['\n', '\r']).to_string())
+        }
+    };
+    Ok(line.map(Expr::from).unwrap_or(Expr::Eof))
+}
+
+/// The next character of input, or the end-of-file object.
+///
+/// ```scheme
+/// (read-char <port>?)
+/// ```
+fn port_read_char(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match input_port_arg(env, "read-char", args)? {
+        Some(mut port) => Ok(port
+            .borrow_mut()
+            .read_char()?
+            .map(Expr::Char)
+            .unwrap_or(Expr::Eof)),
+        None => Err(Error::Reason(
+            "read-char: reading characters from standard input is not supported".to_string(),
+        )),
+    }
+}
+
+/// The next datum of input, like quoted data in source code, or the end-of-file object.
+///
+/// ```scheme
+/// (read <port>?)
+/// ```
+fn port_read(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match input_port_arg(env, "read", args)? {
+        Some(mut port) => {
+            let datum = port.borrow_mut().read_datum()?;
+            match datum {
+                Some(datum) => literal_datum(&datum),
+                None => Ok(Expr::Eof),
+            }
+        }
+        None => Err(Error::Reason(
+            "read: reading from standard input is not supported".to_string(),
+        )),
+    }
+}
+
+/// An input port reading the characters of a string.
+///
+/// ```scheme
+/// (open-input-string <string>)
+/// ```
+fn port_open_input_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "open-input-string";
+    let text = args1(WHO, args)?.expect_str(WHO, 1)?;
+    Ok(Expr::Port(Handle::new(Port::input_text("string", text))))
+}
+
+/// An output port collecting its output, returned by `get-output-string`.
+///
+/// ```scheme
+/// (open-output-string)
+/// ```
+fn port_open_output_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    args0("open-output-string", args)?;
+    Ok(Expr::Port(Handle::new(Port::output_string("string"))))
+}
+
+/// The text written so far to a port opened by `open-output-string`.
+///
+/// ```scheme
+/// (get-output-string <port>)
+/// ```
+fn port_get_output_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "get-output-string";
+    let port = expect_output_port(args1(WHO, args)?, WHO, 1)?;
+    let text = port.borrow().output_text()?.to_string();
+    Ok(Expr::from(text))
+}
+
+/// Close a port, flushing buffered output. Closing a closed port does nothing.
+///
+/// ```scheme
+/// (close-port <port>)
+/// ```
+fn port_close(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let mut port = args1("close-port", args)?
+        .expect_port("close-port", 1)?
+        .clone();
+    port.borrow_mut().close()?;
+    Ok(Expr::VOID)
+}
+
+fn port_close_input(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let mut port = expect_input_port(args1("close-input-port", args)?, "close-input-port", 1)?;
+    port.borrow_mut().close()?;
+    Ok(Expr::VOID)
+}
+
+fn port_close_output(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let mut port = expect_output_port(args1("close-output-port", args)?, "close-output-port", 1)?;
+    port.borrow_mut().close()?;
+    Ok(Expr::VOID)
+}
+
+fn port_eof_object(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    args0("eof-object", args)?;
+    Ok(Expr::Eof)
+}
+
+fn port_is_eof_object(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    Ok(Expr::Bool(matches!(args1("eof-object?", args)?, Expr::Eof)))
+}
+
+// ----------------------------------------------------------------------------
+// Introspection
+
+/// Print the bytecode of a closure's procedure to the environment's printer.
+///
+/// ```scheme
+/// (disassemble <closure>)
+/// ```
+fn disassemble(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let closure = args1("disassemble", args)?.expect_closure("disassemble", 1)?;
+    let text = disasm::disassemble(closure.borrow().procedure(), Some(env));
+    env.print(&text);
+    Ok(Expr::VOID)
+}
+
+/// Counters of the running virtual machine, as an association list.
+///
+/// ```scheme
+/// (vm-stats) ; => ((instructions . 42) (operand-depth . 3) (peak-operand-depth . 5) (call-depth . 1) (peak-call-depth . 2))
+/// ```
+fn vm_stats(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    args0("vm-stats", args)?;
+
+    let stats = env
+        .vm_stats()
+        .ok_or_else(|| Error::Reason("vm-stats: no machine is running".to_string()))?;
+    let entries = [
+        ("instructions", stats.instructions as f64),
+        ("operand-depth", stats.operand_depth as f64),
+        ("peak-operand-depth", stats.peak_operand_depth as f64),
+        ("call-depth", stats.call_depth as f64),
+        ("peak-call-depth", stats.peak_call_depth as f64),
+    ];
+
+    Ok(Expr::List(
+        entries
+            .into_iter()
+            .map(|(name, value)| {
+                Expr::Pair(Handle::new((Expr::Ident(name.into()), Expr::Number(value))))
+            })
+            .collect(),
+    ))
+}
+
+/// The environment's variables and their values, as an association list
+/// sorted by name.
+///
+/// ```scheme
+/// (environment->alist)
+/// (environment->alist <which>)
+/// ```
+///
+/// `<which>` is one of `'all`, the default, `'user` for the variables
+/// defined by programs, or `'core` for the core library.
+fn environment_to_alist(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "environment->alist";
+    let which = match args {
+        [] => "all",
+        [which] => which.expect_symbol(WHO, 1)?,
+        [..] => return Err(wrong_arg_count(WHO, "0 or 1", args)),
+    };
+
+    let vars: Vec<(&str, &Expr)> = match which {
+        "all" => env.iter_vars_sorted().collect(),
+        "user" => env.iter_user_vars().collect(),
+        "core" => env.iter_core_vars().collect(),
+        _ => {
+            return Err(Error::Reason(format!(
+                "{WHO}: expected all, user or core as argument 1, got {which}"
+            )))
+        }
+    };
+
+    Ok(Expr::List(
+        vars.into_iter()
+            .map(|(name, value)| Expr::Pair(Handle::new((Expr::Ident(name.into()), value.clone()))))
+            .collect(),
+    ))
+}
+
+/// Hand control to the environment's step hook before the next instruction,
+/// even when it isn't single-stepping. Does nothing without a hook.
+///
+/// ```scheme
+/// (breakpoint)
+/// ```
+fn breakpoint(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    args0("breakpoint", args)?;
+    env.request_breakpoint();
+    Ok(Expr::VOID)
+}
+
+// ----------------------------------------------------------------------------
+// Testing
+
+/// Register a thunk as a test. This is what the `define-test` form expands to.
+///
+/// ```scheme
+/// (register-test <name> <thunk>)
+/// ```
+fn register_test(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "register-test";
+    let [name, thunk] = args2(WHO, args)?;
+    let name = name.expect_str(WHO, 1)?.to_string();
+    let thunk = thunk.expect_closure(WHO, 2)?.clone();
+
+    env.tests.push((name, thunk));
+
+    Ok(Expr::VOID)
+}
+
+/// Run every registered test, printing a line per test and a summary.
+///
+/// A failing test doesn't stop the tests after it, but running out of
+/// a budget like [`EvalOptions::fuel`](crate::EvalOptions::fuel) does.
+///
+/// ```scheme
+/// (run-tests) ; => #t when all tests passed
+/// ```
+fn run_tests(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    args0("run-tests", args)?;
+
+    let tests = env.tests.clone();
+    let mut passed = 0;
+
+    for (name, thunk) in &tests {
+        match vm::call_with_env(env, thunk.clone(), &[]) {
+            Ok(_) => {
+                passed += 1;
+                env.print(&format!("test {name} ... ok\n"));
+            }
+            Err(err @ Error::Limit(_)) => return Err(err),
+            Err(err) => env.print(&format!("test {name} ... FAILED: {err}\n")),
+        }
+    }
+
+    env.print(&format!("test result: {passed}/{} passed\n", tests.len()));
+
+    Ok(Expr::Bool(passed == tests.len()))
 }
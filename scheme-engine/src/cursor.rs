@@ -56,6 +56,7 @@ impl<'src> Cursor<'src> {
 
     /// Current position of unicode character in the iteration.
     #[inline]
+    #[allow(dead_code)]
     pub fn pos(&self) -> usize {
         self.current().0
     }
@@ -68,6 +69,7 @@ impl<'src> Cursor<'src> {
 
     /// Current unicode character in the iteration.
     #[inline]
+    #[allow(dead_code)]
     pub fn char(&self) -> char {
         self.current().1
     }
@@ -103,7 +105,7 @@ impl<'src> Cursor<'src> {
     pub fn at_end(&self) -> bool {
         // The iterator may be exhausted, there could be a previous
         // character stored in the state.
-        matches!(self.prev, None)
+        self.prev.is_none()
     }
 
     /// Advances the cursor to the next character.
@@ -154,8 +156,8 @@ mod test {
 
     #[test]
     fn test_eof() {
-        assert_eq!(Cursor::new("").at_end(), false);
-        assert_eq!(Cursor::new("abc").at_end(), false);
+        assert!(!Cursor::new("").at_end());
+        assert!(!Cursor::new("abc").at_end());
 
         // Exhausted cursor must return EOF
         let mut cursor = Cursor::new("a");
@@ -0,0 +1,194 @@
+//! Copies of values that can leave the environment.
+//!
+//! An [`Expr`] holds reference counted handles, so it can't be sent to
+//! another thread, and neither could an [`Error`] holding one. A [`Datum`]
+//! is a deep copy of the data in a value, like numbers, strings, symbols,
+//! lists and vectors, which keeps its structure and types.
+//!
+//! Values that aren't data, like procedures and ports, are kept as their
+//! written text, and so are lists and vectors that contain themselves, or
+//! are nested deeper than [`MAX_DEPTH`].
+//!
+//! [`Error`]: crate::Error
+use std::fmt;
+use std::rc::Rc;
+
+use crate::expr::Expr;
+use crate::handle::Handle;
+
+/// The deepest nesting of lists and vectors copied, beyond which the
+/// rest is kept as text.
+pub const MAX_DEPTH: usize = 64;
+
+/// A value copied out of the environment, see the [module](self) docs.
+///
+/// ```
+/// use scheme_engine::{Datum, Expr};
+///
+/// let list = Expr::list_from_iter([Expr::symbol("a"), Expr::from("b")]);
+/// let datum = Datum::from_expr(&list);
+/// assert_eq!(
+///     datum,
+///     Datum::List(vec![Datum::Symbol("a".into()), Datum::String("b".into())])
+/// );
+/// assert_eq!(datum.to_string(), r#"(a "b")"#);
+/// assert!(datum.to_expr().is_equal(&list));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Datum {
+    /// The empty list.
+    Null,
+    Void,
+    Eof,
+    Bool(bool),
+    /// A number, exact when it's a finite integer, like [`Expr::Number`].
+    Number(f64),
+    /// A number that's inexact even when it's an integer.
+    Inexact(f64),
+    String(String),
+    Char(char),
+    Symbol(String),
+    /// A proper list.
+    List(Vec<Datum>),
+    /// A list ending in something other than the empty list, like `(1 2 . 3)`.
+    DottedList(Vec<Datum>, Box<Datum>),
+    Vector(Vec<Datum>),
+    Bytevector(Vec<u8>),
+    /// A value that isn't data, or can't be copied, as it's written.
+    Opaque(String),
+}
+
+impl Datum {
+    /// A copy of the value.
+    pub fn from_expr(expr: &Expr) -> Datum {
+        if expr.is_cyclic() {
+            return Datum::Opaque(expr.write_repr().to_string());
+        }
+        Self::copy(expr, 0)
+    }
+
+    fn copy(expr: &Expr, depth: usize) -> Datum {
+        if depth >= MAX_DEPTH {
+            return Datum::Opaque(expr.write_repr().to_string());
+        }
+
+        match expr {
+            Expr::Nil => Datum::Null,
+            Expr::Void => Datum::Void,
+            Expr::Eof => Datum::Eof,
+            Expr::Bool(value) => Datum::Bool(*value),
+            Expr::Number(number) => Datum::Number(*number),
+            Expr::Inexact(number) => Datum::Inexact(*number),
+            Expr::String(text) => Datum::String(text.to_string()),
+            Expr::Char(ch) => Datum::Char(*ch),
+            Expr::Ident(name) => Datum::Symbol(name.to_string()),
+            Expr::Quote(quoted) => Datum::List(vec![
+                Datum::Symbol("quote".to_string()),
+                Self::copy(quoted, depth + 1),
+            ]),
+            Expr::List(_) | Expr::Pair(_) => Self::copy_list(expr, depth),
+            Expr::Vector(elements) => Datum::Vector(
+                elements
+                    .borrow()
+                    .iter()
+                    .map(|element| Self::copy(element, depth + 1))
+                    .collect(),
+            ),
+            Expr::Bytevector(bytes) => Datum::Bytevector(bytes.borrow().clone()),
+            _ => Datum::Opaque(expr.write_repr().to_string()),
+        }
+    }
+
+    /// Copy a list, which isn't cyclic.
+    fn copy_list(list: &Expr, depth: usize) -> Datum {
+        let mut elements = Vec::new();
+        let mut rest = list.clone();
+        loop {
+            rest = match &rest {
+                Expr::Pair(pair) => {
+                    let (head, tail) = &*pair.borrow();
+                    elements.push(Self::copy(head, depth + 1));
+                    tail.clone()
+                }
+                Expr::List(list) => {
+                    elements.extend(list.iter().map(|element| Self::copy(element, depth + 1)));
+                    Expr::Nil
+                }
+                _ => break,
+            };
+        }
+
+        if rest.is_null() {
+            Datum::List(elements)
+        } else {
+            Datum::DottedList(elements, Box::new(Self::copy(&rest, depth + 1)))
+        }
+    }
+
+    /// The value the datum was copied from, or an equal one.
+    ///
+    /// Lists are rebuilt as immutable lists, like quoted ones, unless
+    /// they're dotted. An opaque value can't be rebuilt, so it becomes
+    /// the string of its text.
+    pub fn to_expr(&self) -> Expr {
+        match self {
+            Datum::Null => Expr::Nil,
+            Datum::Void => Expr::Void,
+            Datum::Eof => Expr::Eof,
+            Datum::Bool(value) => Expr::Bool(*value),
+            Datum::Number(number) => Expr::Number(*number),
+            Datum::Inexact(number) => Expr::Inexact(*number),
+            Datum::String(text) | Datum::Opaque(text) => Expr::from(text.as_str()),
+            Datum::Char(ch) => Expr::Char(*ch),
+            Datum::Symbol(name) => Expr::symbol(name),
+            Datum::List(elements) => {
+                Expr::List(elements.iter().map(Datum::to_expr).collect::<Rc<[Expr]>>())
+            }
+            Datum::DottedList(elements, tail) => {
+                elements.iter().rev().fold(tail.to_expr(), |tail, head| {
+                    Expr::Pair(Handle::new((head.to_expr(), tail)))
+                })
+            }
+            Datum::Vector(elements) => {
+                Expr::from(elements.iter().map(Datum::to_expr).collect::<Vec<_>>())
+            }
+            Datum::Bytevector(bytes) => Expr::from(bytes.clone()),
+        }
+    }
+}
+
+/// Written like `write` writes the value, with opaque values as their text.
+impl fmt::Display for Datum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_all(f: &mut fmt::Formatter<'_>, elements: &[Datum]) -> fmt::Result {
+            for (index, element) in elements.iter().enumerate() {
+                if index > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{element}")?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Datum::Null => write!(f, "()"),
+            Datum::List(elements) => {
+                write!(f, "(")?;
+                write_all(f, elements)?;
+                write!(f, ")")
+            }
+            Datum::DottedList(elements, tail) => {
+                write!(f, "(")?;
+                write_all(f, elements)?;
+                write!(f, " . {tail})")
+            }
+            Datum::Vector(elements) => {
+                write!(f, "#(")?;
+                write_all(f, elements)?;
+                write!(f, ")")
+            }
+            Datum::Opaque(text) => write!(f, "{text}"),
+            datum => write!(f, "{}", datum.to_expr().write_repr()),
+        }
+    }
+}
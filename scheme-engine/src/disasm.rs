@@ -0,0 +1,131 @@
+//! Bytecode disassembler.
+use std::fmt::Write;
+
+use crate::env::{Env, ProcId};
+use crate::expr::{Constants, Proc};
+use crate::opcode::Op;
+
+/// Render a procedure's bytecode as human readable text, one instruction per line.
+///
+/// When the procedure's environment is given, operands are annotated with
+/// the names of environment variables and the values of constants, and the
+/// bodies of procedures instantiated by [`Op::CreateClosure`] are listed
+/// after their parent, indented one level deeper.
+pub fn disassemble(proc: &Proc, env: Option<&Env>) -> String {
+    let mut output = String::new();
+    let mut visited = Vec::new();
+    write_proc(&mut output, proc, env, 0, &mut visited);
+    output
+}
+
+fn write_proc(
+    output: &mut String,
+    proc: &Proc,
+    env: Option<&Env>,
+    depth: usize,
+    visited: &mut Vec<ProcId>,
+) {
+    let indent = "  ".repeat(depth);
+    let mut nested = Vec::new();
+
+    for (index, op) in proc.bytecode().iter().enumerate() {
+        // Writing to a String can't fail.
+        let _ = write!(output, "{indent}  {index:>6} : {op:?}");
+
+        if let Some(comment) = annotate(proc, env, op) {
+            let _ = write!(output, "  ; {comment}");
+        }
+
+        output.push('\n');
+
+        if let Op::CreateClosure(proc_id) = op {
+            if !visited.contains(proc_id) {
+                visited.push(*proc_id);
+                nested.push(*proc_id);
+            }
+        }
+    }
+
+    let Some(env) = env else {
+        return;
+    };
+
+    for proc_id in nested {
+        if let Some(child) = env.procedures.get(proc_id.as_usize()) {
+            let _ = writeln!(
+                output,
+                "{indent}  procedure #{} (arity {}):",
+                proc_id.as_usize(),
+                child.sig.arity
+            );
+            write_proc(output, child, Some(env), depth + 1, visited);
+        }
+    }
+}
+
+/// Comment describing the instruction's operand, if it can be resolved.
+fn annotate(proc: &Proc, env: Option<&Env>, op: &Op) -> Option<String> {
+    match op {
+        Op::LoadEnvVar(symbol) | Op::StoreEnvVar(symbol) | Op::DefineEnvVar(symbol) => env
+            .and_then(|env| env.var_name(*symbol))
+            .map(str::to_string),
+        Op::PushConstant(constant_id)
+        | Op::PushConstantCopy(constant_id)
+        | Op::MatchConstant(constant_id)
+        | Op::UnpackValues(constant_id) => {
+            let constants = match (&proc.constants, env) {
+                (Constants::Local(constants), _) => constants,
+                (Constants::Shared, Some(env)) => &env.constants[..],
+                (Constants::Shared, None) => return None,
+            };
+            constants
+                .get(constant_id.as_usize())
+                .map(|value| value.write_repr().to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_disassemble() {
+        let env = crate::new_env().expect("create core environment");
+        let expr = parse(r#"(define x "one") (display x)"#, true).expect("parse");
+        let closure = crate::compile(env.clone(), &expr).expect("compile");
+
+        let text = disassemble(closure.borrow().procedure(), Some(&env.borrow()));
+        assert!(
+            text.contains(r#"PushConstant(ConstantId(0))  ; "one""#),
+            "{text}"
+        );
+        assert!(text.contains("; display"), "{text}");
+        assert!(text.contains("; x"), "{text}");
+        assert!(text.contains("CallNative"), "{text}");
+
+        // Without the environment there is nothing to annotate env-vars with.
+        let text = disassemble(closure.borrow().procedure(), None);
+        assert!(!text.contains("; display"), "{text}");
+    }
+
+    #[test]
+    fn test_disassemble_nested() {
+        let env = crate::new_env().expect("create core environment");
+        let expr = parse("(define f (lambda (x) (+ x 1)))", true).expect("parse");
+        let closure = crate::compile(env.clone(), &expr).expect("compile");
+
+        let text = disassemble(closure.borrow().procedure(), Some(&env.borrow()));
+        assert!(text.contains("CreateClosure"), "{text}");
+        assert!(text.contains("(arity 1):"), "{text}");
+        assert!(text.contains(": LoadLocalVar"), "{text}");
+        assert!(text.contains("; +"), "{text}");
+        assert!(text.contains("CallNative"), "{text}");
+
+        // Nested bodies are looked up in the environment.
+        let text = disassemble(closure.borrow().procedure(), None);
+        assert!(!text.contains("LoadLocalVar"), "{text}");
+    }
+}
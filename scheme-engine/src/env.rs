@@ -1,10 +1,17 @@
 //! Execution environment.
+use std::mem;
+use std::ptr::NonNull;
 use std::rc::Rc;
 
 use crate::declare_id;
 use crate::error::{Error, Result};
-use crate::expr::{Expr, NativeFunc, Proc};
+use crate::expr::{Closure, Expr, NativeFunc, Proc};
+use crate::handle::Handle;
+use crate::limits::{MAX_CONSTANTS, MAX_PROCEDURES, MAX_SYMBOLS};
+use crate::port::Port;
+use crate::source_map::SourceMap;
 use crate::symbol::{SymbolId, SymbolTable};
+use crate::vm::{StepControl, StepEvent, StepHook, Vm, VmStats};
 
 declare_id!(
     /// Constant value identifier.
@@ -37,12 +44,65 @@ declare_id!(
     pub struct ProcId(u16)
 );
 
+/// Caps on how much an environment grows, against scripts that define
+/// variables or compile procedures without end.
+///
+/// Unlimited by default, apart from the limits of the bytecode format.
+/// Going over a limit is an error, which fails the compilation or the
+/// native function that declared the variable. The environment stays
+/// usable for what was declared before.
+///
+/// ```
+/// use scheme_engine::{EnvLimits, Expr};
+///
+/// let env = scheme_engine::new_env()?;
+/// let core_count = env.borrow().iter_vars_sorted().count();
+/// env.clone().borrow_mut().set_limits(EnvLimits {
+///     max_symbols: Some(core_count + 1),
+///     max_procedures: None,
+/// });
+///
+/// let program = scheme_engine::parse_program("(define a 1) (define b 2)")?;
+/// let err = scheme_engine::compile(env.clone(), &program).unwrap_err();
+/// assert!(err.to_string().contains("environment symbol limit exceeded"));
+/// # Ok::<(), scheme_engine::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EnvLimits {
+    /// Number of global variables, including the core library's.
+    pub max_symbols: Option<usize>,
+    /// Number of procedures compiled into the environment, one per
+    /// `lambda` in the source of each compiled program.
+    pub max_procedures: Option<usize>,
+}
+
+impl EnvLimits {
+    fn symbols(&self) -> usize {
+        self.max_symbols
+            .map_or(MAX_SYMBOLS, |max| max.min(MAX_SYMBOLS))
+    }
+
+    fn procedures(&self) -> usize {
+        self.max_procedures
+            .map_or(MAX_PROCEDURES, |max| max.min(MAX_PROCEDURES))
+    }
+}
+
+/// Destination for text written by procedures like `display`.
+pub type Printer = Box<dyn FnMut(&str)>;
+
+/// Observer of top-level definitions, installed with [`Env::set_define_hook`].
+pub type DefineHook = Box<dyn FnMut(&str, &Expr)>;
+
 pub struct Env {
     /// Table of values which do not change during runtime.
     ///
-    /// Includes literals like booleans, numbers and strings, but
-    /// also defined procedures.
-    constants: (),
+    /// Includes literals like numbers and strings. This is the shared
+    /// constant pool, only used by procedures compiled with
+    /// [`CompileOptions::shared_constants`] enabled.
+    ///
+    /// [`CompileOptions::shared_constants`]: crate::CompileOptions::shared_constants
+    pub(crate) constants: Vec<Expr>,
 
     /// Table of values which can be mutated during runtime.
     ///
@@ -50,23 +110,258 @@ pub struct Env {
     variables: SymbolTable,
     var_values: Vec<Expr>,
 
+    /// Variables bound to native functions with side effects,
+    /// indexed by symbol.
+    effectful: Vec<bool>,
+
+    /// Variables bound by [`init_core`](crate::init_core), indexed by symbol.
+    core_bindings: Vec<bool>,
+
+    /// Where each variable was last defined by a program, indexed by symbol.
+    def_sites: Vec<Option<DefSite>>,
+
     /// Table of procedure prototypes that were declared in this environment.
     pub(crate) procedures: Vec<Rc<Proc>>,
+
+    /// The machine running in this environment, while it's calling a
+    /// native function, so procedures called back run on its stacks.
+    pub(crate) machine: Option<NonNull<Vm>>,
+
+    /// Where output procedures write their text.
+    pub(crate) printer: Printer,
+
+    /// Where input procedures read from when not given a port,
+    /// or standard input when `None`.
+    pub(crate) input_port: Option<Handle<Port>>,
+
+    /// Debugger callback, see [`Env::set_step_hook`].
+    pub(crate) step_hook: Option<StepHook>,
+
+    /// Called after each top-level definition, see [`Env::set_define_hook`].
+    define_hook: Option<DefineHook>,
+
+    /// Call the step hook before every instruction.
+    ///
+    /// Checked by the instruction loop, so evaluation without
+    /// a debugger only pays for reading a flag.
+    pub(crate) stepping: bool,
+
+    /// A `(breakpoint)` was hit, so the next step event reports it.
+    pub(crate) breakpoint: bool,
+
+    /// Tests registered by `define-test`, in definition order.
+    pub(crate) tests: Vec<(String, Handle<Closure>)>,
+
+    /// The value passed to the last `raise`, until a `guard` catches it.
+    ///
+    /// Errors can't hold values, so the raised value waits here while
+    /// its error unwinds the machine.
+    pub(crate) raised: Option<Expr>,
+
+    /// Source texts of the programs loaded into this environment.
+    sources: SourceMap,
+
+    /// See [`Env::set_limits`].
+    limits: EnvLimits,
+}
+
+/// Where a global variable was defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefSite {
+    /// Name of the source the definition was compiled from.
+    ///
+    /// See [`CompileOptions::source_name`](crate::CompileOptions::source_name).
+    pub source: Option<String>,
+}
+
+/// A snapshot of an environment's variables, to create new
+/// environments from without binding each variable again.
+///
+/// See [`crate::new_env`].
+pub(crate) struct EnvTemplate {
+    variables: SymbolTable,
+    var_values: Vec<Expr>,
+    effectful: Vec<bool>,
+    core_bindings: Vec<bool>,
+}
+
+impl EnvTemplate {
+    /// Build a template by running the given library initializers on an empty environment.
+    pub(crate) fn build(init: impl FnOnce(&mut Env) -> Result<()>) -> Result<Self> {
+        let mut env = Env::new();
+        init(&mut env)?;
+
+        // Procedures and shared constants belong to the environment they
+        // were compiled in, so a template can only hold natives and data.
+        debug_assert!(env.procedures.is_empty() && env.constants.is_empty());
+
+        let Env {
+            variables,
+            var_values,
+            effectful,
+            core_bindings,
+            ..
+        } = env;
+        Ok(Self {
+            variables,
+            var_values,
+            effectful,
+            core_bindings,
+        })
+    }
+
+    /// Create a new environment holding copies of the template's variables.
+    pub(crate) fn instantiate(&self) -> Env {
+        Env {
+            variables: self.variables.clone(),
+            var_values: self.var_values.clone(),
+            effectful: self.effectful.clone(),
+            core_bindings: self.core_bindings.clone(),
+            ..Env::new()
+        }
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Env {
     /// Create a new empty environment.
     pub fn new() -> Self {
         Env {
-            constants: (),
+            constants: Vec::new(),
 
             variables: SymbolTable::new(),
             var_values: Vec::new(),
+            effectful: Vec::new(),
+            core_bindings: Vec::new(),
+            def_sites: Vec::new(),
 
             procedures: Vec::new(),
+
+            machine: None,
+
+            printer: Box::new(|text| print!("{text}")),
+            input_port: None,
+
+            step_hook: None,
+            define_hook: None,
+            stepping: false,
+            breakpoint: false,
+
+            tests: Vec::new(),
+            raised: None,
+
+            sources: SourceMap::new(),
+
+            limits: EnvLimits::default(),
         }
     }
 
+    /// Cap the number of variables and procedures in the environment.
+    ///
+    /// Limits below the current counts keep what's there, and fail
+    /// anything new.
+    pub fn set_limits(&mut self, limits: EnvLimits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &EnvLimits {
+        &self.limits
+    }
+
+    /// Replace the destination of output procedures, which is standard output by default.
+    ///
+    /// Hosts without standard output, like a browser, can capture the text here.
+    pub fn set_printer(&mut self, printer: impl FnMut(&str) + 'static) {
+        self.printer = Box::new(printer);
+    }
+
+    /// Write text to the environment's printer.
+    pub fn print(&mut self, text: &str) {
+        (self.printer)(text)
+    }
+
+    /// Install a debugger callback, invoked before each instruction that
+    /// runs in this environment.
+    ///
+    /// The hook starts out single-stepping. Returning [`StepControl::Continue`]
+    /// runs freely until the next `(breakpoint)`, which resumes stepping.
+    ///
+    /// The environment is borrowed while the hook runs, so the hook
+    /// must not access it.
+    pub fn set_step_hook(&mut self, hook: impl FnMut(&StepEvent) -> StepControl + 'static) {
+        self.step_hook = Some(Box::new(hook));
+        self.stepping = true;
+    }
+
+    /// Remove the debugger callback.
+    pub fn clear_step_hook(&mut self) {
+        self.step_hook = None;
+        self.stepping = false;
+    }
+
+    /// Install a callback, invoked with the name and value of each variable
+    /// that a top-level `define` or `define-values` stores into this
+    /// environment, in the order they're stored. That's the order of the
+    /// program, except that `define-values` stores its last variable first.
+    ///
+    /// Assignments with `set!` don't call it, and neither do internal
+    /// definitions, which are local to their body.
+    ///
+    /// The hook runs after the value is stored, while the environment is
+    /// borrowed, so it must not access the environment. A panic in the hook
+    /// unwinds out of the evaluation, leaving the definition in place and
+    /// the environment usable.
+    pub fn set_define_hook(&mut self, hook: impl FnMut(&str, &Expr) + 'static) {
+        self.define_hook = Some(Box::new(hook));
+    }
+
+    /// Remove the definition callback.
+    pub fn clear_define_hook(&mut self) {
+        self.define_hook = None;
+    }
+
+    /// Store the value of a top-level definition, and tell the define hook.
+    pub(crate) fn define_var(&mut self, symbol: SymbolId, value: Expr) -> Result<()> {
+        self.set_var(symbol, value)?;
+        if let Some(hook) = self.define_hook.as_mut() {
+            let name = self.variables.name(symbol).unwrap_or_default();
+            hook(name, &self.var_values[symbol.as_usize()]);
+        }
+        Ok(())
+    }
+
+    /// Resume stepping at the next instruction, if a step hook is installed.
+    pub(crate) fn request_breakpoint(&mut self) {
+        if self.step_hook.is_some() {
+            self.stepping = true;
+            self.breakpoint = true;
+        }
+    }
+
+    /// Source texts of the programs loaded into this environment,
+    /// for locating errors.
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    pub fn sources_mut(&mut self) -> &mut SourceMap {
+        &mut self.sources
+    }
+
+    /// Counters of the machine running in this environment, while it's
+    /// calling a native function.
+    pub fn vm_stats(&self) -> Option<VmStats> {
+        // SAFETY: The machine doesn't run while it's lent to the native,
+        //         and procedures it calls back take it out of the environment.
+        self.machine
+            .map(|machine| unsafe { machine.as_ref() }.running_stats())
+    }
+
     pub fn lookup_var(&self, name: &str) -> Option<&Expr> {
         self.resolve_var(name)
             .and_then(|symbol| self.get_var(symbol))
@@ -76,6 +371,11 @@ impl Env {
         self.variables.resolve(name)
     }
 
+    /// The name of the variable bound to the given symbol.
+    pub fn var_name(&self, symbol: SymbolId) -> Option<&str> {
+        self.variables.name(symbol)
+    }
+
     pub fn get_var(&self, symbol: SymbolId) -> Option<&Expr> {
         self.var_values.get(symbol.as_usize())
     }
@@ -91,23 +391,209 @@ impl Env {
         }
     }
 
-    pub fn intern_var(&mut self, name: &str) -> SymbolId {
+    /// Declare a variable if needed, returning its symbol.
+    ///
+    /// Fails when a new variable would go over the symbol limit.
+    /// See [`Env::set_limits`].
+    pub fn intern_var(&mut self, name: &str) -> Result<SymbolId> {
+        if self.variables.resolve(name).is_none() {
+            self.check_symbol_limit()?;
+        }
         let symbol = self.variables.intern_symbol(name);
-        grow_table(&mut self.var_values, symbol.as_usize());
-        symbol
+        grow_vars(&mut self.var_values, symbol.as_usize());
+        Ok(symbol)
+    }
+
+    /// Declare a variable if needed, and set its value.
+    pub fn define(&mut self, name: &str, value: Expr) -> Result<SymbolId> {
+        let symbol = self.intern_var(name)?;
+        self.var_values[symbol.as_usize()] = value;
+        Ok(symbol)
+    }
+
+    /// The value of a variable, declaring it with the default value
+    /// first if it isn't declared yet.
+    ///
+    /// Natives can keep state for the host in a variable this way:
+    ///
+    /// ```
+    /// use scheme_engine::{Env, Expr, Result};
+    ///
+    /// fn tick(env: &mut Env, _args: &[Expr]) -> Result<Expr> {
+    ///     let count = env.get_or_define("tick-count", || Expr::Number(0.0))?;
+    ///     *count = Expr::Number(count.as_number().unwrap_or(0.0) + 1.0);
+    ///     Ok(count.clone())
+    /// }
+    /// ```
+    pub fn get_or_define(
+        &mut self,
+        name: &str,
+        default: impl FnOnce() -> Expr,
+    ) -> Result<&mut Expr> {
+        let symbol = match self.resolve_var(name) {
+            Some(symbol) => symbol,
+            None => self.define(name, default())?,
+        };
+        Ok(&mut self.var_values[symbol.as_usize()])
+    }
+
+    /// Replace the value of a declared variable with the result of
+    /// calling `f` on the old value.
+    pub fn update(&mut self, symbol: SymbolId, f: impl FnOnce(Expr) -> Expr) -> Result<()> {
+        let slot = self
+            .var_values
+            .get_mut(symbol.as_usize())
+            .ok_or_else(|| Error::Reason(format!("variable is not declared: {symbol:?}")))?;
+        let value = mem::replace(slot, Expr::Void);
+        *slot = f(value);
+        Ok(())
+    }
+
+    /// Move the value out of a variable, leaving `#!void` in its place.
+    ///
+    /// Returns `None` when the variable isn't declared.
+    pub fn take_var(&mut self, symbol: SymbolId) -> Option<Expr> {
+        let slot = self.var_values.get_mut(symbol.as_usize())?;
+        Some(mem::replace(slot, Expr::Void))
+    }
+
+    /// Add a value to the shared constant pool, reusing an existing
+    /// slot when an equal value is already stored.
+    pub(crate) fn add_constant(&mut self, value: Expr) -> Result<ConstantId> {
+        intern_constant(&mut self.constants, value)
     }
 
-    pub(crate) fn add_procedure(&mut self, procedure: Proc) -> ProcId {
+    /// Store a compiled procedure prototype, which closures are created from.
+    ///
+    /// Fails when it would go over the procedure limit.
+    pub(crate) fn add_procedure(&mut self, procedure: Proc) -> Result<ProcId> {
         let index = self.procedures.len();
+        let max = self.limits.procedures();
+        if index >= max {
+            return Err(Error::Reason(format!(
+                "environment procedure limit exceeded ({max})"
+            )));
+        }
         self.procedures.push(Rc::new(procedure));
-        ProcId::new(index as u16)
+        Ok(ProcId::new(index as u16))
+    }
+
+    fn check_symbol_limit(&self) -> Result<()> {
+        let max = self.limits.symbols();
+        if self.variables.len() >= max {
+            return Err(Error::Reason(format!(
+                "environment symbol limit exceeded ({max})"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the variable holds a procedure with side effects, like output
+    /// or mutation, which is not allowed in pure expression mode.
+    ///
+    /// See [`CompileOptions::pure`](crate::CompileOptions::pure).
+    pub fn is_effectful(&self, symbol: SymbolId) -> bool {
+        if self
+            .effectful
+            .get(symbol.as_usize())
+            .copied()
+            .unwrap_or(false)
+        {
+            return true;
+        }
+
+        match self.get_var(symbol) {
+            Some(Expr::Closure(closure)) => closure.borrow().procedure().effectful,
+            _ => false,
+        }
+    }
+
+    /// Whether the variable was bound by the core library.
+    ///
+    /// Redefining it is allowed, but often a mistake. See
+    /// [`CompileOptions::allow_shadowing`](crate::CompileOptions::allow_shadowing).
+    pub fn is_core_binding(&self, symbol: SymbolId) -> bool {
+        self.core_bindings
+            .get(symbol.as_usize())
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Where a program last defined the variable with `define`.
+    ///
+    /// Variables bound by native functions have no definition site.
+    pub fn def_site(&self, symbol: SymbolId) -> Option<&DefSite> {
+        self.def_sites.get(symbol.as_usize())?.as_ref()
+    }
+
+    pub(crate) fn set_def_site(&mut self, symbol: SymbolId, site: DefSite) {
+        grow_table(&mut self.def_sites, symbol.as_usize());
+        self.def_sites[symbol.as_usize()] = Some(site);
+    }
+
+    /// The variables and their values, sorted by name.
+    pub fn iter_vars_sorted(&self) -> impl Iterator<Item = (&str, &Expr)> {
+        self.sorted_vars(|_| true)
+    }
+
+    /// The variables defined by programs with `define`, sorted by name.
+    ///
+    /// A program's definition of a core procedure's name is included,
+    /// but the variables bound by native functions are not.
+    pub fn iter_user_vars(&self) -> impl Iterator<Item = (&str, &Expr)> {
+        self.sorted_vars(|symbol| self.def_site(symbol).is_some())
+    }
+
+    /// The variables bound by [`init_core`](crate::init_core), sorted by name.
+    pub fn iter_core_vars(&self) -> impl Iterator<Item = (&str, &Expr)> {
+        self.sorted_vars(|symbol| self.is_core_binding(symbol))
+    }
+
+    fn sorted_vars(
+        &self,
+        include: impl Fn(SymbolId) -> bool,
+    ) -> impl Iterator<Item = (&str, &Expr)> {
+        let mut vars: Vec<(&str, &Expr)> = self
+            .variables
+            .items()
+            .filter(|(symbol, _)| include(*symbol))
+            .filter_map(|(symbol, name)| Some((name, self.get_var(symbol)?)))
+            .collect();
+        vars.sort_by_key(|(name, _)| *name);
+        vars.into_iter()
+    }
+
+    /// The number of bound variables, which is also the next [`SymbolId`].
+    pub(crate) fn var_count(&self) -> usize {
+        self.var_values.len()
+    }
+
+    /// Mark the variables bound since the given count as core bindings.
+    pub(crate) fn mark_core_bindings(&mut self, from: usize) {
+        let count = self.var_values.len();
+        if self.core_bindings.len() < count {
+            self.core_bindings.resize(count, false);
+        }
+        self.core_bindings[from..count].fill(true);
+    }
+
+    /// Bind a native function that has side effects, like output or
+    /// mutating its arguments.
+    ///
+    /// Functions bound with [`Env::bind_native_func`] are assumed to be pure.
+    pub fn bind_effectful_func(&mut self, name: &str, func: NativeFunc) -> Result<SymbolId> {
+        let symbol = self.bind_native_func(name, func)?;
+        grow_table(&mut self.effectful, symbol.as_usize());
+        self.effectful[symbol.as_usize()] = true;
+        Ok(symbol)
     }
 
     /// TODO: Store argument arity information so it can be validated on compile or at runtime.
     pub fn bind_native_func(&mut self, name: &str, func: NativeFunc) -> Result<SymbolId> {
+        self.check_symbol_limit()?;
         match self.variables.insert_unique(name) {
             Some(symbol) => {
-                grow_table(&mut self.var_values, symbol.as_usize());
+                grow_vars(&mut self.var_values, symbol.as_usize());
                 self.var_values[symbol.as_usize()] = Expr::NativeFunc(func);
                 Ok(symbol)
             }
@@ -116,8 +602,144 @@ impl Env {
     }
 }
 
+/// Find the slot of the same value in a constant table, or append it.
+///
+/// Slots are given in the order values are first seen, which keeps
+/// compiling deterministic.
+pub(crate) fn intern_constant(constants: &mut Vec<Expr>, value: Expr) -> Result<ConstantId> {
+    match constants.iter().position(|el| is_same_constant(el, &value)) {
+        Some(index) => Ok(ConstantId::new(index as u16)),
+        None => {
+            let next_index = constants.len();
+            if next_index >= MAX_CONSTANTS {
+                return Err(Error::Reason(format!(
+                    "too many constants, the limit is {MAX_CONSTANTS}"
+                )));
+            }
+            constants.push(value);
+            Ok(ConstantId::new(next_index as u16))
+        }
+    }
+}
+
+/// Whether two constants can share a slot.
+///
+/// Numbers are compared by their bits rather than `==`, which would merge
+/// `0.0` and `-0.0`, and never find a slot for a NaN.
+fn is_same_constant(a: &Expr, b: &Expr) -> bool {
+    fn all_same(a: &[Expr], b: &[Expr]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| is_same_constant(a, b))
+    }
+
+    match (a, b) {
+        (Expr::Number(a), Expr::Number(b)) | (Expr::Inexact(a), Expr::Inexact(b)) => {
+            a.to_bits() == b.to_bits()
+        }
+        (Expr::Number(_), Expr::Inexact(_)) | (Expr::Inexact(_), Expr::Number(_)) => false,
+        (Expr::List(a), Expr::List(b)) => all_same(a, b),
+        (Expr::Vector(a), Expr::Vector(b)) => all_same(&a.borrow(), &b.borrow()),
+        (Expr::Quote(a), Expr::Quote(b)) => is_same_constant(a, b),
+        _ => a == b,
+    }
+}
+
+/// Make room for variable `index`. A declared variable is unspecified
+/// until it's defined, rather than the empty list of `Expr::default`.
+fn grow_vars(values: &mut Vec<Expr>, index: usize) {
+    if index >= values.len() {
+        values.resize(index + 1, Expr::VOID);
+    }
+}
+
 fn grow_table<T: Default>(table: &mut Vec<T>, index: usize) {
     if index >= table.len() {
         table.extend((table.len()..index + 1).map(|_| T::default()));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::expr::Expr;
+    use crate::parser::parse;
+    use crate::symbol::SymbolId;
+    use crate::CompileOptions;
+
+    #[test]
+    fn test_intern_constant_by_bits() {
+        // Zeros of either sign are equal by `==`, and NaN isn't equal to
+        // itself, but neither decides which constants share a slot.
+        let mut constants = vec![];
+        for value in [0.0, -0.0, 0.0, f64::NAN, f64::NAN] {
+            super::intern_constant(&mut constants, Expr::Number(value)).unwrap();
+        }
+        assert_eq!(constants.len(), 3);
+    }
+
+    #[test]
+    fn test_pure_eval_keeps_variables() {
+        let env = crate::new_env().expect("create core environment");
+        let expr = parse("(define total 10) (define half (lambda (x) (/ x 2)))", true).unwrap();
+        crate::eval(crate::compile(env.clone(), &expr).unwrap()).unwrap();
+        let before = env.borrow().var_values.clone();
+
+        let options = CompileOptions {
+            pure: true,
+            ..CompileOptions::default()
+        };
+        let expr = parse("(if (> total 5) (half total) total)", true).unwrap();
+        let closure = crate::compile_with_options(env.clone(), &expr, &options).unwrap();
+        assert_eq!(crate::eval(closure).unwrap(), Expr::Number(5.0));
+
+        let env = env.borrow();
+        assert_eq!(env.var_values.len(), before.len());
+        for (after, before) in env.var_values.iter().zip(&before) {
+            match (after, before) {
+                // Natives don't compare equal, so check they're the same function.
+                (Expr::NativeFunc(a), Expr::NativeFunc(b)) => assert!(std::ptr::fn_addr_eq(*a, *b)),
+                _ => assert_eq!(after, before),
+            }
+        }
+    }
+
+    #[test]
+    fn test_define_and_get_or_define() {
+        let mut env = crate::Env::new();
+
+        let symbol = env.define("answer", Expr::Number(42.0)).unwrap();
+        assert_eq!(env.get_var(symbol), Some(&Expr::Number(42.0)));
+        assert_eq!(env.define("answer", Expr::Number(7.0)).unwrap(), symbol);
+        assert_eq!(env.lookup_var("answer"), Some(&Expr::Number(7.0)));
+
+        // The default is only used for an undeclared variable.
+        let value = env.get_or_define("answer", || unreachable!()).unwrap();
+        assert_eq!(value, &Expr::Number(7.0));
+        *env.get_or_define("fresh", || Expr::Number(1.0)).unwrap() = Expr::Number(2.0);
+        assert_eq!(env.lookup_var("fresh"), Some(&Expr::Number(2.0)));
+    }
+
+    #[test]
+    fn test_update_and_take_var() {
+        let mut env = crate::Env::new();
+        let symbol = env
+            .define("items", Expr::List(vec![Expr::Number(1.0)].into()))
+            .unwrap();
+
+        env.update(symbol, |value| {
+            let mut items = value.as_slice().unwrap().to_vec();
+            items.push(Expr::Number(2.0));
+            Expr::List(items.into())
+        })
+        .unwrap();
+        let items = env.take_var(symbol).unwrap();
+        assert_eq!(items.as_slice().unwrap().len(), 2);
+        assert_eq!(env.get_var(symbol), Some(&Expr::Void));
+
+        // A symbol from another environment isn't declared in this one.
+        let undeclared = SymbolId::new(1000);
+        assert!(matches!(
+            env.update(undeclared, |value| value),
+            Err(crate::Error::Reason(message)) if message.starts_with("variable is not declared")
+        ));
+        assert_eq!(env.take_var(undeclared), None);
+    }
+}
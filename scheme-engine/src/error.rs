@@ -1,25 +1,314 @@
+use std::{fmt, io};
+
+use crate::datum::Datum;
+use crate::error_object::ErrorKind;
+use crate::parser::describe_token;
+use crate::source_map::SourceId;
+use crate::span::Span;
 use crate::token::TokenKind;
 
 pub type Result<T> = std::result::Result<T, self::Error>;
 
+/// Errors raised while parsing, compiling or evaluating.
+///
+/// Values from the environment are stored as their written representation
+/// rather than as live handles, so the error is `Send + Sync` and can
+/// cross threads or be boxed as a `dyn std::error::Error`:
+///
+/// ```
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let env = scheme_engine::new_env()?;
+///     let program = scheme_engine::parse_program("(+ 1 2)")?;
+///     let closure = scheme_engine::compile(env.clone(), &program)?;
+///     assert_eq!(scheme_engine::eval(closure)?, scheme_engine::Expr::Number(3.0));
+///     Ok(())
+/// }
+/// ```
 #[derive(Debug)]
 pub enum Error {
     Reason(String),
     TokenError {
         expected: TokenKind,
         actual: TokenKind,
+        /// Source text of the actual token.
+        fragment: String,
+        span: Span,
+        /// One-based line where the actual token starts.
+        line: usize,
+        /// One-based column where the actual token starts.
+        column: usize,
     },
     UnexpectedEOF,
+    /// The source ended inside an expression, like an unclosed list,
+    /// string or block comment, so more input could complete it.
+    Incomplete {
+        /// What the parser expected instead of the end, like `')'`.
+        expected: String,
+        /// One-based line where the source ended.
+        line: usize,
+        /// One-based column where the source ended.
+        column: usize,
+    },
+    /// An error raised while evaluating, with a snapshot of the virtual machine.
+    Runtime {
+        message: String,
+        /// Values on the operand stack, starting at the top.
+        stack_preview: Vec<String>,
+        /// The instruction each call frame was executing, starting at the innermost frame.
+        trace: Vec<String>,
+    },
+    /// An error raised while compiling the body of a definition,
+    /// or a top-level form of a program with several.
+    Compile {
+        message: String,
+        /// The enclosing definitions, starting at the outermost. Named
+        /// ones are quoted, like `'fib'`, and anonymous procedures
+        /// are `lambda`.
+        definitions: Vec<String>,
+        /// Line and column of the innermost named definition, when the
+        /// program was parsed from a source registered under its name.
+        /// See [`crate::parse_program_named`].
+        location: Option<(usize, usize)>,
+        /// The top-level form, like `form 3 (define (f) ...`, with
+        /// its text cut short.
+        form: Option<String>,
+    },
+    /// An input or output operation of the host failed.
+    Io(io::Error),
+    /// An error in a source registered in a [`SourceMap`], located
+    /// by its span. [`SourceMap::render_error`] names the source.
+    ///
+    /// [`SourceMap`]: crate::SourceMap
+    /// [`SourceMap::render_error`]: crate::SourceMap::render_error
+    InSource {
+        source_id: SourceId,
+        span: Span,
+        error: Box<Error>,
+    },
+    /// Evaluation ran out of a budget set in [`EvalOptions`].
+    ///
+    /// [`EvalOptions`]: crate::EvalOptions
+    Limit(Limit),
+    /// An expression given to the compiler isn't shaped like the output
+    /// of the parser, say because it was built by hand.
+    MalformedTree {
+        /// What is wrong, like `sequence nested`.
+        violation: String,
+        /// The zero-based positions of the elements leading to the
+        /// problem, starting in the top-level form.
+        path: Vec<usize>,
+        /// The number of the top-level form, when the program is a
+        /// sequence of several.
+        form: Option<usize>,
+    },
+    /// An error object that no `guard` caught, raised by `raise` or
+    /// `error`, or by a failed type or arity check when it isn't wrapped
+    /// in a [`Runtime`](Error::Runtime) error.
+    Raised {
+        /// The condition type, like `error`, `type-error` or `arity-error`.
+        kind: ErrorKind,
+        message: String,
+        /// A copy of each irritant.
+        irritants: Vec<Datum>,
+    },
+}
+
+/// A budget of [`EvalOptions`](crate::EvalOptions) that evaluation ran out of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// The number of instructions to execute.
+    Fuel(u64),
+    /// The instant to finish by.
+    Deadline,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fuel(fuel) => write!(f, "ran out of fuel after {fuel} instructions"),
+            Self::Deadline => write!(f, "ran past its deadline"),
+        }
+    }
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Reason(message) => write!(f, "{}", message),
-            Self::TokenError { expected, actual } => {
-                write!(f, "token error: expected {:?} found {:?}", expected, actual)
+            Self::TokenError {
+                expected,
+                actual,
+                fragment,
+                line,
+                column,
+                ..
+            } => {
+                let found = describe_token(*actual, fragment);
+                write!(
+                    f,
+                    "expected {expected} but found {found} at {line}:{column}"
+                )
             }
             Self::UnexpectedEOF => write!(f, "unexpected end-of-file"),
+            Self::Incomplete {
+                expected,
+                line,
+                column,
+            } => write!(
+                f,
+                "expected {expected} but found end-of-file at {line}:{column}"
+            ),
+            Self::Runtime { message, .. } => write!(f, "{message}"),
+            Self::Compile {
+                message,
+                definitions,
+                location,
+                form,
+            } => {
+                let location = match location {
+                    Some((line, column)) => format!(" at {line}:{column}"),
+                    None => String::new(),
+                };
+                match form {
+                    Some(form) if definitions.is_empty() => write!(f, "in {form}: {message}"),
+                    Some(form) => write!(
+                        f,
+                        "in {form}, definition of {}{location}: {message}",
+                        definitions.join(" > ")
+                    ),
+                    None => write!(
+                        f,
+                        "in definition of {}{location}: {message}",
+                        definitions.join(" > ")
+                    ),
+                }
+            }
+            Self::Io(err) => write!(f, "{err}"),
+            Self::InSource { error, .. } => write!(f, "{error}"),
+            Self::Limit(limit) => write!(f, "{limit}"),
+            Self::MalformedTree {
+                violation,
+                path,
+                form,
+            } => {
+                write!(f, "malformed syntax tree: {violation}")?;
+                if !path.is_empty() {
+                    let path: Vec<String> = path.iter().map(usize::to_string).collect();
+                    write!(f, " at list position {}", path.join(" > "))?;
+                }
+                match form {
+                    Some(form) if path.is_empty() => write!(f, " in form {form}"),
+                    Some(form) => write!(f, " of form {form}"),
+                    None => Ok(()),
+                }
+            }
+            Self::Raised {
+                message, irritants, ..
+            } => {
+                write!(f, "{message}")?;
+                for irritant in irritants {
+                    write!(f, " {irritant}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::InSource { error, .. } => error.source(),
+            _ => None,
         }
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Self::Reason("formatting failed".to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error as _;
+
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn test_from_io() {
+        let err = Error::from(io::Error::new(io::ErrorKind::NotFound, "missing.scm"));
+        assert_eq!(err.to_string(), "missing.scm");
+        assert!(err.source().is_some());
+        assert!(Error::Reason("no source".to_string()).source().is_none());
+    }
+
+    #[test]
+    fn test_compile_display() {
+        let err = Error::Compile {
+            message: "unbound variable \"n2\"".to_string(),
+            definitions: vec!["'outer'".to_string(), "lambda".to_string()],
+            location: None,
+            form: None,
+        };
+        assert_eq!(
+            err.to_string(),
+            "in definition of 'outer' > lambda: unbound variable \"n2\""
+        );
+
+        let err = Error::Compile {
+            message: "unbound variable \"z\"".to_string(),
+            definitions: vec!["'make-adder'".to_string()],
+            location: Some((7, 1)),
+            form: Some("form 7 (define make-adder 1)".to_string()),
+        };
+        assert_eq!(
+            err.to_string(),
+            "in form 7 (define make-adder 1), definition of 'make-adder' at 7:1: unbound variable \"z\""
+        );
+    }
+
+    #[test]
+    fn test_malformed_tree_display() {
+        let err = Error::MalformedTree {
+            violation: "sequence nested".to_string(),
+            path: vec![2],
+            form: Some(3),
+        };
+        assert_eq!(
+            err.to_string(),
+            "malformed syntax tree: sequence nested at list position 2 of form 3"
+        );
+
+        let err = Error::MalformedTree {
+            violation: "procedure value embedded".to_string(),
+            path: vec![1, 0],
+            form: None,
+        };
+        assert_eq!(
+            err.to_string(),
+            "malformed syntax tree: procedure value embedded at list position 1 > 0"
+        );
+    }
+
+    #[test]
+    fn test_from_fmt() {
+        let err = Error::from(fmt::Error);
+        assert_eq!(err.to_string(), "formatting failed");
+    }
+}
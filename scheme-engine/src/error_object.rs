@@ -0,0 +1,153 @@
+//! Error objects, the conditions `raise` and `guard` pass around.
+//!
+//! An error object is made by `(error message irritant ...)`, or by a
+//! `guard` catching an error of the engine, like a failed type check of a
+//! native function. Each has a kind, a symbol naming its condition type,
+//! so `guard` clauses can tell them apart:
+//!
+//! ```scheme
+//! (guard (e ((eq? (error-kind e) 'arity-error) (error-object-message e)))
+//!   (car 1 2))
+//! ```
+//!
+//! Errors cross into Rust as [`Error::Raised`], which keeps the kind,
+//! message and irritants. The irritants are copied as [`Datum`]s, since
+//! an [`Error`] is sent between threads and values aren't, and become
+//! values again when a `guard` catches the error.
+//!
+//! Unbound variables are found by the compiler before a program runs, so
+//! no `guard` can catch them, and they have no kind. They're reported as
+//! an [`Error::Compile`] instead.
+//!
+//! Errors can't be resumed. A `guard` that catches one continues after
+//! the `guard` form, and evaluation limits like fuel can't be caught.
+//!
+//! A failed check in a procedure called by a native function, like the
+//! callback of `map`, leaves that call as a runtime error, so a `guard`
+//! around the native function sees an error object of kind `error`.
+use std::fmt;
+use std::rc::Rc;
+
+use crate::datum::Datum;
+use crate::error::Error;
+use crate::expr::Expr;
+
+/// The condition type of an error object, which `error-kind` returns
+/// as a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// Made by `error`, or any error of the engine without a kind of its own.
+    Error,
+    /// A value other than an error object passed to `raise`.
+    Raise,
+    /// A failed check of an argument's type.
+    TypeError,
+    /// A call with the wrong number of arguments.
+    ArityError,
+}
+
+impl ErrorKind {
+    /// The name of the kind, like `type-error`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Error => "error",
+            ErrorKind::Raise => "raise",
+            ErrorKind::TypeError => "type-error",
+            ErrorKind::ArityError => "arity-error",
+        }
+    }
+
+    /// Whether the kind is of a failed check by the engine, rather than a
+    /// condition raised by Scheme code.
+    pub(crate) fn is_check(self) -> bool {
+        matches!(self, ErrorKind::TypeError | ErrorKind::ArityError)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A condition, as `raise` passes to a `guard`.
+#[derive(Debug)]
+pub struct ErrorObject {
+    kind: ErrorKind,
+    message: String,
+    irritants: Vec<Expr>,
+}
+
+impl ErrorObject {
+    pub fn new(kind: ErrorKind, message: impl Into<String>, irritants: Vec<Expr>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            irritants,
+        }
+    }
+
+    /// The error of the engine, as the error object a `guard` catches.
+    ///
+    /// Errors that don't name a kind, like a runtime error of a native
+    /// function called from a higher-order one, are of kind `error`.
+    pub(crate) fn from_error(err: &Error) -> Self {
+        match err {
+            Error::Raised {
+                kind,
+                message,
+                irritants,
+            } => Self::new(
+                *kind,
+                message.as_str(),
+                irritants.iter().map(Datum::to_expr).collect(),
+            ),
+            Error::Runtime { message, .. } => {
+                Self::new(ErrorKind::Error, message.as_str(), Vec::new())
+            }
+            err => Self::new(ErrorKind::Error, err.to_string(), Vec::new()),
+        }
+    }
+
+    /// The condition type, like `error` or `type-error`.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn irritants(&self) -> &[Expr] {
+        &self.irritants
+    }
+
+    /// The error that leaves the engine when the object isn't caught.
+    pub fn to_error(&self) -> Error {
+        Error::Raised {
+            kind: self.kind,
+            message: self.message.clone(),
+            irritants: self.irritants.iter().map(Datum::from_expr).collect(),
+        }
+    }
+}
+
+/// The error for a failed check, which a `guard` sees as an error object
+/// of the given kind.
+pub(crate) fn condition(kind: ErrorKind, message: String) -> Error {
+    Error::Raised {
+        kind,
+        message,
+        irritants: Vec::new(),
+    }
+}
+
+/// What a `guard` catches for the error: the value passed to `raise`,
+/// which waited in the environment, or an error object for the error.
+pub(crate) fn caught(err: &Error, raised: Option<Expr>) -> Expr {
+    match (raised, err) {
+        // Failed checks don't go through `raise`, so they leave no value.
+        (Some(value), Error::Raised { kind, .. }) if !kind.is_check() => value,
+        _ => Expr::ErrorObject(Rc::new(ErrorObject::from_error(err))),
+    }
+}
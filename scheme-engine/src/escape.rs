@@ -0,0 +1,370 @@
+//! Escape sequences in string and character literals.
+//!
+//! Both literal kinds share the same hex scalar and character name
+//! rules, so the parser and the `write` representation stay in sync.
+use crate::error::{Error, Result};
+use crate::parser::{read_number, NumberLiteral};
+use std::fmt;
+
+/// Names accepted in `#\<name>` character literals.
+///
+/// Writing a character uses the same names, so they can be read back.
+const CHAR_NAMES: &[(&str, char)] = &[
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+    ("newline", '\n'),
+    ("null", '\0'),
+    ("return", '\r'),
+    ("space", ' '),
+    ("tab", '\t'),
+];
+
+/// The name of a character, as written in a `#\<name>` literal.
+pub(crate) fn char_name(ch: char) -> Option<&'static str> {
+    CHAR_NAMES
+        .iter()
+        .find(|(_, named)| *named == ch)
+        .map(|(name, _)| *name)
+}
+
+/// Decode a string literal fragment, including its enclosing double quotes.
+pub(crate) fn decode_string(fragment: &str) -> Result<String> {
+    decode_delimited(fragment, '"', "string literal")
+}
+
+/// Decode a symbol enclosed in vertical bars, including the bars.
+pub(crate) fn decode_symbol(fragment: &str) -> Result<String> {
+    decode_delimited(fragment, '|', "symbol")
+}
+
+/// Whether a symbol must be written between vertical bars to read
+/// back as the same symbol.
+///
+/// That's the case for the empty symbol, names that read as numbers or
+/// other literals, and names containing delimiters or special characters.
+pub(crate) fn symbol_needs_bars(name: &str) -> bool {
+    let Some(first) = name.chars().next() else {
+        return true;
+    };
+
+    // The reader only starts identifiers with these characters, and
+    // with a dot when it isn't alone.
+    let identifier_start = matches!(
+        first,
+        '!' | '$'
+            | '%'
+            | '&'
+            | '*'
+            | '/'
+            | ':'
+            | '<'
+            | '='
+            | '>'
+            | '?'
+            | '^'
+            | '_'
+            | '~'
+            | '+'
+            | '-'
+    ) || first.is_ascii_alphabetic()
+        || (first == '.' && name.len() > 1);
+
+    !identifier_start
+        || read_number(name) != NumberLiteral::NotNumeric
+        || name.chars().any(|ch| {
+            ch.is_whitespace()
+                || ch.is_control()
+                || matches!(ch, '(' | ')' | '"' | ';' | '\'' | '|' | '\\')
+        })
+}
+
+/// Write a symbol between vertical bars, escaping what the reader
+/// would otherwise take as the closing bar or an escape.
+pub(crate) fn write_bar_symbol(f: &mut fmt::Formatter, name: &str) -> fmt::Result {
+    write!(f, "|")?;
+    for ch in name.chars() {
+        match ch {
+            '|' => write!(f, "\\|")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            _ if ch.is_control() => write!(f, "\\x{:x};", ch as u32)?,
+            _ => write!(f, "{ch}")?,
+        }
+    }
+    write!(f, "|")
+}
+
+/// Decode text between delimiters, like the quotes of a string.
+///
+/// `what` names the kind of literal in error messages.
+fn decode_delimited(fragment: &str, delimiter: char, what: &str) -> Result<String> {
+    let chars: Vec<char> = fragment.chars().collect();
+    if chars.first() != Some(&delimiter) {
+        return Err(Error::Reason(format!("expected {what}")));
+    }
+
+    let mut string = String::new();
+    let mut pos = 1;
+
+    loop {
+        match chars.get(pos) {
+            Some(ch) if *ch == delimiter => {
+                pos += 1;
+                break;
+            }
+            Some('\\') => {
+                let (decoded, next) = decode_escape(&chars, pos, what)?;
+                string.extend(decoded);
+                pos = next;
+            }
+            Some(ch) => {
+                string.push(*ch);
+                pos += 1;
+            }
+            None => return Err(Error::Reason(format!("unterminated {what}"))),
+        }
+    }
+
+    if pos != chars.len() {
+        return Err(Error::Reason(format!("unterminated {what}")));
+    }
+
+    Ok(string)
+}
+
+/// Decode a character literal fragment, including the `#\` prefix.
+pub(crate) fn decode_char(fragment: &str) -> Result<char> {
+    let body = fragment
+        .strip_prefix("#\\")
+        .ok_or_else(|| Error::Reason("expected character literal".to_string()))?;
+
+    let mut chars = body.chars();
+    match (chars.next(), chars.as_str()) {
+        (None, _) => Err(Error::Reason(
+            "expected character after #\\ in character literal".to_string(),
+        )),
+        (Some(ch), "") => Ok(ch),
+        (Some('x' | 'X'), digits) => hex_scalar(digits).ok_or_else(|| {
+            Error::Reason("invalid hex escape at column 3 of character literal".to_string())
+        }),
+        _ => CHAR_NAMES
+            .iter()
+            .find(|(name, _)| *name == body)
+            .map(|(_, ch)| *ch)
+            .ok_or_else(|| Error::Reason(format!("unknown character name: #\\{body}"))),
+    }
+}
+
+/// Decode the escape sequence starting at the backslash at `start`.
+///
+/// Returns the decoded character, if any, and the position after the
+/// sequence. A line continuation decodes to nothing.
+fn decode_escape(chars: &[char], start: usize, what: &str) -> Result<(Option<char>, usize)> {
+    // Columns are one-based, counted from the opening quote.
+    let column = start + 1;
+
+    let escaped = match chars.get(start + 1) {
+        Some('a') => '\u{7}',
+        Some('b') => '\u{8}',
+        Some('0') => '\0',
+        Some('t') => '\t',
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('"') => '"',
+        Some('\\') => '\\',
+        Some('|') => '|',
+        Some('x' | 'X') => {
+            let digits_start = start + 2;
+            let semicolon = chars[digits_start..]
+                .iter()
+                .position(|ch| *ch == ';')
+                .map(|offset| digits_start + offset);
+
+            let decoded = semicolon.and_then(|end| {
+                let digits: String = chars[digits_start..end].iter().collect();
+                hex_scalar(&digits).map(|ch| (ch, end + 1))
+            });
+
+            return match decoded {
+                Some((ch, next)) => Ok((Some(ch), next)),
+                None => Err(Error::Reason(format!(
+                    "invalid hex escape at column {column} of {what}"
+                ))),
+            };
+        }
+        Some(ch) if is_intraline_whitespace(*ch) || *ch == '\n' || *ch == '\r' => {
+            return line_continuation(chars, start + 1)
+                .map(|next| (None, next))
+                .ok_or_else(|| {
+                    Error::Reason(format!(
+                        "invalid line continuation at column {column} of {what}"
+                    ))
+                });
+        }
+        Some(ch) => {
+            return Err(Error::Reason(format!(
+                "unknown escape sequence \\{ch} at column {column} of {what}"
+            )))
+        }
+        None => return Err(Error::Reason(format!("unterminated {what}"))),
+    };
+
+    Ok((Some(escaped), start + 2))
+}
+
+/// Skip a line continuation: trailing whitespace, a line ending,
+/// and the leading whitespace of the next line.
+fn line_continuation(chars: &[char], mut pos: usize) -> Option<usize> {
+    let skip_whitespace = |mut pos: usize| {
+        while chars.get(pos).copied().is_some_and(is_intraline_whitespace) {
+            pos += 1;
+        }
+        pos
+    };
+
+    pos = skip_whitespace(pos);
+    match chars.get(pos) {
+        Some('\n') => pos += 1,
+        Some('\r') if chars.get(pos + 1) == Some(&'\n') => pos += 2,
+        Some('\r') => pos += 1,
+        _ => return None,
+    }
+
+    Some(skip_whitespace(pos))
+}
+
+#[inline]
+fn is_intraline_whitespace(ch: char) -> bool {
+    matches!(ch, ' ' | '\t')
+}
+
+/// Parse hexadecimal digits into a Unicode scalar value.
+fn hex_scalar(digits: &str) -> Option<char> {
+    if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::Expr;
+
+    #[test]
+    fn test_decode_string() {
+        let table = [
+            (r#""""#, ""),
+            (r#""abc""#, "abc"),
+            (r#""a\tb\n""#, "a\tb\n"),
+            (r#""\a\b\0""#, "\u{7}\u{8}\0"),
+            (r#""\"\\\|""#, "\"\\|"),
+            (r#""\x41;\x62;""#, "Ab"),
+            (r#""\x3bb;""#, "λ"),
+            (r#""\x1F600;!""#, "😀!"),
+            (r#""héllo""#, "héllo"),
+            ("\"one \\\n    two\"", "one two"),
+            ("\"one \\  \r\n\ttwo\"", "one two"),
+        ];
+
+        for (literal, expected) in table {
+            let decoded = decode_string(literal).expect(literal);
+            assert_eq!(decoded, expected, "{literal}");
+        }
+    }
+
+    #[test]
+    fn test_decode_string_errors() {
+        let table = [
+            (r#""unterminated"#, "unterminated string literal"),
+            (r#""trailing \"#, "unterminated string literal"),
+            (
+                r#""bad \q escape""#,
+                "unknown escape sequence \\q at column 6 of string literal",
+            ),
+            (
+                r#""abc \x41 def""#,
+                "invalid hex escape at column 6 of string literal",
+            ),
+            (
+                r#""\x;""#,
+                "invalid hex escape at column 2 of string literal",
+            ),
+            (
+                r#""\xZZ;""#,
+                "invalid hex escape at column 2 of string literal",
+            ),
+            (
+                r#""\xD800;""#,
+                "invalid hex escape at column 2 of string literal",
+            ),
+            (
+                r#""\x110000;""#,
+                "invalid hex escape at column 2 of string literal",
+            ),
+            (
+                "\"a\\  b\"",
+                "invalid line continuation at column 3 of string literal",
+            ),
+        ];
+
+        for (literal, expected) in table {
+            match decode_string(literal) {
+                Err(Error::Reason(message)) => assert_eq!(message, expected, "{literal}"),
+                other => panic!("expected error for {literal}, found {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_char() {
+        let table = [
+            (r"#\a", 'a'),
+            (r"#\(", '('),
+            (r"#\ ", ' '),
+            (r"#\λ", 'λ'),
+            (r"#\x", 'x'),
+            (r"#\x41", 'A'),
+            (r"#\x3bb", 'λ'),
+            (r"#\space", ' '),
+            (r"#\newline", '\n'),
+            (r"#\null", '\0'),
+            (r"#\alarm", '\u{7}'),
+        ];
+
+        for (literal, expected) in table {
+            assert_eq!(decode_char(literal).expect(literal), expected, "{literal}");
+        }
+
+        assert!(decode_char(r"#\").is_err());
+        assert!(decode_char(r"#\xZZ").is_err());
+        assert!(decode_char(r"#\spaceship").is_err());
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let strings = [
+            "plain",
+            "tab\tline\n",
+            "\u{7}\u{8}\0\u{1b}\u{7f}",
+            "λ 😀 \"q\"",
+        ];
+        for string in strings {
+            let written = Expr::from(string).write_repr().to_string();
+            assert_eq!(decode_string(&written).expect(&written), string);
+        }
+
+        let chars = ['a', ' ', '\n', '\0', '\u{1}', 'λ', '\u{a0}'];
+        for ch in chars {
+            let written = Expr::Char(ch).write_repr().to_string();
+            assert_eq!(decode_char(&written).expect(&written), ch);
+        }
+    }
+}
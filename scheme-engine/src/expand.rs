@@ -0,0 +1,845 @@
+//! Expansion of derived forms into core forms.
+//!
+//! The compiler only knows the core forms: `quote`, `lambda`, `define` of
+//! a variable, `define-values`, `set!`, `if`, `begin`, `when`, `unless`,
+//! `cond`, `case`, `do`, `guard`, `define-test` and `define-syntax`.
+//! Every other special form is derived, and rewritten here into core forms
+//! before compiling:
+//!
+//! ```scheme
+//! (define (name . formals) body ...)  ; (define name (lambda formals body ...))
+//! (let ((var init) ...) body ...)     ; ((lambda (var ...) body ...) init ...)
+//! (let name ((var init) ...) body ...)
+//! ; ((lambda () (define name (lambda (var ...) body ...)) (name init ...)))
+//! (let* ((var init) rest ...) body ...) ; (let ((var init)) (let* (rest ...) body ...))
+//! (letrec ((var init) ...) body ...)  ; ((lambda () (define var init) ... body ...))
+//! (let-values ((formals init) ...) body ...)
+//! ; ((lambda () (define |let-values 1| init) ... (define-values formals |let-values 1|) ... body ...))
+//! ```
+//!
+//! The `let-values` temporaries have a space in their name, so only a
+//! `|bar symbol|` written on purpose can refer to them.
+//!
+//! `let-syntax` and `letrec-syntax` bind macros, whose transformers are
+//! `syntax-rules`, in their body, which is expanded like that of `let`:
+//!
+//! ```scheme
+//! (let-syntax ((keyword transformer) ...) body ...) ; ((lambda () body ...))
+//! ```
+//!
+//! with each use of a macro in the body rewritten by its transformer. The
+//! innermost binding of a name decides what it means: a macro shadows an
+//! outer macro or special form of the same name, and a local variable
+//! shadows a macro, inside the variable's scope. Local variables named
+//! like special forms don't change them, as in the compiler.
+//!
+//! A macro's output is expanded with the bindings around its definition,
+//! which for `letrec-syntax` include the macros of the same form, so they
+//! can be recursive. See [`crate::syntax_rules`] for the transformers.
+//!
+//! The name of a procedure definition can itself be a list, for curried
+//! procedures, so `(define ((adder n) m) (+ n m))` defines `adder` as a
+//! procedure returning a procedure.
+//!
+//! Expanded programs only contain core forms, so expanding them
+//! again returns the same program.
+//!
+//! [`expand_once`] rewrites a single derived form at a time, for seeing
+//! how a program is expanded.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use smol_str::SmolStr;
+
+use crate::env::Env;
+use crate::error::{Error, Result};
+use crate::expr::Expr;
+use crate::limits::MAX_EXPANSION_DEPTH;
+use crate::syntax_rules::SyntaxRules;
+
+macro_rules! error_ill_special_form {
+    ($name:expr) => {
+        Error::Reason(format!("ill-formed special form {:?}", $name))
+    };
+}
+
+/// Rewrite the derived forms in a program into core forms.
+///
+/// Quoted data is left as is. Malformed core forms are left for
+/// the compiler to report.
+///
+/// The environment is where macros defined by `define-syntax` will
+/// be looked up, once it's implemented.
+pub fn expand(env: &Env, expr: &Expr) -> Result<Expr> {
+    expand_in(env, &mut Scopes::default(), expr)
+}
+
+/// The bindings around the form being expanded, innermost last, which
+/// decide whether a list is the use of a macro.
+///
+/// Only macros bound by `let-syntax` and `letrec-syntax` are scoped, so
+/// variables are only tracked inside those forms.
+#[derive(Default)]
+struct Scopes {
+    scopes: Vec<HashMap<SmolStr, Binding>>,
+    /// Number of macro uses being expanded, one inside the output of another.
+    depth: usize,
+}
+
+enum Binding {
+    /// A macro, whose output is expanded with the first `scopes` of the
+    /// stack, the ones around its definition.
+    Macro {
+        rules: Rc<SyntaxRules>,
+        scopes: usize,
+    },
+    Variable,
+}
+
+impl Scopes {
+    /// The macro that the innermost binding of the name is, if it's one.
+    fn lookup_macro(&self, name: &str) -> Option<(Rc<SyntaxRules>, usize)> {
+        let binding = self.scopes.iter().rev().find_map(|scope| scope.get(name))?;
+        match binding {
+            Binding::Macro { rules, scopes } => Some((rules.clone(), *scopes)),
+            Binding::Variable => None,
+        }
+    }
+
+    /// Expand in the scope of local variables, when any macros are bound.
+    fn with_variables<T>(
+        &mut self,
+        variables: impl FnOnce() -> Vec<SmolStr>,
+        block: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        if self.scopes.is_empty() {
+            return block(self);
+        }
+
+        let scope = variables()
+            .into_iter()
+            .map(|name| (name, Binding::Variable))
+            .collect();
+        self.scopes.push(scope);
+        let result = block(self);
+        self.scopes.pop();
+        result
+    }
+}
+
+fn expand_in(env: &Env, scopes: &mut Scopes, expr: &Expr) -> Result<Expr> {
+    match expr {
+        Expr::Sequence(forms) => expand_all(env, scopes, forms).map(Expr::Sequence),
+        Expr::List(list) => expand_form(env, scopes, list),
+        _ => Ok(expr.clone()),
+    }
+}
+
+/// The most rewrites [`expand_steps`] makes before giving up, by default.
+pub const MAX_EXPAND_STEPS: usize = 10_000;
+
+/// Rewrite the first derived form in the program into the forms it's
+/// short for, or return `None` when only core forms are left.
+///
+/// Forms are searched outermost first, then left to right, so a form's
+/// own rewrite comes before those of the forms inside it. Its result may
+/// contain further derived forms, like `let*` rewriting into `let`, which
+/// are left for the next step.
+///
+/// ```
+/// use scheme_engine::{expand_once, parse, Env};
+///
+/// let env = Env::new();
+/// let expr = parse("(let* ((a 1) (b a)) b)", false)?;
+/// let step = expand_once(&env, &expr)?.unwrap();
+/// assert_eq!(step.write_repr().to_string(), "(let ((a 1)) (let ((b a)) b))");
+/// # Ok::<(), scheme_engine::Error>(())
+/// ```
+pub fn expand_once(env: &Env, expr: &Expr) -> Result<Option<Expr>> {
+    match expr {
+        Expr::Sequence(forms) => Ok(step_first(env, forms, 0)?.map(Expr::Sequence)),
+        Expr::List(list) => step_form(env, list),
+        _ => Ok(None),
+    }
+}
+
+/// Rewrite the program with [`expand_once`] until only core forms are
+/// left, returning it with the number of rewrites made.
+///
+/// Fails when the program still has derived forms after `max_steps`
+/// rewrites, instead of expanding forever.
+pub fn expand_steps(env: &Env, expr: &Expr, max_steps: usize) -> Result<(Expr, usize)> {
+    let mut expr = expr.clone();
+    for steps in 0..max_steps {
+        match expand_once(env, &expr)? {
+            Some(next) => expr = next,
+            None => return Ok((expr, steps)),
+        }
+    }
+
+    match expand_once(env, &expr)? {
+        Some(_) => Err(Error::Reason(format!(
+            "expansion didn't finish within {max_steps} steps"
+        ))),
+        None => Ok((expr, max_steps)),
+    }
+}
+
+fn step_form(env: &Env, list: &[Expr]) -> Result<Option<Expr>> {
+    if let Some(derived) = derive(list)? {
+        return Ok(Some(derived));
+    }
+
+    let Some((Expr::Ident(operator), rest)) = list.split_first() else {
+        return Ok(step_first(env, list, 0)?.map(make_list));
+    };
+
+    // The same code positions as `expand_form`.
+    let first_code = match (operator.as_str(), rest) {
+        // The macros are applied to the whole body in one step.
+        ("let-syntax" | "letrec-syntax", _) => {
+            return expand(env, &make_list(list.to_vec())).map(Some)
+        }
+        ("quote" | "define-syntax", _) => return Ok(None),
+        ("lambda" | "define-values" | "define", [_, ..]) => 2,
+        ("do", [Expr::List(specs), ..]) => {
+            for (index, spec) in specs.iter().enumerate() {
+                let Some([variable, code @ ..]) = spec.as_slice() else {
+                    continue;
+                };
+                if let Some(code) = step_first(env, code, 0)? {
+                    let mut spec = vec![variable.clone()];
+                    spec.extend(code);
+                    let mut specs = specs.to_vec();
+                    specs[index] = make_list(spec);
+                    let mut form = list.to_vec();
+                    form[1] = make_list(specs);
+                    return Ok(Some(make_list(form)));
+                }
+            }
+            2
+        }
+        ("case", [key, clauses @ ..]) => {
+            if let Some(key) = expand_once(env, key)? {
+                let mut form = list.to_vec();
+                form[1] = key;
+                return Ok(Some(make_list(form)));
+            }
+            for (index, clause) in clauses.iter().enumerate() {
+                let Some(clause) = clause.as_slice() else {
+                    continue;
+                };
+                if let Some(clause) = step_first(env, clause, 1)? {
+                    let mut form = list.to_vec();
+                    form[index + 2] = make_list(clause);
+                    return Ok(Some(make_list(form)));
+                }
+            }
+            return Ok(None);
+        }
+        _ => 1,
+    };
+    Ok(step_first(env, list, first_code)?.map(make_list))
+}
+
+/// The expressions with the first one that has a derived form, from
+/// `start` on, rewritten by one step.
+fn step_first(env: &Env, expressions: &[Expr], start: usize) -> Result<Option<Vec<Expr>>> {
+    for (index, expr) in expressions.iter().enumerate().skip(start) {
+        if let Some(step) = expand_once(env, expr)? {
+            let mut expressions = expressions.to_vec();
+            expressions[index] = step;
+            return Ok(Some(expressions));
+        }
+    }
+    Ok(None)
+}
+
+/// One rewrite of a derived form into the forms it's short for,
+/// or `None` for a core form or procedure call.
+fn derive(list: &[Expr]) -> Result<Option<Expr>> {
+    let Some((Expr::Ident(operator), rest)) = list.split_first() else {
+        return Ok(None);
+    };
+
+    let derived = match (operator.as_str(), rest) {
+        ("define", [Expr::List(target), body @ ..]) => define_procedure(target, body)?,
+        ("let", _) => derive_let(rest)?,
+        ("let*", _) => derive_let_star(rest)?,
+        ("letrec", _) => derive_letrec(rest)?,
+        ("let-values", _) => derive_let_values(rest)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(derived))
+}
+
+fn expand_all(env: &Env, scopes: &mut Scopes, expressions: &[Expr]) -> Result<Vec<Expr>> {
+    expressions
+        .iter()
+        .map(|expr| expand_in(env, scopes, expr))
+        .collect()
+}
+
+fn expand_form(env: &Env, scopes: &mut Scopes, list: &[Expr]) -> Result<Expr> {
+    if let Some(Expr::Ident(keyword)) = list.first() {
+        if let Some((rules, definition_scopes)) = scopes.lookup_macro(keyword) {
+            return expand_macro_use(env, scopes, &rules, definition_scopes, keyword, list);
+        }
+    }
+
+    if let Some(derived) = derive(list)? {
+        return expand_in(env, scopes, &derived);
+    }
+
+    let Some((Expr::Ident(operator), rest)) = list.split_first() else {
+        return expand_all(env, scopes, list).map(make_list);
+    };
+
+    match (operator.as_str(), rest) {
+        ("quote" | "define-syntax", _) => Ok(Expr::List(list.into())),
+        ("let-syntax" | "letrec-syntax", _) => expand_let_syntax(env, scopes, operator, rest),
+        ("lambda", [formals, body @ ..]) => {
+            let mut form = vec![ident("lambda"), formals.clone()];
+            form.extend(scopes.with_variables(
+                || body_variables(formals, body),
+                |scopes| expand_all(env, scopes, body),
+            )?);
+            Ok(make_list(form))
+        }
+        ("define-values", [formals, value @ ..]) => {
+            let mut form = vec![ident("define-values"), formals.clone()];
+            form.extend(expand_all(env, scopes, value)?);
+            Ok(make_list(form))
+        }
+        ("define", [target, value @ ..]) => {
+            let mut form = vec![ident("define"), target.clone()];
+            form.extend(expand_all(env, scopes, value)?);
+            Ok(make_list(form))
+        }
+        ("do", [Expr::List(specs), exit, commands @ ..]) => {
+            // Only the init and step expressions of the variable specs are
+            // code, and the steps are in the scope of the variables.
+            let inits = specs
+                .iter()
+                .map(|spec| match spec.as_slice() {
+                    Some([_, init, ..]) => expand_in(env, scopes, init).map(Some),
+                    _ => Ok(None),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let variables = || {
+                specs
+                    .iter()
+                    .filter_map(|spec| match spec.as_slice() {
+                        Some([Expr::Ident(variable), ..]) => Some(variable.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            };
+
+            scopes.with_variables(variables, |scopes| {
+                let specs = specs
+                    .iter()
+                    .zip(inits)
+                    .map(|(spec, init)| match (spec.as_slice(), init) {
+                        (Some([variable, _, steps @ ..]), Some(init)) => {
+                            let mut spec = vec![variable.clone(), init];
+                            spec.extend(expand_all(env, scopes, steps)?);
+                            Ok(make_list(spec))
+                        }
+                        _ => Ok(spec.clone()),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let mut form = vec![ident("do"), make_list(specs), expand_in(env, scopes, exit)?];
+                form.extend(expand_all(env, scopes, commands)?);
+                Ok(make_list(form))
+            })
+        }
+        ("case", [key, clauses @ ..]) => {
+            // The datums of the clauses are data, not code.
+            let mut form = vec![ident("case"), expand_in(env, scopes, key)?];
+            for clause in clauses {
+                form.push(match clause.as_slice() {
+                    Some([datums, body @ ..]) => {
+                        let mut clause = vec![datums.clone()];
+                        clause.extend(expand_all(env, scopes, body)?);
+                        make_list(clause)
+                    }
+                    _ => clause.clone(),
+                });
+            }
+            Ok(make_list(form))
+        }
+        ("guard", [Expr::List(spec), body @ ..]) => {
+            // The clauses are in the scope of the condition's variable.
+            let spec = match spec.split_first() {
+                Some((variable @ Expr::Ident(name), clauses)) => {
+                    let mut spec = vec![variable.clone()];
+                    spec.extend(scopes.with_variables(
+                        || vec![name.clone()],
+                        |scopes| expand_all(env, scopes, clauses),
+                    )?);
+                    make_list(spec)
+                }
+                _ => Expr::List(spec.clone()),
+            };
+
+            let mut form = vec![ident("guard"), spec];
+            form.extend(expand_all(env, scopes, body)?);
+            Ok(make_list(form))
+        }
+        // The remaining core forms, and procedure calls.
+        _ => expand_all(env, scopes, list).map(make_list),
+    }
+}
+
+/// Rewrite a use of a macro, and expand its output with the bindings
+/// around the macro's definition.
+fn expand_macro_use(
+    env: &Env,
+    scopes: &mut Scopes,
+    rules: &SyntaxRules,
+    definition_scopes: usize,
+    keyword: &str,
+    list: &[Expr],
+) -> Result<Expr> {
+    if scopes.depth == MAX_EXPANSION_DEPTH {
+        return Err(Error::Reason(format!(
+            "uses of macro {keyword} are nested more than {MAX_EXPANSION_DEPTH} deep"
+        )));
+    }
+    let output = rules.apply(keyword, list)?;
+
+    let inner = scopes.scopes.split_off(definition_scopes);
+    scopes.depth += 1;
+    let result = expand_in(env, scopes, &output);
+    scopes.depth -= 1;
+    scopes.scopes.extend(inner);
+    result
+}
+
+/// Expand the body of a `let-syntax` or `letrec-syntax` with its macros
+/// bound, into `((lambda () body ...))`.
+fn expand_let_syntax(env: &Env, scopes: &mut Scopes, who: &str, rest: &[Expr]) -> Result<Expr> {
+    let (bindings, body) = rest
+        .split_first()
+        .ok_or_else(|| error_ill_special_form!(who))?;
+
+    // The output of a `letrec-syntax` macro can use the macros of the form.
+    let definition_scopes = match who {
+        "letrec-syntax" => scopes.scopes.len() + 1,
+        _ => scopes.scopes.len(),
+    };
+    let scope = binding_list(who, bindings)?
+        .iter()
+        .map(|binding| match binding.as_slice() {
+            Some([Expr::Ident(keyword), transformer]) => {
+                let rules = Rc::new(SyntaxRules::new(keyword, transformer)?);
+                let binding = Binding::Macro {
+                    rules,
+                    scopes: definition_scopes,
+                };
+                Ok((keyword.clone(), binding))
+            }
+            _ => Err(error_ill_special_form!(who)),
+        })
+        .collect::<Result<_>>()?;
+
+    scopes.scopes.push(scope);
+    let thunk = lambda(make_list(Vec::new()), body);
+    let result = expand_in(env, scopes, &make_list(vec![thunk]));
+    scopes.scopes.pop();
+    result
+}
+
+/// The variables bound in a procedure's body: its formals, and the
+/// names of its internal definitions.
+fn body_variables(formals: &Expr, body: &[Expr]) -> Vec<SmolStr> {
+    let mut variables = formals_variables(formals);
+    for expr in body {
+        match expr.as_slice() {
+            Some([Expr::Ident(keyword), target, ..]) if keyword == "define" => {
+                // The target of a curried definition is itself a list.
+                let mut target = target;
+                while let Some([first, ..]) = target.as_slice() {
+                    target = first;
+                }
+                if let Expr::Ident(name) = target {
+                    variables.push(name.clone());
+                }
+            }
+            Some([Expr::Ident(keyword), formals, ..]) if keyword == "define-values" => {
+                variables.extend(formals_variables(formals));
+            }
+            _ => {}
+        }
+    }
+    variables
+}
+
+/// The variables of formals, like `(a b . rest)` or `args`.
+fn formals_variables(formals: &Expr) -> Vec<SmolStr> {
+    match formals {
+        Expr::Ident(name) => vec![name.clone()],
+        Expr::List(list) => list
+            .iter()
+            .filter_map(|formal| match formal {
+                Expr::Ident(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `(define (name . formals) body ...)` is short for
+/// `(define name (lambda formals body ...))`.
+fn define_procedure(target: &[Expr], body: &[Expr]) -> Result<Expr> {
+    let (name, formals) = target
+        .split_first()
+        .ok_or_else(|| Error::Reason("expected procedure name in define, found ()".to_string()))?;
+    if !matches!(name, Expr::Ident(_) | Expr::List(_)) {
+        return Err(Error::Reason(format!(
+            "expected identifier as procedure name, found {} {}",
+            name.type_name(),
+            name.write_repr()
+        )));
+    }
+
+    Ok(make_list(vec![
+        ident("define"),
+        name.clone(),
+        lambda(Expr::List(formals.into()), body),
+    ]))
+}
+
+fn derive_let(rest: &[Expr]) -> Result<Expr> {
+    match rest {
+        // Named let, for loops.
+        [Expr::Ident(name), bindings, body @ ..] => {
+            let (variables, inits) = let_bindings("let", bindings)?;
+            let procedure = lambda(make_list(variables), body);
+            let definition = make_list(vec![ident("define"), Expr::Ident(name.clone()), procedure]);
+
+            let mut call = vec![Expr::Ident(name.clone())];
+            call.extend(inits);
+            let thunk = lambda(make_list(Vec::new()), &[definition, make_list(call)]);
+            Ok(make_list(vec![thunk]))
+        }
+        [bindings, body @ ..] => {
+            let (variables, inits) = let_bindings("let", bindings)?;
+            let mut call = vec![lambda(make_list(variables), body)];
+            call.extend(inits);
+            Ok(make_list(call))
+        }
+        [] => Err(error_ill_special_form!("let")),
+    }
+}
+
+fn derive_let_star(rest: &[Expr]) -> Result<Expr> {
+    let (bindings, body) = rest
+        .split_first()
+        .ok_or_else(|| error_ill_special_form!("let*"))?;
+    let bindings = binding_list("let*", bindings)?;
+
+    // The innermost `let` has the last binding, if any, and the body.
+    let (innermost, outer) = match bindings.split_last() {
+        Some((last, outer)) => (vec![last.clone()], outer),
+        None => (Vec::new(), &[][..]),
+    };
+    let mut form = vec![ident("let"), make_list(innermost)];
+    form.extend(body.iter().cloned());
+
+    let mut form = make_list(form);
+    for binding in outer.iter().rev() {
+        form = make_list(vec![ident("let"), make_list(vec![binding.clone()]), form]);
+    }
+    Ok(form)
+}
+
+fn derive_letrec(rest: &[Expr]) -> Result<Expr> {
+    let (bindings, body) = rest
+        .split_first()
+        .ok_or_else(|| error_ill_special_form!("letrec"))?;
+    let (variables, inits) = let_bindings("letrec", bindings)?;
+
+    let mut forms: Vec<Expr> = variables
+        .into_iter()
+        .zip(inits)
+        .map(|(variable, init)| make_list(vec![ident("define"), variable, init]))
+        .collect();
+    forms.extend(body.iter().cloned());
+    Ok(make_list(vec![lambda(make_list(Vec::new()), &forms)]))
+}
+
+/// Every init is evaluated before any formals are bound,
+/// so the inits are stored in temporaries first.
+fn derive_let_values(rest: &[Expr]) -> Result<Expr> {
+    let (bindings, body) = rest
+        .split_first()
+        .ok_or_else(|| error_ill_special_form!("let-values"))?;
+    let (formals, inits): (Vec<Expr>, Vec<Expr>) = binding_list("let-values", bindings)?
+        .iter()
+        .map(|binding| match binding.as_slice() {
+            Some([formals, init]) => Ok((formals.clone(), init.clone())),
+            _ => Err(error_ill_special_form!("let-values")),
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+
+    let temporaries: Vec<Expr> = (1..=formals.len())
+        .map(|index| ident(&format!("let-values {index}")))
+        .collect();
+    let mut forms: Vec<Expr> = temporaries
+        .iter()
+        .zip(inits)
+        .map(|(temporary, init)| make_list(vec![ident("define"), temporary.clone(), init]))
+        .collect();
+    forms.extend(
+        formals
+            .into_iter()
+            .zip(temporaries)
+            .map(|(formals, temporary)| {
+                make_list(vec![ident("define-values"), formals, temporary])
+            }),
+    );
+    forms.extend(body.iter().cloned());
+    Ok(make_list(vec![lambda(make_list(Vec::new()), &forms)]))
+}
+
+/// The bindings of a `let` form, like `((a 1) (b 2))`.
+fn binding_list(who: &str, bindings: &Expr) -> Result<Vec<Expr>> {
+    match bindings {
+        Expr::Nil => Ok(Vec::new()),
+        Expr::List(list) => Ok(list.to_vec()),
+        _ => Err(error_ill_special_form!(who)),
+    }
+}
+
+/// The variables and init expressions of a `let` form's bindings.
+fn let_bindings(who: &str, bindings: &Expr) -> Result<(Vec<Expr>, Vec<Expr>)> {
+    binding_list(who, bindings)?
+        .iter()
+        .map(|binding| match binding.as_slice() {
+            Some([variable @ Expr::Ident(_), init]) => Ok((variable.clone(), init.clone())),
+            _ => Err(error_ill_special_form!(who)),
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|pairs| pairs.into_iter().unzip())
+}
+
+fn lambda(formals: Expr, body: &[Expr]) -> Expr {
+    let mut form = vec![ident("lambda"), formals];
+    form.extend(body.iter().cloned());
+    make_list(form)
+}
+
+#[inline]
+fn ident(name: &str) -> Expr {
+    Expr::Ident(name.into())
+}
+
+#[inline]
+fn make_list(elements: Vec<Expr>) -> Expr {
+    Expr::List(Rc::from(elements))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    /// Expand the source, and write the result.
+    fn expand_source(source: &str) -> String {
+        let env = Env::new();
+        let expr = parse(source, false).unwrap();
+        let expanded = expand(&env, &expr).unwrap();
+
+        // Expanded programs are left alone.
+        let written = expanded.write_repr().to_string();
+        let again = expand(&env, &expanded).unwrap();
+        assert_eq!(again.write_repr().to_string(), written, "{source}");
+        written
+    }
+
+    #[test]
+    fn test_derived_forms() {
+        let table = [
+            ("(define (f) 1)", "(define f (lambda () 1))"),
+            (
+                "(define (f a . rest) rest)",
+                "(define f (lambda (a . rest) rest))",
+            ),
+            (
+                "(define ((adder n) m) (+ n m))",
+                "(define adder (lambda (n) (lambda (m) (+ n m))))",
+            ),
+            ("(let ((a 1) (b 2)) (+ a b))", "((lambda (a b) (+ a b)) 1 2)"),
+            ("(let () 1)", "((lambda () 1))"),
+            (
+                "(let loop ((i 0)) (loop (+ i 1)))",
+                "((lambda () (define loop (lambda (i) (loop (+ i 1)))) (loop 0)))",
+            ),
+            (
+                "(let* ((a 1) (b a)) b)",
+                "((lambda (a) ((lambda (b) b) a)) 1)",
+            ),
+            ("(let* () 1)", "((lambda () 1))"),
+            (
+                "(let-values (((a b) (values 1 2)) (all (values))) (list a b all))",
+                "((lambda () (define |let-values 1| (values 1 2)) (define |let-values 2| (values)) (define-values (a b) |let-values 1|) (define-values all |let-values 2|) (list a b all)))",
+            ),
+            ("(let-values () 1)", "((lambda () 1))"),
+            (
+                "(letrec ((even? (lambda (n) (odd? n))) (odd? (lambda (n) #t))) (even? 1))",
+                "((lambda () (define even? (lambda (n) (odd? n))) (define odd? (lambda (n) #t)) (even? 1)))",
+            ),
+        ];
+
+        for (source, expected) in table {
+            assert_eq!(expand_source(source), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_nested_derived_forms() {
+        // Bodies, arguments and loop expressions are expanded.
+        let table = [
+            (
+                "(lambda (x) (define (g) (let ((y x)) y)) (g))",
+                "(lambda (x) (define g (lambda () ((lambda (y) y) x))) (g))",
+            ),
+            ("(if (let ((a #t)) a) 1 2)", "(if ((lambda (a) a) #t) 1 2)"),
+            (
+                "(do ((i (let ((a 0)) a) (+ i 1))) ((= i 3) i))",
+                "(do ((i ((lambda (a) a) 0) (+ i 1))) ((= i 3) i))",
+            ),
+            (
+                "(case (let () 1) ((1) (let () 2)) (else => (let () f)))",
+                "(case ((lambda () 1)) ((1) ((lambda () 2))) (else => ((lambda () f))))",
+            ),
+        ];
+
+        for (source, expected) in table {
+            assert_eq!(expand_source(source), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_quoted_and_binding_positions() {
+        // Quoted data and formals aren't code, even when they look like forms.
+        let table = [
+            "'(let ((a 1)) a)",
+            "(quote (define (f) 1))",
+            "(lambda (let x) (+ let x))",
+            "(define let 1)",
+            "(case x ((let) 1) ((define) 2))",
+        ];
+
+        for source in table {
+            assert_eq!(expand_source(source), source, "{source}");
+        }
+    }
+
+    /// Each step of expanding the source, written.
+    fn expand_source_steps(source: &str) -> Vec<String> {
+        let env = Env::new();
+        let mut expr = parse(source, false).unwrap();
+        let mut steps = vec![];
+        while let Some(step) = expand_once(&env, &expr).unwrap() {
+            steps.push(step.write_repr().to_string());
+            expr = step;
+        }
+        steps
+    }
+
+    #[test]
+    fn test_expand_once() {
+        // A derived form rewritten into another, which takes a second step.
+        assert_eq!(
+            expand_source_steps("(let* ((a 1)) a)"),
+            ["(let ((a 1)) a)", "((lambda (a) a) 1)"]
+        );
+
+        // Outermost first, then left to right.
+        assert_eq!(
+            expand_source_steps("(f (let () (let () 1)) (let () 2))"),
+            [
+                "(f ((lambda () (let () 1))) (let () 2))",
+                "(f ((lambda () ((lambda () 1)))) (let () 2))",
+                "(f ((lambda () ((lambda () 1)))) ((lambda () 2)))",
+            ]
+        );
+        assert_eq!(
+            expand_source_steps("(do ((i (let () 0) (let () i))) (#t i))"),
+            [
+                "(do ((i ((lambda () 0)) (let () i))) (#t i))",
+                "(do ((i ((lambda () 0)) ((lambda () i)))) (#t i))",
+            ]
+        );
+        assert_eq!(
+            expand_source_steps("(case (let () 0) ((let) (let () 1)))"),
+            [
+                "(case ((lambda () 0)) ((let) (let () 1)))",
+                "(case ((lambda () 0)) ((let) ((lambda () 1))))",
+            ]
+        );
+
+        for source in ["'(let () 1)", "(lambda (let) let)", "(if a b c)", "1"] {
+            assert!(expand_source_steps(source).is_empty(), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_expand_steps_matches_expand() {
+        let sources = [
+            "(define ((adder n) m) (let* ((a n) (b m)) (+ a b)))",
+            "(let loop ((i 0)) (if (< i 3) (loop (let ((j i)) (+ j 1))) i))",
+            "(letrec ((f (lambda () (let-values (((a) (values 1))) a)))) (f))",
+            "(lambda (x) (define (g) (let ((y x)) y)) (g))",
+        ];
+
+        for source in sources {
+            let env = Env::new();
+            let expr = parse(source, true).unwrap();
+            let (stepped, steps) = expand_steps(&env, &expr, MAX_EXPAND_STEPS).unwrap();
+            assert!(steps > 1, "{source}");
+            assert_eq!(
+                stepped.write_repr().to_string(),
+                expand(&env, &expr).unwrap().write_repr().to_string(),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_expand_step_limit() {
+        let env = Env::new();
+        let expr = parse("(let* ((a 1) (b 2)) b)", false).unwrap();
+        assert_eq!(expand_steps(&env, &expr, 3).unwrap().1, 3);
+
+        let err = expand_steps(&env, &expr, 2).unwrap_err();
+        assert_eq!(err.to_string(), "expansion didn't finish within 2 steps");
+    }
+
+    #[test]
+    fn test_malformed_derived_forms() {
+        let table = [
+            ("(let)", "ill-formed special form \"let\""),
+            ("(let (a) a)", "ill-formed special form \"let\""),
+            ("(let* 5 1)", "ill-formed special form \"let*\""),
+            ("(letrec ((a)) a)", "ill-formed special form \"letrec\""),
+            (
+                "(let-values ((a)) a)",
+                "ill-formed special form \"let-values\"",
+            ),
+        ];
+
+        for (source, expected) in table {
+            let expr = parse(source, false).unwrap();
+            let err = expand(&Env::new(), &expr).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{source}");
+        }
+    }
+}
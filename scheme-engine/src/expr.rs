@@ -1,16 +1,25 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::Formatter;
 use std::rc::Rc;
 
 use smol_str::SmolStr;
 
-use crate::env::{Env, LocalId};
-use crate::error::Result;
-use crate::handle::{Handle, RcWeak};
+use crate::env::Env;
+use crate::error::{Error, Result};
+use crate::error_object::{self, ErrorObject};
+use crate::escape;
+use crate::handle::{Handle, HandleCell, RcWeak, Ref};
 use crate::opcode::Op;
+use crate::port::Port;
+use crate::small_vec::SmallVec;
 
-#[derive(Debug, Clone)]
+/// A Scheme value, or a form of source code.
+///
+/// Variants are declared in the order of their [`ExprKind`]. Both kinds of
+/// number are of [`ExprKind::Number`].
+#[derive(Debug, Clone, Default)]
 pub enum Expr {
     /// Nil, null or none.
     ///
@@ -21,6 +30,7 @@ pub enum Expr {
     /// ```scheme
     /// '()
     /// ```
+    #[default]
     Nil,
     /// Returned by special forms or procedures that only have side-effects,
     /// but don't evaluate to values.
@@ -29,42 +39,289 @@ pub enum Expr {
     ///
     /// Also the value of a variable that was declared, but never defined.
     Void,
+    /// Returned by input procedures like `read-line` at the end of input.
+    Eof,
     Bool(bool),
+    /// A number, exact when it's a finite integer.
+    ///
+    /// There are no rationals yet, so an exact division like `(/ 1 2)`
+    /// has an inexact result.
     Number(f64),
-    String(String),
+    /// A number that's inexact even when it's an integer, like `4.0` or
+    /// `1e3` read from source, or the result of `(inexact 4)`.
+    ///
+    /// Arithmetic with an inexact operand has an inexact result.
+    Inexact(f64),
+    /// Immutable string, shared by clones instead of copying the text.
+    String(Rc<str>),
+    Char(char),
     Ident(SmolStr),
     Keyword(Keyword),
     Quote(Box<Expr>),
+    /// Immutable list, shared by clones instead of copying the elements.
     // TODO: List must be a linked list
-    List(Vec<Expr>),
+    List(Rc<[Expr]>),
     // TODO: Handle of tuples, or tuple of handles?
     Pair(Handle<(Expr, Expr)>),
-    Vector(Vec<Expr>),
+    /// Mutable array of values, compared by identity.
+    ///
+    /// ```scheme
+    /// #(1 "two" (3))
+    /// ```
+    Vector(Handle<Vec<Expr>>),
+    /// Mutable buffer of bytes, compared by identity.
+    ///
+    /// ```scheme
+    /// #u8(0 255 16)
+    /// ```
+    Bytevector(Handle<Vec<u8>>),
+    /// Source or destination of characters, compared by identity.
+    Port(Handle<Port>),
+    /// The results of `(values ...)` with zero or several arguments.
+    /// A single value is never wrapped.
+    Values(Rc<[Expr]>),
     Sequence(Vec<Expr>),
     Procedure(Rc<Proc>),
     Closure(Handle<Closure>),
     NativeFunc(NativeFunc),
+    /// A condition made by `error`, or caught by `guard`, compared by identity.
+    ErrorObject(Rc<ErrorObject>),
+}
+
+/// The variant of an [`Expr`], without its payload.
+///
+/// Cheaper to compare than matching on values, for checking the types of
+/// arguments. The numbering is stable, so it can be relied on to tag
+/// serialized values: kinds are only ever added at the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum ExprKind {
+    Nil = 0,
+    Void = 1,
+    Eof = 2,
+    Bool = 3,
+    Number = 4,
+    String = 5,
+    Char = 6,
+    /// An identifier, [`Expr::Ident`].
+    Symbol = 7,
+    Keyword = 8,
+    Quote = 9,
+    List = 10,
+    Pair = 11,
+    Vector = 12,
+    Bytevector = 13,
+    Port = 14,
+    Values = 15,
+    Sequence = 16,
+    Procedure = 17,
+    Closure = 18,
+    NativeFunc = 19,
+    ErrorObject = 20,
+}
+
+impl ExprKind {
+    /// The name of the type, as used in Scheme error messages.
+    ///
+    /// Procedures, closures and native functions are all procedures.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExprKind::Nil => "null",
+            ExprKind::Void => "void",
+            ExprKind::Eof => "eof-object",
+            ExprKind::Bool => "boolean",
+            ExprKind::Number => "number",
+            ExprKind::String => "string",
+            ExprKind::Char => "char",
+            ExprKind::Symbol => "symbol",
+            ExprKind::Keyword => "keyword",
+            ExprKind::Quote => "quote",
+            ExprKind::List => "list",
+            ExprKind::Pair => "pair",
+            ExprKind::Vector => "vector",
+            ExprKind::Bytevector => "bytevector",
+            ExprKind::Port => "port",
+            ExprKind::Values => "values",
+            ExprKind::Sequence => "sequence",
+            ExprKind::Procedure | ExprKind::Closure | ExprKind::NativeFunc => "procedure",
+            ExprKind::ErrorObject => "error-object",
+        }
+    }
 }
 
 impl Expr {
+    /// The empty list.
+    pub const NIL: Expr = Expr::Nil;
+    /// The unspecified value.
+    pub const VOID: Expr = Expr::Void;
+    pub const TRUE: Expr = Expr::Bool(true);
+    pub const FALSE: Expr = Expr::Bool(false);
+
+    /// The symbol with the given name, which may contain any characters.
+    ///
+    /// Symbols compare by name, so this is the same symbol, by `eq?`, as
+    /// one read from source or made by `string->symbol`.
+    ///
+    /// ```
+    /// use scheme_engine::Expr;
+    ///
+    /// let click = Expr::symbol("click");
+    /// assert_eq!(click.as_symbol(), Some("click"));
+    /// assert!(click.is_eqv(&Expr::symbol("click")));
+    /// ```
+    pub fn symbol(name: &str) -> Expr {
+        Expr::Ident(name.into())
+    }
+
+    pub fn kind(&self) -> ExprKind {
+        match self {
+            Expr::Nil => ExprKind::Nil,
+            Expr::Void => ExprKind::Void,
+            Expr::Eof => ExprKind::Eof,
+            Expr::Bool(_) => ExprKind::Bool,
+            Expr::Number(_) | Expr::Inexact(_) => ExprKind::Number,
+            Expr::String(_) => ExprKind::String,
+            Expr::Char(_) => ExprKind::Char,
+            Expr::Ident(_) => ExprKind::Symbol,
+            Expr::Keyword(_) => ExprKind::Keyword,
+            Expr::Quote(_) => ExprKind::Quote,
+            Expr::List(_) => ExprKind::List,
+            Expr::Pair(_) => ExprKind::Pair,
+            Expr::Vector(_) => ExprKind::Vector,
+            Expr::Bytevector(_) => ExprKind::Bytevector,
+            Expr::Port(_) => ExprKind::Port,
+            Expr::Values(_) => ExprKind::Values,
+            Expr::Sequence(_) => ExprKind::Sequence,
+            Expr::Procedure(_) => ExprKind::Procedure,
+            Expr::Closure(_) => ExprKind::Closure,
+            Expr::NativeFunc(_) => ExprKind::NativeFunc,
+            Expr::ErrorObject(_) => ExprKind::ErrorObject,
+        }
+    }
+
+    /// The name of the value's type, as used in Scheme error messages.
+    pub fn type_name(&self) -> &'static str {
+        self.kind().name()
+    }
+
+    /// Whether the value can be called, being a closure or a native function.
+    ///
+    /// A bare [`Proc`] can't, until it's instantiated as a closure.
+    #[inline]
+    pub fn is_callable(&self) -> bool {
+        matches!(self.kind(), ExprKind::Closure | ExprKind::NativeFunc)
+    }
+
     pub fn is_boolean(&self) -> bool {
         matches!(self, Expr::Bool(_))
     }
 
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Expr::Bool(boolean) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Expr::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            Expr::Char(ch) => Some(*ch),
+            _ => None,
+        }
+    }
+
+    pub fn as_symbol(&self) -> Option<&str> {
+        match self {
+            Expr::Ident(name) => Some(name.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_pair(&self) -> Option<&Handle<(Expr, Expr)>> {
+        match self {
+            Expr::Pair(pair) => Some(pair),
+            _ => None,
+        }
+    }
+
+    /// The elements of a vector.
+    pub fn as_vector(&self) -> Option<Ref<'_, [Expr]>> {
+        match self {
+            Expr::Vector(vector) => Some(Ref::map(vector.borrow(), Vec::as_slice)),
+            _ => None,
+        }
+    }
+
+    pub fn as_vector_handle(&self) -> Option<&Handle<Vec<Expr>>> {
+        match self {
+            Expr::Vector(vector) => Some(vector),
+            _ => None,
+        }
+    }
+
+    /// The contents of a bytevector.
+    pub fn as_bytes(&self) -> Option<Ref<'_, [u8]>> {
+        match self {
+            Expr::Bytevector(bytes) => Some(Ref::map(bytes.borrow(), Vec::as_slice)),
+            _ => None,
+        }
+    }
+
+    pub fn as_port(&self) -> Option<&Handle<Port>> {
+        match self {
+            Expr::Port(port) => Some(port),
+            _ => None,
+        }
+    }
+
+    pub fn as_error_object(&self) -> Option<&ErrorObject> {
+        match self {
+            Expr::ErrorObject(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytevector(&self) -> Option<&Handle<Vec<u8>>> {
+        match self {
+            Expr::Bytevector(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
     pub fn as_number(&self) -> Option<f64> {
         match self {
-            Expr::Number(number) => Some(*number),
+            Expr::Number(number) | Expr::Inexact(number) => Some(*number),
             _ => None,
         }
     }
 
     pub fn is_number(&self) -> bool {
-        matches!(self, Expr::Number(_))
+        matches!(self, Expr::Number(_) | Expr::Inexact(_))
+    }
+
+    /// Whether the value is an exact number, an integer that wasn't made
+    /// inexact.
+    ///
+    /// ```
+    /// # use scheme_engine::Expr;
+    /// assert!(Expr::Number(4.0).is_exact());
+    /// assert!(!Expr::Inexact(4.0).is_exact());
+    /// assert!(!Expr::Number(0.5).is_exact());
+    /// ```
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Expr::Number(number) if number.is_finite() && number.fract() == 0.0)
     }
 
     pub fn as_slice(&self) -> Option<&[Expr]> {
         match self {
-            Expr::List(list) => Some(list.as_slice()),
+            Expr::List(list) => Some(list),
             Expr::Sequence(sequence) => Some(sequence.as_slice()),
             _ => None,
         }
@@ -80,7 +337,7 @@ impl Expr {
     pub fn as_sequence(&self) -> Option<&[Expr]> {
         match self {
             Expr::Sequence(expressions) => Some(expressions.as_slice()),
-            Expr::List(expressions) => Some(expressions.as_slice()),
+            Expr::List(expressions) => Some(expressions),
             _ => None,
         }
     }
@@ -92,15 +349,407 @@ impl Expr {
         }
     }
 
+    /// Argument `position` of procedure `who` as a number, or a type error.
+    ///
+    /// Positions are one-based, as they appear in the message.
+    pub fn expect_number(&self, who: &str, position: usize) -> Result<f64> {
+        self.as_number()
+            .ok_or_else(|| self.type_error(who, ExprKind::Number.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as a boolean, or a type error.
+    pub fn expect_bool(&self, who: &str, position: usize) -> Result<bool> {
+        self.as_bool()
+            .ok_or_else(|| self.type_error(who, ExprKind::Bool.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as a string, or a type error.
+    pub fn expect_str(&self, who: &str, position: usize) -> Result<&str> {
+        self.as_str()
+            .ok_or_else(|| self.type_error(who, ExprKind::String.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as a character, or a type error.
+    pub fn expect_char(&self, who: &str, position: usize) -> Result<char> {
+        self.as_char()
+            .ok_or_else(|| self.type_error(who, ExprKind::Char.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as a symbol, or a type error.
+    pub fn expect_symbol(&self, who: &str, position: usize) -> Result<&str> {
+        self.as_symbol()
+            .ok_or_else(|| self.type_error(who, ExprKind::Symbol.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as something that can be
+    /// called, a closure or a native function, or a type error.
+    pub fn expect_callable(&self, who: &str, position: usize) -> Result<&Expr> {
+        if self.is_callable() {
+            Ok(self)
+        } else {
+            Err(self.type_error(who, ExprKind::Procedure.name(), position))
+        }
+    }
+
+    /// Argument `position` of procedure `who` as the elements of a list,
+    /// or a type error.
+    pub fn expect_list(&self, who: &str, position: usize) -> Result<&[Expr]> {
+        match self {
+            Expr::List(list) => Ok(list),
+            Expr::Nil => Ok(&[]),
+            _ => Err(self.type_error(who, ExprKind::List.name(), position)),
+        }
+    }
+
+    /// Argument `position` of procedure `who` as a bytevector, or a type error.
+    pub fn expect_vector(&self, who: &str, position: usize) -> Result<&Handle<Vec<Expr>>> {
+        self.as_vector_handle()
+            .ok_or_else(|| self.type_error(who, ExprKind::Vector.name(), position))
+    }
+
+    pub fn expect_bytevector(&self, who: &str, position: usize) -> Result<&Handle<Vec<u8>>> {
+        self.as_bytevector()
+            .ok_or_else(|| self.type_error(who, ExprKind::Bytevector.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as a port, or a type error.
+    pub fn expect_port(&self, who: &str, position: usize) -> Result<&Handle<Port>> {
+        self.as_port()
+            .ok_or_else(|| self.type_error(who, ExprKind::Port.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as an error object, or a type error.
+    pub fn expect_error_object(&self, who: &str, position: usize) -> Result<&ErrorObject> {
+        self.as_error_object()
+            .ok_or_else(|| self.type_error(who, ExprKind::ErrorObject.name(), position))
+    }
+
+    /// Argument `position` of procedure `who` as a closure, or a type error.
+    ///
+    /// Native functions are procedures too, so the error names closures.
+    pub fn expect_closure(&self, who: &str, position: usize) -> Result<&Handle<Closure>> {
+        self.as_closure()
+            .ok_or_else(|| self.type_error(who, "closure", position))
+    }
+
+    /// The standard error for an argument of the wrong type, such as:
+    ///
+    /// ```text
+    /// car: expected pair as argument 1, got 42
+    /// ```
+    ///
+    /// A `guard` catches it as an error object of kind `type-error`.
+    pub fn type_error(&self, who: &str, expected: &str, position: usize) -> Error {
+        error_object::condition(
+            error_object::ErrorKind::TypeError,
+            format!(
+                "{who}: expected {expected} as argument {position}, got {}",
+                self.write_repr()
+            ),
+        )
+    }
+
+    /// Whether this is the empty list.
+    pub fn is_null(&self) -> bool {
+        match self {
+            Expr::Nil => true,
+            Expr::List(list) => list.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Structural equality, as in `equal?`.
+    ///
+    /// Lists, pairs, vectors and bytevectors are equal when their
+    /// contents are, however the lists are represented. Other values
+    /// compare as with `==`.
+    ///
+    /// Cyclic structure compares equal when it unfolds the same way.
+    /// Each two pairs or vectors compared are remembered, and taken to be equal when
+    /// they're reached again, so the comparison always terminates.
+    pub fn is_equal(&self, other: &Expr) -> bool {
+        self.is_equal_with(other, &mut HashSet::new())
+    }
+
+    /// Whether two values are the same object, as `eqv?` and `eq?` see it.
+    ///
+    /// Symbols, characters and booleans compare by value. Numbers compare
+    /// by their exactness and bits, so `2` and `2.0` differ, as do `0.0`
+    /// and `-0.0`, and a NaN is the same as itself. Values with contents,
+    /// like strings, pairs and procedures, are only the same as themselves
+    /// or their clones.
+    pub fn is_eqv(&self, other: &Expr) -> bool {
+        match (self, other) {
+            _ if self.is_null() || other.is_null() => self.is_null() && other.is_null(),
+            (Expr::Void, Expr::Void) | (Expr::Eof, Expr::Eof) => true,
+            (Expr::Bool(a), Expr::Bool(b)) => a == b,
+            (a, b) if a.is_number() && b.is_number() => {
+                a.is_exact() == b.is_exact()
+                    && a.as_number().map(f64::to_bits) == b.as_number().map(f64::to_bits)
+            }
+            (Expr::Char(a), Expr::Char(b)) => a == b,
+            (Expr::Ident(a), Expr::Ident(b)) => a == b,
+            (Expr::Keyword(a), Expr::Keyword(b)) => a == b,
+            (Expr::String(a), Expr::String(b)) => Rc::ptr_eq(a, b),
+            (Expr::List(a), Expr::List(b)) | (Expr::Values(a), Expr::Values(b)) => Rc::ptr_eq(a, b),
+            (Expr::Pair(a), Expr::Pair(b)) => a.ptr_eq(b),
+            (Expr::Vector(a), Expr::Vector(b)) => a.ptr_eq(b),
+            (Expr::Bytevector(a), Expr::Bytevector(b)) => a.ptr_eq(b),
+            (Expr::Port(a), Expr::Port(b)) => a.ptr_eq(b),
+            (Expr::Procedure(a), Expr::Procedure(b)) => Rc::ptr_eq(a, b),
+            (Expr::Closure(a), Expr::Closure(b)) => a.ptr_eq(b),
+            (Expr::NativeFunc(a), Expr::NativeFunc(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Expr::ErrorObject(a), Expr::ErrorObject(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// See [`Expr::is_equal`]. The pairs and vectors compared so far are kept by address.
+    fn is_equal_with(&self, other: &Expr, compared: &mut HashSet<(usize, usize)>) -> bool {
+        let (mut left, mut right) = (self.clone(), other.clone());
+        loop {
+            // The spines of lists are walked here, so only the
+            // elements recurse.
+            let (left_rest, right_rest) = match (&left, &right) {
+                _ if left.is_null() || right.is_null() => return left.is_null() && right.is_null(),
+                (Expr::List(a), Expr::List(b)) => {
+                    return a.len() == b.len()
+                        && a.iter()
+                            .zip(b.iter())
+                            .all(|(a, b)| a.is_equal_with(b, compared))
+                }
+                (Expr::Pair(a), Expr::Pair(b)) if !compared.insert((a.addr(), b.addr())) => {
+                    return true
+                }
+                (Expr::List(_) | Expr::Pair(_), Expr::List(_) | Expr::Pair(_)) => {
+                    let (left_head, left_tail) = left.uncons().expect("non-empty list");
+                    let (right_head, right_tail) = right.uncons().expect("non-empty list");
+                    if !left_head.is_equal_with(&right_head, compared) {
+                        return false;
+                    }
+                    (left_tail, right_tail)
+                }
+                (Expr::Vector(a), Expr::Vector(b)) if !compared.insert((a.addr(), b.addr())) => {
+                    return true
+                }
+                (Expr::Vector(a), Expr::Vector(b)) => {
+                    let (a, b) = (a.borrow(), b.borrow());
+                    return a.len() == b.len()
+                        && a.iter()
+                            .zip(b.iter())
+                            .all(|(a, b)| a.is_equal_with(b, compared));
+                }
+                (Expr::Bytevector(a), Expr::Bytevector(b)) => return *a.borrow() == *b.borrow(),
+                _ => return left == right,
+            };
+            left = left_rest;
+            right = right_rest;
+        }
+    }
+
+    /// The first element and the rest of a pair or non-empty list.
+    fn uncons(&self) -> Option<(Expr, Expr)> {
+        match self {
+            Expr::Pair(pair) => Some(pair.borrow().clone()),
+            Expr::List(list) if !list.is_empty() => {
+                Some((list[0].clone(), Expr::List(list[1..].into())))
+            }
+            _ => None,
+        }
+    }
+
+    /// A proper list of pairs holding the elements in order.
+    ///
+    /// The pairs are linked from the last element backwards, so building
+    /// a long list takes no more stack than a short one.
+    pub fn list_from_iter(iter: impl IntoIterator<Item = Expr>) -> Expr {
+        let elements: Vec<Expr> = iter.into_iter().collect();
+        elements.into_iter().rev().fold(Expr::Nil, |tail, head| {
+            Expr::Pair(Handle::new((head, tail)))
+        })
+    }
+
+    /// Iterate over the elements of a proper list, made of pairs, an
+    /// immutable list or both.
+    ///
+    /// Improper and cyclic lists are an error, found before any
+    /// element is yielded.
+    pub fn iter_list(&self) -> Result<ListIter> {
+        match self.spine() {
+            Spine::Proper(len) => Ok(ListIter {
+                rest: self.clone(),
+                offset: 0,
+                remaining: len,
+            }),
+            Spine::Improper(_) => Err(Error::Reason(format!(
+                "expected proper list, got {}",
+                self.write_repr()
+            ))),
+            Spine::Cyclic => Err(Error::Reason(
+                "expected proper list, got a circular list".to_string(),
+            )),
+        }
+    }
+
+    /// Walk the spine of a list, following the tails of pairs until
+    /// something other than a pair is reached.
+    ///
+    /// Uses Floyd's tortoise and hare, so a cyclic chain of pairs
+    /// is detected instead of walked forever.
+    pub(crate) fn spine(&self) -> Spine {
+        let start = match self {
+            Expr::Pair(pair) => pair.clone(),
+            end => return Spine::ended_by(end, 0),
+        };
+
+        let mut slow = start.clone();
+        let mut fast = start;
+        let mut len = 1;
+        loop {
+            for _ in 0..2 {
+                let next = match &fast.borrow().1 {
+                    Expr::Pair(next) => next.clone(),
+                    end => return Spine::ended_by(end, len),
+                };
+                fast = next;
+                len += 1;
+            }
+
+            let next = slow.borrow().1.as_pair().cloned();
+            slow = next.expect("hare has walked past the tortoise");
+            if slow.ptr_eq(&fast) {
+                return Spine::Cyclic;
+            }
+        }
+    }
+
+    /// Whether the value holds data that can be changed in place, like
+    /// pairs, vectors and bytevectors, anywhere inside it.
+    pub(crate) fn has_mutable_parts(&self) -> bool {
+        match self {
+            Expr::Pair(_) | Expr::Vector(_) | Expr::Bytevector(_) => true,
+            Expr::List(list) => list.iter().any(Expr::has_mutable_parts),
+            Expr::Sequence(elements) => elements.iter().any(Expr::has_mutable_parts),
+            Expr::Quote(quoted) => quoted.has_mutable_parts(),
+            _ => false,
+        }
+    }
+
+    /// Copy the mutable parts of a literal, so changing the copy leaves
+    /// the literal as it was written.
+    ///
+    /// Pairs, vectors and bytevectors reached more than once are copied once, so
+    /// shared and cyclic structure, read from datum labels, stays that
+    /// way in the copy.
+    pub(crate) fn copy_literal(&self) -> Expr {
+        self.copy_literal_with(&mut HashMap::new())
+    }
+
+    /// See [`Expr::copy_literal`]. The copies are keyed by the
+    /// address of the original handle.
+    fn copy_literal_with(&self, copies: &mut HashMap<usize, Expr>) -> Expr {
+        match self {
+            Expr::Bytevector(bytes) => copies
+                .entry(bytes.addr())
+                .or_insert_with(|| Expr::Bytevector(Handle::new(bytes.borrow().clone())))
+                .clone(),
+            Expr::Pair(pair) => {
+                if let Some(copy) = copies.get(&pair.addr()) {
+                    return copy.clone();
+                }
+
+                // Walk the spine first, so a long list isn't copied
+                // with one level of recursion per element. The copies
+                // are made empty up front, so the heads and the tail
+                // can refer back to them.
+                let mut spine = Vec::new();
+                let mut end = self.clone();
+                while let Expr::Pair(next) = &end {
+                    if copies.contains_key(&next.addr()) {
+                        break;
+                    }
+                    let copy = Handle::new((Expr::Nil, Expr::Nil));
+                    copies.insert(next.addr(), Expr::Pair(copy.clone()));
+                    spine.push((next.clone(), copy));
+                    let tail = next.borrow().1.clone();
+                    end = tail;
+                }
+
+                let mut tail = end.copy_literal_with(copies);
+                for (pair, mut copy) in spine.into_iter().rev() {
+                    let head = pair.borrow().0.copy_literal_with(copies);
+                    *copy.borrow_mut() = (head, tail);
+                    tail = Expr::Pair(copy);
+                }
+                tail
+            }
+            Expr::List(list) if self.has_mutable_parts() => Expr::List(
+                list.iter()
+                    .map(|expr| expr.copy_literal_with(copies))
+                    .collect(),
+            ),
+            Expr::Vector(vector) => {
+                if let Some(copy) = copies.get(&vector.addr()) {
+                    return copy.clone();
+                }
+                // Made empty up front, so the elements can refer back to it.
+                let mut copy = Handle::new(Vec::new());
+                copies.insert(vector.addr(), Expr::Vector(copy.clone()));
+                let elements = vector
+                    .borrow()
+                    .iter()
+                    .map(|expr| expr.copy_literal_with(copies))
+                    .collect();
+                *copy.borrow_mut() = elements;
+                Expr::Vector(copy)
+            }
+            Expr::Quote(quoted) => Expr::Quote(Box::new(quoted.copy_literal_with(copies))),
+            _ => self.clone(),
+        }
+    }
+
+    /// Human readable representation, as printed by `display`.
+    ///
+    /// Strings are printed without enclosing quotes or escapes.
+    /// Cycles are written with datum labels, like `write`.
     #[inline]
-    pub fn repr(&self) -> ExprRepr {
-        ExprRepr { expr: self }
+    pub fn repr(&self) -> ExprRepr<'_> {
+        ExprRepr {
+            expr: self,
+            write: false,
+            depth: None,
+            length: None,
+            labels: DatumLabels::Cycles,
+            found: None,
+            precision: None,
+        }
     }
-}
 
-impl Default for Expr {
-    fn default() -> Self {
-        Expr::Nil
+    /// Machine readable representation, as printed by `write`.
+    ///
+    /// Strings are enclosed in double quotes with special characters escaped,
+    /// so literal data can be read back by the parser. Characters are
+    /// written as `#\` literals, and pairs that are part of a cycle are
+    /// labelled, like `#0=(1 2 . #0#)`.
+    #[inline]
+    pub fn write_repr(&self) -> ExprRepr<'_> {
+        ExprRepr {
+            expr: self,
+            write: true,
+            depth: None,
+            length: None,
+            labels: DatumLabels::Cycles,
+            found: None,
+            precision: None,
+        }
+    }
+
+    /// Whether a pair can be reached from itself, through the heads
+    /// or tails of pairs and the elements of lists and vectors.
+    pub fn is_cyclic(&self) -> bool {
+        !LabelTable::find(self, DatumLabels::Cycles)
+            .labels
+            .is_empty()
     }
 }
 
@@ -111,42 +760,608 @@ impl PartialEq<Expr> for Expr {
         match (self, other) {
             (Nil, Nil) => true,
             (Void, Void) => true,
+            (Eof, Eof) => true,
+            (Values(a), Values(b)) => a == b,
             (Bool(a), Bool(b)) => a == b,
-            (Number(a), Number(b)) => a == b,
+            (a, b) if a.is_number() && b.is_number() => {
+                a.is_exact() == b.is_exact() && a.as_number() == b.as_number()
+            }
             (String(a), String(b)) => a == b,
+            (Char(a), Char(b)) => a == b,
             (Ident(a), Ident(b)) => a == b,
             (Keyword(a), Keyword(b)) => a == b,
+            (List(a), List(b)) => a == b,
+            (Vector(a), Vector(b)) => a.ptr_eq(b),
             (Procedure(a), Procedure(b)) => Rc::ptr_eq(a, b),
             (Closure(a), Closure(b)) => a.ptr_eq(b),
+            (Pair(a), Pair(b)) => a.ptr_eq(b),
+            (Bytevector(a), Bytevector(b)) => a.ptr_eq(b),
+            (Port(a), Port(b)) => a.ptr_eq(b),
+            (ErrorObject(a), ErrorObject(b)) => Rc::ptr_eq(a, b),
             _ => false,
         }
     }
 }
 
+impl From<f64> for Expr {
+    fn from(number: f64) -> Self {
+        Expr::Number(number)
+    }
+}
+
+impl From<bool> for Expr {
+    fn from(boolean: bool) -> Self {
+        Expr::Bool(boolean)
+    }
+}
+
+impl From<char> for Expr {
+    fn from(ch: char) -> Self {
+        Expr::Char(ch)
+    }
+}
+
+impl From<&str> for Expr {
+    fn from(string: &str) -> Self {
+        Expr::String(string.into())
+    }
+}
+
+impl From<String> for Expr {
+    fn from(string: String) -> Self {
+        Expr::String(string.into())
+    }
+}
+
+/// A vector of the values.
+impl From<Vec<Expr>> for Expr {
+    fn from(elements: Vec<Expr>) -> Self {
+        Expr::Vector(Handle::new(elements))
+    }
+}
+
+impl From<Vec<u8>> for Expr {
+    fn from(bytes: Vec<u8>) -> Self {
+        Expr::Bytevector(Handle::new(bytes))
+    }
+}
+
+impl From<&[u8]> for Expr {
+    fn from(bytes: &[u8]) -> Self {
+        Expr::from(bytes.to_vec())
+    }
+}
+
+/// The shape of a list, as found by [`Expr::spine`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Spine {
+    /// Ends in the empty list, with this many elements.
+    Proper(usize),
+    /// Ends in a value other than the empty list, after this many pairs.
+    Improper(usize),
+    /// The tail of a pair leads back to an earlier pair.
+    Cyclic,
+}
+
+impl Spine {
+    /// The shape of a list whose walk reached a value that isn't a pair.
+    fn ended_by(end: &Expr, len: usize) -> Self {
+        match end {
+            Expr::Nil => Spine::Proper(len),
+            Expr::List(list) => Spine::Proper(len + list.len()),
+            _ => Spine::Improper(len),
+        }
+    }
+}
+
+/// The elements of a proper list, from [`Expr::iter_list`].
+///
+/// The length is measured before iterating, so a list whose pairs are
+/// changed in the meantime can't make the iterator loop forever.
+/// Running out of elements early is an error.
+pub struct ListIter {
+    rest: Expr,
+    /// Index of the next element when `rest` is an immutable list.
+    offset: usize,
+    remaining: usize,
+}
+
+impl Iterator for ListIter {
+    type Item = Result<Expr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        match &self.rest {
+            Expr::Pair(pair) => {
+                let (head, tail) = pair.borrow().clone();
+                self.rest = tail;
+                Some(Ok(head))
+            }
+            Expr::List(list) if self.offset < list.len() => {
+                self.offset += 1;
+                Some(Ok(list[self.offset - 1].clone()))
+            }
+            _ => {
+                self.remaining = 0;
+                Some(Err(Error::Reason(
+                    "list was shortened while iterating over it".to_string(),
+                )))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl FromIterator<Expr> for Expr {
+    /// A proper list of pairs, like [`Expr::list_from_iter`].
+    fn from_iter<I: IntoIterator<Item = Expr>>(iter: I) -> Self {
+        Expr::list_from_iter(iter)
+    }
+}
+
+impl Drop for Expr {
+    /// Take nested values apart from a worklist, since letting them drop
+    /// each other would overflow the stack for long lists or deep nesting.
+    ///
+    /// Only values this is the last owner of are taken apart. Each value
+    /// popped off the worklist has its own contents moved onto it before
+    /// it's dropped, so its drop has nothing left to recurse into.
+    fn drop(&mut self) {
+        let mut pending = vec![];
+        self.take_contents(&mut pending);
+        while let Some(mut expr) = pending.pop() {
+            expr.take_contents(&mut pending);
+        }
+    }
+}
+
+impl Expr {
+    /// Move the values nested in this one onto the worklist, when nothing
+    /// else shares them. Values with nothing nested are left to drop.
+    fn take_contents(&mut self, pending: &mut Vec<Expr>) {
+        fn push(pending: &mut Vec<Expr>, expr: Expr) {
+            if expr.has_contents() {
+                pending.push(expr);
+            }
+        }
+
+        match self {
+            Expr::Quote(quoted) => push(pending, std::mem::take(&mut **quoted)),
+            Expr::List(elements) | Expr::Values(elements) => {
+                if let Some(elements) = Rc::get_mut(elements) {
+                    for element in elements.iter_mut() {
+                        push(pending, std::mem::take(element));
+                    }
+                }
+            }
+            Expr::Sequence(elements) => {
+                for element in elements.drain(..) {
+                    push(pending, element);
+                }
+            }
+            Expr::Vector(vector) if vector.is_unique() => {
+                for element in vector.borrow_mut().drain(..) {
+                    push(pending, element);
+                }
+            }
+            Expr::Pair(pair) if pair.is_unique() => {
+                let (head, tail) = std::mem::take(&mut *pair.borrow_mut());
+                push(pending, head);
+                push(pending, tail);
+            }
+            Expr::Closure(closure) if closure.is_unique() => {
+                for up_value in closure.borrow_mut().up_values.iter_mut() {
+                    if up_value.is_unique() {
+                        if let UpValue::Closed(value) = &mut *up_value.borrow_mut() {
+                            push(pending, std::mem::take(value));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the value can hold other values, which its drop takes apart.
+    fn has_contents(&self) -> bool {
+        match self {
+            Expr::Quote(_) | Expr::Pair(_) | Expr::Vector(_) | Expr::Closure(_) => true,
+            Expr::List(elements) | Expr::Values(elements) => !elements.is_empty(),
+            Expr::Sequence(elements) => !elements.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+/// The text of a number, which reads back as the same number.
+///
+/// Infinities are written `+inf.0` and `-inf.0`, and every NaN `+nan.0`.
+/// With a precision, the number is rounded to at most that many digits
+/// after the decimal point, and may no longer read back the same.
+pub(crate) fn number_repr(number: f64, precision: Option<usize>) -> String {
+    if number.is_nan() {
+        return "+nan.0".to_string();
+    }
+    if number.is_infinite() {
+        return if number > 0.0 { "+inf.0" } else { "-inf.0" }.to_string();
+    }
+
+    match precision {
+        Some(digits) => {
+            let text = format!("{number:.digits$}");
+            if text.contains('.') {
+                text.trim_end_matches('0').trim_end_matches('.').to_string()
+            } else {
+                text
+            }
+        }
+        None => number.to_string(),
+    }
+}
+
+/// Like [`number_repr`], but an integer keeps a `.0` so it reads back as
+/// an inexact number.
+pub(crate) fn inexact_repr(number: f64, precision: Option<usize>) -> String {
+    let mut text = number_repr(number, precision);
+    if number.is_finite() && !text.contains(['.', 'e']) {
+        text.push_str(".0");
+    }
+    text
+}
+
+/// Which pairs and vectors are written with datum labels, like
+/// `#0=(a . #0#)`, so the structure can be read back as it was.
+///
+/// Pairs and vectors are the only values that hold others and have
+/// identity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatumLabels {
+    /// No labels, as `write-simple` writes. A cyclic list is cut
+    /// short with `...` where it loops back.
+    Never,
+    /// Pairs and vectors that are part of a cycle, as `write` and
+    /// `display` write.
+    Cycles,
+    /// Pairs and vectors reached more than once, as `write-shared` writes.
+    Shared,
+}
+
+/// The pairs and vectors of a value that get datum labels, keyed by address.
+#[derive(Debug, Default)]
+struct LabelTable {
+    /// The number of each label, given when its value is first written.
+    labels: HashMap<usize, Option<usize>>,
+    next: usize,
+}
+
+/// How a labelled pair or vector is written.
+enum Label {
+    /// The first time, like `#0=`, followed by the value.
+    Definition(usize),
+    /// Every later time, like `#0#`, instead of the value.
+    Reference(usize),
+}
+
+impl LabelTable {
+    /// Find the pairs and vectors that need labels, walking the value
+    /// depth first.
+    ///
+    /// A pair or vector reached again while its own contents are being
+    /// walked is part of a cycle. The walk keeps its own stack, so long lists don't
+    /// recurse.
+    fn find(expr: &Expr, policy: DatumLabels) -> Self {
+        enum Visit {
+            Enter(Expr),
+            Leave(usize),
+        }
+
+        let mut table = Self::default();
+        if policy == DatumLabels::Never {
+            return table;
+        }
+
+        // Whether each pair and vector reached so far has been walked completely.
+        let mut walked: HashMap<usize, bool> = HashMap::new();
+        let mut stack = vec![Visit::Enter(expr.clone())];
+        while let Some(visit) = stack.pop() {
+            let expr = match &visit {
+                Visit::Enter(expr) => expr,
+                Visit::Leave(addr) => {
+                    walked.insert(*addr, true);
+                    continue;
+                }
+            };
+            let addr = match expr {
+                Expr::Pair(pair) => pair.addr(),
+                Expr::Vector(vector) => vector.addr(),
+                Expr::List(elements) | Expr::Values(elements) => {
+                    stack.extend(elements.iter().cloned().map(Visit::Enter));
+                    continue;
+                }
+                Expr::Sequence(elements) => {
+                    stack.extend(elements.iter().cloned().map(Visit::Enter));
+                    continue;
+                }
+                Expr::Quote(quoted) => {
+                    stack.push(Visit::Enter((**quoted).clone()));
+                    continue;
+                }
+                _ => continue,
+            };
+            match walked.get(&addr) {
+                Some(false) => {
+                    table.labels.insert(addr, None);
+                }
+                Some(true) if policy == DatumLabels::Shared => {
+                    table.labels.insert(addr, None);
+                }
+                Some(true) => {}
+                None => {
+                    walked.insert(addr, false);
+                    stack.push(Visit::Leave(addr));
+                    match expr {
+                        Expr::Pair(pair) => {
+                            let (head, tail) = pair.borrow().clone();
+                            stack.push(Visit::Enter(tail));
+                            stack.push(Visit::Enter(head));
+                        }
+                        Expr::Vector(vector) => {
+                            stack.extend(vector.borrow().iter().cloned().map(Visit::Enter))
+                        }
+                        _ => unreachable!("only pairs and vectors have labels"),
+                    }
+                }
+            }
+        }
+        table
+    }
+}
+
 pub struct ExprRepr<'a> {
     expr: &'a Expr,
+    /// Use the `write` style instead of the `display` style.
+    write: bool,
+    /// Levels of nested lists and vectors left to print, when limited.
+    depth: Option<usize>,
+    /// Elements printed per list or vector, when limited.
+    length: Option<usize>,
+    /// Which pairs are labelled.
+    labels: DatumLabels,
+    /// The labels of the outermost value being written, found before
+    /// writing it and shared with the values nested in it.
+    found: Option<Rc<RefCell<LabelTable>>>,
+    /// Digits printed after the decimal point of numbers, when limited.
+    precision: Option<usize>,
 }
 
 impl<'a> ExprRepr<'a> {
+    /// Abbreviate large values, like the results printed by a REPL.
+    ///
+    /// At most `depth` levels of nested lists and vectors are printed, with
+    /// deeper ones replaced by `#`. Each prints at most `length` elements,
+    /// followed by `...` when there are more.
+    pub fn with_limits(self, depth: usize, length: usize) -> Self {
+        Self {
+            depth: Some(depth),
+            length: Some(length),
+            ..self
+        }
+    }
+
+    /// Round numbers to at most `digits` after the decimal point, with
+    /// trailing zeros left out.
+    ///
+    /// Without it, numbers are printed with the fewest digits that read
+    /// back as the same number.
+    pub fn with_precision(self, digits: usize) -> Self {
+        Self {
+            precision: Some(digits),
+            ..self
+        }
+    }
+
+    /// Choose which pairs are written with datum labels.
+    pub fn with_labels(self, labels: DatumLabels) -> Self {
+        Self { labels, ..self }
+    }
+
+    /// The representation of an element nested one level deeper.
+    fn nested<'b>(&self, expr: &'b Expr) -> ExprRepr<'b> {
+        ExprRepr {
+            expr,
+            write: self.write,
+            depth: self.depth.map(|depth| depth.saturating_sub(1)),
+            length: self.length,
+            labels: self.labels,
+            found: self.found.clone(),
+            precision: self.precision,
+        }
+    }
+
+    /// The label to write for the pair or vector at the address, if it has one.
+    fn label(&self, addr: usize) -> Option<Label> {
+        let mut found = self.found.as_ref()?.borrow_mut();
+        let LabelTable { labels, next } = &mut *found;
+        match labels.get_mut(&addr)? {
+            Some(number) => Some(Label::Reference(*number)),
+            number @ None => {
+                *number = Some(*next);
+                *next += 1;
+                Some(Label::Definition(*next - 1))
+            }
+        }
+    }
+
+    fn is_labelled(&self, pair: &Handle<(Expr, Expr)>) -> bool {
+        self.found
+            .as_ref()
+            .is_some_and(|found| found.borrow().labels.contains_key(&pair.addr()))
+    }
+
+    /// Whether the element at `index` is past the length limit.
+    fn is_past_length(&self, index: usize) -> bool {
+        self.length.is_some_and(|length| index >= length)
+    }
+
     fn fmt_expressions(&self, f: &mut fmt::Formatter, expressions: &[Expr]) -> fmt::Result {
         write!(f, "(")?;
         for (idx, expr) in expressions.iter().enumerate() {
             if idx != 0 {
                 write!(f, " ")?;
             }
-            let repr = ExprRepr { expr };
-            write!(f, "{repr}")?;
+            if self.is_past_length(idx) {
+                write!(f, "...")?;
+                break;
+            }
+            write!(f, "{}", self.nested(expr))?;
         }
         write!(f, ")")?;
         Ok(())
     }
+
+    /// A chain of pairs in list notation, with a dot before an improper tail.
+    ///
+    /// A labelled pair in the tail is written after a dot, so its label
+    /// can be. Without labels, a cyclic chain is cut short with `...`
+    /// where it loops back.
+    fn fmt_pairs(&self, f: &mut fmt::Formatter, pair: &Handle<(Expr, Expr)>) -> fmt::Result {
+        let cyclic = self.found.is_none() && Expr::Pair(pair.clone()).spine() == Spine::Cyclic;
+        let mut visited = Vec::new();
+
+        write!(f, "(")?;
+        let mut pair = pair.clone();
+        for index in 0.. {
+            if cyclic {
+                if visited.iter().any(|seen: &Handle<_>| seen.ptr_eq(&pair)) {
+                    write!(f, "...")?;
+                    break;
+                }
+                visited.push(pair.clone());
+            }
+            if self.is_past_length(index) {
+                write!(f, "...")?;
+                break;
+            }
+
+            let next = {
+                let (head, tail) = &*pair.borrow();
+                write!(f, "{}", self.nested(head))?;
+
+                match tail {
+                    Expr::Pair(next) if self.is_labelled(next) => {
+                        write!(f, " . {}", self.nested(tail))?;
+                        break;
+                    }
+                    Expr::Pair(next) => next.clone(),
+                    Expr::Nil => break,
+                    Expr::List(list) => {
+                        for (offset, expr) in list.iter().enumerate() {
+                            if self.is_past_length(index + 1 + offset) {
+                                write!(f, " ...")?;
+                                break;
+                            }
+                            write!(f, " {}", self.nested(expr))?;
+                        }
+                        break;
+                    }
+                    tail => {
+                        write!(f, " . {}", self.nested(tail))?;
+                        break;
+                    }
+                }
+            };
+            write!(f, " ")?;
+            pair = next;
+        }
+        write!(f, ")")
+    }
+
+    fn fmt_string(&self, f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+        if !self.write {
+            return write!(f, "{string}");
+        }
+
+        write!(f, "\"")?;
+        for ch in string.chars() {
+            match ch {
+                '"' => write!(f, "\\\"")?,
+                '\\' => write!(f, "\\\\")?,
+                '\n' => write!(f, "\\n")?,
+                '\t' => write!(f, "\\t")?,
+                '\r' => write!(f, "\\r")?,
+                '\u{7}' => write!(f, "\\a")?,
+                '\u{8}' => write!(f, "\\b")?,
+                _ if ch.is_control() => write!(f, "\\x{:x};", ch as u32)?,
+                _ => write!(f, "{ch}")?,
+            }
+        }
+        write!(f, "\"")
+    }
+
+    fn fmt_char(&self, f: &mut fmt::Formatter, ch: char) -> fmt::Result {
+        if !self.write {
+            return write!(f, "{ch}");
+        }
+
+        match escape::char_name(ch) {
+            Some(name) => write!(f, "#\\{name}"),
+            None if ch.is_control() || ch.is_whitespace() => write!(f, "#\\x{:x}", ch as u32),
+            None => write!(f, "#\\{ch}"),
+        }
+    }
 }
 
 impl<'a> fmt::Display for ExprRepr<'a> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let nested = match self.expr {
+            Expr::List(list) => !list.is_empty(),
+            Expr::Vector(vector) => !vector.borrow().is_empty(),
+            Expr::Sequence(list) => !list.is_empty(),
+            Expr::Pair(_) => true,
+            _ => false,
+        };
+
+        // Labels are found once, for the outermost value.
+        let may_hold_pairs = nested || matches!(self.expr, Expr::Quote(_) | Expr::Values(_));
+        if self.found.is_none() && self.labels != DatumLabels::Never && may_hold_pairs {
+            let found = LabelTable::find(self.expr, self.labels);
+            let outermost = ExprRepr {
+                expr: self.expr,
+                write: self.write,
+                depth: self.depth,
+                length: self.length,
+                labels: self.labels,
+                found: Some(Rc::new(RefCell::new(found))),
+                precision: self.precision,
+            };
+            return write!(f, "{outermost}");
+        }
+
+        if nested && self.depth == Some(0) {
+            return write!(f, "#");
+        }
+
         match self.expr {
             Expr::Nil => write!(f, "'()"),
             Expr::Void => write!(f, "#!void"),
+            Expr::Eof => write!(f, "#<eof>"),
+            // Each value, separated by spaces.
+            Expr::Values(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", self.nested(value))?;
+                }
+                Ok(())
+            }
             Expr::Bool(boolean) => {
                 if *boolean {
                     write!(f, "#t")
@@ -154,8 +1369,13 @@ impl<'a> fmt::Display for ExprRepr<'a> {
                     write!(f, "#f")
                 }
             }
-            Expr::Number(number) => write!(f, "{number}"),
-            Expr::String(string) => write!(f, "{string}"),
+            Expr::Number(number) => write!(f, "{}", number_repr(*number, self.precision)),
+            Expr::Inexact(number) => write!(f, "{}", inexact_repr(*number, self.precision)),
+            Expr::String(string) => self.fmt_string(f, string),
+            Expr::Char(ch) => self.fmt_char(f, *ch),
+            Expr::Ident(name) if self.write && escape::symbol_needs_bars(name) => {
+                escape::write_bar_symbol(f, name)
+            }
             Expr::Ident(name) => write!(f, "{name}"),
             Expr::Keyword(keyword) => match keyword {
                 Keyword::Dot => write!(f, "."),
@@ -168,6 +1388,44 @@ impl<'a> fmt::Display for ExprRepr<'a> {
                 self.fmt_expressions(f, expressions)?;
                 Ok(())
             }
+            // Quoted code, as the reader would read it.
+            Expr::Quote(value) => match **value {
+                // Nil is already written quoted.
+                Expr::Nil => write!(f, "'()"),
+                _ => write!(f, "'{}", self.nested(value)),
+            },
+            Expr::Vector(vector) => {
+                match self.label(vector.addr()) {
+                    Some(Label::Reference(number)) => return write!(f, "#{number}#"),
+                    Some(Label::Definition(number)) => write!(f, "#{number}=")?,
+                    None => {}
+                }
+                write!(f, "#")?;
+                self.fmt_expressions(f, &vector.borrow())
+            }
+            Expr::Bytevector(bytes) => {
+                write!(f, "#u8(")?;
+                for (index, byte) in bytes.borrow().iter().enumerate() {
+                    if index != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{byte}")?;
+                }
+                write!(f, ")")
+            }
+            Expr::Pair(pair) => match self.label(pair.addr()) {
+                Some(Label::Reference(number)) => write!(f, "#{number}#"),
+                Some(Label::Definition(number)) => {
+                    write!(f, "#{number}=")?;
+                    self.fmt_pairs(f, pair)
+                }
+                None => self.fmt_pairs(f, pair),
+            },
+            Expr::Port(port) => {
+                let port = port.borrow();
+                let direction = if port.is_input() { "input" } else { "output" };
+                write!(f, "#<{direction}-port {}>", port.name())
+            }
             Expr::Procedure(procedure) => {
                 write!(f, "<procedure {:?}>", Rc::as_ptr(procedure))
             }
@@ -178,12 +1436,22 @@ impl<'a> fmt::Display for ExprRepr<'a> {
                     Rc::as_ptr(&closure.borrow().procedure_rc())
                 )
             }
-            Expr::NativeFunc(func) => {
+            Expr::NativeFunc(_func) => {
                 //  TODO!("keep Rust function name")
                 write!(f, "<native-function>")
             }
-            unsupported_type => {
-                todo!("expression type repr not implemented yet: {unsupported_type:?}")
+            Expr::ErrorObject(object) => {
+                write!(f, "#<{} ", object.kind())?;
+                self.fmt_string(f, object.message())?;
+                for irritant in object.irritants() {
+                    let irritant = if self.write {
+                        irritant.write_repr()
+                    } else {
+                        irritant.repr()
+                    };
+                    write!(f, " {irritant}")?;
+                }
+                write!(f, ">")
             }
         }
     }
@@ -207,21 +1475,54 @@ pub struct Proc {
     /// The number of arguments this function accepts.
     pub(crate) sig: Signature,
 
-    pub(crate) constants: Box<[Expr]>,
+    pub(crate) constants: Constants,
 
-    /// The number of local variables per call frame that this procedure needs.
+    /// The number of local variable slots per call frame that this
+    /// procedure needs, including its parameters.
     pub(crate) local_count: usize,
 
     /// The number of up-values that a closure of this procedure will close
     /// over when instantiated.
     pub(crate) up_value_count: usize,
 
+    /// Whether calling the procedure may have side effects, like output
+    /// or mutating the environment.
+    ///
+    /// Conservative: any use of `set!`, a top-level `define`, or an effectful
+    /// variable in the body or a nested procedure counts.
+    pub(crate) effectful: bool,
+
+    /// The top-level forms of the program the code was compiled from, as
+    /// the index of the form's first instruction and the form's number,
+    /// in order. Empty when the program was a single form.
+    pub(crate) forms: Box<[(usize, usize)]>,
+
     /// The environment where the procedure was defined.
     ///
     /// Because the procedure is referenced by a closure, and both can
     /// be stored in variables within the environment, a circular reference
     /// is created that would prevent the environment from being dropped.
-    pub(crate) env: RcWeak<RefCell<Env>>,
+    pub(crate) env: RcWeak<HandleCell<Env>>,
+}
+
+/// Where a procedure's [`Op::PushConstant`] operands are looked up.
+#[derive(Debug)]
+pub(crate) enum Constants {
+    /// The procedure owns its constant table.
+    Local(Box<[Expr]>),
+    /// Constants live in the shared pool of the procedure's environment.
+    Shared,
+}
+
+impl Constants {
+    /// Resolve the constant table, given the procedure's environment.
+    #[inline]
+    pub(crate) fn table<'a>(&'a self, env: &'a Env) -> &'a [Expr] {
+        match self {
+            Constants::Local(constants) => constants,
+            Constants::Shared => &env.constants,
+        }
+    }
 }
 
 /// Procedure signature.
@@ -248,14 +1549,33 @@ impl Signature {
 
 impl Proc {
     /// Bytecode instructions for this procedure.
+    ///
+    /// See [`crate::disassemble`] for a readable listing.
     #[inline]
-    pub fn bytecode(&self) -> &[Op] {
-        &*self.code
+    pub(crate) fn bytecode(&self) -> &[Op] {
+        &self.code
+    }
+
+    /// The number of local variable slots per call frame, including the parameters.
+    #[inline]
+    pub fn local_count(&self) -> usize {
+        self.local_count
+    }
+
+    /// The arguments this procedure accepts.
+    #[inline]
+    pub fn signature(&self) -> &Signature {
+        &self.sig
+    }
+
+    /// Number of the top-level form the instruction at `pc` was compiled from.
+    pub(crate) fn form_at(&self, pc: usize) -> Option<usize> {
+        let end = self.forms.partition_point(|(start, _)| *start <= pc);
+        end.checked_sub(1).map(|index| self.forms[index].1)
     }
 }
 
 /// A callable instance of a function.
-#[derive(Debug)]
 pub struct Closure {
     /// Shared handle to the function definition.
     ///
@@ -264,26 +1584,27 @@ pub struct Closure {
     /// offered by [`Handle`].
     pub(crate) proc: Rc<Proc>,
 
-    // TODO: Change to Box<[UpValue]>
-    pub(crate) up_values: Vec<Handle<UpValue>>,
+    /// Most closures capture a few variables at most, which are kept
+    /// inline instead of in a separate allocation.
+    pub(crate) up_values: SmallVec<Handle<UpValue>>,
 }
 
 impl Closure {
     pub fn new(proc: Rc<Proc>) -> Self {
         Self {
             proc,
-            up_values: Vec::new(),
+            up_values: SmallVec::new(),
         }
     }
 
-    pub fn with_up_values(proc: Rc<Proc>, up_values: Vec<Handle<UpValue>>) -> Self {
+    pub(crate) fn with_up_values(proc: Rc<Proc>, up_values: SmallVec<Handle<UpValue>>) -> Self {
         Self { proc, up_values }
     }
 
     /// The procedure definition that this closure instances.
     #[inline]
     pub fn procedure(&self) -> &Proc {
-        &*self.proc
+        &self.proc
     }
 
     /// The procedure definition that this closure instances.
@@ -293,10 +1614,24 @@ impl Closure {
     }
 }
 
+/// Prints the procedure's address and signature instead of its code and
+/// constants, which can hold other procedures, and the up-values' count
+/// instead of their values.
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Closure")
+            .field("proc", &Rc::as_ptr(&self.proc))
+            .field("arity", &self.proc.sig.arity)
+            .field("variadic", &self.proc.sig.variadic)
+            .field("up_values", &self.up_values.len())
+            .finish()
+    }
+}
+
 /// An Up-value is a variable that is referenced within a scope, but is not
 /// local to that scope.
 #[derive(Debug, Clone)]
-pub enum UpValue {
+pub(crate) enum UpValue {
     /// A local variable is an **open** up-value when it is still within scope
     /// and on the operand stack.
     ///
@@ -319,3 +1654,249 @@ impl UpValue {
         *self = UpValue::Closed(value);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// `(1 (2 (3 ... (n))))`
+    fn nested_list(n: usize) -> Expr {
+        (1..=n)
+            .rev()
+            .fold(Expr::List(Rc::new([])), |inner, number| {
+                let mut list = vec![Expr::Number(number as f64)];
+                if !inner.is_null() {
+                    list.push(inner);
+                }
+                Expr::List(list.into())
+            })
+    }
+
+    fn flat_list(n: usize) -> Expr {
+        Expr::List((0..n).map(|number| Expr::Number(number as f64)).collect())
+    }
+
+    #[test]
+    fn test_list_clone_shares() {
+        let list = flat_list(1000);
+        let copy = list.clone();
+        match (&list, &copy) {
+            (Expr::List(a), Expr::List(b)) => assert!(Rc::ptr_eq(a, b)),
+            _ => unreachable!(),
+        }
+
+        // Equality still compares the elements.
+        assert_eq!(list, flat_list(1000));
+        assert_ne!(list, flat_list(999));
+    }
+
+    #[test]
+    fn test_copy_literal_keeps_sharing() {
+        let pair = Expr::Pair(Handle::new((Expr::Number(1.0), Expr::Number(2.0))));
+        let bytes = Expr::from(vec![7_u8]);
+        let literal = Expr::from(vec![pair.clone(), pair.clone(), bytes.clone(), bytes]);
+
+        let copy = literal.copy_literal();
+        let elements = copy.as_vector().unwrap();
+        assert_ne!(elements[0], pair);
+        assert_eq!(elements[0], elements[1]);
+        assert_eq!(elements[2], elements[3]);
+        assert_eq!(
+            copy.write_repr().to_string(),
+            literal.write_repr().to_string()
+        );
+    }
+
+    #[test]
+    fn test_repr_depth_limit() {
+        let list = nested_list(6);
+        assert_eq!(list.write_repr().to_string(), "(1 (2 (3 (4 (5 (6))))))");
+        assert_eq!(
+            list.write_repr().with_limits(3, 64).to_string(),
+            "(1 (2 (3 #)))"
+        );
+        assert_eq!(list.write_repr().with_limits(0, 64).to_string(), "#");
+    }
+
+    #[test]
+    fn test_repr_length_limit() {
+        let list = flat_list(100);
+        assert_eq!(
+            list.write_repr().with_limits(8, 5).to_string(),
+            "(0 1 2 3 4 ...)"
+        );
+        assert_eq!(
+            flat_list(5).write_repr().with_limits(8, 5).to_string(),
+            "(0 1 2 3 4)"
+        );
+
+        let vector = Expr::from(vec![flat_list(3), flat_list(3)]);
+        assert_eq!(
+            vector.write_repr().with_limits(8, 2).to_string(),
+            "#((0 1 ...) (0 1 ...))"
+        );
+    }
+
+    #[test]
+    fn test_repr_pairs_limit() {
+        let pairs = (0..10).rev().fold(Expr::Nil, |tail, number| {
+            Expr::Pair(Handle::new((Expr::Number(number as f64), tail)))
+        });
+        assert_eq!(
+            pairs.write_repr().with_limits(8, 3).to_string(),
+            "(0 1 2 ...)"
+        );
+
+        let mixed = Expr::Pair(Handle::new((Expr::Number(0.0), flat_list(5))));
+        assert_eq!(
+            mixed.write_repr().with_limits(8, 3).to_string(),
+            "(0 0 1 ...)"
+        );
+    }
+
+    #[test]
+    fn test_drop_deep_nesting() {
+        // `((((... #u8(0)))))` nested in the heads of pairs, which dropping
+        // the tails one at a time doesn't reach.
+        let sentinel = Handle::new(vec![0_u8]);
+        let weak = sentinel.downgrade();
+        let mut nested = Expr::Bytevector(sentinel);
+        for _ in 0..1_000_000 {
+            nested = Expr::Pair(Handle::new((nested, Expr::Nil)));
+        }
+        assert_eq!(weak.strong_count(), 1);
+        drop(nested);
+        assert_eq!(weak.strong_count(), 0);
+
+        let sentinel = Handle::new(vec![0_u8]);
+        let weak = sentinel.downgrade();
+        let mut nested = Expr::Bytevector(sentinel);
+        for depth in 0..300_000 {
+            nested = match depth % 3 {
+                0 => Expr::from(vec![nested]),
+                1 => Expr::List(Rc::new([Expr::Nil, nested])),
+                _ => Expr::Quote(Box::new(nested)),
+            };
+        }
+        drop(nested);
+        assert_eq!(weak.strong_count(), 0);
+    }
+
+    #[test]
+    fn test_clones_share_contents() {
+        let string = Expr::from("x".repeat(1000));
+        let list: Expr = Expr::List((0..1000).map(|n| Expr::Number(n as f64)).collect());
+        for value in [&string, &list] {
+            let copy = value.clone();
+            match (value, &copy) {
+                (Expr::String(a), Expr::String(b)) => assert!(Rc::ptr_eq(a, b)),
+                (Expr::List(a), Expr::List(b)) => assert!(Rc::ptr_eq(a, b)),
+                _ => unreachable!(),
+            }
+        }
+
+        // Strings still compare and print by their text.
+        assert_eq!(string, Expr::from("x".repeat(1000)));
+        assert_ne!(Expr::from("a"), Expr::from("b"));
+        assert_eq!(Expr::from("a\"b").repr().to_string(), "a\"b");
+        assert_eq!(Expr::from("a\"b").write_repr().to_string(), "\"a\\\"b\"");
+    }
+
+    #[test]
+    fn test_is_eqv() {
+        let pair = Expr::Pair(Handle::new((Expr::Number(1.0), Expr::Nil)));
+        let string = Expr::from("a");
+        let same = [
+            (Expr::Nil, Expr::List(Rc::from([]))),
+            (Expr::Ident("a".into()), Expr::Ident("a".into())),
+            (Expr::Number(1.0), Expr::Number(1.0)),
+            (Expr::Number(f64::NAN), Expr::Number(f64::NAN)),
+            (Expr::Char('x'), Expr::Char('x')),
+            (pair.clone(), pair.clone()),
+            (string.clone(), string.clone()),
+        ];
+        for (a, b) in &same {
+            assert!(a.is_eqv(b), "{a:?} {b:?}");
+        }
+
+        let different = [
+            (Expr::Ident("a".into()), Expr::Ident("b".into())),
+            (Expr::Ident("a".into()), Expr::from("a")),
+            (Expr::Number(0.0), Expr::Number(-0.0)),
+            (Expr::Number(1.0), Expr::Bool(true)),
+            (
+                pair.clone(),
+                Expr::Pair(Handle::new((Expr::Number(1.0), Expr::Nil))),
+            ),
+            (string.clone(), Expr::from("a")),
+        ];
+        for (a, b) in &different {
+            assert!(!a.is_eqv(b), "{a:?} {b:?}");
+        }
+    }
+
+    #[test]
+    fn test_drop_keeps_shared_values() {
+        let shared = nested_list(3);
+        let pair = Expr::Pair(Handle::new((shared.clone(), shared.clone())));
+        let vector = Expr::from(vec![pair.clone(), shared.clone()]);
+        drop(vector);
+
+        // The pair is still owned here, so its contents were left alone.
+        assert_eq!(pair.write_repr().to_string(), "((1 (2 (3))) 1 (2 (3)))");
+        drop(pair);
+        assert_eq!(shared.write_repr().to_string(), "(1 (2 (3)))");
+    }
+
+    /// One value of each kind, in the order of the kinds.
+    #[test]
+    fn test_expr_kind() {
+        fn native(_env: &mut Env, _args: &[Expr]) -> Result<Expr> {
+            Ok(Expr::Void)
+        }
+        let env = crate::new_env().unwrap();
+        let program = crate::parse("(lambda () 1)", true).unwrap();
+        let closure = crate::compile(env.clone(), &program).unwrap();
+        let procedure = closure.borrow().procedure_rc();
+
+        let values = [
+            Expr::Nil,
+            Expr::Void,
+            Expr::Eof,
+            Expr::Bool(true),
+            Expr::Number(1.0),
+            Expr::String("a".into()),
+            Expr::Char('a'),
+            Expr::Ident("a".into()),
+            Expr::Keyword(Keyword::Dot),
+            Expr::Quote(Box::new(Expr::Nil)),
+            Expr::List(Rc::new([Expr::Nil])),
+            Expr::Pair(Handle::new((Expr::Nil, Expr::Nil))),
+            Expr::from(Vec::<Expr>::new()),
+            Expr::from(vec![1_u8]),
+            Expr::Port(Handle::new(Port::output_string("string"))),
+            Expr::Values(Rc::new([])),
+            Expr::Sequence(Vec::new()),
+            Expr::Procedure(procedure),
+            Expr::Closure(closure),
+            Expr::NativeFunc(native),
+            Expr::ErrorObject(Rc::new(ErrorObject::new(
+                error_object::ErrorKind::Error,
+                "",
+                Vec::new(),
+            ))),
+        ];
+
+        for (index, value) in values.iter().enumerate() {
+            // The numbering is stable, so a new kind must come last.
+            assert_eq!(value.kind() as usize, index, "{value:?}");
+            assert_eq!(value.type_name(), value.kind().name());
+        }
+        let callable: Vec<ExprKind> = values
+            .iter()
+            .filter(|value| value.is_callable())
+            .map(Expr::kind)
+            .collect();
+        assert_eq!(callable, [ExprKind::Closure, ExprKind::NativeFunc]);
+    }
+}
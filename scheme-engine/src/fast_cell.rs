@@ -0,0 +1,310 @@
+//! Single-threaded cell for [`Handle`] without borrow checks in release
+//! builds, behind the `fast-handle` feature.
+//!
+//! It has the parts of the [`RefCell`] API that handles use, so nothing
+//! else changes when the feature is on. Debug builds keep track of the
+//! borrows like [`RefCell`] does, and panic on a conflicting borrow.
+//! Release builds only keep track of mutable borrows, and only check for
+//! them in [`FastCell::try_borrow_mut`].
+//!
+//! # Invariants
+//!
+//! Without the checks, a conflicting borrow in a release build is
+//! undefined behaviour instead of a panic. The interpreter upholds these:
+//!
+//! - A value is never borrowed mutably while another borrow of it is alive.
+//!   Borrows of a handle end before anything is called that could borrow
+//!   it again, like a native function or a nested evaluation.
+//! - The virtual machine isn't re-entered on an environment that is
+//!   already running. Native functions are given the environment as
+//!   `&mut Env`, and must call back with [`call_with_env`], never with
+//!   [`call`], which borrows the environment's handle again. The running
+//!   evaluation holds a mutable borrow, which `try_borrow_mut` sees, so that
+//!   mistake is reported as "environment is busy", with or without the feature.
+//! - Handles aren't shared between threads, which `Rc` already rules out.
+//!
+//! Embedders whose host code borrows other handles, like the pairs and
+//! vectors of a script, across a call back into the interpreter should
+//! leave the feature off.
+//!
+//! # Measurements
+//!
+//! Medians from `cargo bench`, without and with the feature, on one
+//! machine. Separate runs of the closures bench in the same configuration
+//! ranged from 460 to 644 µs without the feature and from 415 to 586 µs
+//! with it, so the gains are within the noise there.
+//!
+//! | Bench                         | RefCell  | fast-handle |
+//! |-------------------------------|----------|-------------|
+//! | fib 10                        | 48.9 µs  | 47.6 µs     |
+//! | fib 20                        | 5.87 ms  | 5.62 ms     |
+//! | fib 25                        | 63.6 ms  | 62.9 ms     |
+//! | create and call 1000 closures | 581 µs   | 397 µs      |
+//!
+//! [`Handle`]: crate::Handle
+//! [`RefCell`]: std::cell::RefCell
+//! [`call`]: crate::call
+//! [`call_with_env`]: crate::call_with_env
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// Mutable memory location with borrow checks in debug builds only.
+pub struct FastCell<T> {
+    /// The number of shared borrows, or -1 while borrowed mutably.
+    #[cfg(debug_assertions)]
+    borrows: Cell<isize>,
+    /// Whether the value is borrowed mutably.
+    #[cfg(not(debug_assertions))]
+    borrowed_mut: Cell<bool>,
+    value: UnsafeCell<T>,
+}
+
+/// The cell was already borrowed. Only returned by debug builds.
+#[derive(Debug)]
+pub struct BorrowMutError;
+
+impl fmt::Display for BorrowMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "already borrowed")
+    }
+}
+
+impl<T> FastCell<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            #[cfg(debug_assertions)]
+            borrows: Cell::new(0),
+            #[cfg(not(debug_assertions))]
+            borrowed_mut: Cell::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Immutably borrow the value.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, when the value is borrowed mutably.
+    #[inline(always)]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        let guard = BorrowGuard::shared(self);
+        Ref {
+            // SAFETY: There are no mutable borrows, which debug builds
+            //         check and the module's invariants guarantee.
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            guard,
+        }
+    }
+
+    /// Mutably borrow the value.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, when the value is borrowed.
+    #[inline(always)]
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        match self.try_borrow_mut() {
+            Ok(value) => value,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Mutably borrow the value, or fail when it's borrowed. Release
+    /// builds only tell when it's borrowed mutably.
+    #[inline(always)]
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
+        let guard = BorrowGuard::exclusive(self)?;
+        Ok(RefMut {
+            // SAFETY: There are no other borrows, which debug builds
+            //         check and the module's invariants guarantee.
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            guard,
+            marker: PhantomData,
+        })
+    }
+
+    /// Raw pointer to the value.
+    #[inline(always)]
+    pub fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+}
+
+/// Releases a borrow when dropped. Release builds only release
+/// mutable borrows.
+struct BorrowGuard<'a> {
+    #[cfg(debug_assertions)]
+    borrows: &'a Cell<isize>,
+    #[cfg(not(debug_assertions))]
+    borrowed_mut: Option<&'a Cell<bool>>,
+}
+
+impl<'a> BorrowGuard<'a> {
+    #[cfg(debug_assertions)]
+    fn shared<T>(cell: &'a FastCell<T>) -> Self {
+        let borrows = cell.borrows.get();
+        if borrows < 0 {
+            panic!("already mutably borrowed");
+        }
+        cell.borrows.set(borrows + 1);
+        Self {
+            borrows: &cell.borrows,
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn exclusive<T>(cell: &'a FastCell<T>) -> Result<Self, BorrowMutError> {
+        if cell.borrows.get() != 0 {
+            return Err(BorrowMutError);
+        }
+        cell.borrows.set(-1);
+        Ok(Self {
+            borrows: &cell.borrows,
+        })
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline(always)]
+    fn shared<T>(_cell: &'a FastCell<T>) -> Self {
+        Self { borrowed_mut: None }
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline(always)]
+    fn exclusive<T>(cell: &'a FastCell<T>) -> Result<Self, BorrowMutError> {
+        if cell.borrowed_mut.replace(true) {
+            return Err(BorrowMutError);
+        }
+        Ok(Self {
+            borrowed_mut: Some(&cell.borrowed_mut),
+        })
+    }
+}
+
+impl Drop for BorrowGuard<'_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            let borrows = self.borrows.get();
+            self.borrows.set(if borrows > 0 { borrows - 1 } else { 0 });
+        }
+        #[cfg(not(debug_assertions))]
+        if let Some(borrowed_mut) = self.borrowed_mut {
+            borrowed_mut.set(false);
+        }
+    }
+}
+
+/// A shared borrow of the value in a [`FastCell`].
+pub struct Ref<'a, T: ?Sized> {
+    value: NonNull<T>,
+    guard: BorrowGuard<'a>,
+}
+
+impl<'a, T: ?Sized> Ref<'a, T> {
+    /// Borrow a part of the value, like [`std::cell::Ref::map`].
+    pub fn map<U: ?Sized>(orig: Ref<'a, T>, f: impl FnOnce(&T) -> &U) -> Ref<'a, U> {
+        let Ref { value, guard } = orig;
+        // SAFETY: The borrow is still held by the guard.
+        let value = f(unsafe { value.as_ref() });
+        Ref {
+            value: NonNull::from(value),
+            guard,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for Ref<'_, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // SAFETY: The borrow is held for the lifetime of self.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Ref<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A mutable borrow of the value in a [`FastCell`].
+pub struct RefMut<'a, T: ?Sized> {
+    value: NonNull<T>,
+    #[allow(dead_code)]
+    guard: BorrowGuard<'a>,
+    /// Invariant over `T`, like `&mut T`.
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // SAFETY: The borrow is held for the lifetime of self.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RefMut<'_, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The borrow is held for the lifetime of self,
+        //         and is the only one.
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_borrows() {
+        let cell = FastCell::new(vec![1, 2, 3]);
+        cell.borrow_mut().push(4);
+        {
+            let first = cell.borrow();
+            let second = Ref::map(cell.borrow(), |numbers| &numbers[1..]);
+            assert_eq!(first.len(), 4);
+            assert_eq!(*second, [2, 3, 4]);
+        }
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_double_borrow_panics() {
+        let cell = FastCell::new(1);
+        let _first = cell.borrow();
+        let _second = cell.borrow_mut();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_borrow_released() {
+        let cell = FastCell::new(1);
+        let shared = cell.borrow();
+        assert!(cell.try_borrow_mut().is_err());
+        drop(shared);
+
+        let exclusive = cell.borrow_mut();
+        assert!(cell.try_borrow_mut().is_err());
+        drop(exclusive);
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+}
@@ -0,0 +1,130 @@
+//! File system library.
+//!
+//! Not loaded by [`init_core`](crate::init_core), so sandboxed environments
+//! have no access to files unless the embedder opts in with [`init_file_io`].
+use std::fs;
+use std::io;
+
+use crate::core::wrong_arg_count;
+use crate::env::Env;
+use crate::error::{Error, Result};
+use crate::expr::Expr;
+use crate::handle::Handle;
+use crate::port::Port;
+use crate::vm;
+
+pub fn init_file_io(env: &mut Env) -> Result<()> {
+    env.bind_effectful_func("read-file->string", read_file_to_string)?;
+    env.bind_effectful_func("write-file!", write_file)?;
+    env.bind_effectful_func("file-exists?", file_exists)?;
+    env.bind_effectful_func("delete-file", delete_file)?;
+    env.bind_effectful_func("open-input-file", open_input_file)?;
+    env.bind_effectful_func("open-output-file", open_output_file)?;
+    env.bind_effectful_func("with-input-from-file", with_input_from_file)?;
+
+    Ok(())
+}
+
+/// The error for a failed file operation, naming the path and the OS error.
+fn io_error(who: &str, path: &str, err: io::Error) -> Error {
+    Error::Reason(format!("{who}: {path:?}: {err}"))
+}
+
+fn path_arg<'a>(who: &str, args: &'a [Expr]) -> Result<&'a str> {
+    match args {
+        [path] => path.expect_str(who, 1),
+        [..] => Err(wrong_arg_count(who, "1", args)),
+    }
+}
+
+/// The contents of a text file.
+///
+/// ```scheme
+/// (read-file->string <path>)
+/// ```
+fn read_file_to_string(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "read-file->string";
+    let path = path_arg(WHO, args)?;
+    let text = fs::read_to_string(path).map_err(|err| io_error(WHO, path, err))?;
+    Ok(Expr::from(text))
+}
+
+/// Replace the contents of a file with a string, creating it if needed.
+///
+/// ```scheme
+/// (write-file! <path> <string>)
+/// ```
+fn write_file(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "write-file!";
+    let (path, text) = match args {
+        [path, text] => (path.expect_str(WHO, 1)?, text.expect_str(WHO, 2)?),
+        [..] => return Err(wrong_arg_count(WHO, "2", args)),
+    };
+    fs::write(path, text).map_err(|err| io_error(WHO, path, err))?;
+    Ok(Expr::Void)
+}
+
+fn file_exists(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    let path = path_arg("file-exists?", args)?;
+    Ok(Expr::Bool(fs::metadata(path).is_ok()))
+}
+
+/// ```scheme
+/// (delete-file <path>)
+/// ```
+fn delete_file(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "delete-file";
+    let path = path_arg(WHO, args)?;
+    fs::remove_file(path).map_err(|err| io_error(WHO, path, err))?;
+    Ok(Expr::Void)
+}
+
+fn open_input_port(who: &str, path: &str) -> Result<Handle<Port>> {
+    let text = fs::read_to_string(path).map_err(|err| io_error(who, path, err))?;
+    Ok(Handle::new(Port::input_text(path, text)))
+}
+
+/// An input port reading a text file.
+///
+/// ```scheme
+/// (open-input-file <path>)
+/// ```
+fn open_input_file(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "open-input-file";
+    let path = path_arg(WHO, args)?;
+    Ok(Expr::Port(open_input_port(WHO, path)?))
+}
+
+/// An output port writing to a file, replacing its contents.
+///
+/// ```scheme
+/// (open-output-file <path>)
+/// ```
+fn open_output_file(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "open-output-file";
+    let path = path_arg(WHO, args)?;
+    let file = fs::File::create(path).map_err(|err| io_error(WHO, path, err))?;
+    Ok(Expr::Port(Handle::new(Port::output_file(path, file))))
+}
+
+/// Call the thunk with the file as the current input port, which procedures
+/// like `read-line` use when not given a port.
+///
+/// ```scheme
+/// (with-input-from-file <path> <thunk>)
+/// ```
+fn with_input_from_file(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    const WHO: &str = "with-input-from-file";
+    let (path, thunk) = match args {
+        [path, thunk] => (path.expect_str(WHO, 1)?, thunk.expect_callable(WHO, 2)?),
+        [..] => return Err(wrong_arg_count(WHO, "2", args)),
+    };
+
+    let mut port = open_input_port(WHO, path)?;
+    let previous = env.input_port.replace(port.clone());
+    let result = vm::Caller::new().call_with(env, thunk, |_| {});
+    env.input_port = previous;
+    port.borrow_mut().close()?;
+
+    result
+}
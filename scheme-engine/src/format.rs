@@ -0,0 +1,431 @@
+//! Formatted output for the `format` procedure.
+//!
+//! ```scheme
+//! (format #f "x=~a y=~s n=~0,2f~%" x y n)
+//! ```
+//!
+//! The control string is copied to the output, with directives
+//! introduced by a tilde (`~`) replaced as follows:
+//!
+//! | Directive  | Output                                                         |
+//! |------------|----------------------------------------------------------------|
+//! | `~a`       | Next argument as printed by `display`                          |
+//! | `~s`       | Next argument as printed by `write`                            |
+//! | `~d`       | Next argument as an integer, with optional width `~5d`         |
+//! | `~f`       | Next argument as a decimal, with optional width and precision `~8,2f` |
+//! | `~%`       | Newline                                                        |
+//! | `~~`       | Literal tilde                                                  |
+//!
+//! Numeric output is right aligned within the given width, and
+//! is never truncated when it doesn't fit. Widths and precisions are
+//! at most [`MAX_PARAMETER`].
+//!
+//! Directives are numbered from 1 in the order they appear in the
+//! control string, which is used to point out the offending directive
+//! in error messages.
+use std::fmt::Write;
+
+use crate::error::{Error, Result};
+use crate::expr::Expr;
+
+/// Largest width or precision a directive takes.
+const MAX_PARAMETER: usize = 255;
+
+/// Piece of a parsed control string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Piece<'a> {
+    /// Literal text copied to the output as-is.
+    Text(&'a str),
+    Directive(Directive),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Directive {
+    /// Position of the directive in the control string, counting from 1.
+    pub(crate) index: usize,
+    pub(crate) kind: DirectiveKind,
+    /// Minimum number of characters to output.
+    pub(crate) width: Option<usize>,
+    /// Number of digits after the decimal point.
+    pub(crate) precision: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DirectiveKind {
+    /// `~a`
+    Display,
+    /// `~s`
+    Write,
+    /// `~d`
+    Decimal,
+    /// `~f`
+    Fixed,
+    /// `~%`
+    Newline,
+    /// `~~`
+    Tilde,
+}
+
+impl DirectiveKind {
+    fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            'a' | 'A' => Some(Self::Display),
+            's' | 'S' => Some(Self::Write),
+            'd' | 'D' => Some(Self::Decimal),
+            'f' | 'F' => Some(Self::Fixed),
+            '%' => Some(Self::Newline),
+            '~' => Some(Self::Tilde),
+            _ => None,
+        }
+    }
+
+    fn as_char(self) -> char {
+        match self {
+            Self::Display => 'a',
+            Self::Write => 's',
+            Self::Decimal => 'd',
+            Self::Fixed => 'f',
+            Self::Newline => '%',
+            Self::Tilde => '~',
+        }
+    }
+
+    /// Indicates whether the directive consumes an argument.
+    fn takes_argument(self) -> bool {
+        matches!(
+            self,
+            Self::Display | Self::Write | Self::Decimal | Self::Fixed
+        )
+    }
+}
+
+/// Format the arguments according to the given control string.
+pub(crate) fn render(control: &str, args: &[Expr]) -> Result<String> {
+    let pieces = parse_control(control)?;
+    let mut output = String::new();
+    let mut consumed = 0;
+
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => output.push_str(text),
+            Piece::Directive(directive) => {
+                let arg = if directive.kind.takes_argument() {
+                    let arg = args.get(consumed).ok_or_else(|| {
+                        Error::Reason(format!(
+                            "format: missing argument for directive {} (~{})",
+                            directive.index,
+                            directive.kind.as_char()
+                        ))
+                    })?;
+                    consumed += 1;
+                    Some(arg)
+                } else {
+                    None
+                };
+
+                render_directive(&mut output, directive, arg)?;
+            }
+        }
+    }
+
+    if consumed < args.len() {
+        return Err(Error::Reason(format!(
+            "format: too many arguments, control string uses {consumed} but {} were given",
+            args.len()
+        )));
+    }
+
+    Ok(output)
+}
+
+fn render_directive(output: &mut String, directive: Directive, arg: Option<&Expr>) -> Result<()> {
+    let width = directive.width.unwrap_or(0);
+
+    match (directive.kind, arg) {
+        (DirectiveKind::Display, Some(arg)) => {
+            write!(output, "{}", arg.repr())?;
+        }
+        (DirectiveKind::Write, Some(arg)) => {
+            write!(output, "{}", arg.write_repr())?;
+        }
+        (DirectiveKind::Decimal, Some(arg)) => {
+            let number = expect_number(directive, arg)?;
+            if number.fract() != 0.0 || !number.is_finite() {
+                return Err(Error::Reason(format!(
+                    "format: directive {} (~d) expects an integer, but encountered {}",
+                    directive.index,
+                    arg.repr()
+                )));
+            }
+            write!(output, "{:>width$}", number)?;
+        }
+        (DirectiveKind::Fixed, Some(arg)) => {
+            let number = expect_number(directive, arg)?;
+            let digits = match directive.precision {
+                Some(precision) => format!("{number:.precision$}"),
+                None => format!("{number}"),
+            };
+            write!(output, "{digits:>width$}")?;
+        }
+        (DirectiveKind::Newline, None) => output.push('\n'),
+        (DirectiveKind::Tilde, None) => output.push('~'),
+        _ => unreachable!("directive argument mismatch"),
+    }
+
+    Ok(())
+}
+
+fn expect_number(directive: Directive, arg: &Expr) -> Result<f64> {
+    arg.as_number().ok_or_else(|| {
+        Error::Reason(format!(
+            "format: directive {} (~{}) expects a number, but encountered {}",
+            directive.index,
+            directive.kind.as_char(),
+            arg.repr()
+        ))
+    })
+}
+
+/// Split the control string into literal text and directives.
+pub(crate) fn parse_control(control: &str) -> Result<Vec<Piece<'_>>> {
+    let mut pieces = Vec::new();
+    let mut rest = control;
+    let mut index = 0;
+
+    while let Some(tilde) = rest.find('~') {
+        if tilde > 0 {
+            pieces.push(Piece::Text(&rest[..tilde]));
+        }
+
+        index += 1;
+        let (directive, remainder) = parse_directive(&rest[tilde + 1..], index)?;
+        pieces.push(Piece::Directive(directive));
+        rest = remainder;
+    }
+
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+
+    Ok(pieces)
+}
+
+/// Parse a single directive, starting just after its tilde.
+///
+/// ```text
+/// ~[width][,precision]<char>
+/// ```
+///
+/// Returns the directive and the remainder of the control string.
+fn parse_directive(source: &str, index: usize) -> Result<(Directive, &str)> {
+    let (width, rest) = parse_parameter(source, index)?;
+    let (precision, rest) = match rest.strip_prefix(',') {
+        Some(rest) => parse_parameter(rest, index)?,
+        None => (None, rest),
+    };
+
+    let ch = rest.chars().next().ok_or_else(|| {
+        Error::Reason(format!(
+            "format: incomplete directive {index} at end of control string"
+        ))
+    })?;
+    let kind = DirectiveKind::from_char(ch).ok_or_else(|| {
+        Error::Reason(format!(
+            "format: unknown directive ~{ch} (directive {index})"
+        ))
+    })?;
+
+    match kind {
+        DirectiveKind::Fixed => {}
+        DirectiveKind::Decimal if precision.is_none() => {}
+        DirectiveKind::Decimal => {
+            return Err(Error::Reason(format!(
+                "format: directive {index} (~d) does not take a precision"
+            )));
+        }
+        _ if width.is_some() || precision.is_some() => {
+            return Err(Error::Reason(format!(
+                "format: directive {index} (~{}) does not take parameters",
+                kind.as_char()
+            )));
+        }
+        _ => {}
+    }
+
+    let directive = Directive {
+        index,
+        kind,
+        width,
+        precision,
+    };
+
+    Ok((directive, &rest[ch.len_utf8()..]))
+}
+
+/// Parse an optional numeric directive parameter.
+fn parse_parameter(source: &str, index: usize) -> Result<(Option<usize>, &str)> {
+    let end = source
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(source.len());
+
+    if end == 0 {
+        return Ok((None, source));
+    }
+
+    // Only digits were taken, so parsing fails when the value overflows.
+    let digits = &source[..end];
+    match digits.parse::<usize>() {
+        Ok(value) if value <= MAX_PARAMETER => Ok((Some(value), &source[end..])),
+        _ => Err(Error::Reason(format!(
+            "format: parameter {digits} of directive {index} is larger than {MAX_PARAMETER}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn string(value: &str) -> Expr {
+        Expr::from(value)
+    }
+
+    #[test]
+    fn test_parse_control() {
+        let pieces = parse_control("x=~a n=~8,2f~%").unwrap();
+        assert_eq!(
+            pieces,
+            vec![
+                Piece::Text("x="),
+                Piece::Directive(Directive {
+                    index: 1,
+                    kind: DirectiveKind::Display,
+                    width: None,
+                    precision: None,
+                }),
+                Piece::Text(" n="),
+                Piece::Directive(Directive {
+                    index: 2,
+                    kind: DirectiveKind::Fixed,
+                    width: Some(8),
+                    precision: Some(2),
+                }),
+                Piece::Directive(Directive {
+                    index: 3,
+                    kind: DirectiveKind::Newline,
+                    width: None,
+                    precision: None,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_directives() {
+        assert_eq!(render("plain text", &[]).unwrap(), "plain text");
+        assert_eq!(render("~a", &[string("a \"b\"")]).unwrap(), "a \"b\"");
+        assert_eq!(
+            render("~s", &[string("a \"b\"")]).unwrap(),
+            "\"a \\\"b\\\"\""
+        );
+        assert_eq!(
+            render("~A ~S", &[Expr::Bool(true), Expr::Nil]).unwrap(),
+            "#t '()"
+        );
+        assert_eq!(render("~d", &[Expr::Number(42.0)]).unwrap(), "42");
+        assert_eq!(render("~d", &[Expr::Number(-7.0)]).unwrap(), "-7");
+        assert_eq!(render("~f", &[Expr::Number(1.5)]).unwrap(), "1.5");
+        assert_eq!(render("a~%b", &[]).unwrap(), "a\nb");
+        assert_eq!(render("100~~", &[]).unwrap(), "100~");
+    }
+
+    #[test]
+    fn test_width_precision() {
+        let pi = Expr::Number(std::f64::consts::PI);
+        assert_eq!(render("~0,2f", std::slice::from_ref(&pi)).unwrap(), "3.14");
+        assert_eq!(render("~,3f", std::slice::from_ref(&pi)).unwrap(), "3.142");
+        assert_eq!(
+            render("~8,2f", std::slice::from_ref(&pi)).unwrap(),
+            "    3.14"
+        );
+        assert_eq!(render("~8f", &[Expr::Number(2.5)]).unwrap(), "     2.5");
+        assert_eq!(render("~,0f", &[Expr::Number(2.0)]).unwrap(), "2");
+        assert_eq!(render("~,2f", &[Expr::Number(-0.5)]).unwrap(), "-0.50");
+        assert_eq!(render("~5d|", &[Expr::Number(42.0)]).unwrap(), "   42|");
+        // Output wider than the width is never truncated.
+        assert_eq!(render("~2d", &[Expr::Number(12345.0)]).unwrap(), "12345");
+    }
+
+    #[test]
+    fn test_argument_mismatch() {
+        let err = render("~a and ~a", &[Expr::Number(1.0)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: missing argument for directive 2 (~a)"
+        );
+
+        let err = render("~% ~s", &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: missing argument for directive 2 (~s)"
+        );
+
+        let err = render("~a", &[Expr::Number(1.0), Expr::Number(2.0)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: too many arguments, control string uses 1 but 2 were given"
+        );
+
+        let err = render("~d", &[string("x")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: directive 1 (~d) expects a number, but encountered x"
+        );
+
+        let err = render("~d", &[Expr::Number(1.5)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: directive 1 (~d) expects an integer, but encountered 1.5"
+        );
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        let err = render("~a ~q", &[Expr::Nil]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: unknown directive ~q (directive 2)"
+        );
+
+        let err = render("trailing ~", &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: incomplete directive 1 at end of control string"
+        );
+
+        let err = render("~3a", &[Expr::Nil]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: directive 1 (~a) does not take parameters"
+        );
+
+        let err = render("~3,1d", &[Expr::Number(1.0)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: directive 1 (~d) does not take a precision"
+        );
+
+        let err = render("~99999999999d", &[Expr::Number(1.0)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: parameter 99999999999 of directive 1 is larger than 255"
+        );
+
+        let err = render("~a ~1,256f", &[Expr::Nil, Expr::Number(1.0)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "format: parameter 256 of directive 2 is larger than 255"
+        );
+        assert_eq!(render("~255d", &[Expr::Number(1.0)]).unwrap().len(), 255);
+    }
+}
@@ -0,0 +1,472 @@
+//! Source formatter.
+//!
+//! Programs are re-indented from the tokens of a lexer in trivia mode,
+//! so comments are kept where they were written. Only whitespace changes,
+//! except for comments between a quote mark or datum label and its datum,
+//! which are moved before them.
+//!
+//! A list that fits on one line, and was written on one line, stays on one line.
+//! Otherwise it's broken into lines:
+//!
+//! ```scheme
+//! (define (f x)         ; Special forms indent their body by two spaces.
+//!   (if (> x 0)         ; Calls align their arguments with the first one.
+//!       (g x)
+//!       (h x)))
+//!
+//! ((1 2)                ; Data aligns its elements with the first one.
+//!  (3 4))
+//! ```
+//!
+//! Formatting a formatted program returns it unchanged.
+use crate::error::Result;
+use crate::lexer::{datum_label_definition, Lexer};
+use crate::parser::{parse, read_number, NumberLiteral};
+use crate::span::Span;
+use crate::token::{Token, TokenKind};
+
+/// Options controlling [`format_source`].
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// The width lines are kept within, where possible.
+    pub width: usize,
+
+    /// Indentation of the bodies of special forms, like `lambda`.
+    pub indent: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 2,
+        }
+    }
+}
+
+/// Format a program's source text.
+///
+/// The source must parse, or its parse error is returned.
+///
+/// ```
+/// use scheme_engine::{format_source, FormatOptions};
+///
+/// let source = "(define (square x)\n(* x x))  (square 4)";
+/// let formatted = format_source(source, FormatOptions::default())?;
+/// assert_eq!(formatted, "(define (square x)\n  (* x x))\n\n(square 4)\n");
+/// # Ok::<(), scheme_engine::Error>(())
+/// ```
+pub fn format_source(source: &str, options: FormatOptions) -> Result<String> {
+    // The tree is built assuming balanced parentheses.
+    parse(source, true)?;
+
+    let tokens: Vec<Token> = Lexer::with_trivia(source).into_iter().collect();
+    let mut builder = TreeBuilder {
+        source,
+        tokens,
+        position: 0,
+        displaced: Vec::new(),
+    };
+    let items = builder.items();
+
+    let mut printer = Printer {
+        options,
+        output: String::new(),
+        column: 0,
+    };
+    printer.top_level(&items);
+    Ok(printer.output)
+}
+
+#[derive(Debug)]
+enum Node<'a> {
+    /// Identifier, literal, or any other single token.
+    Atom(&'a str),
+    List {
+        /// The opening token, like `(` or `#u8(`.
+        open: &'a str,
+        items: Vec<Item<'a>>,
+        /// Whether the source of the list spans several lines.
+        multiline: bool,
+    },
+    /// A datum written right after a quote mark or a datum label, like `'x` or `#0=x`.
+    Prefixed {
+        prefix: &'a str,
+        datum: Box<Node<'a>>,
+    },
+}
+
+#[derive(Debug)]
+struct Item<'a> {
+    kind: ItemKind<'a>,
+    /// Whether the item was preceded by a blank line.
+    blank_before: bool,
+}
+
+#[derive(Debug)]
+enum ItemKind<'a> {
+    Node(Node<'a>),
+    Comment {
+        text: &'a str,
+        /// Whether the comment is on the same line as the item before it.
+        trailing: bool,
+    },
+}
+
+struct TreeBuilder<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    /// Comments found between a quote mark or datum label and its datum.
+    displaced: Vec<&'a str>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn next_token(&mut self) -> Token {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .unwrap_or_else(|| Token {
+                kind: TokenKind::EOF,
+                span: Span::new(self.source.len(), 0),
+            });
+        self.position += 1;
+        token
+    }
+
+    /// The items up to the closing parenthesis of the current list,
+    /// or the end of the source.
+    fn items(&mut self) -> Vec<Item<'a>> {
+        let mut items = Vec::new();
+        let mut newlines = 0;
+
+        loop {
+            let token = self.next_token();
+            match token.kind {
+                TokenKind::Whitespace => {
+                    newlines += token.fragment(self.source).matches('\n').count();
+                    continue;
+                }
+                TokenKind::RightParen | TokenKind::EOF => return items,
+                TokenKind::LineComment | TokenKind::BlockComment => {
+                    items.push(Item {
+                        kind: ItemKind::Comment {
+                            text: token.fragment(self.source),
+                            trailing: !items.is_empty() && newlines == 0,
+                        },
+                        blank_before: newlines > 1,
+                    });
+                }
+                _ => {
+                    let node = self.node(token);
+                    for text in self.displaced.drain(..) {
+                        items.push(Item {
+                            kind: ItemKind::Comment {
+                                text,
+                                trailing: false,
+                            },
+                            blank_before: false,
+                        });
+                    }
+                    items.push(Item {
+                        kind: ItemKind::Node(node),
+                        blank_before: newlines > 1,
+                    });
+                }
+            }
+            newlines = 0;
+        }
+    }
+
+    fn node(&mut self, token: Token) -> Node<'a> {
+        match token.kind {
+            TokenKind::LeftParen | TokenKind::VectorOpen | TokenKind::BytevectorOpen => {
+                let items = self.items();
+                let end = self.tokens[self.position - 1].span.high();
+                Node::List {
+                    open: token.fragment(self.source),
+                    items,
+                    multiline: self.source[token.span.low()..end].contains('\n'),
+                }
+            }
+            TokenKind::QuoteMark => self.prefixed(token),
+            TokenKind::Atom if is_label_definition(token.fragment(self.source)) => {
+                self.prefixed(token)
+            }
+            _ => Node::Atom(token.fragment(self.source)),
+        }
+    }
+
+    /// The datum after a quote mark or datum label.
+    fn prefixed(&mut self, token: Token) -> Node<'a> {
+        let mut comments = Vec::new();
+        let datum = loop {
+            let token = self.next_token();
+            match token.kind {
+                TokenKind::Whitespace => {}
+                TokenKind::LineComment | TokenKind::BlockComment => {
+                    comments.push(token.fragment(self.source));
+                }
+                _ => break self.node(token),
+            }
+        };
+
+        // Taken after the datum, so a list datum doesn't take them as its own,
+        // and before those of a quoted quote.
+        comments.append(&mut self.displaced);
+        self.displaced = comments;
+        Node::Prefixed {
+            prefix: token.fragment(self.source),
+            datum: Box::new(datum),
+        }
+    }
+}
+
+struct Printer {
+    options: FormatOptions,
+    output: String,
+    column: usize,
+}
+
+impl Printer {
+    fn write(&mut self, text: &str) {
+        match text.rfind('\n') {
+            Some(index) => self.column = text[index + 1..].chars().count(),
+            None => self.column += text.chars().count(),
+        }
+        self.output.push_str(text);
+    }
+
+    fn newline(&mut self, blank: bool, indent: usize) {
+        self.output.push('\n');
+        if blank {
+            self.output.push('\n');
+        }
+        self.output.extend(std::iter::repeat_n(' ', indent));
+        self.column = indent;
+    }
+
+    /// Top-level forms are separated by a blank line. Comments are kept
+    /// next to the forms they were written next to.
+    fn top_level(&mut self, items: &[Item]) {
+        let mut previous: Option<&ItemKind> = None;
+
+        for item in items {
+            match (&item.kind, previous) {
+                (ItemKind::Comment { trailing: true, .. }, Some(_)) => self.write(" "),
+                (_, None) => {}
+                (
+                    _,
+                    Some(ItemKind::Comment {
+                        trailing: false, ..
+                    }),
+                ) => self.newline(item.blank_before, 0),
+                (_, Some(_)) => self.newline(true, 0),
+            }
+            self.item(&item.kind);
+            previous = Some(&item.kind);
+        }
+
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+    }
+
+    fn item(&mut self, item: &ItemKind) {
+        match item {
+            ItemKind::Node(node) => self.node(node, false),
+            ItemKind::Comment { text, .. } => self.write(text),
+        }
+    }
+
+    fn node(&mut self, node: &Node, data: bool) {
+        match node {
+            Node::Atom(text) => self.write(text),
+            Node::Prefixed { prefix, datum } => {
+                self.write(prefix);
+                self.node(datum, true);
+            }
+            Node::List { open, items, .. } => match flat(node) {
+                Some(text) if self.fits(&text) => self.write(&text),
+                _ => self.broken_list(open, items, data || *open != "("),
+            },
+        }
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.column + text.chars().count() <= self.options.width
+    }
+
+    /// Write a list over several lines. The first few elements go on the
+    /// line of the opening parenthesis, and the others on their own lines,
+    /// except in data where lines are filled.
+    fn broken_list(&mut self, open: &str, items: &[Item], data: bool) {
+        let layout = self.layout(open, items, data);
+        self.write(open);
+
+        let mut first_line = true;
+        let mut line_empty = true;
+        let mut needs_newline = false;
+        let mut nodes = 0;
+
+        for item in items {
+            let column = if nodes < layout.distinguished {
+                layout.align
+            } else {
+                layout.indent
+            };
+            let blank = item.blank_before && !first_line && !data;
+
+            match &item.kind {
+                ItemKind::Comment { text, trailing } => {
+                    if *trailing && !line_empty {
+                        self.write(" ");
+                    } else {
+                        self.newline(blank, column);
+                        first_line = false;
+                    }
+                    self.write(text);
+                    needs_newline = true;
+                }
+                ItemKind::Node(node) => {
+                    let same_line = if needs_newline {
+                        false
+                    } else if line_empty {
+                        true
+                    } else if data {
+                        flat(node).is_some_and(|text| self.fits(&format!(" {text}")))
+                    } else {
+                        first_line && nodes < layout.inline
+                    };
+
+                    if !same_line {
+                        self.newline(blank, column);
+                        first_line = false;
+                    } else if !line_empty {
+                        self.write(" ");
+                    }
+                    self.node(node, data);
+                    nodes += 1;
+                    needs_newline = false;
+                }
+            }
+            line_empty = false;
+        }
+
+        // A line comment runs to the end of the line.
+        if needs_newline {
+            self.newline(false, layout.indent);
+        }
+        self.write(")");
+    }
+
+    fn layout(&self, open: &str, items: &[Item], data: bool) -> Layout {
+        let column = self.column;
+        let nodes: Vec<&Node> = items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Node(node) => Some(node),
+                ItemKind::Comment { .. } => None,
+            })
+            .collect();
+
+        // Elements are aligned with the first one.
+        let mut layout = Layout {
+            inline: 1,
+            distinguished: usize::MAX,
+            align: column + open.len(),
+            indent: column + open.len(),
+        };
+
+        if let [Node::Atom(operator), rest @ ..] = nodes.as_slice() {
+            if data || !is_operator(operator) {
+                return layout;
+            }
+
+            // Arguments are aligned with the first one.
+            layout.inline = 2;
+            layout.align = column + open.len() + operator.chars().count() + 1;
+
+            if let Some(distinguished) = body_position(operator, rest) {
+                layout.distinguished = 1 + distinguished;
+                layout.indent = column + self.options.indent;
+                // The test of `do` goes under its variables.
+                if *operator != "do" {
+                    layout.inline = layout.distinguished;
+                }
+            }
+        }
+        layout
+    }
+}
+
+/// The placement of the elements of a broken list.
+struct Layout {
+    /// The number of elements on the line of the opening parenthesis.
+    inline: usize,
+    /// The number of elements before the body of a special form,
+    /// including the keyword. Those not on the first line are aligned.
+    distinguished: usize,
+    /// Column of the distinguished elements, or of all elements outside special forms.
+    align: usize,
+    /// Column of the body of a special form.
+    indent: usize,
+}
+
+fn is_label_definition(fragment: &str) -> bool {
+    datum_label_definition(fragment) == Some(fragment.len())
+}
+
+/// The list on one line, unless it contains comments or
+/// was written over several lines.
+fn flat(node: &Node) -> Option<String> {
+    match node {
+        Node::Atom(text) if text.contains('\n') => None,
+        Node::Atom(text) => Some(text.to_string()),
+        Node::Prefixed { prefix, datum } => flat(datum).map(|text| format!("{prefix}{text}")),
+        Node::List {
+            multiline: true, ..
+        } => None,
+        Node::List { open, items, .. } => {
+            let mut text = open.to_string();
+            for (index, item) in items.iter().enumerate() {
+                let ItemKind::Node(node) = &item.kind else {
+                    return None;
+                };
+                if index > 0 {
+                    text.push(' ');
+                }
+                text.push_str(&flat(node)?);
+            }
+            text.push(')');
+            Some(text)
+        }
+    }
+}
+
+/// Whether the atom at the head of a list looks like an identifier,
+/// rather than a literal.
+fn is_operator(atom: &str) -> bool {
+    !atom.starts_with(['"', '#'])
+        && !matches!(
+            read_number(atom),
+            NumberLiteral::Number(_) | NumberLiteral::Inexact(_)
+        )
+}
+
+/// The number of elements after the keyword of a special form that go
+/// before its body, like the formals of a `lambda`, or `None` for calls.
+fn body_position(keyword: &str, rest: &[&Node]) -> Option<usize> {
+    match keyword {
+        "begin" => Some(0),
+        "define" | "define-values" | "define-syntax" | "define-test" | "lambda" | "let*"
+        | "letrec" | "letrec*" | "let-values" | "let*-values" | "fluid-let" | "when" | "unless"
+        | "case" | "syntax-rules" => Some(1),
+        // Named let.
+        "let" if matches!(rest.first(), Some(Node::Atom(_))) => Some(2),
+        "let" => Some(1),
+        "do" => Some(2),
+        _ => None,
+    }
+}
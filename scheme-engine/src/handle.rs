@@ -1,21 +1,31 @@
-use std::cell::RefCell;
+use std::any::type_name;
+use std::cell::Cell;
 use std::fmt;
 use std::fmt::Formatter;
 use std::rc::Rc;
 
 // Re-exports
-pub use std::cell::{Ref, RefMut};
+//
+// The cell behind handles depends on the `fast-handle` feature, so code
+// naming it, like the target of a weak handle, should use `HandleCell`.
+#[cfg(feature = "fast-handle")]
+pub use crate::fast_cell::{FastCell as HandleCell, Ref, RefMut};
+#[cfg(not(feature = "fast-handle"))]
+pub use std::cell::{Ref, RefCell as HandleCell, RefMut};
 pub use std::rc::Weak as RcWeak;
 
 /// A shared, mutable handle.
+///
+/// The value is in a [`RefCell`](std::cell::RefCell), or with the
+/// `fast-handle` feature a cell that only checks borrows in debug builds.
 pub struct Handle<T> {
-    rc: Rc<RefCell<T>>,
+    rc: Rc<HandleCell<T>>,
 }
 
 impl<T> Handle<T> {
     pub fn new(value: T) -> Self {
         Self {
-            rc: Rc::new(RefCell::new(value)),
+            rc: Rc::new(HandleCell::new(value)),
         }
     }
 
@@ -29,17 +39,30 @@ impl<T> Handle<T> {
         self.rc.borrow_mut()
     }
 
+    #[inline]
     pub fn ptr_eq(&self, other: &Handle<T>) -> bool {
         Rc::ptr_eq(&self.rc, &other.rc)
     }
 
+    /// Address of the shared value, which identifies it while it's alive.
+    pub(crate) fn addr(&self) -> usize {
+        Rc::as_ptr(&self.rc) as usize
+    }
+
+    /// Whether this is the only strong handle to the value.
+    #[inline]
+    pub(crate) fn is_unique(&self) -> bool {
+        Rc::strong_count(&self.rc) == 1
+    }
+
     /// TODO: Weak newtype so users can omit `RefCell` from `Weak<RefCell<...>>`
-    pub fn downgrade(&self) -> RcWeak<RefCell<T>> {
+    pub fn downgrade(&self) -> RcWeak<HandleCell<T>> {
         Rc::downgrade(&self.rc)
     }
 }
 
 impl<T> Clone for Handle<T> {
+    #[inline]
     fn clone(&self) -> Self {
         Handle {
             rc: self.rc.clone(),
@@ -47,8 +70,80 @@ impl<T> Clone for Handle<T> {
     }
 }
 
+/// How many handles deep [`Debug`](fmt::Debug) prints the values behind
+/// them. Deeper handles print as their type and address, so a list whose
+/// tail refers back to itself can't make printing loop forever.
+const DEBUG_DEPTH: usize = 4;
+
+thread_local! {
+    /// Number of handles whose values are being printed on this thread.
+    static DEBUG_NESTING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Restores the nesting of debug printing, even when a value's
+/// `Debug` implementation panics.
+struct DebugNesting(usize);
+
+impl Drop for DebugNesting {
+    fn drop(&mut self) {
+        DEBUG_NESTING.with(|nesting| nesting.set(self.0));
+    }
+}
+
 impl<T: fmt::Debug> fmt::Debug for Handle<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let depth = DEBUG_NESTING.with(Cell::get);
+        if depth >= DEBUG_DEPTH {
+            return write!(f, "Handle(<{} {:#x}>)", short_type_name::<T>(), self.addr());
+        }
+
+        DEBUG_NESTING.with(|nesting| nesting.set(depth + 1));
+        let _nesting = DebugNesting(depth);
         f.debug_tuple("Handle").field(&*self.rc.borrow()).finish()
     }
 }
+
+/// Name of the type without the paths of its modules, like
+/// `(Expr, Expr)` for the value of a pair.
+fn short_type_name<T>() -> String {
+    let full = type_name::<T>();
+    let mut short = String::with_capacity(full.len());
+    for segment in full.split("::") {
+        // Drop the module that the text so far ended in.
+        let keep = short
+            .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+            .len();
+        short.truncate(keep);
+        short.push_str(segment);
+    }
+    short
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::Expr;
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(short_type_name::<Vec<Expr>>(), "Vec<Expr>");
+        assert_eq!(short_type_name::<(Expr, Expr)>(), "(Expr, Expr)");
+        assert_eq!(short_type_name::<u8>(), "u8");
+    }
+
+    #[test]
+    fn test_debug_cyclic_pair() {
+        let mut pair = Handle::new((Expr::Number(1.0), Expr::Nil));
+        pair.borrow_mut().1 = Expr::Pair(pair.clone());
+
+        let debug = format!("{pair:?}");
+        assert_eq!(debug.matches("Number(1.0)").count(), DEBUG_DEPTH);
+        assert!(debug.contains("Handle(<(Expr, Expr) 0x"), "{debug}");
+
+        // Printing again starts from the top.
+        assert_eq!(format!("{pair:?}"), debug);
+
+        // Break the cycle so the pair is dropped.
+        pair.borrow_mut().1 = Expr::Nil;
+    }
+}
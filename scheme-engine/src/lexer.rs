@@ -5,12 +5,6 @@ use crate::{
     token::{Token, TokenKind},
 };
 
-macro_rules! trace {
-    ($($arg:tt)+) => {
-        println!($($arg)+)
-    };
-}
-
 pub struct Lexer<'a> {
     cursor: Cursor<'a>,
     /// Original source.
@@ -18,8 +12,10 @@ pub struct Lexer<'a> {
     /// Byte position where the current token starts
     /// in the original source string.
     start_pos: usize,
-    /// Copy of the previous token that was created.
-    prev_token: Option<Token>,
+    /// Emit comments and whitespace as tokens, instead of skipping them.
+    trivia: bool,
+    /// A block comment ran to the end of the source without being closed.
+    unclosed_comment: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -33,19 +29,31 @@ impl<'a> Lexer<'a> {
         //
         // Prime the cursor for the first iteration.
         cursor.bump();
-        let start_pos = cursor.pos();
+        let start_pos = cursor.try_pos().unwrap_or(source.len());
 
         Self {
             cursor,
             source,
             start_pos,
-            prev_token: None,
+            trivia: false,
+            unclosed_comment: false,
+        }
+    }
+
+    /// Create a lexer that emits comments and whitespace as tokens.
+    ///
+    /// Together the tokens cover every byte of the source, so tools
+    /// like formatters can reproduce it. See [`tokens_to_source`].
+    pub fn with_trivia(source: &'a str) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(source)
         }
     }
 
     /// Original source passed into the lexer.
     #[inline]
-    pub fn source(&self) -> &str {
+    pub fn source(&self) -> &'a str {
         self.source
     }
 
@@ -53,10 +61,6 @@ impl<'a> Lexer<'a> {
         self.cursor.rest()
     }
 
-    pub fn current_token(&self) -> Option<&Token> {
-        self.prev_token.as_ref()
-    }
-
     /// Indicates whether the lexer is at the end of the source.
     ///
     /// Note that source can contain '\0' (end-of-file) characters,
@@ -66,6 +70,12 @@ impl<'a> Lexer<'a> {
         self.cursor.at_end()
     }
 
+    /// Indicates whether the source ended inside a block comment, which
+    /// is skipped like a closed one.
+    pub fn unclosed_comment(&self) -> bool {
+        self.unclosed_comment
+    }
+
     /// Primes the lexer to consume the next token.
     fn start_token(&mut self) {
         // Default position to one after last.
@@ -95,13 +105,10 @@ impl<'a> Lexer<'a> {
         // for the next iteration.
         self.cursor.bump();
 
-        // Store the token so the parser can check that the current token is.
-        self.prev_token = Some(token.clone());
-
-        println!(
+        trace!(
             "make_token() -> {:?} {:?}",
             token,
-            token.fragment(self.source)
+            token.try_fragment(self.source)
         );
 
         token
@@ -120,16 +127,40 @@ impl<'a> Lexer<'a> {
 
             let token = match self.cursor.try_char() {
                 Some(ch) if ch.is_whitespace() => {
-                    self.cursor.bump();
-                    continue;
-                }
-                Some(';') => {
-                    self.skip_line();
-                    continue;
+                    if !self.trivia {
+                        self.cursor.bump();
+                        continue;
+                    }
+                    self.consume_whitespace()
                 }
+                Some(';') => self.consume_line_comment(),
+                Some('#') if self.cursor.peek_char() == Some('|') => self.consume_block_comment(),
                 Some('(') => self.make_token(T::LeftParen),
                 Some(')') => self.make_token(T::RightParen),
                 Some('\'') => self.make_token(T::QuoteMark),
+                Some('"') => self.consume_string(),
+                Some('|') => self.consume_bar_symbol(),
+                Some('#') if self.cursor.peek_char() == Some('\\') => self.consume_char(),
+                Some('#') if self.cursor.rest().starts_with("#u8(") => {
+                    // Cursor is on the hash, the token ends on the parenthesis.
+                    for _ in 0..3 {
+                        self.cursor.bump();
+                    }
+                    self.make_token(T::BytevectorOpen)
+                }
+                Some('#') if self.cursor.peek_char() == Some('(') => {
+                    self.cursor.bump();
+                    self.make_token(T::VectorOpen)
+                }
+                Some('#') if datum_label_definition(self.cursor.rest()).is_some() => {
+                    // The labelled datum follows straight after, like `#0=(a)`,
+                    // so the atom ends on the equals sign.
+                    let length = datum_label_definition(self.cursor.rest()).unwrap_or(1);
+                    for _ in 1..length {
+                        self.cursor.bump();
+                    }
+                    self.make_token(TokenKind::Atom)
+                }
                 Some(EOF_CHAR) => {
                     // Source may contain a \0 character but not
                     // actually be at the end of the stream.
@@ -139,20 +170,65 @@ impl<'a> Lexer<'a> {
                 None => self.make_token(TokenKind::EOF),
             };
 
+            if token.kind.is_trivia() && !self.trivia {
+                continue;
+            }
+
             return token;
         }
     }
 
-    /// Skip over the remainder of a line, until we encounter a newline character,
-    /// or reach the end of the stream.
-    fn skip_line(&mut self) {
-        while let Some(ch) = self.cursor.try_char() {
-            if ch == '\n' {
-                break;
-            } else {
-                self.cursor.bump();
+    fn consume_whitespace(&mut self) -> Token {
+        while self.cursor.peek_char().is_some_and(char::is_whitespace) {
+            self.cursor.bump();
+        }
+
+        self.make_token(TokenKind::Whitespace)
+    }
+
+    /// Consume the remainder of a line, up to but excluding the newline
+    /// character, or until the end of the stream.
+    fn consume_line_comment(&mut self) -> Token {
+        while self.cursor.peek_char().is_some_and(|ch| ch != '\n') {
+            self.cursor.bump();
+        }
+
+        self.make_token(TokenKind::LineComment)
+    }
+
+    /// Consume a block comment, including nested block comments.
+    ///
+    /// An unterminated comment runs to the end of the source, and
+    /// is reported by [`Lexer::unclosed_comment`].
+    fn consume_block_comment(&mut self) -> Token {
+        // Cursor is on the hash, followed by the bar.
+        self.cursor.bump();
+        let mut depth = 1;
+
+        while let Some(ch) = self.cursor.peek_char() {
+            self.cursor.bump();
+
+            match (ch, self.cursor.peek_char()) {
+                ('|', Some('#')) => {
+                    self.cursor.bump();
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                ('#', Some('|')) => {
+                    self.cursor.bump();
+                    depth += 1;
+                }
+                _ => {}
             }
         }
+
+        if depth > 0 {
+            self.unclosed_comment = true;
+        }
+
+        self.make_token(TokenKind::BlockComment)
     }
 
     fn consume_atom(&mut self) -> Token {
@@ -167,6 +243,73 @@ impl<'a> Lexer<'a> {
 
         self.make_token(TokenKind::Atom)
     }
+
+    /// Consume a string literal, including the enclosing double quotes.
+    ///
+    /// Escape sequences are not decoded here, only skipped over so
+    /// an escaped quote doesn't terminate the literal. An unterminated
+    /// string runs to the end of the source, and is rejected by the parser.
+    fn consume_string(&mut self) -> Token {
+        // Cursor is on the opening quote.
+        while let Some(ch) = self.cursor.peek_char() {
+            self.cursor.bump();
+
+            match ch {
+                '\\' if self.cursor.peek_char().is_some() => {
+                    self.cursor.bump();
+                }
+                '"' => break,
+                _ => {}
+            }
+        }
+
+        self.make_token(TokenKind::String)
+    }
+
+    /// Consume a symbol enclosed in vertical bars, like `|hello world|`.
+    ///
+    /// Any characters may appear between the bars, with escapes like
+    /// in strings. An unterminated symbol runs to the end of the source,
+    /// and is rejected by the parser.
+    fn consume_bar_symbol(&mut self) -> Token {
+        // Cursor is on the opening bar.
+        while let Some(ch) = self.cursor.peek_char() {
+            self.cursor.bump();
+
+            match ch {
+                '\\' if self.cursor.peek_char().is_some() => {
+                    self.cursor.bump();
+                }
+                '|' => break,
+                _ => {}
+            }
+        }
+
+        self.make_token(TokenKind::Atom)
+    }
+
+    /// Consume a character literal, including the `#\` prefix.
+    ///
+    /// The first character after the prefix is always part of the literal,
+    /// even when it's whitespace or a parenthesis, so `#\(` and `#\ ` work.
+    /// Names like `#\space` run until the next delimiter.
+    fn consume_char(&mut self) -> Token {
+        // Cursor is on the hash, followed by the backslash.
+        self.cursor.bump();
+        if self.cursor.peek_char().is_some() {
+            self.cursor.bump();
+        }
+
+        while let Some(ch) = self.cursor.peek_char() {
+            if ch.is_whitespace() || matches!(ch, '(' | ')') {
+                break;
+            }
+
+            self.cursor.bump();
+        }
+
+        self.make_token(TokenKind::Char)
+    }
 }
 
 /// Methods for consuming token types.
@@ -196,32 +339,32 @@ impl<'a> Lexer<'a> {
 
 /// Functions for testing characters.
 mod rules {
-    /// Test whether the character is considered whitespace
-    /// that should be ignored by the parser later.
-    #[inline(always)]
-    pub fn is_whitespace(c: char) -> bool {
-        matches!(
-            c,
-            '\u{0020}' // space
-            | '\u{0009}' // tab
-            | '\u{000A}' // linefeed
-            | '\u{000D}' // carriage return
-            | '\u{00A0}' // no-break space
-            | '\u{FEFF}' // zero width no-break space
-        )
-    }
-
-    #[inline(always)]
-    pub fn is_number(c: char) -> bool {
-        matches!(c, '0'..='9')
-    }
-
+    /// Only the tests use it for now.
+    #[allow(dead_code)]
     #[inline(always)]
     pub fn is_symbol(c: char) -> bool {
         matches!(c, 'a'..='z' | 'A'..='Z' | '_')
     }
 }
 
+/// The length of the datum label definition at the start of the text,
+/// like `#0=`, which ends an atom even without a delimiter after it.
+pub(crate) fn datum_label_definition(text: &str) -> Option<usize> {
+    let digits = text.strip_prefix('#')?;
+    let count = digits.bytes().take_while(u8::is_ascii_digit).count();
+    (count > 0 && digits[count..].starts_with('=')).then_some(count + 2)
+}
+
+/// Concatenate the source text of the tokens.
+///
+/// For the tokens of a lexer in trivia mode, this reproduces the source.
+pub fn tokens_to_source<'a>(tokens: impl IntoIterator<Item = &'a Token>, source: &str) -> String {
+    tokens
+        .into_iter()
+        .map(|token| token.fragment(source))
+        .collect()
+}
+
 impl<'a> IntoIterator for Lexer<'a> {
     type Item = Token;
     type IntoIter = LexerIter<'a>;
@@ -245,16 +388,17 @@ impl<'a> Iterator for LexerIter<'a> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.lexer.at_end() {
-            if self.done {
-                None
-            } else {
-                self.done = true;
-                Some(self.lexer.next_token())
-            }
-        } else {
-            Some(self.lexer.next_token())
+        if self.done {
+            return None;
         }
+
+        // Skipped whitespace at the end can bring the lexer to the end
+        // while it's making a token, so check after the token.
+        let token = self.lexer.next_token();
+        if token.kind == TokenKind::EOF && self.lexer.at_end() {
+            self.done = true;
+        }
+        Some(token)
     }
 }
 
@@ -287,4 +431,124 @@ mod test {
 
     #[test]
     fn test_lexer() {}
+
+    #[test]
+    fn test_string() {
+        let source = r#"(display "a \"b\" c") "unterminated"#;
+        let mut lexer = Lexer::new(source);
+        assert_eq!(lexer.next_token().kind, TokenKind::LeftParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::Atom);
+
+        let token = lexer.next_token();
+        assert_eq!(token.kind, TokenKind::String);
+        assert_eq!(token.fragment(source), r#""a \"b\" c""#);
+
+        assert_eq!(lexer.next_token().kind, TokenKind::RightParen);
+
+        let token = lexer.next_token();
+        assert_eq!(token.kind, TokenKind::String);
+        assert_eq!(token.fragment(source), r#""unterminated"#);
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "a ; line\n#| block #| nested |# |#b";
+        let kinds = Lexer::new(source)
+            .into_iter()
+            .map(|token| token.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [TokenKind::Atom, TokenKind::Atom, TokenKind::EOF],
+            "comments are skipped by default"
+        );
+
+        let tokens = Lexer::with_trivia(source).into_iter().collect::<Vec<_>>();
+        let fragments = tokens
+            .iter()
+            .map(|token| (token.kind, token.fragment(source)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fragments,
+            [
+                (TokenKind::Atom, "a"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::LineComment, "; line"),
+                (TokenKind::Whitespace, "\n"),
+                (TokenKind::BlockComment, "#| block #| nested |# |#"),
+                (TokenKind::Atom, "b"),
+                (TokenKind::EOF, ""),
+            ]
+        );
+    }
+
+    /// Single character tokens and atoms as the last character of the
+    /// source, with and without a newline after them.
+    #[test]
+    fn test_final_token() {
+        use TokenKind as T;
+
+        let table: [(&str, &[(TokenKind, &str)]); 8] = [
+            ("'", &[(T::QuoteMark, "'")]),
+            ("(", &[(T::LeftParen, "(")]),
+            (")", &[(T::RightParen, ")")]),
+            ("a", &[(T::Atom, "a")]),
+            ("abc", &[(T::Atom, "abc")]),
+            ("é", &[(T::Atom, "é")]),
+            ("abλ", &[(T::Atom, "abλ")]),
+            ("'λ", &[(T::QuoteMark, "'"), (T::Atom, "λ")]),
+        ];
+
+        for (text, expected) in table {
+            for source in [text.to_string(), format!("{text}\n"), format!("x {text}")] {
+                let tokens = Lexer::new(&source).into_iter().collect::<Vec<_>>();
+                let fragments = tokens
+                    .iter()
+                    .map(|token| (token.kind, token.try_fragment(&source)))
+                    .collect::<Vec<_>>();
+
+                let mut wanted = vec![];
+                if source.starts_with("x ") {
+                    wanted.push((T::Atom, Some("x")));
+                }
+                wanted.extend(expected.iter().map(|(kind, text)| (*kind, Some(*text))));
+                wanted.push((T::EOF, Some("")));
+                assert_eq!(fragments, wanted, "{source:?}");
+
+                let eof = tokens.last().unwrap();
+                assert_eq!(
+                    eof.span.as_range(),
+                    source.len()..source.len(),
+                    "{source:?}"
+                );
+
+                let trivia = Lexer::with_trivia(&source).into_iter().collect::<Vec<_>>();
+                assert_eq!(tokens_to_source(&trivia, &source), source);
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_fragment() {
+        let source = "aé";
+        let token = |start, size| Token {
+            kind: TokenKind::Atom,
+            span: Span::new(start, size),
+        };
+        assert_eq!(token(0, 3).try_fragment(source), Some("aé"));
+        assert_eq!(token(3, 0).try_fragment(source), Some(""));
+        // Past the end, and inside the two bytes of é.
+        assert_eq!(token(3, 1).try_fragment(source), None);
+        assert_eq!(token(0, 2).try_fragment(source), None);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let source = "(a) #| open #| nested |#";
+        let tokens = Lexer::with_trivia(source).into_iter().collect::<Vec<_>>();
+        let last = &tokens[tokens.len() - 2];
+        assert_eq!(last.kind, TokenKind::BlockComment);
+        assert_eq!(last.fragment(source), "#| open #| nested |#");
+        assert_eq!(tokens_to_source(&tokens, source), source);
+    }
 }
@@ -1,35 +1,241 @@
+/// Debug tracing of the lexer, parser and compiler.
+///
+/// Printed only when the `trace` feature is enabled. The arguments are
+/// still type checked, so traced values don't become unused without it.
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        if cfg!(feature = "trace") {
+            println!($($arg)+)
+        }
+    };
+}
+
+use std::cell::{OnceCell, RefCell};
+use std::rc::Rc;
+
+mod arena;
+mod compile_cache;
 mod compiler;
 mod core;
 mod cursor;
+mod datum;
+mod disasm;
 mod env;
 pub mod error;
+mod error_object;
+mod escape;
+mod expand;
 mod expr;
 mod ext;
+#[cfg(feature = "fast-handle")]
+mod fast_cell;
+mod file_io;
+mod format;
+mod formatter;
 mod handle;
 mod lexer;
 mod limits;
 mod opcode;
 mod parser;
+mod port;
+mod profile;
+mod script;
+mod small_vec;
+mod source_map;
 mod span;
 mod symbol;
+mod syntax_rules;
 mod token;
+mod token_cache;
+mod validate;
 mod vm;
 
-pub use self::compiler::compile;
+pub use self::arena::{parse_program_in, ArenaExpr, ParseArena};
+pub use self::compile_cache::{CompilationCache, COMPILER_VERSION};
+pub use self::compiler::{
+    compile, compile_core, compile_with_options, compile_with_warnings, CompileOptions,
+    Redefinition, Warning,
+};
 pub use self::core::init_core;
-pub use self::env::Env;
-pub use self::expr::{Closure, Expr, Proc};
-pub use self::handle::Handle;
-pub use self::parser::parse;
-pub use self::vm::{call, eval};
+pub use self::datum::Datum;
+pub use self::disasm::disassemble;
+pub use self::env::{DefSite, DefineHook, Env, EnvLimits, Printer};
+pub use self::error::{Error, Limit, Result};
+pub use self::error_object::{ErrorKind, ErrorObject};
+pub use self::expand::{expand, expand_once, expand_steps, MAX_EXPAND_STEPS};
+pub use self::expr::{
+    Closure, DatumLabels, Expr, ExprKind, Keyword, ListIter, NativeFunc, Proc, Signature,
+};
+pub use self::file_io::init_file_io;
+pub use self::formatter::{format_source, FormatOptions};
+pub use self::handle::{Handle, HandleCell};
+pub use self::lexer::{tokens_to_source, Lexer};
+pub use self::parser::{
+    parse, parse_program, parse_program_named, parse_with_options, ParseOptions,
+};
+pub use self::port::Port;
+pub use self::profile::ProcProfile;
+pub use self::script::{run_script, FormResult, RunLimits};
+pub use self::source_map::{SourceId, SourceMap};
+pub use self::span::Span;
+pub use self::symbol::SymbolId;
+pub use self::token::{Token, TokenKind};
+pub use self::token_cache::{TextEdit, TokenCache};
+pub use self::vm::{
+    call, call_protected, call_with_env, eval, eval_protected, eval_with_options,
+    eval_with_profile, EvalOptions, StepControl, StepEvent, StepHook, Vm, VmConfig, VmStats,
+    DEADLINE_INTERVAL,
+};
+
+use self::env::EnvTemplate;
+
+/// The types and functions needed by most embedders.
+///
+/// ```
+/// use scheme_engine::prelude::*;
+///
+/// let env = new_env()?;
+/// let program = parse_program("(+ 1 2)")?;
+/// // The closure only holds a weak reference, so keep the env alive.
+/// let closure = compile(env.clone(), &program)?;
+/// assert_eq!(eval(closure)?, Expr::Number(3.0));
+/// # Ok::<(), Error>(())
+/// ```
+pub mod prelude {
+    pub use crate::{
+        call, compile, eval, new_env, parse_program, Closure, Env, Error, Expr, Handle, NativeFunc,
+        Result,
+    };
+}
 
-pub mod prelude {}
+thread_local! {
+    /// The core library, bound once per thread and copied into each new environment.
+    ///
+    /// Values hold reference counted handles, so the template can't be shared across threads.
+    static CORE_TEMPLATE: OnceCell<EnvTemplate> = const { OnceCell::new() };
+}
 
 /// Create a new environment loaded with the core library.
-pub fn new_env() -> error::Result<Handle<Env>> {
-    let mut env = Env::new();
-    init_core(&mut env)?;
-    Ok(Handle::new(env))
+///
+/// The core library is bound once per thread, and later environments
+/// start from a copy of it, so creating one is cheap. Each environment
+/// owns its variables, so changing one leaves the others unaffected.
+pub fn new_env() -> Result<Handle<Env>> {
+    CORE_TEMPLATE.with(|cell| {
+        let template = match cell.get() {
+            Some(template) => template,
+            None => {
+                let _ = cell.set(EnvTemplate::build(init_core)?);
+                cell.get().unwrap()
+            }
+        };
+        Ok(Handle::new(template.instantiate()))
+    })
+}
+
+/// Evaluate a program, returning the text it printed followed by the
+/// written result, or the error message if it failed.
+///
+/// A small entry point for hosts without standard output, like a browser
+/// binding the engine through `wasm-bindgen`.
+pub fn eval_to_string(source: &str) -> String {
+    let output = Rc::new(RefCell::new(String::new()));
+
+    let result = (|| {
+        let mut env = new_env()?;
+        let sink = output.clone();
+        env.borrow_mut()
+            .set_printer(move |text| sink.borrow_mut().push_str(text));
+
+        let expr = parse(source, true)?;
+        let closure = compile(env.clone(), &expr)?;
+        eval(closure)
+    })();
+
+    let mut output = output.take();
+    match result {
+        Ok(Expr::Void) => {}
+        Ok(value) => output.push_str(&value.write_repr().to_string()),
+        Err(err) => output.push_str(&format!("error: {err}")),
+    }
+    output
+}
+
+/// Compile a procedure from source, and bind it in the environment under the name.
+///
+/// The source must be a single lambda expression, or a `define` of a procedure
+/// with the same name. The closure is returned so the host can [`call`] it
+/// directly, and later programs evaluated in the environment can call it by name.
+///
+/// ```
+/// use scheme_engine::prelude::*;
+///
+/// let env = new_env()?;
+/// let square = scheme_engine::define_procedure(&env, "square", "(lambda (x) (* x x))")?;
+/// assert_eq!(call(square, &[Expr::Number(3.0)])?, Expr::Number(9.0));
+///
+/// let closure = compile(env.clone(), &parse_program("(square 4)")?)?;
+/// assert_eq!(eval(closure)?, Expr::Number(16.0));
+/// # Ok::<(), Error>(())
+/// ```
+pub fn define_procedure(env: &Handle<Env>, name: &str, source: &str) -> Result<Handle<Closure>> {
+    const WHO: &str = "define-procedure";
+    let program = parse(source, true)?;
+    let form = match &program {
+        Expr::Sequence(forms) if forms.len() == 1 => forms[0].clone(),
+        Expr::Sequence(forms) => {
+            return Err(Error::Reason(format!(
+                "{WHO}: expected a single lambda expression or define, found {} forms",
+                forms.len()
+            )))
+        }
+        _ => program,
+    };
+
+    let lambda = match form.as_slice() {
+        Some([Expr::Ident(keyword), ..]) if keyword == "lambda" => form.clone(),
+        Some([Expr::Ident(keyword), ..]) if keyword == "define" => {
+            let definition = expand(&env.borrow(), &form)?;
+            match definition.as_slice() {
+                Some([_, Expr::Ident(target), _]) if target != name => {
+                    return Err(Error::Reason(format!(
+                        "{WHO}: source defines '{target}', expected '{name}'"
+                    )))
+                }
+                Some([_, _, value @ Expr::List(list)]) if matches!(list.first(), Some(Expr::Ident(keyword)) if keyword == "lambda") => {
+                    value.clone()
+                }
+                _ => {
+                    return Err(Error::Reason(format!(
+                        "{WHO}: expected a procedure definition, found {}",
+                        form.write_repr()
+                    )))
+                }
+            }
+        }
+        _ => {
+            return Err(Error::Reason(format!(
+                "{WHO}: expected a lambda expression or define, found {}",
+                form.write_repr()
+            )))
+        }
+    };
+
+    // Compiled as a definition, so the procedure is named and can refer to itself.
+    let definition = Expr::List(Rc::from([
+        Expr::Ident("define".into()),
+        Expr::Ident(name.into()),
+        lambda,
+    ]));
+    let closure = compile(env.clone(), &definition)?;
+    eval(closure)?;
+
+    match env.borrow().lookup_var(name) {
+        Some(Expr::Closure(closure)) => Ok(closure.clone()),
+        _ => Err(Error::Reason(format!(
+            "{WHO}: '{name}' is not bound to a closure"
+        ))),
+    }
 }
 
 /// Convenience macro for declaring type safe identifiers.
@@ -70,6 +276,8 @@ macro_rules! declare_id {
                 Self(value)
             }
 
+            // Not every id type reads its raw value.
+            #[allow(dead_code)]
             #[inline]
             $vis const fn as_inner(self) -> $ty {
                 self.0
@@ -1,11 +1,57 @@
-/// Maximum number of local variables, including up-values, per call frame.
+/// Maximum number of local variables in scope at once, per call frame.
 ///
 /// This limitation is from using `u8` as the local ID in bytecode.
-/// See [`scheme_engine::opcodes`]
+/// Up-values are numbered separately, see [`MAX_UP_VALUES`].
 pub const MAX_LOCALS: usize = 1 << 8;
 
+/// Maximum number of variables captured by a procedure.
+///
+/// This limitation is from using `u8` as the up-value ID in bytecode.
+/// A procedure can have this many up-values on top of its locals.
+pub const MAX_UP_VALUES: usize = 1 << 8;
+
+/// Maximum number of arguments in a call, and of parameters of a procedure.
+///
+/// This limitation is from using `u8` as the arity in bytecode.
+pub const MAX_ARITY: usize = (1 << 8) - 1;
+
+/// Maximum number of constants per constant table.
+///
+/// This limitation is from using `u16` as the constant ID in bytecode.
+pub const MAX_CONSTANTS: usize = 1 << 16;
+
 /// Maximum bytecode address that can be stored in a jump instruction.
 ///
 /// Limited by the amount of space in a 32-bit instruction after the opcode.
 pub const MAX_JUMP_ADDR: usize = 1 << MAX_JUMP_ADDR_BITS;
 pub const MAX_JUMP_ADDR_BITS: usize = 24;
+
+/// Maximum number of global variables per environment.
+///
+/// This limitation is from using `u16` as the [`SymbolId`](crate::SymbolId).
+pub const MAX_SYMBOLS: usize = 1 << 16;
+
+/// Maximum number of procedure prototypes per environment.
+///
+/// This limitation is from using `u16` as the procedure ID in bytecode.
+pub const MAX_PROCEDURES: usize = 1 << 16;
+
+/// Maximum number of interpreter loops running at once on a thread.
+///
+/// A procedure called back by a native function, like the one passed to
+/// `map`, runs in an interpreter loop nested in the native's Rust stack
+/// frame, so recursion through natives is limited before the thread's
+/// stack overflows.
+pub const MAX_NESTING: usize = 1 << 6;
+
+/// Deepest nesting of lists, vectors and quotes in source text.
+///
+/// The parser, and the compiler after it, recurse once per level, so
+/// deeper source would overflow the stack.
+pub const MAX_PARSE_DEPTH: usize = 1 << 8;
+
+/// Deepest nesting of macro uses, each in the output of the one before.
+///
+/// The expander recurses through several frames per level, so a macro
+/// that keeps using itself fails instead of overflowing the stack.
+pub const MAX_EXPANSION_DEPTH: usize = 1 << 6;
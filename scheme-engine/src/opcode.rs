@@ -13,13 +13,27 @@ pub enum Op {
     PushTrue,
     PushFalse,
     PushConstant(ConstantId),
+    /// Push a copy of a constant that holds mutable data, like a quoted
+    /// bytevector, so changing the value doesn't change the literal.
+    PushConstantCopy(ConstantId),
+    /// Push whether the top stack value is `eqv?` to the constant, leaving
+    /// the value on the stack, to compare the key of `case` to its datums.
+    MatchConstant(ConstantId),
 
     /// Remove and discard the top value off the stack.
     Pop,
 
+    /// Exchange the two values on top of the stack, to call a procedure
+    /// evaluated after its argument, like in a `=>` clause of `cond`.
+    Swap,
+
     /// Jump to the specified absolute address if the top stack value is #f
     JumpFalse(JumpAddr),
 
+    /// Jump to the specified absolute address if the top stack value is
+    /// anything but #f. Like [`Op::JumpFalse`], the value stays on the stack.
+    JumpTrue(JumpAddr),
+
     /// Unconditional jump to the specified absolute address.
     Jump(JumpAddr),
 
@@ -35,9 +49,22 @@ pub enum Op {
     /// Does not implicitly pop the value off the stack.
     StoreEnvVar(SymbolId),
 
+    /// Like [`Op::StoreEnvVar`], for the top-level definition of the
+    /// variable rather than an assignment to it.
+    ///
+    /// Does not implicitly pop the value off the stack.
+    DefineEnvVar(SymbolId),
+
     LoadUpValue(UpValueId),
     StoreUpValue(UpValueId),
 
+    /// Push the running closure onto the operand stack.
+    ///
+    /// A procedure bound to a local variable refers to itself this way,
+    /// rather than capturing the variable, which would make the closure
+    /// own itself and never be freed.
+    LoadSelf,
+
     LoadLocalVar(LocalId),
 
     /// Store the value on the top of the operand stack into the local
@@ -46,6 +73,13 @@ pub enum Op {
     /// Does not implicitly pop the value off the stack.
     StoreLocalVar(LocalId),
 
+    /// Pop a value produced by `values`, and push each of the values
+    /// for the formals stored in the given constant, like `(a b . rest)`.
+    ///
+    /// The values matching a rest formal are pushed as one list.
+    /// A number of values that doesn't fit the formals is an error.
+    UnpackValues(ConstantId),
+
     /// Capture a variable as an up-value for the coming closure creation. See [`Op::CreateClosure`]
     CaptureValue(UpValueOrigin),
 
@@ -59,6 +93,7 @@ pub enum Op {
     CreateClosure(ProcId),
 
     /// Call a closure instance instance.
+    #[allow(dead_code)]
     CallClosure {
         arity: u8,
     },
@@ -72,8 +107,54 @@ pub enum Op {
         arity: u8,
     },
 
-    /// End of bytecode sentinel.
-    End,
+    /// Install a handler for errors raised by the instructions up to the
+    /// matching [`Op::PopHandler`], for `guard`.
+    ///
+    /// The top of the operand stack holds the handler procedure. When an
+    /// instruction fails, the machine unwinds back to this frame and
+    /// stack depth, pushes what was raised, and jumps to the address.
+    PushHandler(JumpAddr),
+
+    /// Remove the innermost handler, and the handler procedure from
+    /// under the value on top of the operand stack.
+    PopHandler,
+}
+
+impl Op {
+    /// The instruction's name, without its operands.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Op::Bail => "Bail",
+            Op::PushNil => "PushNil",
+            Op::PushVoid => "PushVoid",
+            Op::PushTrue => "PushTrue",
+            Op::PushFalse => "PushFalse",
+            Op::PushConstant(_) => "PushConstant",
+            Op::PushConstantCopy(_) => "PushConstantCopy",
+            Op::MatchConstant(_) => "MatchConstant",
+            Op::Pop => "Pop",
+            Op::Swap => "Swap",
+            Op::JumpFalse(_) => "JumpFalse",
+            Op::JumpTrue(_) => "JumpTrue",
+            Op::Jump(_) => "Jump",
+            Op::Return => "Return",
+            Op::LoadEnvVar(_) => "LoadEnvVar",
+            Op::StoreEnvVar(_) => "StoreEnvVar",
+            Op::DefineEnvVar(_) => "DefineEnvVar",
+            Op::LoadUpValue(_) => "LoadUpValue",
+            Op::StoreUpValue(_) => "StoreUpValue",
+            Op::LoadSelf => "LoadSelf",
+            Op::LoadLocalVar(_) => "LoadLocalVar",
+            Op::StoreLocalVar(_) => "StoreLocalVar",
+            Op::UnpackValues(_) => "UnpackValues",
+            Op::CaptureValue(_) => "CaptureValue",
+            Op::CreateClosure(_) => "CreateClosure",
+            Op::CallClosure { .. } => "CallClosure",
+            Op::CallNative { .. } => "CallNative",
+            Op::PushHandler(_) => "PushHandler",
+            Op::PopHandler => "PopHandler",
+        }
+    }
 }
 
 /// Absolute bytecode address for jumps.
@@ -86,7 +167,8 @@ impl JumpAddr {
             panic!("maximum jump address size of {MAX_JUMP_ADDR_BITS} bits exceeded")
         }
 
-        let [a, b, c, ..] = index.to_le_bytes();
+        // Fits in 24 bits, so truncating to u32 is lossless on any target.
+        let [a, b, c, _] = (index as u32).to_le_bytes();
         JumpAddr([a, b, c])
     }
 
@@ -97,7 +179,7 @@ impl JumpAddr {
 
     pub fn as_usize(&self) -> usize {
         let [a, b, c] = self.0;
-        usize::from_le_bytes([a, b, c, 0, 0, 0, 0, 0])
+        u32::from_le_bytes([a, b, c, 0]) as usize
     }
 }
 
@@ -136,18 +218,6 @@ pub enum UpValueOrigin {
     Outer(UpValueId),
 }
 
-impl UpValueOrigin {
-    #[inline]
-    fn is_parent(&self) -> bool {
-        matches!(self, UpValueOrigin::Parent(_))
-    }
-
-    #[inline]
-    fn is_outer(&self) -> bool {
-        matches!(self, UpValueOrigin::Outer(_))
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
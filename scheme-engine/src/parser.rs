@@ -1,135 +1,800 @@
 //! Parser.
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+
 use crate::ext::*;
 use crate::{
     error::{Error, Result},
-    expr::Expr,
+    escape,
+    expr::{Expr, Keyword},
+    handle::Handle,
     lexer::Lexer,
+    limits::MAX_PARSE_DEPTH,
+    source_map::{line_column, DefinitionSite, SourceMap},
+    span::Span,
     token::{Token, TokenKind},
 };
 
+/// Options controlling how source text is read.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Fold identifiers to lowercase, for code that assumes
+    /// case-insensitive symbols.
+    ///
+    /// The `#!fold-case` and `#!no-fold-case` directives override this
+    /// for the remainder of the source.
+    pub fold_case: bool,
+}
+
+/// Parse the source of a whole program, a sequence of top-level expressions.
+pub fn parse_program(source: &str) -> Result<Expr> {
+    parse(source, true)
+}
+
 pub fn parse(source: &str, is_sequence: bool) -> Result<Expr> {
-    let mut lexer = Lexer::new(source);
+    parse_with_options(source, is_sequence, &ParseOptions::default())
+}
 
-    // Position the lexer so the current token points to the first token.
-    lexer.next_token();
+/// Parse source text, using the given options.
+///
+/// See [`parse`].
+pub fn parse_with_options(source: &str, is_sequence: bool, options: &ParseOptions) -> Result<Expr> {
+    check_source_len(source)?;
+
+    let mut tokens = TokenStream::new(source, options);
 
     if is_sequence {
         // Top level of file contents
-        parse_sequence(&mut lexer)
+        parse_sequence(&mut tokens)
     } else {
-        parse_expr(&mut lexer)
+        parse_expr(&mut tokens)
     }
 }
 
-fn parse_sequence(lexer: &mut Lexer) -> Result<Expr> {
-    println!("parse_sequence({:?})", lexer.rest());
+/// Token spans can't reach further than 4 GiB into the source.
+pub(crate) fn check_source_len(source: &str) -> Result<()> {
+    if u32::try_from(source.len()).is_err() {
+        return Err(Error::Reason(
+            "source is larger than the 4 GiB limit".to_string(),
+        ));
+    }
+    Ok(())
+}
 
-    let mut expressions = Vec::new();
+/// Register the source of a whole program under a name, like its
+/// file path, and parse it.
+///
+/// Errors are wrapped in [`Error::InSource`], so they can be
+/// rendered with the name by [`SourceMap::render_error`].
+///
+/// Where each `define` starts is remembered too, so compile errors
+/// can locate their definition when the program is compiled with the
+/// name as [`CompileOptions::source_name`](crate::CompileOptions::source_name).
+///
+/// ```
+/// use scheme_engine::{parse_program_named, SourceMap};
+///
+/// let mut sources = SourceMap::new();
+/// let err = parse_program_named(&mut sources, "main.scm", "(display 1)\n(car))").unwrap_err();
+/// assert!(sources.render_error(&err).starts_with("main.scm:2:6: "));
+/// ```
+pub fn parse_program_named(
+    sources: &mut SourceMap,
+    name: impl Into<String>,
+    text: impl Into<String>,
+) -> Result<Expr> {
+    let source_id = sources.add(name, text)?;
+    let source = sources.text(source_id).expect("source was just added");
 
-    while let Some(token) = lexer.current_token() {
-        if token.kind == TokenKind::EOF {
-            break;
+    let mut tokens = TokenStream::new(source, &ParseOptions::default());
+    tokens.definitions = Some(Vec::new());
+    let program = parse_sequence(&mut tokens).map_err(|error| {
+        // The source ends wherever more input was expected.
+        let span = match error {
+            Error::Incomplete { .. } => Span::new(source.len(), 0),
+            _ => tokens.last.clone(),
+        };
+        Error::InSource {
+            source_id,
+            span,
+            error: Box::new(error),
         }
+    })?;
+
+    let definitions = tokens.definitions.take().unwrap_or_default();
+    sources.set_definitions(source_id, definitions);
+    Ok(program)
+}
+
+/// Read the first datum of the source, returning it with the number of
+/// bytes it took, or `None` when nothing but whitespace and comments is left.
+pub(crate) fn read_datum(source: &str) -> Result<Option<(Expr, usize)>> {
+    let mut tokens = TokenStream::new(source, &ParseOptions::default());
+    if tokens.peek().kind == TokenKind::EOF {
+        return Ok(None);
+    }
+
+    let datum = parse_expr(&mut tokens)?;
+    debug_assert!(tokens.peeked.is_none(), "datum must end at its last token");
+    Ok(Some((datum, source.len() - tokens.rest().len())))
+}
 
-        let expr = parse_expr(lexer)?;
+fn parse_sequence(tokens: &mut TokenStream) -> Result<Expr> {
+    trace!("parse_sequence({:?})", tokens.rest());
+
+    let mut expressions = Vec::new();
+
+    while tokens.peek().kind != TokenKind::EOF {
+        // Datum labels are only visible within their outermost datum.
+        tokens.labels.clear();
+        tokens.form = expressions.len();
+        let expr = parse_expr(tokens)?;
         expressions.push(expr);
     }
 
+    // Block comments are skipped by the lexer, even when unclosed.
+    if tokens.lexer.unclosed_comment() {
+        let token = tokens.next();
+        return Err(tokens.incomplete(token.span.low(), "expression"));
+    }
+
     Ok(Expr::Sequence(expressions))
 }
 
-fn parse_expr(lexer: &mut Lexer) -> Result<Expr> {
-    println!("parse_expr({:?})", lexer.rest());
+fn parse_expr(tokens: &mut TokenStream) -> Result<Expr> {
+    trace!("parse_expr({:?})", tokens.rest());
+
+    tokens.enter()?;
+    let expr = parse_datum(tokens);
+    tokens.leave();
+    expr
+}
 
-    let token = lexer
-        .current_token()
-        .cloned()
-        .ok_or_else(|| Error::Reason(format!("unexpected end")))?;
-    lexer.next_token();
+/// Parse the expression at the next token, once [`parse_expr`]
+/// has checked how deeply it's nested.
+fn parse_datum(tokens: &mut TokenStream) -> Result<Expr> {
+    let token = tokens.next();
 
     match token.kind {
-        TokenKind::LeftParen => parse_list(lexer),
-        TokenKind::EOF => Err(Error::Reason("unexpected end-of-file".to_string())),
-        TokenKind::RightParen => Err(Error::Reason("unexpected right parentheses".to_string())),
-        TokenKind::QuoteMark => parse_quote(lexer),
-        _ => {
-            let fragment = token.fragment(lexer.source());
-            parse_atom(token.clone(), fragment)
+        TokenKind::LeftParen => {
+            let list = parse_list(tokens)?;
+            tokens.record_definition(&list, token.span.low());
+            Ok(list)
+        }
+        TokenKind::BytevectorOpen => parse_bytevector(tokens),
+        TokenKind::VectorOpen => parse_vector(tokens),
+        TokenKind::EOF => Err(tokens.incomplete(token.span.low(), "expression")),
+        TokenKind::RightParen => Err(tokens.unexpected(&token, "expression")),
+        TokenKind::QuoteMark => parse_quote(tokens),
+        TokenKind::String => {
+            let fragment = tokens.fragment(&token)?;
+            if is_closed(fragment, '"') {
+                parse_string(fragment)
+            } else {
+                Err(tokens.incomplete(token.span.high(), "'\"'"))
+            }
+        }
+        TokenKind::Char => parse_char(tokens.fragment(&token)?),
+        TokenKind::Atom => {
+            let fragment = tokens.fragment(&token)?;
+            if fragment.starts_with('|') && !is_closed(fragment, '|') {
+                return Err(tokens.incomplete(token.span.high(), "'|'"));
+            }
+            match datum_label(fragment) {
+                Some((number, true)) => parse_labelled(tokens, number),
+                Some((number, false)) => tokens
+                    .labels
+                    .get(&number)
+                    .cloned()
+                    .ok_or_else(|| Error::Reason(format!("undefined datum label: {fragment}"))),
+                None => parse_atom(tokens, &token, fragment),
+            }
+        }
+        TokenKind::LineComment | TokenKind::BlockComment | TokenKind::Whitespace => {
+            unreachable!("trivia is skipped by the token stream")
         }
     }
 }
 
-fn parse_list(lexer: &mut Lexer) -> Result<Expr> {
-    println!("parse_list({:?})", lexer.rest());
+fn parse_list(tokens: &mut TokenStream) -> Result<Expr> {
+    trace!("parse_list({:?})", tokens.rest());
 
     let mut expressions = Vec::new();
 
-    while let Some(token) = lexer.current_token() {
-        match token.kind {
-            TokenKind::RightParen => {
-                lexer.next_token();
-                break;
-            }
+    loop {
+        let kind = tokens.peek().kind;
+        match kind {
+            TokenKind::RightParen => break,
             TokenKind::EOF => {
-                return Err(Error::Reason("unexpected end-of-file".to_string()));
+                let token = tokens.next();
+                return Err(tokens.incomplete(token.span.low(), "')'"));
+            }
+            _ if tokens.peek_is_dot() => {
+                parse_dotted_tail(tokens, &mut expressions)?;
+                break;
             }
             _ => {
-                let expr = parse_expr(lexer)?;
+                let expr = parse_expr(tokens)?;
                 expressions.push(expr);
             }
         }
     }
 
-    Ok(Expr::List(expressions))
+    tokens.expect(TokenKind::RightParen)?;
+
+    Ok(Expr::List(expressions.into()))
 }
 
-fn parse_quote(lexer: &mut Lexer) -> Result<Expr> {
-    println!("parse_quote({:?})", lexer.rest());
-    parse_expr(lexer).map(Box::new).map(Expr::Quote)
+/// Parse the dot and the final element of a dotted list, like `(a b . c)`.
+///
+/// The list keeps the dot as [`Keyword::Dot`], followed by the tail.
+fn parse_dotted_tail(tokens: &mut TokenStream, expressions: &mut Vec<Expr>) -> Result<()> {
+    let dot = tokens.next();
+    if expressions.is_empty() {
+        return Err(tokens.unexpected(&dot, "datum before '.'"));
+    }
+
+    expressions.push(Expr::Keyword(Keyword::Dot));
+    if tokens.peek().kind == TokenKind::RightParen || tokens.peek_is_dot() {
+        let token = tokens.next();
+        return Err(tokens.unexpected(&token, "datum after '.'"));
+    }
+    expressions.push(parse_expr(tokens)?);
+
+    match tokens.peek().kind {
+        TokenKind::RightParen => Ok(()),
+        TokenKind::EOF => {
+            let token = tokens.next();
+            Err(tokens.incomplete(token.span.low(), "')'"))
+        }
+        _ => {
+            let token = tokens.next();
+            Err(tokens.unexpected(&token, "')' after the tail of a dotted list"))
+        }
+    }
 }
 
-fn parse_atom(token: Token, fragment: &str) -> Result<Expr> {
-    println!("parse_atom({:?}, {:?})", token, fragment);
+/// Parse the elements of a vector literal, after the opening `#(`.
+fn parse_vector(tokens: &mut TokenStream) -> Result<Expr> {
+    trace!("parse_vector({:?})", tokens.rest());
+
+    let mut elements = Vec::new();
 
-    use TokenKind::*;
-    debug_assert_eq!(token.kind, Atom);
+    loop {
+        match tokens.peek().kind {
+            TokenKind::RightParen => break,
+            TokenKind::EOF => {
+                let token = tokens.next();
+                return Err(tokens.incomplete(token.span.low(), "')'"));
+            }
+            _ => elements.push(parse_expr(tokens)?),
+        }
+    }
+
+    tokens.expect(TokenKind::RightParen)?;
+
+    Ok(Expr::Vector(Handle::new(elements)))
+}
+
+/// Parse the elements of a bytevector literal, after the opening `#u8(`.
+fn parse_bytevector(tokens: &mut TokenStream) -> Result<Expr> {
+    trace!("parse_bytevector({:?})", tokens.rest());
+    read_bytes(tokens).map(Expr::from)
+}
+
+/// Read the bytes of a bytevector literal, up to and including the closing `)`.
+pub(crate) fn read_bytes(tokens: &mut TokenStream) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    loop {
+        let token = tokens.next();
+        match token.kind {
+            TokenKind::RightParen => break,
+            TokenKind::Atom => {
+                let fragment = tokens.fragment(&token)?;
+                match fragment.parse::<u8>() {
+                    Ok(byte) => bytes.push(byte),
+                    Err(_) => return Err(tokens.unexpected(&token, "byte between 0 and 255")),
+                }
+            }
+            TokenKind::EOF => return Err(tokens.incomplete(token.span.low(), "')'")),
+            _ => return Err(tokens.unexpected(&token, "byte between 0 and 255")),
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn parse_quote(tokens: &mut TokenStream) -> Result<Expr> {
+    trace!("parse_quote({:?})", tokens.rest());
+    parse_expr(tokens).map(Box::new).map(Expr::Quote)
+}
+
+/// The number of a datum label, and whether it's the definition `#N=`
+/// rather than the reference `#N#`.
+pub(crate) fn datum_label(fragment: &str) -> Option<(usize, bool)> {
+    let rest = fragment.strip_prefix('#')?;
+    let (digits, is_definition) = match rest.strip_suffix('=') {
+        Some(digits) => (digits, true),
+        None => (rest.strip_suffix('#')?, false),
+    };
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, is_definition))
+}
+
+/// Parse the datum after a datum label definition, like `#0=(a . #0#)`.
+///
+/// A labelled list is read as pairs, with its first pair made before the
+/// elements are parsed. References to the label inside the list are to
+/// that pair, which is how cycles are read.
+pub(crate) fn parse_labelled(tokens: &mut TokenStream, number: usize) -> Result<Expr> {
+    if tokens.peek().kind != TokenKind::LeftParen {
+        let datum = parse_expr(tokens)?;
+        tokens.labels.insert(number, datum.clone());
+        return Ok(datum);
+    }
+
+    tokens.next();
+    let mut first = Handle::new((Expr::Nil, Expr::Nil));
+    tokens.labels.insert(number, Expr::Pair(first.clone()));
+    let list = parse_list(tokens)?;
+
+    let elements = list.as_slice().unwrap_or_default();
+    let (elements, tail) = match elements {
+        [elements @ .., Expr::Keyword(Keyword::Dot), tail] => (elements, tail.clone()),
+        elements => (elements, Expr::Nil),
+    };
+    let Some((head, rest)) = elements.split_first() else {
+        // Nothing inside the empty list can refer to it.
+        tokens.labels.insert(number, list.clone());
+        return Ok(list);
+    };
+
+    let rest = rest.iter().rev().fold(tail, |tail, head| {
+        Expr::Pair(Handle::new((head.clone(), tail)))
+    });
+    *first.borrow_mut() = (head.clone(), rest);
+    Ok(Expr::Pair(first))
+}
+
+fn parse_atom(tokens: &TokenStream, token: &Token, fragment: &str) -> Result<Expr> {
+    trace!("parse_atom({:?}, {:?})", token, fragment);
+
+    match read_atom(tokens, token, fragment)? {
+        Atom::Value(value) => Ok(value),
+        Atom::Ident(name) => Ok(Expr::Ident(name.as_ref().into())),
+    }
+}
+
+/// An atom as it's read, before an identifier is made into a value.
+pub(crate) enum Atom<'a> {
+    Value(Expr),
+    /// The name of an identifier, borrowed from the source unless it
+    /// was decoded or folded.
+    Ident(Cow<'a, str>),
+}
+
+/// Read an atom, like a number, boolean or identifier.
+pub(crate) fn read_atom<'a>(
+    tokens: &TokenStream,
+    token: &Token,
+    fragment: &'a str,
+) -> Result<Atom<'a>> {
+    debug_assert_eq!(token.kind, TokenKind::Atom);
+
+    // Names between bars are taken as they are, without case folding.
+    if fragment.starts_with('|') {
+        return escape::decode_symbol(fragment).map(|name| Atom::Ident(name.into()));
+    }
+
+    match read_number(fragment) {
+        NumberLiteral::Number(number) => return Ok(Atom::Value(Expr::Number(number))),
+        NumberLiteral::Inexact(number) => return Ok(Atom::Value(Expr::Inexact(number))),
+        NumberLiteral::Malformed => {
+            return Err(Error::Reason(format!(
+                "malformed number literal: {fragment}"
+            )))
+        }
+        NumberLiteral::NotNumeric => {}
+    }
 
     if let Some((ch, rest)) = fragment.split_first_char() {
         match ch {
-            '0'..='9' => parse_number(token, fragment),
             '#' => match rest.first() {
-                Some('t') => Ok(Expr::Bool(true)),
-                Some('f') => Ok(Expr::Bool(false)),
-                Some('b') => todo!("parse binary number"),
-                Some('x') => todo!("parse hexadecimal number"),
+                Some('t') => Ok(Atom::Value(Expr::Bool(true))),
+                Some('f') => Ok(Atom::Value(Expr::Bool(false))),
+                Some('b') => parse_radix_integer(fragment, &rest[1..], 2).map(Atom::Value),
+                Some('x') => parse_radix_integer(fragment, &rest[1..], 16).map(Atom::Value),
                 _ => match rest {
-                    "void" => Ok(Expr::Void),
-                    _ => Err(Error::Reason(format!("unknown atom: {ch:?}"))),
+                    "void" => Ok(Atom::Value(Expr::Void)),
+                    _ => Err(tokens.unexpected(token, "expression")),
                 },
             },
-            '+' | '-' | '*' | '=' | '<' | '>' | 'a'..='z' => {
+            '!'
+            | '$'
+            | '%'
+            | '&'
+            | '*'
+            | '/'
+            | ':'
+            | '<'
+            | '='
+            | '>'
+            | '?'
+            | '^'
+            | '_'
+            | '~'
+            | '+'
+            | '-'
+            | 'a'..='z'
+            | 'A'..='Z' => {
                 // TODO: The complex identifier rules
-                parse_identifier(token, fragment)
+                Ok(Atom::Ident(parse_identifier(
+                    token.clone(),
+                    fragment,
+                    tokens.fold_case,
+                )))
             }
-            _ => Err(Error::Reason(format!("unexpected character: {ch:?}"))),
+            // A lone dot is only valid in a dotted list, but `...` is an identifier.
+            '.' if !rest.is_empty() => Ok(Atom::Ident(parse_identifier(
+                token.clone(),
+                fragment,
+                tokens.fold_case,
+            ))),
+            _ => Err(tokens.unexpected(token, "expression")),
         }
     } else {
         Err(Error::Reason("expected atom".to_string()))
     }
 }
 
-fn parse_number(_token: Token, fragment: &str) -> Result<Expr> {
-    let number = fragment
-        .parse::<f64>()
-        .map_err(|err| Error::Reason(format!("failed to parse number: {err}")))?;
+/// Read the digits of a `#b` or `#x` integer literal, after the prefix.
+fn parse_radix_integer(fragment: &str, digits: &str, radix: u32) -> Result<Expr> {
+    let (negative, unsigned) = match digits.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, digits.strip_prefix('+').unwrap_or(digits)),
+    };
 
-    Ok(Expr::Number(number))
+    // The sign is already taken, so a second one is malformed.
+    match u64::from_str_radix(unsigned, radix) {
+        Ok(value) if !unsigned.starts_with(['+', '-']) => {
+            let value = value as f64;
+            Ok(Expr::Number(if negative { -value } else { value }))
+        }
+        _ => Err(Error::Reason(format!(
+            "malformed number literal: {fragment}"
+        ))),
+    }
+}
+
+/// How a fragment of text reads as a number.
+#[derive(Debug, PartialEq)]
+pub(crate) enum NumberLiteral {
+    /// An integer without a dot or exponent, which is exact.
+    Number(f64),
+    /// Has a dot or exponent, or is an infinity or NaN.
+    Inexact(f64),
+    /// Starts like a number, but isn't one, like `1.2.3`.
+    Malformed,
+    /// Not a number at all, like an identifier.
+    NotNumeric,
 }
 
-fn parse_identifier(_token: Token, fragment: &str) -> Result<Expr> {
+/// Decide whether text is a number, shared by the reader and `string->number`.
+///
+/// The accepted grammar is an optional sign, decimal digits with at most
+/// one dot, and an optional exponent, or one of `+inf.0`, `-inf.0`,
+/// `+nan.0` and `-nan.0`. Either side of the dot may be empty,
+/// so `.5`, `5.` and `-.5` are numbers, but a lone `.` is not. A number
+/// with a dot or exponent is inexact, even when it's an integer like `5.`.
+///
+/// Text that starts with a digit, or a dot followed by a digit, after the
+/// optional sign is numeric. A sign followed by anything else, like `-`
+/// or `->string`, is an identifier.
+pub(crate) fn read_number(text: &str) -> NumberLiteral {
+    match text {
+        "+inf.0" => return NumberLiteral::Inexact(f64::INFINITY),
+        "-inf.0" => return NumberLiteral::Inexact(f64::NEG_INFINITY),
+        "+nan.0" | "-nan.0" => return NumberLiteral::Inexact(f64::NAN),
+        _ => {}
+    }
+
+    let unsigned = text.strip_prefix(['+', '-']).unwrap_or(text);
+    let mut chars = unsigned.chars().peekable();
+
+    let looks_numeric = match chars.peek() {
+        Some(ch) if ch.is_ascii_digit() => true,
+        Some('.') => unsigned[1..].starts_with(|ch: char| ch.is_ascii_digit()),
+        _ => false,
+    };
+    if !looks_numeric {
+        return NumberLiteral::NotNumeric;
+    }
+
+    let mut digits = 0;
+    let mut seen_dot = false;
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            '0'..='9' => digits += 1,
+            '.' if !seen_dot => seen_dot = true,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    let mut exact = !seen_dot;
+    if let Some('e' | 'E') = chars.peek() {
+        exact = false;
+        chars.next();
+        if let Some('+' | '-') = chars.peek() {
+            chars.next();
+        }
+        let mut exponent_digits = 0;
+        while chars.next_if(char::is_ascii_digit).is_some() {
+            exponent_digits += 1;
+        }
+        if exponent_digits == 0 {
+            return NumberLiteral::Malformed;
+        }
+    }
+
+    if digits == 0 || chars.next().is_some() {
+        return NumberLiteral::Malformed;
+    }
+
+    match text.parse::<f64>() {
+        Ok(number) if exact => NumberLiteral::Number(number),
+        Ok(number) => NumberLiteral::Inexact(number),
+        Err(_) => NumberLiteral::Malformed,
+    }
+}
+
+/// Whether a string literal or bar-quoted symbol fragment ends with
+/// its closing delimiter, rather than running to the end of the source.
+pub(crate) fn is_closed(fragment: &str, delimiter: char) -> bool {
+    match fragment
+        .get(1..)
+        .and_then(|inner| inner.strip_suffix(delimiter))
+    {
+        // The closing delimiter isn't escaped by an odd number of backslashes.
+        Some(inner) => inner.chars().rev().take_while(|ch| *ch == '\\').count() % 2 == 0,
+        None => false,
+    }
+}
+
+/// Decode a string literal fragment, including its enclosing double quotes.
+fn parse_string(fragment: &str) -> Result<Expr> {
+    escape::decode_string(fragment).map(Expr::from)
+}
+
+/// Decode a character literal fragment, including the `#\` prefix.
+fn parse_char(fragment: &str) -> Result<Expr> {
+    escape::decode_char(fragment).map(Expr::Char)
+}
+
+fn parse_identifier(_token: Token, fragment: &str, fold_case: bool) -> Cow<'_, str> {
     // TODO: The complex identifier rules
-    Ok(Expr::Ident(fragment.into()))
+    if fold_case {
+        Cow::Owned(fragment.to_lowercase())
+    } else {
+        Cow::Borrowed(fragment)
+    }
+}
+
+/// Peekable stream of tokens over the lexer.
+///
+/// Reader directives like `#!fold-case` are consumed here, since they
+/// change the reader's state but produce no expression.
+pub(crate) struct TokenStream<'a> {
+    pub(crate) lexer: Lexer<'a>,
+    /// The next token, if it was already scanned by a peek.
+    peeked: Option<Token>,
+    /// Fold identifiers to lowercase.
+    fold_case: bool,
+    /// Span of the most recently consumed token, where errors are located.
+    last: Span,
+    /// The data of the datum labels defined so far, like `#0=`.
+    pub(crate) labels: HashMap<usize, Expr>,
+    /// Number of expressions being parsed that enclose the next token.
+    depth: usize,
+    /// Where the `define` forms read so far start, when they're recorded
+    /// for [`parse_program_named`].
+    definitions: Option<Vec<DefinitionSite>>,
+    /// Index of the top-level form being read.
+    form: usize,
+}
+
+impl<'a> TokenStream<'a> {
+    pub(crate) fn new(source: &'a str, options: &ParseOptions) -> Self {
+        Self {
+            lexer: Lexer::new(source),
+            peeked: None,
+            fold_case: options.fold_case,
+            last: Span::new(0, 0),
+            labels: HashMap::new(),
+            depth: 0,
+            definitions: None,
+            form: 0,
+        }
+    }
+
+    /// Remember where a list starting at the offset is, when it's a
+    /// `define` form and definitions are being recorded.
+    fn record_definition(&mut self, list: &Expr, offset: usize) {
+        let Some(definitions) = &mut self.definitions else {
+            return;
+        };
+        let name = match list.as_slice() {
+            Some([Expr::Ident(keyword), target, ..]) if keyword == "define" => match target {
+                Expr::Ident(name) => name,
+                Expr::List(formals) => match formals.first() {
+                    Some(Expr::Ident(name)) => name,
+                    _ => return,
+                },
+                _ => return,
+            },
+            _ => return,
+        };
+        definitions.push(DefinitionSite {
+            form: self.form,
+            name: name.clone(),
+            offset,
+        });
+    }
+
+    /// Start parsing an expression inside the current one, failing
+    /// when they're nested deeper than [`MAX_PARSE_DEPTH`].
+    pub(crate) fn enter(&mut self) -> Result<()> {
+        if self.depth == MAX_PARSE_DEPTH {
+            let offset = self.peek().span.low();
+            let (line, column) = self.position_at(offset);
+            return Err(Error::Reason(format!(
+                "expression at {line}:{column} is nested more than {MAX_PARSE_DEPTH} deep"
+            )));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Finish parsing an expression started with [`enter`](Self::enter).
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Scan the next token from the lexer, applying any directives before it.
+    fn scan(lexer: &mut Lexer, fold_case: &mut bool) -> Token {
+        loop {
+            let token = lexer.next_token();
+            if token.kind.is_trivia() {
+                // Only a lexer in trivia mode produces these.
+                continue;
+            } else if token.kind == TokenKind::Atom {
+                match token.try_fragment(lexer.source()).unwrap_or_default() {
+                    "#!fold-case" => *fold_case = true,
+                    "#!no-fold-case" => *fold_case = false,
+                    _ => return token,
+                }
+            } else {
+                return token;
+            }
+        }
+    }
+
+    /// The source remaining after the scanned tokens.
+    pub(crate) fn rest(&self) -> &str {
+        self.lexer.rest()
+    }
+
+    /// The next token, without consuming it.
+    pub(crate) fn peek(&mut self) -> &Token {
+        let Self {
+            lexer,
+            peeked,
+            fold_case,
+            ..
+        } = self;
+        peeked.get_or_insert_with(|| Self::scan(lexer, fold_case))
+    }
+
+    /// Whether the next token is a lone dot, as in a dotted list.
+    pub(crate) fn peek_is_dot(&mut self) -> bool {
+        let token = self.peek().clone();
+        token.kind == TokenKind::Atom && self.fragment(&token).is_ok_and(|text| text == ".")
+    }
+
+    /// Consume the next token.
+    pub(crate) fn next(&mut self) -> Token {
+        let token = match self.peeked.take() {
+            Some(token) => token,
+            None => Self::scan(&mut self.lexer, &mut self.fold_case),
+        };
+        self.last = token.span.clone();
+        token
+    }
+
+    /// Consume the next token, which must be of the expected kind.
+    pub(crate) fn expect(&mut self, expected: TokenKind) -> Result<Token> {
+        let token = self.next();
+        if token.kind == expected {
+            Ok(token)
+        } else {
+            let (line, column) = self.position(&token);
+            Err(Error::TokenError {
+                expected,
+                actual: token.kind,
+                fragment: self.fragment(&token)?.to_string(),
+                span: token.span,
+                line,
+                column,
+            })
+        }
+    }
+
+    /// Error for a token that can't appear where the parser expected
+    /// something more general than a single token kind.
+    pub(crate) fn unexpected(&self, token: &Token, expected: &str) -> Error {
+        let (line, column) = self.position(token);
+        let found = match self.fragment(token) {
+            Ok(fragment) => describe_token(token.kind, fragment),
+            Err(err) => return err,
+        };
+        Error::Reason(format!(
+            "expected {expected} but found {found} at {line}:{column}"
+        ))
+    }
+
+    /// Error for source that ends at the given byte offset, before
+    /// the expected token.
+    ///
+    /// An unclosed block comment swallows the rest of the source,
+    /// so it's reported instead.
+    pub(crate) fn incomplete(&self, offset: usize, expected: &str) -> Error {
+        let (line, column) = self.position_at(offset);
+        let expected = if self.lexer.unclosed_comment() {
+            "'|#'"
+        } else {
+            expected
+        };
+        Error::Incomplete {
+            expected: expected.to_string(),
+            line,
+            column,
+        }
+    }
+
+    /// The source text of the token, or an error for a span that
+    /// isn't a range of whole characters in the source.
+    pub(crate) fn fragment(&self, token: &Token) -> Result<&'a str> {
+        token.try_fragment(self.lexer.source()).ok_or_else(|| {
+            Error::Reason(format!(
+                "{} token at bytes {:?} doesn't fall on the characters of the source, {} bytes long",
+                token.kind,
+                token.span.as_range(),
+                self.lexer.source().len()
+            ))
+        })
+    }
+
+    /// One-based line and column where the token starts.
+    fn position(&self, token: &Token) -> (usize, usize) {
+        self.position_at(token.span.low())
+    }
+
+    /// One-based line and column of a byte offset in the source.
+    fn position_at(&self, offset: usize) -> (usize, usize) {
+        line_column(self.lexer.source(), offset)
+    }
+}
+
+/// Describe a token for error messages, such as `atom "define"`.
+pub(crate) fn describe_token(kind: TokenKind, fragment: &str) -> String {
+    match kind {
+        TokenKind::Atom | TokenKind::String | TokenKind::Char => format!("{kind} {fragment:?}"),
+        _ => kind.to_string(),
+    }
 }
 
 #[cfg(test)]
@@ -158,6 +823,52 @@ mod test {
         assert_eq!(list4[2], Expr::Number(8.0));
     }
 
+    #[test]
+    fn test_number_spellings() {
+        // A dot or exponent makes the number inexact.
+        let accepted = [
+            ("0", Expr::Number(0.0)),
+            ("42", Expr::Number(42.0)),
+            ("-5", Expr::Number(-5.0)),
+            ("+5", Expr::Number(5.0)),
+            ("1.5", Expr::Inexact(1.5)),
+            (".5", Expr::Inexact(0.5)),
+            ("5.", Expr::Inexact(5.0)),
+            ("-.5", Expr::Inexact(-0.5)),
+            ("+.5", Expr::Inexact(0.5)),
+            ("-5.", Expr::Inexact(-5.0)),
+            ("1e3", Expr::Inexact(1000.0)),
+            ("1.5E-2", Expr::Inexact(0.015)),
+            (".5e+1", Expr::Inexact(5.0)),
+            ("#x1F", Expr::Number(31.0)),
+            ("#x-ff", Expr::Number(-255.0)),
+            ("#b101", Expr::Number(5.0)),
+            ("#b+1", Expr::Number(1.0)),
+        ];
+        for (fragment, expected) in accepted {
+            let expr = parse(fragment, false).expect(fragment);
+            assert_eq!(expr, expected, "{fragment}");
+        }
+
+        let malformed = [
+            "1.2.3", "5..", "-1.2.3", "12abc", "1e", "1e+", "5.e", "#x", "#xg", "#b2", "#x+-1",
+        ];
+        for fragment in malformed {
+            let err = parse(fragment, false).expect_err(fragment);
+            assert_eq!(
+                err.to_string(),
+                format!("malformed number literal: {fragment}")
+            );
+        }
+
+        // Signs and dots that don't lead into digits start identifiers.
+        let identifiers = ["+", "-", "-inf", "->string", "-.x"];
+        for fragment in identifiers {
+            let expr = parse(fragment, false).expect(fragment);
+            assert_eq!(expr.as_ident(), Some(fragment));
+        }
+    }
+
     #[test]
     fn test_boolean() {
         let expr = parse("(#t #f)", false).expect("parse failed");
@@ -168,6 +879,338 @@ mod test {
         assert_eq!(list[1], Expr::Bool(false));
     }
 
+    #[test]
+    fn test_string() {
+        let expr = parse(r#"("" "abc" "a\tb\n" "\"quoted\" \\")"#, false).expect("parse failed");
+
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0], Expr::String("".into()));
+        assert_eq!(list[1], Expr::String("abc".into()));
+        assert_eq!(list[2], Expr::String("a\tb\n".into()));
+        assert_eq!(list[3], Expr::String("\"quoted\" \\".into()));
+
+        assert!(parse(r#""unterminated"#, false).is_err());
+        assert!(parse(r#""bad \q escape""#, false).is_err());
+    }
+
+    #[test]
+    fn test_bar_symbol() {
+        let expr = parse(r"(|a b| || |\x41;\|| |MiXed|)", false).expect("parse failed");
+
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0], Expr::Ident("a b".into()));
+        assert_eq!(list[1], Expr::Ident("".into()));
+        assert_eq!(list[2], Expr::Ident("A|".into()));
+        assert_eq!(list[3], Expr::Ident("MiXed".into()));
+
+        // Case folding leaves names between bars alone.
+        let options = ParseOptions { fold_case: true };
+        let expr = parse_with_options("(ABC |ABC|)", false, &options).unwrap();
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0], Expr::Ident("abc".into()));
+        assert_eq!(list[1], Expr::Ident("ABC".into()));
+    }
+
+    #[test]
+    fn test_special_initial_identifiers() {
+        // The identifiers of syntax-rules, among others.
+        let expr = parse("(_ ... ?x !done $ %a &b :key ^c ~d .e)", false).unwrap();
+        let names: Vec<String> = expr
+            .as_slice()
+            .unwrap()
+            .iter()
+            .map(|ident| ident.repr().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            ["_", "...", "?x", "!done", "$", "%a", "&b", ":key", "^c", "~d", ".e"]
+        );
+    }
+
+    #[test]
+    fn test_char() {
+        let expr = parse(r"(#\a #\( #\) #\space #\x41 #\λ)", false).expect("parse failed");
+
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0], Expr::Char('a'));
+        assert_eq!(list[1], Expr::Char('('));
+        assert_eq!(list[2], Expr::Char(')'));
+        assert_eq!(list[3], Expr::Char(' '));
+        assert_eq!(list[4], Expr::Char('A'));
+        assert_eq!(list[5], Expr::Char('λ'));
+
+        assert!(parse(r"#\nope", false).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let message = |source: &str| parse(source, true).unwrap_err().to_string();
+
+        assert_eq!(
+            message("(define x\n  (+ 1 2)"),
+            "expected ')' but found end-of-file at 2:10"
+        );
+        assert_eq!(
+            message("(a b))"),
+            "expected expression but found ')' at 1:6"
+        );
+        assert_eq!(
+            message("(a '"),
+            "expected expression but found end-of-file at 1:5"
+        );
+        assert_eq!(
+            message("'"),
+            "expected expression but found end-of-file at 1:2"
+        );
+    }
+
+    #[test]
+    fn test_incomplete() {
+        let table = [
+            ("(define x\n  (+ 1 2)", "')'", 2, 10),
+            ("(a (b c)", "')'", 1, 9),
+            ("'", "expression", 1, 2),
+            ("(a '", "expression", 1, 5),
+            ("'\n", "expression", 2, 1),
+            ("(\"é\" '", "expression", 1, 7),
+            ("(\"λ\"", "')'", 1, 5),
+            ("(\"λ\"\n", "')'", 2, 1),
+            ("#u8(1 2", "')'", 1, 8),
+            (r#"(display "abc"#, r#"'"'"#, 1, 14),
+            (r#""abc\""#, r#"'"'"#, 1, 7),
+            ("#| comment", "'|#'", 1, 11),
+            ("1 #| outer #| inner |#", "'|#'", 1, 23),
+            ("(a #| b", "'|#'", 1, 8),
+            ("(a |b c", "'|'", 1, 8),
+            (r"|a\|", "'|'", 1, 5),
+        ];
+
+        for (source, expected_token, expected_line, expected_column) in table {
+            match parse(source, true) {
+                Err(Error::Incomplete {
+                    expected,
+                    line,
+                    column,
+                }) => {
+                    assert_eq!(expected, expected_token, "{source}");
+                    assert_eq!((line, column), (expected_line, expected_column), "{source}");
+                }
+                other => panic!("expected incomplete input for {source:?}, found {other:?}"),
+            }
+        }
+
+        // A single datum can be incomplete too.
+        assert!(matches!(parse("", false), Err(Error::Incomplete { .. })));
+        assert!(matches!(parse("(1", false), Err(Error::Incomplete { .. })));
+    }
+
+    #[test]
+    fn test_malformed_is_not_incomplete() {
+        for source in ["(a b))", ")", "(1.2.3", "#u8(1 256", "(#z", "\"\\q\""] {
+            match parse(source, true) {
+                Err(Error::Incomplete { .. }) | Ok(_) => {
+                    panic!("expected malformed input error for {source:?}")
+                }
+                Err(_) => {}
+            }
+        }
+
+        // Tokens ending on the last character, multibyte or not, are complete.
+        for source in [
+            "'a",
+            "'\"λ\"",
+            "(a)",
+            "(\"λ\")\n",
+            "\"abλ\"",
+            "#\\λ",
+            "#\\λ\n",
+            "|abλ|",
+        ] {
+            assert!(parse(source, true).is_ok(), "{source}");
+        }
+
+        // Closed strings and comments at the end are complete.
+        for source in [r#""a\\""#, "#| a |# 1", "1 ; comment", r#""(""#] {
+            assert!(parse(source, true).is_ok(), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_interactive_input() {
+        // What a prompt gets from a single line: nothing to run, a request
+        // for more lines, or an error to show.
+        enum Class {
+            Empty,
+            Incomplete,
+            Malformed(&'static str),
+        }
+        use Class::*;
+
+        let table = [
+            ("", Empty),
+            ("   \t", Empty),
+            ("\n", Empty),
+            ("; just a comment", Empty),
+            ("  ; indented comment\n", Empty),
+            ("#| block |#", Empty),
+            ("#| block |# ; and line", Empty),
+            ("'", Incomplete),
+            ("(", Incomplete),
+            ("#(", Incomplete),
+            ("#u8(", Incomplete),
+            ("(1 .", Incomplete),
+            ("\"abc", Incomplete),
+            ("#| unclosed", Incomplete),
+            ("#0=", Incomplete),
+            (")", Malformed("expected expression but found ')' at 1:1")),
+            ("  )", Malformed("expected expression but found ')' at 1:3")),
+            (
+                "`",
+                Malformed("expected expression but found atom \"`\" at 1:1"),
+            ),
+            (
+                ",",
+                Malformed("expected expression but found atom \",\" at 1:1"),
+            ),
+            (
+                "#;",
+                Malformed("expected expression but found atom \"#;\" at 1:1"),
+            ),
+            (
+                "1 #z",
+                Malformed("expected expression but found atom \"#z\" at 1:3"),
+            ),
+        ];
+
+        for (source, class) in table {
+            match (parse(source, true), class) {
+                (Ok(Expr::Sequence(ref forms)), Empty) => assert!(forms.is_empty(), "{source:?}"),
+                (Err(Error::Incomplete { .. }), Incomplete) => {}
+                (Err(err), Malformed(message)) => {
+                    assert!(!matches!(err, Error::Incomplete { .. }), "{source:?}");
+                    assert_eq!(err.to_string(), message, "{source:?}");
+                }
+                (other, _) => panic!("unexpected result for {source:?}: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fold_case() {
+        let folded = ParseOptions { fold_case: true };
+        let expr = parse_with_options("(DEFINE Xy 3)", false, &folded).expect("parse failed");
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0].as_ident(), Some("define"));
+        assert_eq!(list[1].as_ident(), Some("xy"));
+
+        // Strings and characters keep their case.
+        let expr = parse_with_options(r#"("ABC" #\A)"#, false, &folded).expect("parse failed");
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0], Expr::String("ABC".into()));
+        assert_eq!(list[1], Expr::Char('A'));
+
+        let expr = parse("(DEFINE Xy 3)", false).expect("parse failed");
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0].as_ident(), Some("DEFINE"));
+    }
+
+    #[test]
+    fn test_fold_case_directives() {
+        let source = "ONE #!fold-case TWO (THREE #!no-fold-case FOUR) FIVE";
+        let expr = parse(source, true).expect("parse failed");
+        let names: Vec<&str> = expr
+            .as_slice()
+            .unwrap()
+            .iter()
+            .flat_map(|expr| match expr {
+                Expr::List(list) => list.iter().filter_map(Expr::as_ident).collect(),
+                expr => expr.as_ident().into_iter().collect::<Vec<_>>(),
+            })
+            .collect();
+        assert_eq!(names, ["ONE", "two", "three", "FOUR", "FIVE"]);
+
+        // A directive alone produces no expression.
+        let expr = parse("#!fold-case", true).expect("parse failed");
+        assert_eq!(expr.as_slice().map(<[Expr]>::len), Some(0));
+    }
+
+    #[test]
+    fn test_bytevector() {
+        let expr = parse("(#u8(0 255 16) #u8())", false).expect("parse failed");
+        let list = expr.as_slice().unwrap();
+        assert_eq!(list[0].as_bytes().as_deref(), Some(&[0, 255, 16][..]));
+        assert_eq!(list[1].as_bytes().as_deref(), Some(&[][..]));
+
+        let message = |source: &str| parse(source, true).unwrap_err().to_string();
+        assert_eq!(
+            message("#u8(1 256)"),
+            "expected byte between 0 and 255 but found atom \"256\" at 1:7"
+        );
+        assert_eq!(
+            message("#u8(1 -1)"),
+            "expected byte between 0 and 255 but found atom \"-1\" at 1:7"
+        );
+        assert_eq!(
+            message("#u8(1.5)"),
+            "expected byte between 0 and 255 but found atom \"1.5\" at 1:5"
+        );
+        assert_eq!(
+            message("#u8((1))"),
+            "expected byte between 0 and 255 but found '(' at 1:5"
+        );
+        assert_eq!(
+            message("#u8(1 2"),
+            "expected ')' but found end-of-file at 1:8"
+        );
+    }
+
+    #[test]
+    fn test_vector() {
+        let expr = parse("#(1 (a) #(\"x\") #u8(2))", false).expect("parse failed");
+        let vector = expr.as_vector().unwrap();
+        assert_eq!(vector.len(), 4);
+        assert_eq!(vector[0], Expr::Number(1.0));
+        assert!(vector[2].is_equal(&Expr::from(vec![Expr::String("x".into())])));
+        assert_eq!(vector[3].as_bytes().as_deref(), Some(&[2][..]));
+
+        assert_eq!(
+            parse("#(1 2", true).unwrap_err().to_string(),
+            "expected ')' but found end-of-file at 1:6"
+        );
+    }
+
+    #[test]
+    fn test_dotted_list() {
+        let expr = parse("(1 2 . 3)", false).expect("parse failed");
+        assert_eq!(
+            expr.as_slice().unwrap(),
+            &[
+                Expr::Number(1.0),
+                Expr::Number(2.0),
+                Expr::Keyword(Keyword::Dot),
+                Expr::Number(3.0)
+            ]
+        );
+
+        let message = |source: &str| parse(source, true).unwrap_err().to_string();
+        assert_eq!(
+            message("(. 1)"),
+            "expected datum before '.' but found atom \".\" at 1:2"
+        );
+        assert_eq!(
+            message("(1 .)"),
+            "expected datum after '.' but found ')' at 1:5"
+        );
+        assert_eq!(
+            message("(1 . 2 3)"),
+            "expected ')' after the tail of a dotted list but found atom \"3\" at 1:8"
+        );
+        assert_eq!(
+            message("(1 . 2"),
+            "expected ')' but found end-of-file at 1:7"
+        );
+    }
+
     #[test]
     fn test_sequence() {
         let source = r#"
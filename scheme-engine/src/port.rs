@@ -0,0 +1,234 @@
+//! Ports, the sources and destinations of characters.
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::error::{Error, Result};
+use crate::expr::Expr;
+use crate::parser;
+
+/// A first-class source or destination of characters.
+///
+/// Input ports read from text loaded when the port was opened. Output ports
+/// write through to their destination. Once closed, a port can't be used
+/// for reading or writing.
+pub struct Port {
+    /// Describes where the port reads or writes, like a file path.
+    name: String,
+    /// The open port, or `None` after it was closed.
+    inner: Option<PortInner>,
+    input: bool,
+}
+
+enum PortInner {
+    /// Text read from the start, up to `pos` bytes in.
+    Text {
+        text: String,
+        pos: usize,
+    },
+    File(BufWriter<File>),
+    /// Text written so far, kept in memory.
+    Buffer(String),
+}
+
+impl Port {
+    /// An input port reading the given text.
+    pub fn input_text(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            inner: Some(PortInner::Text {
+                text: text.into(),
+                pos: 0,
+            }),
+            input: true,
+        }
+    }
+
+    /// An output port writing to a file.
+    pub fn output_file(name: impl Into<String>, file: File) -> Self {
+        Self {
+            name: name.into(),
+            inner: Some(PortInner::File(BufWriter::new(file))),
+            input: false,
+        }
+    }
+
+    /// An output port collecting the written text in memory.
+    ///
+    /// See [`Port::output_text`].
+    pub fn output_string(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            inner: Some(PortInner::Buffer(String::new())),
+            input: false,
+        }
+    }
+
+    /// Describes where the port reads or writes.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_input(&self) -> bool {
+        self.input
+    }
+
+    pub fn is_output(&self) -> bool {
+        !self.input
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// The next line without its line ending, or `None` at the end of input.
+    pub fn read_line(&mut self) -> Result<Option<String>> {
+        let (text, pos) = self.text_mut()?;
+        let rest = &text[*pos..];
+        if rest.is_empty() {
+            return Ok(None);
+        }
+
+        let (line, consumed) = match rest.find('\n') {
+            Some(index) => (&rest[..index], index + 1),
+            None => (rest, rest.len()),
+        };
+        let line = line.strip_suffix('\r').unwrap_or(line).to_string();
+        *pos += consumed;
+        Ok(Some(line))
+    }
+
+    /// The next character, or `None` at the end of input.
+    pub fn read_char(&mut self) -> Result<Option<char>> {
+        let (text, pos) = self.text_mut()?;
+        let ch = text[*pos..].chars().next();
+        if let Some(ch) = ch {
+            *pos += ch.len_utf8();
+        }
+        Ok(ch)
+    }
+
+    /// The next datum, as written in source code, or `None` at the end of input.
+    ///
+    /// The datum is returned as parsed, so dotted lists hold a
+    /// [`Keyword::Dot`](crate::Keyword::Dot) before their tail.
+    pub fn read_datum(&mut self) -> Result<Option<Expr>> {
+        let (text, pos) = self.text_mut()?;
+        match parser::read_datum(&text[*pos..])? {
+            Some((datum, consumed)) => {
+                *pos += consumed;
+                Ok(Some(datum))
+            }
+            None => {
+                *pos = text.len();
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn write_str(&mut self, text: &str) -> Result<()> {
+        match &mut self.inner {
+            Some(PortInner::File(file)) => file
+                .write_all(text.as_bytes())
+                .map_err(|err| Error::Reason(format!("{}: {err}", self.name))),
+            Some(PortInner::Buffer(buffer)) => {
+                buffer.push_str(text);
+                Ok(())
+            }
+            Some(PortInner::Text { .. }) => Err(port_error(&self.name, "not an output port")),
+            None => Err(port_error(&self.name, "port is closed")),
+        }
+    }
+
+    /// The text written so far to a port opened with [`Port::output_string`].
+    pub fn output_text(&self) -> Result<&str> {
+        match &self.inner {
+            Some(PortInner::Buffer(buffer)) => Ok(buffer),
+            Some(_) => Err(port_error(&self.name, "not a string output port")),
+            None => Err(port_error(&self.name, "port is closed")),
+        }
+    }
+
+    /// Close the port, flushing any buffered output. Closing twice does nothing.
+    pub fn close(&mut self) -> Result<()> {
+        match self.inner.take() {
+            Some(PortInner::File(mut file)) => file
+                .flush()
+                .map_err(|err| Error::Reason(format!("{}: {err}", self.name))),
+            _ => Ok(()),
+        }
+    }
+
+    fn text_mut(&mut self) -> Result<(&str, &mut usize)> {
+        match &mut self.inner {
+            Some(PortInner::Text { text, pos }) => Ok((text, pos)),
+            Some(PortInner::File(_) | PortInner::Buffer(_)) => {
+                Err(port_error(&self.name, "not an input port"))
+            }
+            None => Err(port_error(&self.name, "port is closed")),
+        }
+    }
+}
+
+fn port_error(name: &str, reason: &str) -> Error {
+    Error::Reason(format!("{reason}: {name}"))
+}
+
+impl fmt::Debug for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Port")
+            .field("name", &self.name)
+            .field("input", &self.input)
+            .field("open", &self.is_open())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_lines() {
+        let mut port = Port::input_text("lines", "one\r\ntwo\n\nthree");
+        assert_eq!(port.read_line().unwrap().as_deref(), Some("one"));
+        assert_eq!(port.read_char().unwrap(), Some('t'));
+        assert_eq!(port.read_line().unwrap().as_deref(), Some("wo"));
+        assert_eq!(port.read_line().unwrap().as_deref(), Some(""));
+        assert_eq!(port.read_line().unwrap().as_deref(), Some("three"));
+        assert_eq!(port.read_line().unwrap(), None);
+        assert_eq!(port.read_char().unwrap(), None);
+
+        port.close().unwrap();
+        assert!(!port.is_open());
+        assert_eq!(
+            port.read_line().unwrap_err().to_string(),
+            "port is closed: lines"
+        );
+    }
+
+    #[test]
+    fn test_read_datums() {
+        let mut port = Port::input_text("string", " (1 2) three ; rest\n");
+        let first = port.read_datum().unwrap().unwrap();
+        assert_eq!(first.repr().to_string(), "(1 2)");
+        assert_eq!(
+            port.read_datum().unwrap(),
+            Some(Expr::Ident("three".into()))
+        );
+        assert_eq!(port.read_datum().unwrap(), None);
+        assert_eq!(port.read_char().unwrap(), None);
+    }
+
+    #[test]
+    fn test_output_string() {
+        let mut port = Port::output_string("string");
+        port.write_str("one").unwrap();
+        port.write_str(" two").unwrap();
+        assert_eq!(port.output_text().unwrap(), "one two");
+        assert_eq!(
+            port.read_char().unwrap_err().to_string(),
+            "not an input port: string"
+        );
+    }
+}
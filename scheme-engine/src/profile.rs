@@ -0,0 +1,96 @@
+//! Per-procedure call and instruction counts.
+use crate::env::Env;
+use crate::expr::{Closure, Expr, Proc};
+use crate::handle::Handle;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// What one procedure cost during a profiled evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcProfile {
+    /// Name of the global variable holding the procedure, `top-level` for
+    /// the evaluated program, or `lambda` for anonymous procedures.
+    pub name: String,
+    /// Number of times the procedure was called.
+    pub calls: u64,
+    /// Number of instructions executed while the procedure's frame was
+    /// on top of the call stack, excluding the procedures it called.
+    pub instructions: u64,
+}
+
+/// Counts kept by the machine while profiling.
+///
+/// Instructions are attributed when control switches frames, using the
+/// machine's instruction counter, so the instruction loop itself is
+/// untouched.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    /// Procedures in order of first call. The `Rc` keeps each
+    /// address in the index from being reused.
+    entries: Vec<(Rc<Proc>, u64, u64)>,
+    index: HashMap<*const Proc, usize>,
+    /// The machine's instruction counter at the last frame switch.
+    last_switch: u64,
+}
+
+impl Profiler {
+    /// Count a call to the closure, which is about to get the top frame.
+    pub(crate) fn enter(&mut self, closure: &Handle<Closure>, instructions: u64) {
+        self.entry(closure).1 += 1;
+        self.last_switch = instructions;
+    }
+
+    /// Attribute the instructions since the last switch to the closure
+    /// losing the top frame.
+    pub(crate) fn leave(&mut self, closure: &Handle<Closure>, instructions: u64) {
+        let elapsed = instructions - self.last_switch;
+        self.entry(closure).2 += elapsed;
+        self.last_switch = instructions;
+    }
+
+    fn entry(&mut self, closure: &Handle<Closure>) -> &mut (Rc<Proc>, u64, u64) {
+        let proc = closure.borrow().procedure_rc();
+        let index = *self.index.entry(Rc::as_ptr(&proc)).or_insert_with(|| {
+            self.entries.push((proc, 0, 0));
+            self.entries.len() - 1
+        });
+        &mut self.entries[index]
+    }
+
+    /// The counts, named after the global variables holding each procedure,
+    /// with the most instructions first.
+    pub(crate) fn finish(self, env: &Env, top_level: &Closure) -> Vec<ProcProfile> {
+        let mut names: HashMap<*const Proc, &str> = HashMap::new();
+        for (name, value) in env.iter_vars_sorted() {
+            if let Expr::Closure(closure) = value {
+                let proc = closure.borrow().procedure_rc();
+                names.entry(Rc::as_ptr(&proc)).or_insert(name);
+            }
+        }
+        let top_level = top_level.procedure() as *const Proc;
+
+        let mut profiles: Vec<ProcProfile> = self
+            .entries
+            .into_iter()
+            .map(|(proc, calls, instructions)| {
+                let ptr = Rc::as_ptr(&proc);
+                let name = match names.get(&ptr) {
+                    Some(name) => name.to_string(),
+                    None if ptr == top_level => "top-level".to_string(),
+                    None => "lambda".to_string(),
+                };
+                ProcProfile {
+                    name,
+                    calls,
+                    instructions,
+                }
+            })
+            .collect();
+        profiles.sort_by(|a, b| {
+            b.instructions
+                .cmp(&a.instructions)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        profiles
+    }
+}
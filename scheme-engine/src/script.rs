@@ -0,0 +1,108 @@
+//! Running untrusted scripts one top-level form at a time.
+use std::time::{Duration, Instant};
+
+use crate::compiler::compile;
+use crate::env::Env;
+use crate::error::{Error, Limit, Result};
+use crate::expr::Expr;
+use crate::handle::Handle;
+use crate::parser::parse_program;
+use crate::vm::{eval_with_options, EvalOptions};
+
+/// Budgets for [`run_script`]. Every limit is off by default.
+#[derive(Debug, Clone, Default)]
+pub struct RunLimits {
+    /// Instructions each form may execute. See [`EvalOptions::fuel`].
+    pub fuel_per_form: Option<u64>,
+
+    /// Time each form may take to evaluate, checked as often as fuel
+    /// every [`DEADLINE_INTERVAL`](crate::DEADLINE_INTERVAL) instructions.
+    pub wall_clock_per_form: Option<Duration>,
+
+    /// Number of forms to run. The rest are skipped.
+    pub max_total_forms: Option<usize>,
+
+    /// Skip the remaining forms once one runs out of a budget,
+    /// instead of carrying on with the next.
+    pub abort_on_limit: bool,
+}
+
+/// The outcome of one top-level form of a script.
+#[derive(Debug)]
+pub enum FormResult {
+    /// The form evaluated to this value.
+    Value(Expr),
+    /// The form failed to compile or raised an error.
+    Error(Error),
+    /// The form ran out of a budget of [`RunLimits`].
+    Limited(Limit),
+    /// The form wasn't run, because of [`RunLimits::max_total_forms`]
+    /// or [`RunLimits::abort_on_limit`].
+    Skipped,
+}
+
+/// Compile and evaluate each top-level form of a script on its own,
+/// with one result per form.
+///
+/// A form that fails or runs out of its budget doesn't stop the forms
+/// after it, which see the definitions made so far. A `define` whose
+/// value never finished leaves its variable unspecified.
+///
+/// Only a source that doesn't parse is an error, as no form can be
+/// told apart from the next.
+///
+/// ```
+/// use scheme_engine::{run_script, FormResult, Limit, RunLimits};
+///
+/// let env = scheme_engine::new_env()?;
+/// let limits = RunLimits {
+///     fuel_per_form: Some(10_000),
+///     ..RunLimits::default()
+/// };
+/// let results = run_script(&env, "(define (spin) (do () (#f))) (spin) 42", &limits)?;
+/// assert!(matches!(results[1], FormResult::Limited(Limit::Fuel(10_000))));
+/// assert!(matches!(results[2], FormResult::Value(_)));
+/// # Ok::<(), scheme_engine::Error>(())
+/// ```
+pub fn run_script(env: &Handle<Env>, source: &str, limits: &RunLimits) -> Result<Vec<FormResult>> {
+    let program = parse_program(source)?;
+    let forms = match &program {
+        Expr::Sequence(forms) => forms.as_slice(),
+        form => std::slice::from_ref(form),
+    };
+
+    let mut results = Vec::with_capacity(forms.len());
+    let mut aborted = false;
+    for (index, form) in forms.iter().enumerate() {
+        let over_total = limits.max_total_forms.is_some_and(|max| index >= max);
+        if aborted || over_total {
+            results.push(FormResult::Skipped);
+            continue;
+        }
+
+        let result = run_form(env, form, limits);
+        aborted = limits.abort_on_limit && matches!(result, FormResult::Limited(_));
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn run_form(env: &Handle<Env>, form: &Expr, limits: &RunLimits) -> FormResult {
+    let closure = match compile(env.clone(), form) {
+        Ok(closure) => closure,
+        Err(err) => return FormResult::Error(err),
+    };
+
+    let options = EvalOptions {
+        fuel: limits.fuel_per_form,
+        deadline: limits
+            .wall_clock_per_form
+            .map(|duration| Instant::now() + duration),
+        ..EvalOptions::default()
+    };
+    match eval_with_options(closure, &options) {
+        Ok(value) => FormResult::Value(value),
+        Err(Error::Limit(limit)) => FormResult::Limited(limit),
+        Err(err) => FormResult::Error(err),
+    }
+}
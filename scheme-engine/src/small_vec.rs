@@ -0,0 +1,158 @@
+//! Vector that keeps up to two elements inline.
+//!
+//! Closures and call frames each hold a list of up-values, and most of
+//! those lists are empty or have one or two entries. Keeping them inline
+//! saves an allocation for every closure created, which adds up when
+//! callbacks are made in a loop. Longer lists move to the heap.
+//!
+//! Only the parts of the [`Vec`] API that the virtual machine uses are
+//! here. The contents are reached as a slice through [`Deref`].
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// A vector of up to two elements without a heap allocation.
+pub(crate) struct SmallVec<T>(Repr<T>);
+
+enum Repr<T> {
+    Empty,
+    One([T; 1]),
+    Two([T; 2]),
+    Heap(Vec<T>),
+}
+
+impl<T> SmallVec<T> {
+    pub(crate) const fn new() -> Self {
+        Self(Repr::Empty)
+    }
+
+    pub(crate) fn push(&mut self, value: T) {
+        self.0 = match std::mem::replace(&mut self.0, Repr::Empty) {
+            Repr::Empty => Repr::One([value]),
+            Repr::One([first]) => Repr::Two([first, value]),
+            Repr::Two([first, second]) => Repr::Heap(vec![first, second, value]),
+            Repr::Heap(mut values) => {
+                values.push(value);
+                Repr::Heap(values)
+            }
+        };
+    }
+
+    /// Whether the elements are kept on the heap.
+    #[cfg(test)]
+    fn spilled(&self) -> bool {
+        matches!(self.0, Repr::Heap(_))
+    }
+}
+
+impl<T> Default for SmallVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Deref for SmallVec<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        match &self.0 {
+            Repr::Empty => &[],
+            Repr::One(values) => values,
+            Repr::Two(values) => values,
+            Repr::Heap(values) => values,
+        }
+    }
+}
+
+impl<T> DerefMut for SmallVec<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        match &mut self.0 {
+            Repr::Empty => &mut [],
+            Repr::One(values) => values,
+            Repr::Two(values) => values,
+            Repr::Heap(values) => values,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SmallVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> IntoIterator for SmallVec<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(match self.0 {
+            // An empty vector doesn't allocate.
+            Repr::Empty => IterRepr::Heap(Vec::new().into_iter()),
+            Repr::One(values) => IterRepr::One(values.into_iter()),
+            Repr::Two(values) => IterRepr::Two(values.into_iter()),
+            Repr::Heap(values) => IterRepr::Heap(values.into_iter()),
+        })
+    }
+}
+
+/// Owning iterator over the elements of a [`SmallVec`].
+pub(crate) struct IntoIter<T>(IterRepr<T>);
+
+enum IterRepr<T> {
+    One(std::array::IntoIter<T, 1>),
+    Two(std::array::IntoIter<T, 2>),
+    Heap(std::vec::IntoIter<T>),
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match &mut self.0 {
+            IterRepr::One(values) => values.next(),
+            IterRepr::Two(values) => values.next(),
+            IterRepr::Heap(values) => values.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_push_spills_after_two() {
+        let mut values = SmallVec::new();
+        assert!(values.is_empty());
+        for value in 0..5 {
+            values.push(value);
+            assert_eq!(*values, (0..=value).collect::<Vec<_>>()[..]);
+            assert_eq!(values.spilled(), value >= 2, "{value}");
+        }
+
+        values[3] = 30;
+        assert_eq!(values.last(), Some(&4));
+        assert_eq!(values.into_iter().collect::<Vec<_>>(), [0, 1, 2, 30, 4]);
+    }
+
+    #[test]
+    fn test_into_iter_drops_the_rest() {
+        for len in 0..4 {
+            let value = Rc::new(());
+            let mut values = SmallVec::new();
+            for _ in 0..len {
+                values.push(value.clone());
+            }
+            assert_eq!(format!("{values:?}"), format!("{:?}", vec![(); len]));
+
+            let mut iter = values.into_iter();
+            let first = iter.next();
+            assert_eq!(first.is_some(), len > 0);
+            drop(iter);
+            assert_eq!(Rc::strong_count(&value), 1 + first.iter().count(), "{len}");
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Registry of source texts, for locating errors across files.
+use smol_str::SmolStr;
+
+use crate::declare_id;
+use crate::error::{Error, Result};
+use crate::span::Span;
+
+declare_id!(
+    /// Source text identifier, unique within a [`SourceMap`].
+    pub struct SourceId(u16)
+);
+
+/// The source texts of the programs loaded into an environment, by name.
+///
+/// Parse errors in a registered source are wrapped in [`Error::InSource`],
+/// which [`SourceMap::render_error`] turns into `file:line:column` text.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Debug)]
+struct SourceFile {
+    name: String,
+    text: String,
+    /// The `define` forms of the source, in the order they start.
+    definitions: Vec<DefinitionSite>,
+}
+
+/// Where a `define` form starts in a source, so compile errors can
+/// say where the definition they happened in is.
+#[derive(Debug, Clone)]
+pub(crate) struct DefinitionSite {
+    /// Index of the top-level form the definition is in.
+    pub(crate) form: usize,
+    pub(crate) name: SmolStr,
+    /// Byte offset of the opening parenthesis.
+    pub(crate) offset: usize,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a source text under a name, like its file path.
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> Result<SourceId> {
+        let name = name.into();
+        let text = text.into();
+        if u32::try_from(text.len()).is_err() {
+            return Err(Error::Reason(format!(
+                "source {name} is larger than the 4 GiB limit"
+            )));
+        }
+        let id = u16::try_from(self.files.len())
+            .map_err(|_| Error::Reason("too many sources".to_string()))?;
+
+        self.files.push(SourceFile {
+            name,
+            text,
+            definitions: Vec::new(),
+        });
+        Ok(SourceId::new(id))
+    }
+
+    pub fn name(&self, id: SourceId) -> Option<&str> {
+        self.files.get(id.as_usize()).map(|file| file.name.as_str())
+    }
+
+    pub fn text(&self, id: SourceId) -> Option<&str> {
+        self.files.get(id.as_usize()).map(|file| file.text.as_str())
+    }
+
+    /// The most recently added source with the name.
+    pub(crate) fn find(&self, name: &str) -> Option<SourceId> {
+        let index = self.files.iter().rposition(|file| file.name == name)?;
+        Some(SourceId::new(index as u16))
+    }
+
+    /// Remember where the `define` forms of a source start, as
+    /// recorded while parsing it.
+    pub(crate) fn set_definitions(&mut self, id: SourceId, mut definitions: Vec<DefinitionSite>) {
+        if let Some(file) = self.files.get_mut(id.as_usize()) {
+            definitions.sort_by_key(|site| site.offset);
+            file.definitions = definitions;
+        }
+    }
+
+    /// Line and column where the innermost of nested definitions starts.
+    ///
+    /// The definitions are found by name in a top-level form, starting at
+    /// the outermost, each one after the one enclosing it. Names without a
+    /// `define` in the source, like those of definitions made by macros,
+    /// are skipped, but the innermost one must be found.
+    pub(crate) fn locate_definition(
+        &self,
+        id: SourceId,
+        form: usize,
+        names: &[&str],
+    ) -> Option<(usize, usize)> {
+        let file = self.files.get(id.as_usize())?;
+        let sites: Vec<&DefinitionSite> = file
+            .definitions
+            .iter()
+            .filter(|site| site.form == form)
+            .collect();
+
+        let mut rest = sites.as_slice();
+        let mut found = None;
+        for name in names {
+            found = rest
+                .iter()
+                .position(|site| site.name == *name)
+                .map(|index| {
+                    let site = rest[index];
+                    rest = &rest[index + 1..];
+                    site
+                });
+        }
+        found.map(|site| line_column(&file.text, site.offset))
+    }
+
+    /// The location of a span, like `main.scm:3:7`.
+    pub fn describe(&self, id: SourceId, span: &Span) -> String {
+        match self.files.get(id.as_usize()) {
+            Some(file) => {
+                let offset = span.low().min(file.text.len());
+                let (line, column) = line_column(&file.text, offset);
+                format!("{}:{line}:{column}", file.name)
+            }
+            None => format!("<unknown source {}>", id.as_usize()),
+        }
+    }
+
+    /// The error's message, prefixed by its location when it
+    /// happened in a registered source.
+    pub fn render_error(&self, err: &Error) -> String {
+        match err {
+            Error::InSource {
+                source_id,
+                span,
+                error,
+            } => format!("{}: {error}", self.describe(*source_id, span)),
+            _ => err.to_string(),
+        }
+    }
+}
+
+/// One-based line and column of a byte offset in the text.
+///
+/// Columns count characters, not bytes.
+pub(crate) fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
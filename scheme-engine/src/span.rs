@@ -2,33 +2,65 @@
 
 use std::ops::Range;
 
+/// Byte range in a source text.
+///
+/// Offsets are stored as `u32` to keep tokens small, which limits
+/// sources to 4 GiB.
 #[derive(Debug, Clone)]
 pub struct Span {
-    pub(crate) lo: usize, // inclusive
-    pub(crate) hi: usize, // exclusive
+    pub(crate) lo: u32, // inclusive
+    pub(crate) hi: u32, // exclusive
 }
 
 impl Span {
+    /// # Panics
+    ///
+    /// When the span ends past 4 GiB. See [`Span::try_new`].
     pub fn new(lo: usize, size: usize) -> Self {
-        Self { lo, hi: lo + size }
+        Self::try_new(lo, size).expect("span ends past the 4 GiB limit of source offsets")
+    }
+
+    /// A span, unless it ends past the 4 GiB limit of source offsets.
+    pub fn try_new(lo: usize, size: usize) -> Option<Self> {
+        let hi = lo.checked_add(size)?;
+        Some(Self {
+            lo: u32::try_from(lo).ok()?,
+            hi: u32::try_from(hi).ok()?,
+        })
     }
 
     #[inline(always)]
     pub fn low(&self) -> usize {
-        self.lo
+        self.lo as usize
     }
 
     #[inline(always)]
     pub fn high(&self) -> usize {
-        self.hi
+        self.hi as usize
     }
 
     #[inline(always)]
     pub fn size(&self) -> usize {
-        self.hi - self.lo
+        (self.hi - self.lo) as usize
     }
 
     pub fn as_range(&self) -> Range<usize> {
-        self.lo..self.hi
+        self.low()..self.high()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_span_limit() {
+        let span = Span::new(10, 5);
+        assert_eq!(span.as_range(), 10..15);
+
+        let last = u32::MAX as usize;
+        assert!(Span::try_new(last - 1, 1).is_some());
+        assert!(Span::try_new(last, 1).is_none());
+        assert!(Span::try_new(usize::MAX, 1).is_none());
     }
 }
@@ -1,53 +1,75 @@
+use std::collections::HashMap;
+
+use smol_str::SmolStr;
+
 use crate::declare_id;
 
 declare_id!(pub struct SymbolId(u16));
 
-#[derive(Debug)]
+/// Table of interned names.
+///
+/// Symbols are assigned densely in insertion order, starting at zero,
+/// so a [`SymbolId`] doubles as an index into parallel value tables.
+/// Iterating the table with [`SymbolTable::items`] yields the symbols
+/// in that same stable order.
+#[derive(Debug, Default, Clone)]
 pub struct SymbolTable {
-    symbols: Vec<String>,
+    /// Names in insertion order, indexed by [`SymbolId`].
+    symbols: Vec<SmolStr>,
+    /// Reverse lookup from name to symbol.
+    index: HashMap<SmolStr, SymbolId>,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
         Self {
             symbols: Vec::new(),
+            index: HashMap::new(),
         }
     }
 
     pub fn resolve(&self, name_query: impl AsRef<str>) -> Option<SymbolId> {
-        let name_query = name_query.as_ref();
-        self.symbols
-            .iter()
-            .position(|name| name.as_str() == name_query)
-            .map(|index| SymbolId(index as u16))
+        self.index.get(name_query.as_ref()).copied()
     }
 
-    pub fn intern_symbol(&mut self, name: impl ToString) -> SymbolId {
-        let name = name.to_string();
+    /// The name of the given symbol.
+    pub fn name(&self, symbol: SymbolId) -> Option<&str> {
+        self.symbols.get(symbol.as_usize()).map(SmolStr::as_str)
+    }
+
+    pub fn intern_symbol(&mut self, name: impl AsRef<str>) -> SymbolId {
+        let name = name.as_ref();
 
-        match self.resolve(name.as_str()) {
+        match self.resolve(name) {
             Some(symbol) => symbol,
-            None => {
-                let next_index = self.symbols.len();
-                self.symbols.push(name);
-                SymbolId(next_index as u16)
-            }
+            None => self.push(name),
         }
     }
 
-    pub fn insert_unique(&mut self, name: impl ToString) -> Option<SymbolId> {
-        let name = name.to_string();
+    pub fn insert_unique(&mut self, name: impl AsRef<str>) -> Option<SymbolId> {
+        let name = name.as_ref();
 
-        match self.resolve(name.as_str()) {
+        match self.resolve(name) {
             Some(_) => None,
-            None => {
-                let next_index = self.symbols.len();
-                self.symbols.push(name);
-                Some(SymbolId(next_index as u16))
-            }
+            None => Some(self.push(name)),
         }
     }
 
+    /// Append a new name without checking whether it already exists.
+    fn push(&mut self, name: &str) -> SymbolId {
+        let name = SmolStr::from(name);
+        let symbol = SymbolId(self.symbols.len() as u16);
+        self.symbols.push(name.clone());
+        self.index.insert(name, symbol);
+        symbol
+    }
+
+    /// Number of interned names.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Iterate the symbols in insertion order.
     pub fn items(&self) -> impl Iterator<Item = (SymbolId, &str)> {
         self.symbols
             .iter()
@@ -55,3 +77,74 @@ impl SymbolTable {
             .map(|(index, name)| (SymbolId(index as u16), name.as_str()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let mut table = SymbolTable::new();
+        assert_eq!(table.resolve("a"), None);
+
+        let a = table.intern_symbol("a");
+        let b = table.intern_symbol("b");
+        assert_eq!(table.resolve("a"), Some(a));
+        assert_eq!(table.resolve("b"), Some(b));
+        assert_eq!(table.resolve("c"), None);
+    }
+
+    #[test]
+    fn test_intern() {
+        let mut table = SymbolTable::new();
+
+        // Miss assigns symbols densely in insertion order.
+        let a = table.intern_symbol("a");
+        let b = table.intern_symbol("b");
+        assert_eq!(a, SymbolId(0));
+        assert_eq!(b, SymbolId(1));
+
+        // Hit returns the existing symbol.
+        assert_eq!(table.intern_symbol("a"), a);
+        assert_eq!(table.intern_symbol("b"), b);
+        assert_eq!(table.items().count(), 2);
+    }
+
+    #[test]
+    fn test_insert_unique() {
+        let mut table = SymbolTable::new();
+
+        assert_eq!(table.insert_unique("a"), Some(SymbolId(0)));
+        assert_eq!(table.insert_unique("b"), Some(SymbolId(1)));
+
+        // Hit is rejected, and doesn't consume an id.
+        assert_eq!(table.insert_unique("a"), None);
+        assert_eq!(table.insert_unique("c"), Some(SymbolId(2)));
+    }
+
+    #[test]
+    fn test_name() {
+        let mut table = SymbolTable::new();
+        let a = table.intern_symbol("a");
+        let b = table.intern_symbol("b");
+
+        assert_eq!(table.name(a), Some("a"));
+        assert_eq!(table.name(b), Some("b"));
+        assert_eq!(table.name(SymbolId(2)), None);
+    }
+
+    #[test]
+    fn test_items_order() {
+        let mut table = SymbolTable::new();
+        for name in ["c", "a", "b"] {
+            table.intern_symbol(name);
+        }
+        table.intern_symbol("a");
+
+        let items: Vec<_> = table.items().collect();
+        assert_eq!(
+            items,
+            vec![(SymbolId(0), "c"), (SymbolId(1), "a"), (SymbolId(2), "b")]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! Pattern matching macros of `syntax-rules`.
+//!
+//! A transformer is a list of rules, each a pattern and a template:
+//!
+//! ```scheme
+//! (syntax-rules (<literal> ...) (<pattern> <template>) ...)
+//! (syntax-rules <ellipsis> (<literal> ...) (<pattern> <template>) ...)
+//! ```
+//!
+//! A use of the macro is rewritten with the template of the first rule
+//! whose pattern matches it. The keyword at the start of a pattern is
+//! ignored. In the rest of the pattern, `_` matches anything, a literal
+//! matches the same identifier, and any other identifier is a pattern
+//! variable, matching anything and standing for it in the template. A
+//! pattern followed by `...` matches zero or more elements, and so does
+//! a template followed by it, once per element the pattern matched.
+//! `(... ...)` in a template is a literal `...`.
+//!
+//! Macros aren't hygienic: identifiers the template introduces can be
+//! captured by bindings around the use, and the other way around.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use smol_str::SmolStr;
+
+use crate::error::{Error, Result};
+use crate::expr::{Expr, Keyword};
+
+/// The rules of a `syntax-rules` transformer.
+#[derive(Debug)]
+pub(crate) struct SyntaxRules {
+    ellipsis: SmolStr,
+    literals: Vec<SmolStr>,
+    /// Patterns, without their keyword, and the templates they're rewritten to.
+    rules: Vec<(Expr, Expr)>,
+}
+
+/// What a pattern variable matched.
+#[derive(Debug, Clone)]
+enum Binding {
+    One(Expr),
+    /// The matches of a pattern followed by an ellipsis.
+    Many(Vec<Binding>),
+}
+
+type Bindings = HashMap<SmolStr, Binding>;
+
+impl SyntaxRules {
+    /// The transformer of the macro `keyword`, from its specification.
+    pub(crate) fn new(keyword: &str, spec: &Expr) -> Result<Self> {
+        let ill_formed = || {
+            Error::Reason(format!(
+                "expected syntax-rules transformer for {keyword}, found {}",
+                spec.write_repr()
+            ))
+        };
+
+        let (ellipsis, literals, rules) = match spec.as_slice() {
+            Some([Expr::Ident(head), Expr::Ident(ellipsis), literals, rules @ ..])
+                if head == "syntax-rules" =>
+            {
+                (ellipsis.clone(), literals, rules)
+            }
+            Some([Expr::Ident(head), literals, rules @ ..]) if head == "syntax-rules" => {
+                (SmolStr::new("..."), literals, rules)
+            }
+            _ => return Err(ill_formed()),
+        };
+
+        let literals = match literals {
+            Expr::Nil => Vec::new(),
+            Expr::List(list) => list
+                .iter()
+                .map(|literal| match literal {
+                    Expr::Ident(name) => Ok(name.clone()),
+                    _ => Err(ill_formed()),
+                })
+                .collect::<Result<_>>()?,
+            _ => return Err(ill_formed()),
+        };
+
+        let rules = rules
+            .iter()
+            .map(|rule| match rule.as_slice() {
+                Some([Expr::List(pattern), template]) => {
+                    Ok((list_or_nil(pattern[1..].to_vec()), template.clone()))
+                }
+                _ => Err(ill_formed()),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            ellipsis,
+            literals,
+            rules,
+        })
+    }
+
+    /// Rewrite a use of the macro `keyword`, a list starting with it.
+    pub(crate) fn apply(&self, keyword: &str, form: &[Expr]) -> Result<Expr> {
+        let operands = list_or_nil(form[1..].to_vec());
+        for (pattern, template) in &self.rules {
+            let mut bindings = Bindings::new();
+            if self.matches(pattern, &operands, &mut bindings) {
+                return self.instantiate(template, &bindings);
+            }
+        }
+
+        Err(Error::Reason(format!(
+            "no syntax-rules pattern of {keyword} matches {}",
+            Expr::List(form.into()).write_repr()
+        )))
+    }
+
+    fn is_ellipsis(&self, expr: &Expr) -> bool {
+        matches!(expr, Expr::Ident(name) if *name == self.ellipsis)
+    }
+
+    fn matches(&self, pattern: &Expr, input: &Expr, bindings: &mut Bindings) -> bool {
+        match pattern {
+            Expr::Ident(name) if name == "_" => true,
+            Expr::Ident(name) if self.literals.contains(name) => {
+                matches!(input, Expr::Ident(other) if other == name)
+            }
+            Expr::Ident(name) => {
+                bindings.insert(name.clone(), Binding::One(input.clone()));
+                true
+            }
+            Expr::Quote(pattern) => match input {
+                Expr::Quote(input) => self.matches(pattern, input, bindings),
+                _ => false,
+            },
+            Expr::Nil | Expr::List(_) => match (split_list(pattern), split_list(input)) {
+                (Some(pattern), Some(input)) => self.matches_list(pattern, input, bindings),
+                _ => false,
+            },
+            datum => datum == input,
+        }
+    }
+
+    fn matches_list(
+        &self,
+        (patterns, pattern_tail): (&[Expr], Option<&Expr>),
+        (inputs, input_tail): (&[Expr], Option<&Expr>),
+        bindings: &mut Bindings,
+    ) -> bool {
+        let ellipsis = patterns
+            .iter()
+            .position(|pattern| self.is_ellipsis(pattern));
+        let (before, repeated, after) = match ellipsis {
+            Some(index) if index > 0 => (
+                &patterns[..index - 1],
+                Some(&patterns[index - 1]),
+                &patterns[index + 1..],
+            ),
+            _ => (patterns, None, &[][..]),
+        };
+
+        let fixed = before.len() + after.len();
+        let enough = match (repeated, pattern_tail) {
+            (None, None) => inputs.len() == fixed && input_tail.is_none(),
+            (Some(_), None) => inputs.len() >= fixed && input_tail.is_none(),
+            (_, Some(_)) => inputs.len() >= fixed,
+        };
+        if !enough {
+            return false;
+        }
+
+        // Elements the repeated pattern doesn't take are left for the tail.
+        let repeats = match (repeated, pattern_tail) {
+            (Some(_), _) => inputs.len() - fixed,
+            (None, _) => 0,
+        };
+        let (head, rest) = inputs.split_at(before.len());
+        let (middle, rest) = rest.split_at(repeats);
+        let (last, rest) = rest.split_at(after.len().min(rest.len()));
+
+        for (pattern, input) in before.iter().zip(head).chain(after.iter().zip(last)) {
+            if !self.matches(pattern, input, bindings) {
+                return false;
+            }
+        }
+
+        if let Some(repeated) = repeated {
+            let mut matches = Vec::new();
+            for input in middle {
+                let mut repeat = Bindings::new();
+                if !self.matches(repeated, input, &mut repeat) {
+                    return false;
+                }
+                matches.push(repeat);
+            }
+            for name in self.variables(repeated) {
+                let each = matches
+                    .iter_mut()
+                    .map(|repeat| repeat.remove(&name).expect("variable of the pattern"))
+                    .collect();
+                bindings.insert(name, Binding::Many(each));
+            }
+        }
+
+        match pattern_tail {
+            Some(tail) => {
+                let mut rest = rest.to_vec();
+                let tail_input = match input_tail {
+                    Some(tail) if rest.is_empty() => tail.clone(),
+                    Some(tail) => {
+                        rest.push(Expr::Keyword(Keyword::Dot));
+                        rest.push(tail.clone());
+                        list_or_nil(rest)
+                    }
+                    None => list_or_nil(rest),
+                };
+                self.matches(tail, &tail_input, bindings)
+            }
+            None => true,
+        }
+    }
+
+    /// The pattern variables of a pattern.
+    fn variables(&self, pattern: &Expr) -> Vec<SmolStr> {
+        match pattern {
+            Expr::Ident(name)
+                if name != "_" && *name != self.ellipsis && !self.literals.contains(name) =>
+            {
+                vec![name.clone()]
+            }
+            Expr::Quote(pattern) => self.variables(pattern),
+            Expr::List(list) => list
+                .iter()
+                .flat_map(|pattern| self.variables(pattern))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn instantiate(&self, template: &Expr, bindings: &Bindings) -> Result<Expr> {
+        match template {
+            Expr::Ident(name) => match bindings.get(name) {
+                Some(Binding::One(expr)) => Ok(expr.clone()),
+                Some(Binding::Many(_)) => Err(Error::Reason(format!(
+                    "syntax-rules: pattern variable {name} must be followed by {} in the template",
+                    self.ellipsis
+                ))),
+                None => Ok(template.clone()),
+            },
+            Expr::Quote(template) => {
+                Ok(Expr::Quote(Box::new(self.instantiate(template, bindings)?)))
+            }
+            Expr::List(list) => match &list[..] {
+                // An escaped ellipsis, which is left as is.
+                [escape, template] if self.is_ellipsis(escape) => Ok(template.clone()),
+                list => self.instantiate_list(list, bindings),
+            },
+            _ => Ok(template.clone()),
+        }
+    }
+
+    fn instantiate_list(&self, templates: &[Expr], bindings: &Bindings) -> Result<Expr> {
+        let mut elements = Vec::new();
+        let mut index = 0;
+        while index < templates.len() {
+            let template = &templates[index];
+            index += 1;
+
+            if let Expr::Keyword(Keyword::Dot) = template {
+                let tail = match templates.get(index) {
+                    Some(tail) => self.instantiate(tail, bindings)?,
+                    None => Expr::Nil,
+                };
+                // A tail that's a list continues this one.
+                match split_list(&tail) {
+                    Some((rest, tail)) => {
+                        elements.extend(rest.iter().cloned());
+                        if let Some(tail) = tail {
+                            elements.push(Expr::Keyword(Keyword::Dot));
+                            elements.push(tail.clone());
+                        }
+                    }
+                    None => {
+                        elements.push(Expr::Keyword(Keyword::Dot));
+                        elements.push(tail);
+                    }
+                }
+                break;
+            }
+
+            let mut depth = 0;
+            while templates
+                .get(index)
+                .is_some_and(|next| self.is_ellipsis(next))
+            {
+                depth += 1;
+                index += 1;
+            }
+            if depth == 0 {
+                elements.push(self.instantiate(template, bindings)?);
+            } else {
+                self.instantiate_repeated(template, bindings, depth, &mut elements)?;
+            }
+        }
+
+        Ok(list_or_nil(elements))
+    }
+
+    /// Instantiate a template followed by `depth` ellipses, once for each
+    /// match of the repeated pattern variables in it.
+    fn instantiate_repeated(
+        &self,
+        template: &Expr,
+        bindings: &Bindings,
+        depth: usize,
+        elements: &mut Vec<Expr>,
+    ) -> Result<()> {
+        let repeated: Vec<(&SmolStr, &Vec<Binding>)> = self
+            .variables(template)
+            .iter()
+            .filter_map(|name| match bindings.get_key_value(name) {
+                Some((name, Binding::Many(each))) => Some((name, each)),
+                _ => None,
+            })
+            .collect();
+
+        let Some((_, first)) = repeated.first() else {
+            return Err(Error::Reason(format!(
+                "syntax-rules: template followed by {} has no pattern variable that repeats: {}",
+                self.ellipsis,
+                template.write_repr()
+            )));
+        };
+        let count = first.len();
+        if repeated.iter().any(|(_, each)| each.len() != count) {
+            return Err(Error::Reason(format!(
+                "syntax-rules: pattern variables of the template repeat a different number of times: {}",
+                template.write_repr()
+            )));
+        }
+
+        for index in 0..count {
+            let mut repeat = bindings.clone();
+            for (name, each) in &repeated {
+                repeat.insert((*name).clone(), each[index].clone());
+            }
+            if depth == 1 {
+                elements.push(self.instantiate(template, &repeat)?);
+            } else {
+                self.instantiate_repeated(template, &repeat, depth - 1, elements)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The elements of a list, and the tail of a dotted list, or `None`
+/// when the expression isn't a list.
+fn split_list(expr: &Expr) -> Option<(&[Expr], Option<&Expr>)> {
+    match expr {
+        Expr::Nil => Some((&[], None)),
+        Expr::List(list) => match &list[..] {
+            [elements @ .., Expr::Keyword(Keyword::Dot), tail] => Some((elements, Some(tail))),
+            elements => Some((elements, None)),
+        },
+        _ => None,
+    }
+}
+
+fn list_or_nil(elements: Vec<Expr>) -> Expr {
+    if elements.is_empty() {
+        Expr::Nil
+    } else {
+        Expr::List(Rc::from(elements))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    /// Expand a use of the macro defined by the transformer.
+    fn apply(transformer: &str, form: &str) -> Result<String> {
+        let spec = parse(transformer, false)?;
+        let form = parse(form, false)?;
+        let rules = SyntaxRules::new("m", &spec)?;
+        let expanded = rules.apply("m", form.as_slice().unwrap())?;
+        Ok(expanded.write_repr().to_string())
+    }
+
+    #[test]
+    fn test_apply() {
+        let table = [
+            ("(syntax-rules () ((_) 1))", "(m)", "1"),
+            (
+                "(syntax-rules () ((_ a b) (b a)))",
+                "(m 1 (f 2))",
+                "((f 2) 1)",
+            ),
+            (
+                "(syntax-rules (=>) ((_ a => b) (b a)) ((_ a b) (list a b)))",
+                "(m 1 2)",
+                "(list 1 2)",
+            ),
+            (
+                "(syntax-rules () ((_ (name value) ...) '((name . value) ...)))",
+                "(m (a 1) (b 2))",
+                "'((a . 1) (b . 2))",
+            ),
+            (
+                "(syntax-rules () ((_ first rest ... last) (list last rest ... first)))",
+                "(m 1 2 3 4)",
+                "(list 4 2 3 1)",
+            ),
+            (
+                "(syntax-rules () ((_ a . rest) (f . rest)))",
+                "(m 1 2 3)",
+                "(f 2 3)",
+            ),
+            (
+                "(syntax-rules () ((_ (a ...) ...) (+ a ... ...)))",
+                "(m (1 2) () (3))",
+                "(+ 1 2 3)",
+            ),
+            (
+                "(syntax-rules ::: () ((_ a :::) (f (::: :::) ... a :::)))",
+                "(m 1 2)",
+                "(f ::: ... 1 2)",
+            ),
+        ];
+
+        for (transformer, form, expected) in table {
+            assert_eq!(apply(transformer, form).unwrap(), expected, "{form}");
+        }
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let table = [
+            (
+                "(syntax-rules () ((_ a) a))",
+                "(m 1 2)",
+                "no syntax-rules pattern of m matches (m 1 2)",
+            ),
+            (
+                "(syntax-rules () ((_ a ...) a))",
+                "(m 1 2)",
+                "syntax-rules: pattern variable a must be followed by ... in the template",
+            ),
+            (
+                "(syntax-rules)",
+                "(m)",
+                "expected syntax-rules transformer for m, found (syntax-rules)",
+            ),
+        ];
+
+        for (transformer, form, expected) in table {
+            assert_eq!(apply(transformer, form).unwrap_err().to_string(), expected);
+        }
+    }
+}
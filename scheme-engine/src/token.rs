@@ -3,14 +3,57 @@
 use crate::span::Span;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenKind {
     LeftParen,
     RightParen,
+    /// Opening of a bytevector literal, `#u8(`.
+    BytevectorOpen,
+    /// Opening of a vector literal, `#(`.
+    VectorOpen,
     Atom,
+    /// String literal, including the enclosing double quotes.
+    String,
+    /// Character literal, including the `#\` prefix.
+    Char,
     QuoteMark,
+    /// Comment from `;` to the end of the line, excluding the newline.
+    LineComment,
+    /// Comment between `#|` and `|#`, which may be nested.
+    BlockComment,
+    /// A run of whitespace characters.
+    Whitespace,
     EOF,
 }
 
+impl TokenKind {
+    /// Comments and whitespace, only emitted by a lexer in trivia mode.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            Self::LineComment | Self::BlockComment | Self::Whitespace
+        )
+    }
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LeftParen => write!(f, "'('"),
+            Self::RightParen => write!(f, "')'"),
+            Self::BytevectorOpen => write!(f, "'#u8('"),
+            Self::VectorOpen => write!(f, "'#('"),
+            Self::Atom => write!(f, "atom"),
+            Self::String => write!(f, "string"),
+            Self::Char => write!(f, "character"),
+            Self::QuoteMark => write!(f, "quote"),
+            Self::LineComment | Self::BlockComment => write!(f, "comment"),
+            Self::Whitespace => write!(f, "whitespace"),
+            Self::EOF => write!(f, "end-of-file"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
@@ -18,7 +61,19 @@ pub struct Token {
 }
 
 impl Token {
+    /// The source text of the token.
+    ///
+    /// # Panics
+    ///
+    /// When the span is outside the source or splits a character,
+    /// because the token came from another source. See [`Token::try_fragment`].
     pub fn fragment<'a>(&self, source: &'a str) -> &'a str {
         &source[self.span.as_range()]
     }
+
+    /// The source text of the token, or `None` when the span isn't
+    /// a range of whole characters in the source.
+    pub fn try_fragment<'a>(&self, source: &'a str) -> Option<&'a str> {
+        source.get(self.span.as_range())
+    }
 }
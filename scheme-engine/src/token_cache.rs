@@ -0,0 +1,124 @@
+//! Incremental lexing, for editors.
+use crate::lexer::Lexer;
+use crate::span::Span;
+use crate::token::Token;
+use std::ops::Range;
+
+/// A change to the source: the bytes in `range` are replaced.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    /// Byte range in the source before the edit.
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// Source text with its tokens, including trivia, kept up to date
+/// as the text is edited.
+///
+/// An edit re-lexes from the start of the token it touches until the
+/// new tokens line up with the old ones again, and reuses the rest.
+/// The result is always the same as lexing the whole source again.
+///
+/// ```
+/// use scheme_engine::{TextEdit, TokenCache};
+///
+/// let mut cache = TokenCache::new("(define a 1)");
+/// cache.update(TextEdit { range: 8..9, replacement: "abc".to_string() });
+/// assert_eq!(cache.source(), "(define abc 1)");
+/// assert_eq!(cache.tokens()[3].fragment(cache.source()), "abc");
+/// ```
+pub struct TokenCache {
+    source: String,
+    tokens: Vec<Token>,
+}
+
+impl TokenCache {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let tokens = Lexer::with_trivia(&source).into_iter().collect();
+        Self { source, tokens }
+    }
+
+    #[inline]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Tokens covering every byte of the source, ending with
+    /// an end-of-file token.
+    #[inline]
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Apply an edit to the source, and return the updated tokens.
+    ///
+    /// # Panics
+    ///
+    /// When the range is out of bounds, or doesn't fall on character boundaries.
+    pub fn update(&mut self, edit: TextEdit) -> &[Token] {
+        let TextEdit { range, replacement } = edit;
+        self.source.replace_range(range.clone(), &replacement);
+        let edit_end = range.start + replacement.len();
+
+        // Lexing depends only on the position it starts from, so it
+        // can restart at any old token boundary before the edit. The
+        // token just before the edit is included, because its end was
+        // decided by looking at the first edited character.
+        let before = range.start.saturating_sub(1);
+        let first = self
+            .tokens
+            .partition_point(|token| token.span.high() <= before);
+        let restart = self.tokens.get(first).map_or(0, |token| token.span.low());
+
+        // Old tokens after the edit, moved to their new position.
+        let shift = |lo: usize| lo - range.end + edit_end;
+        let mut old = self
+            .tokens
+            .partition_point(|token| token.span.low() < range.end);
+
+        let mut relexed = Vec::new();
+        let mut resync = None;
+        for mut token in Lexer::with_trivia(&self.source[restart..]) {
+            token.span = Span::new(token.span.low() + restart, token.span.size());
+
+            while self
+                .tokens
+                .get(old)
+                .is_some_and(|next| shift(next.span.low()) < token.span.low())
+            {
+                old += 1;
+            }
+
+            // From a shared boundary past the edit, the old tokens
+            // follow from the same text.
+            let aligned = self
+                .tokens
+                .get(old)
+                .is_some_and(|next| shift(next.span.low()) == token.span.low());
+            if token.span.low() >= edit_end && aligned {
+                resync = Some(old);
+                break;
+            }
+
+            relexed.push(token);
+        }
+
+        let reused: Vec<Token> = match resync {
+            Some(old) => self
+                .tokens
+                .drain(old..)
+                .map(|mut token| {
+                    token.span = Span::new(shift(token.span.low()), token.span.size());
+                    token
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.tokens.truncate(first);
+        self.tokens.extend(relexed);
+        self.tokens.extend(reused);
+        &self.tokens
+    }
+}
@@ -0,0 +1,170 @@
+//! Checks that an expression has the shape the compiler expects.
+//!
+//! [`compile`](crate::compile) takes any [`Expr`], but the compiler relies
+//! on the shape the parser gives its output. Trees built by hand, or by
+//! code generators, are checked first, so a mistake is reported as an
+//! [`Error::MalformedTree`] instead of panicking or compiling into the
+//! wrong code.
+//!
+//! The parser's output always passes. In particular, empty identifiers
+//! are allowed, since the reader makes one from `||`, and a dotted list
+//! is allowed wherever a list is. Whether a dot makes sense in a form,
+//! like the formals of a lambda, is up to the compiler.
+use std::collections::HashSet;
+
+use crate::error::{Error, Result};
+use crate::expr::{Expr, Keyword};
+
+/// Check the program, which may be a sequence of top-level forms.
+pub(crate) fn validate_tree(expr: &Expr) -> Result<()> {
+    match expr {
+        Expr::Sequence(forms) => forms
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, form)| validate_form(form, Some(index + 1))),
+        _ => validate_form(expr, None),
+    }
+}
+
+/// Check a top-level form, numbered from one when it's part of a sequence.
+///
+/// The walk keeps its own stack, so deeply nested forms don't recurse.
+fn validate_form(form: &Expr, number: Option<usize>) -> Result<()> {
+    enum Visit {
+        /// A value, and its position in the enclosing list or vector.
+        Enter(Option<usize>, Expr),
+        /// Done with the contents of a list or vector element.
+        Leave,
+    }
+
+    let mut path = Vec::new();
+    // Pairs and vectors can be shared or cyclic, so each is walked once.
+    let mut walked = HashSet::new();
+    let mut stack = vec![Visit::Enter(None, form.clone())];
+
+    let malformed = |violation: &str, path: &[usize]| Error::MalformedTree {
+        violation: violation.to_string(),
+        path: path.to_vec(),
+        form: number,
+    };
+
+    while let Some(visit) = stack.pop() {
+        let expr = match visit {
+            Visit::Enter(position, expr) => {
+                if let Some(position) = position {
+                    path.push(position);
+                    stack.push(Visit::Leave);
+                }
+                expr
+            }
+            Visit::Leave => {
+                path.pop();
+                continue;
+            }
+        };
+
+        let elements: Vec<Expr> = match &expr {
+            Expr::Sequence(_) => return Err(malformed("sequence nested", &path)),
+            Expr::Keyword(Keyword::Dot) => return Err(malformed("'.' outside of a list", &path)),
+            Expr::Procedure(_)
+            | Expr::Closure(_)
+            | Expr::NativeFunc(_)
+            | Expr::Port(_)
+            | Expr::Values(_)
+            | Expr::ErrorObject(_)
+            | Expr::Eof => {
+                let violation = format!("{} value embedded", expr.kind().name());
+                return Err(malformed(&violation, &path));
+            }
+            Expr::Quote(quoted) => {
+                stack.push(Visit::Enter(None, (**quoted).clone()));
+                continue;
+            }
+            Expr::List(list) => {
+                let dot = list
+                    .iter()
+                    .position(|expr| matches!(expr, Expr::Keyword(Keyword::Dot)));
+                match dot {
+                    // Only the tail follows the dot, and something comes before it.
+                    Some(dot) if dot == 0 || dot + 2 != list.len() => {
+                        path.push(dot);
+                        return Err(malformed("'.' misplaced in a dotted list", &path));
+                    }
+                    Some(dot) => list[..dot]
+                        .iter()
+                        .chain(&list[dot + 1..])
+                        .cloned()
+                        .collect(),
+                    None => list.to_vec(),
+                }
+            }
+            Expr::Pair(pair) => {
+                if !walked.insert(pair.addr()) {
+                    continue;
+                }
+                // The heads along the chain, followed by an improper tail.
+                let mut elements = Vec::new();
+                let mut rest = expr.clone();
+                loop {
+                    rest = match &rest {
+                        Expr::Pair(pair) => {
+                            let (head, tail) = pair.borrow().clone();
+                            elements.push(head);
+                            match &tail {
+                                Expr::Pair(next) if !walked.insert(next.addr()) => break,
+                                _ => tail,
+                            }
+                        }
+                        Expr::Nil => break,
+                        _ => {
+                            elements.push(rest.clone());
+                            break;
+                        }
+                    };
+                }
+                elements
+            }
+            Expr::Vector(vector) => {
+                if !walked.insert(vector.addr()) {
+                    continue;
+                }
+                vector.borrow().clone()
+            }
+            _ => continue,
+        };
+
+        stack.extend(
+            elements
+                .into_iter()
+                .enumerate()
+                .rev()
+                .map(|(index, element)| Visit::Enter(Some(index), element)),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_parser_output_passes() {
+        let sources = [
+            "(define (f a . rest) (if (null? rest) a (car rest)))",
+            "(lambda args args)",
+            "'(1 . 2)",
+            "(quote (a b . c))",
+            "#(1 (2 . 3) #u8(4))",
+            "(define || 1) ||",
+            "'#0=(a b . #0#)",
+            "(let loop ((i 0)) (if (< i 3) (loop (+ i 1)) i))",
+        ];
+        for source in sources {
+            let expr = parse(source, true).unwrap();
+            assert!(validate_tree(&expr).is_ok(), "{source}");
+        }
+    }
+}
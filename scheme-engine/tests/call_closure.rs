@@ -1,4 +1,4 @@
-use scheme_engine::Expr;
+use scheme_engine::{Env, Error, Expr, Result};
 
 #[test]
 fn test_call_closure() {
@@ -29,3 +29,135 @@ fn test_call_closure() {
     let value = scheme_engine::call(fibonacci, &args).unwrap();
     assert_eq!(value.as_number(), Some(21.0));
 }
+
+/// `(apply-to f x)`, an embedder's native that calls back into Scheme.
+fn apply_to(env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match args {
+        [Expr::Closure(f), x] => {
+            scheme_engine::call_with_env(env, f.clone(), std::slice::from_ref(x))
+        }
+        _ => Err(Error::Reason(
+            "apply-to: expected a procedure and a value".to_string(),
+        )),
+    }
+}
+
+/// Calls back the wrong way, with the closure's busy environment.
+fn apply_nested(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match args {
+        [Expr::Closure(f), x] => scheme_engine::call(f.clone(), std::slice::from_ref(x)),
+        _ => Err(Error::Reason(
+            "apply-nested: expected a procedure and a value".to_string(),
+        )),
+    }
+}
+
+fn eval_with_natives(source: &str) -> Result<Expr> {
+    let env = scheme_engine::new_env()?;
+    env.clone()
+        .borrow_mut()
+        .bind_native_func("apply-to", apply_to)?;
+    env.clone()
+        .borrow_mut()
+        .bind_native_func("apply-nested", apply_nested)?;
+    let expr = scheme_engine::parse(source, true)?;
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr)?)
+}
+
+#[test]
+fn test_native_calls_back() {
+    // The closure being called is also the one running the native.
+    let source = r"
+    (define countdown
+      (lambda (n)
+        (if (= n 0)
+            'done
+            (apply-to countdown (- n 1)))))
+    (countdown 5)
+    ";
+    assert_eq!(
+        eval_with_natives(source).unwrap(),
+        Expr::Ident("done".into())
+    );
+
+    let source = r"
+    (define walk (lambda (x) (if (list? x) (map walk x) (* x 2))))
+    (walk '(1 (2 3)))
+    ";
+    assert_eq!(
+        eval_with_natives(source).unwrap().repr().to_string(),
+        "(2 (4 6))"
+    );
+}
+
+#[test]
+fn test_nested_call_is_an_error() {
+    let err = eval_with_natives("(apply-nested (lambda (n) (+ n 1)) 1)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "environment is busy; native functions must call back with call_with_env"
+    );
+}
+
+#[test]
+fn test_call_with_other_env() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("(lambda () 1)", true).unwrap();
+    let program = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let closure = scheme_engine::eval(program).unwrap();
+
+    let other = scheme_engine::new_env().unwrap();
+    let err = scheme_engine::call_with_env(
+        &mut other.clone().borrow_mut(),
+        closure.as_closure().unwrap().clone(),
+        &[],
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "closure was not defined in the given environment"
+    );
+}
+
+/// A closure of another environment can't call back into an environment
+/// that is evaluating, since it doesn't have the native's access to it.
+#[test]
+fn test_call_busy_env_from_other_env() {
+    let other = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("(lambda (f) (f))", true).unwrap();
+    let program = scheme_engine::compile(other.clone(), &expr).unwrap();
+    let call_it = scheme_engine::eval(program).unwrap();
+
+    let env = scheme_engine::new_env().unwrap();
+    env.clone().borrow_mut().define("call-it", call_it).unwrap();
+    let expr = scheme_engine::parse("(call-it (lambda () 1))", true).unwrap();
+    let program = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let err = scheme_engine::eval(program).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "environment of the closure is busy evaluating further up the call stack, \
+         so closures of other environments can't call it"
+    );
+}
+
+/// A closure keeps using the globals of the environment it was defined
+/// in when a script in another environment calls it.
+#[test]
+fn test_call_closure_from_other_env() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("(define scale 10) (lambda (n) (* n scale))", true).unwrap();
+    let program = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let closure = scheme_engine::eval(program).unwrap();
+
+    let other = scheme_engine::new_env().unwrap();
+    other
+        .clone()
+        .borrow_mut()
+        .define("times-scale", closure)
+        .unwrap();
+    let source = "(define scale 2) (cons (times-scale 3) (map times-scale '(1 2)))";
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let program = scheme_engine::compile(other.clone(), &expr).unwrap();
+    let value = scheme_engine::eval(program).unwrap();
+    assert_eq!(value.repr().to_string(), "(30 10 20)");
+}
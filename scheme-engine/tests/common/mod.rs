@@ -0,0 +1,19 @@
+//! Helpers shared by the integration tests.
+use scheme_engine::{error::Error, EvalOptions, Expr, ParseOptions};
+
+/// Parse, compile and evaluate a program in a new environment.
+pub fn eval(source: &str) -> Result<Expr, Error> {
+    eval_with(source, &ParseOptions::default(), &EvalOptions::default())
+}
+
+/// Like [`eval`], reading and evaluating the program with the given options.
+pub fn eval_with(
+    source: &str,
+    parse_options: &ParseOptions,
+    options: &EvalOptions,
+) -> Result<Expr, Error> {
+    let env = scheme_engine::new_env()?;
+    let expr = scheme_engine::parse_with_options(source, true, parse_options)?;
+    let closure = scheme_engine::compile(env.clone(), &expr)?;
+    scheme_engine::eval_with_options(closure, options)
+}
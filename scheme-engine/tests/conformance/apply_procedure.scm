@@ -0,0 +1 @@
+(apply + 1 2 '(3 4))
@@ -0,0 +1 @@
+(and #t (or #f 1))
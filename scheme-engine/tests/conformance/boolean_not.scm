@@ -0,0 +1 @@
+(not #f)
@@ -0,0 +1 @@
+(boolean? (not 3))
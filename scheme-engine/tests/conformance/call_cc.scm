@@ -0,0 +1 @@
+(+ 1 (call-with-current-continuation (lambda (k) (k 41))))
@@ -0,0 +1 @@
+(call-with-values (lambda () (values 1 2 3)) *)
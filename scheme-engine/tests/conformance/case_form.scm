@@ -0,0 +1 @@
+(case (* 2 3) ((2 3 5 7) 'prime) ((1 4 6 8 9) 'composite))
@@ -0,0 +1 @@
+(char->integer #\A)
@@ -0,0 +1,10 @@
+(define (make-counter)
+  (define count 0)
+  (lambda ()
+    (set! count (+ count 1))
+    count))
+
+(define counter (make-counter))
+(counter)
+(counter)
+(counter)
@@ -0,0 +1,12 @@
+(define (make-counter)
+  (define count 0)
+  (lambda ()
+    (set! count (+ count 1))
+    count))
+
+(define a (make-counter))
+(define b (make-counter))
+(a)
+(a)
+(b)
+(+ (* 10 (a)) (b))
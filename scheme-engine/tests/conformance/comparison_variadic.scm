@@ -0,0 +1 @@
+(and (< 1 2 3) (>= 3 3 2))
@@ -0,0 +1,6 @@
+(define (classify n)
+  (cond ((< n 0) 'negative)
+        ((= n 0) 'zero)
+        (else 'positive)))
+
+(classify -5)
@@ -0,0 +1,2 @@
+(define ((adder n) m) (+ n m))
+((adder 2) 3)
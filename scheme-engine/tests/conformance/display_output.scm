@@ -0,0 +1,4 @@
+(display "hello")
+(newline)
+(display 42)
+(newline)
@@ -0,0 +1 @@
+(do ((i 0 (+ i 1)) (acc 1 (* acc 2))) ((= i 8) acc))
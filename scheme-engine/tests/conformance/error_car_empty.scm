@@ -0,0 +1 @@
+(car '())
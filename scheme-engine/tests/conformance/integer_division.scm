@@ -0,0 +1 @@
+(+ (quotient 17 5) (remainder 17 5) (modulo -7 2))
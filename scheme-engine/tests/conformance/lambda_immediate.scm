@@ -0,0 +1 @@
+((lambda (x y) (- x y)) 10 3)
@@ -0,0 +1 @@
+((lambda (first . rest) rest) 1 2 3)
@@ -0,0 +1 @@
+(let* ((a 2) (b (* a a))) (+ a b))
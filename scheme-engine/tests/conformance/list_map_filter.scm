@@ -0,0 +1 @@
+(map (lambda (x) (* x x)) (filter (lambda (x) (> x 1)) '(1 2 3)))
@@ -0,0 +1 @@
+(reverse (append '(1 2) '(3 4)))
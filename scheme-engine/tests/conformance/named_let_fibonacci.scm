@@ -0,0 +1,4 @@
+(let loop ((n 20) (a 0) (b 1))
+  (if (= n 0)
+      a
+      (loop (- n 1) b (+ a b))))
@@ -0,0 +1 @@
+(cons 1 2)
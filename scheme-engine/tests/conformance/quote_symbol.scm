@@ -0,0 +1 @@
+(quote hello)
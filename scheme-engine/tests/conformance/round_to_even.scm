@@ -0,0 +1 @@
+(+ (round 2.5) (round 3.5) (round -2.5))
@@ -0,0 +1 @@
+(+ (sqrt 16) (expt 2 10))
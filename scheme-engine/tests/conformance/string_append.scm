@@ -0,0 +1 @@
+(string-append "foo" "bar")
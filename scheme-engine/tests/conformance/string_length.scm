@@ -0,0 +1 @@
+(string-length "hello")
@@ -0,0 +1 @@
+(+ (string->number "40") 2)
@@ -0,0 +1,8 @@
+(define-syntax swap!
+  (syntax-rules ()
+    ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))
+
+(define x 1)
+(define y 2)
+(swap! x y)
+(- x y)
@@ -0,0 +1 @@
+(vector-ref #(1 2 3) 1)
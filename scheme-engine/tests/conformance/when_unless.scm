@@ -0,0 +1 @@
+(when (> 1 0) (unless #f 'yes))
@@ -0,0 +1,4 @@
+(write "a \"quoted\" word")
+(newline)
+(write 'sym)
+(newline)
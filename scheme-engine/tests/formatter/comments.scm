@@ -0,0 +1,21 @@
+;;; Comments stay where they were written.
+; Attached to the next form.
+(define x 1) ; Trailing.
+;; Separated by a blank line from the form before it.
+
+(define (f a)
+  ;; Describes the body.
+  (display a) ; Trailing a body form.
+
+
+  ;; Extra blank lines are collapsed.
+  (newline))
+(define y
+  #| Block comment |# 2)
+(foo ; After the operator.
+ a b)
+'( ; Inside quoted data.
+  1 2)
+' ; Between the quote and its datum.
+(a b)
+(g x) ; Last.
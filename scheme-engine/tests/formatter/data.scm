@@ -0,0 +1,13 @@
+'(alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu xi omicron)
+'((1 2)
+(3 4))
+(define table '(("one" . 1) ("two" . 2)))
+#(1 2 3)
+#u8(1 2
+3)
+((lambda (x) x) 1)
+((lambda (x)
+(* x x))
+2)
+(define shared '(#0=(a b) #0#))
+(define cycle '#1=(1 2 . #1#))
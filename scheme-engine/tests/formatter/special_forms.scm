@@ -0,0 +1,20 @@
+(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))
+(define (fib n)
+(if (< n 2)
+n
+(+ (fib (- n 1)) (fib (- n 2)))))
+(define (classify n)
+      (cond ((< n 0) 'negative)
+  ((= n 0) 'zero)
+            (else 'positive)))
+(define (sum-to n)
+(do ((i 1 (+ i 1)) (sum 0 (+ sum i)))
+((> i n) sum)))
+(let loop ((i 0) (acc '())) (if (< i 10) (loop (+ i 1) (cons (* i i) acc)) (reverse acc)))
+(let ((a 1)
+(b 2))
+(+ a b))
+(define-values (q r)
+(values 1 2))
+(define adder (lambda (n) (lambda (m)
+(+ n m))))
@@ -0,0 +1,6 @@
+(define a 1)   (define b 2)
+
+
+
+(+ a b)
+     (display "indented")
@@ -0,0 +1,4 @@
+;; A case stuck in a loop, which must fail once it runs out of fuel.
+;; fuel: 1000
+(define (loop n) (loop (+ n 1)))
+(loop 0)
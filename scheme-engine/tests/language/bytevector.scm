@@ -0,0 +1,33 @@
+
+;; ===========
+;; Bytevectors
+;; ===========
+
+(define bytes #u8(0 255 16))
+(assert (= (bytevector-length bytes) 3))
+(assert (= (bytevector-u8-ref bytes 1) 255))
+(assert (= (bytevector-length #u8()) 0))
+
+(define built (bytevector 1 2 3))
+(assert (= (bytevector-u8-ref built 2) 3))
+
+(define zeros (make-bytevector 4))
+(assert (= (bytevector-length zeros) 4))
+(assert (= (bytevector-u8-ref zeros 3) 0))
+(assert (= (bytevector-u8-ref (make-bytevector 2 7) 1) 7))
+
+;; Mutation is visible through every reference.
+(define alias built)
+(bytevector-u8-set! alias 0 42)
+(assert (= (bytevector-u8-ref built 0) 42))
+
+;; Copies are not aliases.
+(define copy (bytevector-copy built))
+(bytevector-u8-set! copy 0 1)
+(assert (= (bytevector-u8-ref built 0) 42))
+(assert (= (bytevector-length (bytevector-copy built 1)) 2))
+(assert (= (bytevector-u8-ref (bytevector-copy built 1 2) 0) 2))
+
+(define appended (bytevector-append #u8(1) #u8() #u8(2 3)))
+(assert (= (bytevector-length appended) 3))
+(assert (= (bytevector-u8-ref appended 2) 3))
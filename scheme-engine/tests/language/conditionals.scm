@@ -41,3 +41,111 @@
      (else (iter (+ n 1)))       ; recursive
   )))
 (assert-eq (iter 5) 25)
+
+(define classify (lambda (n)
+  (cond
+    ((< n 0) 'negative)
+    ((= n 0) 'zero)
+    ((< n 10) 'small)
+    (else 'large))))
+(assert-eq 'negative (classify -5))
+(assert-eq 'zero (classify 0))
+(assert-eq 'small (classify 7))
+(assert-eq 'large (classify 100))
+
+;; cond clauses without expressions evaluate to the test
+(assert-eq 3 (cond ((> 1 2)) ((+ 1 2))))
+(assert-eq 'found (cond (#f) ('found) (else 'missed)))
+(assert-eq #void (cond ((> 1 2))))
+
+;; cond clauses with => call the procedure with the test
+(assert-eq 6 (cond ((+ 1 2) => (lambda (x) (* x 2)))))
+(assert-eq 'other (cond (#f => car) (else 'other)))
+(assert-eq 'b (cond ((< 2 1) 'a) ((cons 'b 'c) => car) (else 'd)))
+
+;; cond in the value of an internal define
+(define nested (lambda (n)
+  (define sign (cond ((< n 0) -1) ((= n 0) 0) (else 1)))
+  (define magnitude (* n sign))
+  (cond
+    ((= sign 0) 'zero)
+    ((> magnitude 5) => (lambda (big) (if big 'big 'small)))
+    (else magnitude))))
+(assert-eq 'zero (nested 0))
+(assert-eq 'big (nested -8))
+(assert-eq 3 (nested 3))
+
+;; case
+(define size (lambda (n)
+  (case n
+    ((1 2 3) 'small)
+    ((4 5) 'medium)
+    (else 'large))))
+(assert-eq 'small (size 2))
+(assert-eq 'medium (size 5))
+(assert-eq 'large (size 9))
+
+(define kind (lambda (animal)
+  (case animal
+    ((cat dog) 'mammal)
+    ((sparrow) 'bird)
+    ((dog sparrow) 'unreachable)
+    (() 'never))))
+(assert-eq 'mammal (kind 'dog))
+(assert-eq 'bird (kind 'sparrow))
+(assert-eq #void (kind 'trout))
+(assert-eq #void (case '() (() 'never)))
+
+;; The key is evaluated once.
+(define calls 0)
+(define next-key (lambda () (set! calls (+ calls 1)) calls))
+(assert-eq 'one (case (next-key) ((3) 'three) ((2) 'two) ((1) 'one)))
+(assert-eq 1 calls)
+
+(assert-eq #\b (case #\b ((#\a) #\a) ((#\b #\c) #\b)))
+(assert-eq 10 (case 5 ((1 2) 'low) ((5) => (lambda (x) (* x 2)))))
+(assert-eq 'z (case 'z ((a) 1) (else => (lambda (x) x))))
+
+;; when and unless
+(define log '())
+(define note (lambda (x) (set! log (cons x log)) x))
+
+(assert-eq 2 (when (< 0 1) (note 1) (note 2)))
+(assert-eq #void (when (> 0 1) (note 3) (note 4)))
+(assert-eq 6 (unless (> 0 1) (note 5) (note 6)))
+(assert-eq #void (unless (< 0 1) (note 7) (note 8)))
+(assert (equal? '(6 5 2 1) log))
+
+(define clamp (lambda (n limit)
+  (define result n)
+  (when (> n limit)
+    (note 'clamped)
+    (set! result limit))
+  (unless (number? result)
+    (set! result 0))
+  result))
+(assert-eq 3 (clamp 3 10))
+(assert-eq 10 (clamp 12 10))
+(assert-eq 'clamped (car log))
+
+(define nested (lambda (a b)
+  (when a
+    (unless b
+      'only-a))))
+(assert-eq 'only-a (nested #t #f))
+(assert-eq #void (nested #t #t))
+(assert-eq #void (nested #f #f))
+
+;; A `begin` groups expressions where the branches of an `if` expect one.
+(define counter 0)
+(define bump-if (lambda (flag)
+  (if flag
+      (begin
+        (set! counter (+ counter 1))
+        (note 'bumped)
+        counter)
+      (begin 'skipped))))
+(assert-eq 1 (bump-if #t))
+(assert-eq 'skipped (bump-if #f))
+(assert-eq 2 (bump-if #t))
+(assert-eq 'bumped (car log))
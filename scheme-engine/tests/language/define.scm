@@ -16,3 +16,9 @@
 
 ;; Basic local variable usage
 (lambda (x y) (define z 3) (+ x y z))
+
+;; Definitions in a top-level `begin` define global variables
+(begin
+  (define spliced-a 1)
+  (define (spliced-b) (+ spliced-a 1)))
+(assert (= (spliced-b) 2))
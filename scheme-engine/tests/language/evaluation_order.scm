@@ -0,0 +1,55 @@
+;; ===================
+;; Order of evaluation
+;; ===================
+;;
+;; R7RS leaves the order unspecified, but this implementation guarantees
+;; left to right. Each call to `record!` appends a digit to `order`.
+
+(define order 0)
+(define record! (lambda (digit)
+  (set! order (+ (* order 10) digit))
+  digit))
+(define reset! (lambda () (set! order 0)))
+
+;; call arguments
+(assert-eq (+ (record! 1) (record! 2) (record! 3)) 6)
+(assert-eq order 123)
+
+(reset!)
+(- (record! 4) (record! 3) (record! 2) (record! 1))
+(assert-eq order 4321)
+
+;; nested calls finish before the next argument
+(reset!)
+(+ (record! 1) (* (record! 2) (record! 3)) (record! 4))
+(assert-eq order 1234)
+
+;; the operator is evaluated before the arguments
+(reset!)
+(define pick (lambda (digit) (record! digit) +))
+((pick 1) (record! 2) (record! 3))
+(assert-eq order 123)
+
+;; closure arguments
+(reset!)
+(define ignore (lambda (a b c) #void))
+(ignore (record! 1) (record! 2) (record! 3))
+(assert-eq order 123)
+
+;; define evaluates the value before binding the variable
+(reset!)
+(define x (record! 7))
+(assert-eq order 7)
+(assert-eq x 7)
+
+;; cond tests in clause order, stopping at the first true one
+(reset!)
+(cond ((= (record! 1) 0) 'no) ((= (record! 2) 2) 'yes) ((record! 3) 'no))
+(assert-eq order 12)
+
+;; do initializes, then steps, from left to right
+(reset!)
+(do ((a (record! 1) (record! 3))
+     (b (record! 2) (record! 4)))
+    ((> order 10000)))
+(assert-eq order 123434)
@@ -0,0 +1,23 @@
+;; ================
+;; Formatted output
+;; ================
+
+(assert-eq (number->string 42) "42")
+(assert-eq (number->string 2.5) "2.5")
+(assert-eq (number->string 255 16) "ff")
+(assert-eq (number->string (- 0 5) 2) "-101")
+(assert-eq (number->string 8 8) "10")
+;; Integers past 64 bits are written exactly.
+(assert-eq (number->string 1e20 16) "56bc75e2d63100000")
+(assert-eq (number->string (- 0 1e20) 8) "-12657072742654304000000")
+(assert-eq (number->string 1e20 2) "1010110101111000111010111100010110101100011000100000000000000000000")
+
+(assert-eq (format #f "plain") "plain")
+(assert-eq (format #f "x=~a y=~s" "one" "two") "x=one y=\"two\"")
+(assert-eq (format #f "n=~0,2f" 3.14159) "n=3.14")
+(assert-eq (format #f "[~6,1f]" 2.26) "[   2.3]")
+(assert-eq (format #f "[~4d]" 42) "[  42]")
+(assert-eq (format #f "~a~%" #t) "#t
+")
+(assert-eq (format #f "100~~") "100~")
+(assert-eq (format #t "") #void)
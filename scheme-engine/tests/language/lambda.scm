@@ -30,3 +30,13 @@
 (assert (=
           (((add-nested 13) 17) 19)
           49))
+
+;; Internal definitions each get a slot in the call frame.
+(define three-locals
+  (lambda (x)
+    (define a (+ x 1))
+    (define b (* a 2))
+    (define c (+ a b))
+    (- c x)))
+(assert (= (three-locals 4) 11))
+(assert (= (three-locals 4) (three-locals 4)))
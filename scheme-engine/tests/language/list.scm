@@ -0,0 +1,62 @@
+
+;; =====================
+;; Higher-order on lists
+;; =====================
+
+(define square (lambda (x) (* x x)))
+(assert-eq (map square '(1 2 3)) '(1 4 9))
+(assert-eq (map square '()) '())
+
+;; Several lists are walked together, stopping at the shortest.
+(assert-eq (map + '(1 2 3) '(10 20)) '(11 22))
+(assert-eq (map (lambda (a b) (- a b)) '(10 20 30) '(1 2 3)) '(9 18 27))
+
+(assert-eq (filter (lambda (x) (> x 2)) '(1 2 3 4)) '(3 4))
+(assert-eq (filter (lambda (x) #f) '(1 2 3)) '())
+
+;; Anything but #f keeps the element.
+(assert-eq (filter (lambda (x) x) '(1 #f 2)) '(1 2))
+
+(assert-eq (sort '(3 1 2) <) '(1 2 3))
+(assert-eq (sort '() <) '())
+
+(for-each (lambda (x y) (display (+ x y)) (newline)) '(1 2) '(3 4))
+
+;; Procedures called back by natives see and assign the locals of the
+;; procedures around them.
+(define add-all (lambda (xs n) (map (lambda (x) (+ x n)) xs)))
+(assert-eq (add-all '(1 2 3) 10) '(11 12 13))
+
+(define count-all
+  (lambda (xs)
+    (let ((n 0))
+      (for-each (lambda (x) (set! n (+ n 1))) xs)
+      n)))
+(assert-eq (count-all '(a b c)) 3)
+
+(define sum-sorted
+  (lambda (xs)
+    (let ((compared 0))
+      (sort xs (lambda (a b) (set! compared (+ compared 1)) (< a b)))
+      (> compared 0))))
+(assert (sum-sorted '(3 1 2)))
+
+(define keep-above
+  (lambda (xs limit)
+    (filter (lambda (x) (> x limit)) xs)))
+(assert-eq (keep-above '(1 5 2 7) 3) '(5 7))
+
+;; Callbacks calling back into natives keep the depth of the recursion.
+(define depth
+  (lambda (n)
+    (if (= n 0)
+        0
+        (car (map (lambda (k) (+ k (depth (- n 1)))) '(1))))))
+(assert-eq (depth 50) 50)
+
+;; A closure made in a callback keeps the local it captured after the
+;; callback returns.
+(define counters (map (lambda (start) (lambda () (set! start (+ start 1)) start)) '(10 20)))
+(assert-eq ((car counters)) 11)
+(assert-eq ((car counters)) 12)
+(assert-eq ((car (cdr counters))) 21)
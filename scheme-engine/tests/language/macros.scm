@@ -0,0 +1,57 @@
+;; =============
+;; Scoped macros
+;; =============
+
+;; let-syntax and letrec-syntax bind syntax-rules macros in their body.
+
+(define (describe x) 'procedure)
+
+;; A macro is used inside its body, and the procedure of the same name
+;; outside it.
+(assert-eq (let-syntax ((describe (syntax-rules () ((_ x) 'macro))))
+             (describe 1))
+           'macro)
+(assert-eq (describe 1) 'procedure)
+
+;; Patterns take apart the use, and ellipses repeat.
+(define (pairs)
+  (let-syntax ((pairs (syntax-rules ()
+                        ((_ (key value) ...) '((key . value) ...)))))
+    (pairs (a 1) (b 2))))
+(assert (equal? (pairs) '((a . 1) (b . 2))))
+
+;; Literals only match themselves.
+(assert (equal? (let-syntax ((arrow (syntax-rules (=>)
+                                     ((_ a => b) b)
+                                     ((_ a b) a))))
+                 (cons (arrow 1 => 2) (arrow 3 4)))
+               '(2 . 3)))
+
+;; An inner macro shadows an outer one of the same name.
+(assert (equal? (let-syntax ((which (syntax-rules () ((_) 'outer))))
+                 (cons (which)
+                       (let-syntax ((which (syntax-rules () ((_) 'inner))))
+                         (which))))
+               '(outer . inner)))
+
+;; A local variable shadows a macro of the same name, inside its scope.
+(assert-eq (let-syntax ((twice (syntax-rules () ((_ x) (* 2 x)))))
+             (let ((twice (lambda (x) (+ x x x))))
+               (twice 5)))
+           15)
+(assert-eq (let-syntax ((twice (syntax-rules () ((_ x) (* 2 x)))))
+             (define (twice x) (- x))
+             (twice 5))
+           -5)
+(assert (equal? (let-syntax ((twice (syntax-rules () ((_ x) (* 2 x)))))
+                 (cons ((lambda (twice) (twice 5)) -)
+                       (twice 5)))
+               '(-5 . 10)))
+
+;; The macros of letrec-syntax can use themselves.
+(assert-eq (letrec-syntax ((my-or (syntax-rules ()
+                                    ((_) #f)
+                                    ((_ e) e)
+                                    ((_ e rest ...) (let ((t e)) (if t t (my-or rest ...)))))))
+             (my-or #f #f 7))
+           7)
@@ -0,0 +1,82 @@
+
+;; =======
+;; Memoize
+;; =======
+
+;; A procedure that remembers its results, built from closures over
+;; mutable state, rest parameters, apply and equal?.
+
+;; Rest parameters take the remaining arguments as a list.
+(define list (lambda items items))
+(assert (null? (list)))
+(assert (equal? (list 1 2 3) '(1 2 3)))
+
+(define second-and-rest (lambda (a b . rest) (cons b rest)))
+(assert (equal? (second-and-rest 1 2 3 4) '(2 3 4)))
+(assert (equal? (second-and-rest 1 2) '(2)))
+
+;; Apply spreads a list over the arguments.
+(assert (= (apply + 1 2 '(3 4)) 10))
+(assert (= (apply length (list '(a b c))) 3))
+(assert (equal? (apply list '()) '()))
+
+;; Structural equality, whether lists are literals or built from pairs.
+(assert (equal? (cons 1 (cons (list 2 "three") '())) '(1 (2 "three"))))
+(assert (equal? '#(1 (2)) '#(1 (2))))
+(assert (not (equal? '(1 2) '(1 2 3))))
+(assert (not (equal? '(1 . 2) '(1 2))))
+
+;; The entry for the key in an association list, or #f.
+(define lookup
+  (lambda (key entries)
+    (if (null? entries)
+        #f
+        (if (equal? (car (car entries)) key)
+            (car entries)
+            (lookup key (cdr entries))))))
+
+(define memoize
+  (lambda (f)
+    (define cache '())
+    (define remember!
+      (lambda (key value)
+        (set! cache (cons (cons key value) cache))
+        value))
+    (lambda args
+      (define entry (lookup args cache))
+      (if entry
+          (cdr entry)
+          (remember! args (apply f args))))))
+
+(define naive-calls 0)
+(define naive-fib
+  (lambda (n)
+    (set! naive-calls (+ naive-calls 1))
+    (if (<= n 1)
+        n
+        (+ (naive-fib (- n 1)) (naive-fib (- n 2))))))
+
+(define calls 0)
+(define fib
+  (memoize
+    (lambda (n)
+      (set! calls (+ calls 1))
+      (if (<= n 1)
+          n
+          (+ (fib (- n 1)) (fib (- n 2)))))))
+
+;; The naive version calls itself 2 * fib(n + 1) - 1 times.
+(assert (= (naive-fib 15) 610))
+(assert (= naive-calls 1973))
+
+;; The memoized version computes each number once.
+(assert (= (fib 15) 610))
+(assert (= calls 16))
+(assert (= (fib 25) 75025))
+(assert (= calls 26))
+
+;; Every argument takes part in the key.
+(define add (memoize (lambda (a b) (+ a b))))
+(assert (= (add 1 2) 3))
+(assert (= (add 2 1) 3))
+(assert (equal? (list (add 1 2) (add 2 1)) (list 3 3)))
@@ -8,3 +8,9 @@
 
 (assert (= (+ 1 2 3 4 5 (- 6 7 8)) 6))
 
+
+(assert (= (- 5) (- 0 5)))
+(assert (= (/ 4) 0.25))
+(assert (= (/ 12 2 3) 2))
+(assert (= (+) 0))
+(assert (= (*) 1))
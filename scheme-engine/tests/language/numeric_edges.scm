@@ -0,0 +1,101 @@
+
+;; ==================
+;; Numeric edge cases
+;; ==================
+;;
+;; Numbers are floats until the integer type lands, so these pin
+;; down the behaviours that floats would otherwise get wrong. An
+;; inexact argument, like 2.5, gives an inexact result, like 2.0.
+
+(define inf (/ 1.0 0))
+(define nan (- inf inf))
+
+;; Rounding
+;; --------
+;; floor rounds down, ceiling up, truncate towards zero.
+(assert-eq (floor 2.5) 2.0)
+(assert-eq (floor -2.5) -3.0)
+(assert-eq (ceiling 2.5) 3.0)
+(assert-eq (ceiling -2.5) -2.0)
+(assert-eq (truncate 2.7) 2.0)
+(assert-eq (truncate -2.7) -2.0)
+(assert-eq (floor 7) 7)
+(assert-eq (truncate -7) -7)
+
+;; round goes to even when halfway between two integers.
+(assert-eq (round 0.5) 0.0)
+(assert-eq (round 1.5) 2.0)
+(assert-eq (round 2.5) 2.0)
+(assert-eq (round 3.5) 4.0)
+(assert-eq (round -0.5) 0.0)
+(assert-eq (round -1.5) -2.0)
+(assert-eq (round -2.5) -2.0)
+(assert-eq (round 2.6) 3.0)
+(assert-eq (round -2.4) -2.0)
+(assert-eq (round 7) 7)
+
+;; Infinities stay put, and NaN stays NaN.
+(assert-eq (floor inf) inf)
+(assert-eq (round (- inf)) (- inf))
+(assert-close (truncate nan) nan)
+
+;; Integer division
+;; ----------------
+;; quotient and remainder truncate, remainder has the sign of the dividend.
+(assert-eq (quotient 7 2) 3)
+(assert-eq (quotient -7 2) -3)
+(assert-eq (remainder 7 3) 1)
+(assert-eq (remainder -7 3) -1)
+(assert-eq (remainder 7 -3) 1)
+(assert-eq (remainder -7 -3) -1)
+
+;; modulo floors, and has the sign of the divisor.
+(assert-eq (modulo 7 3) 1)
+(assert-eq (modulo -7 3) 2)
+(assert-eq (modulo 7 -3) -2)
+(assert-eq (modulo -7 -3) -1)
+(assert-eq (modulo -6 3) 0)
+
+;; Integers as floats are still integers.
+(assert-eq (modulo 7.0 2) 1.0)
+
+;; Powers and roots
+;; ----------------
+(assert-eq (expt 0 0) 1)
+(assert-eq (expt 0.0 0) 1.0)
+(assert-eq (expt 0 2) 0)
+(assert-eq (expt 2 10) 1024)
+(assert-eq (expt 2 -1) 0.5)
+(assert-eq (expt -2 3) -8)
+(assert-close (expt 2 0.5) (sqrt 2))
+
+(assert-eq (sqrt 16) 4)
+(assert-eq (sqrt 0) 0)
+(assert-eq (sqrt inf) inf)
+(assert-close (sqrt 2) 1.414213562)
+
+;; Extremes
+;; --------
+;; An inexact argument makes the result inexact.
+(assert-eq (max 1 2.0) 2.0)
+(assert-eq (max 1 3 2) 3)
+(assert-eq (min 1 3 2) 1)
+(assert-eq (min (- inf) 0) (- inf))
+(assert-close (max 1 nan) nan)
+(assert-close (min nan 1) nan)
+(assert-eq (abs -7) 7)
+(assert-eq (abs 7) 7)
+
+;; Division
+;; --------
+;; Exact numbers can't be divided by an exact zero, but with an
+;; inexact operand dividing by zero gives the infinities, and zero
+;; by zero gives NaN.
+(assert-eq (/ 1.0 0) inf)
+(assert-eq (/ -1 0.0) (- inf))
+(assert-close (/ 0 0.0) nan)
+
+;; Tolerance
+;; ---------
+(assert-close 0.1 (- 0.3 0.2))
+(assert-close 1 1.05 0.1)
@@ -0,0 +1,82 @@
+
+;; =========
+;; Exactness
+;; =========
+;;
+;; Numbers are floats until the integer type lands, but still know
+;; their exactness. A finite integer is exact, unless it's written with
+;; a dot or exponent, made by `inexact`, or computed from an inexact
+;; number or to a magnitude of 2^53 or more. Anything else is inexact.
+
+(define inf (/ 1.0 0))
+(define -inf (- inf))
+(define nan (- inf inf))
+(define -zero (- 0.0))
+
+;; integer? accepts any object, and 2.0 is an integer per R7RS.
+(assert (integer? 2))
+(assert (integer? 2.0))
+(assert (integer? -zero))
+(assert (integer? (- 0 7)))
+(assert (not (integer? 2.5)))
+(assert (not (integer? inf)))
+(assert (not (integer? -inf)))
+(assert (not (integer? nan)))
+(assert (not (integer? "2")))
+(assert (not (integer? #t)))
+
+(assert (exact? 2))
+(assert (not (exact? -zero)))
+(assert (not (exact? 2.0)))
+(assert (not (exact? 1e300)))
+(assert (not (exact? 0.5)))
+(assert (not (exact? inf)))
+(assert (not (exact? nan)))
+
+(assert (inexact? 0.5))
+(assert (inexact? inf))
+(assert (inexact? nan))
+(assert (not (inexact? 3)))
+(assert (inexact? (inexact 4)))
+(assert (inexact? (exact->inexact 4)))
+(assert (exact? (exact 4.0)))
+
+;; Arithmetic with an inexact operand is inexact.
+(assert (exact? (+ 1 2)))
+(assert (inexact? (+ 1 2.0)))
+(assert (inexact? (* 2 (inexact 3))))
+(assert (inexact? (max 1 2.0)))
+(assert (inexact? (floor 2.5)))
+(assert (exact? (floor 2)))
+
+;; From 2^53 on, results may have been rounded, so they're inexact.
+(define two-52 (* 4096 1024 1024 1024 1024)) ; 2^52
+(assert (exact? (+ two-52 (- two-52 1))))
+(assert (exact? (- 0 two-52 (- two-52 1))))
+(assert (not (exact? (+ two-52 two-52))))
+(assert (not (exact? (+ two-52 two-52 1))))
+(assert (not (exact? (- 0 two-52 two-52 1))))
+(assert (not (exact? (* 99999999999 99999999999))))
+(assert (not (exact? (expt 2 100))))
+(assert-eq (number->string (expt 2 100)) "1267650600228229400000000000000.0")
+
+;; eqv? tells exactness apart, = only compares values.
+(assert (not (eqv? 2 2.0)))
+(assert (eqv? 2.0 (inexact 2)))
+(assert (= 2 2.0))
+
+(assert (exact-integer? 3))
+(assert (not (exact-integer? 3.5)))
+(assert (not (exact-integer? 3.0)))
+(assert (not (exact-integer? nan)))
+(assert (not (exact-integer? "3")))
+
+;; Conversions
+(assert (= (inexact->exact 4.0) 4))
+(assert (= (exact 4) 4))
+(assert (= (exact->inexact 4) 4))
+(assert (= (inexact 0.5) 0.5))
+(assert (= (exact->inexact inf) inf))
+
+;; Negative zero has no sign when exact.
+(assert (= (/ 1.0 (exact -zero)) inf))
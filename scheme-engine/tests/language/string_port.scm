@@ -0,0 +1,41 @@
+
+;; ============
+;; String ports
+;; ============
+
+;; Reading datums and characters from a string.
+(define input (open-input-string "(1 2) three #\\a"))
+(assert (input-port? input))
+(assert-eq (read input) '(1 2))
+(assert-eq (read input) 'three)
+(assert-eq (read input) #\a)
+(assert (eof-object? (read input)))
+(assert (eof-object? (read-char input)))
+
+;; Dotted data reads as pairs.
+(define pair (read (open-input-string "(1 . 2)")))
+(assert (pair? pair))
+(assert (= (cdr pair) 2))
+
+(define lines (open-input-string "first\nsecond"))
+(assert-eq (read-char lines) #\f)
+(assert-eq (read-line lines) "irst")
+(assert-eq (read-line lines) "second")
+(assert (eof-object? (read-line lines)))
+
+;; Building text with several writes.
+(define output (open-output-string))
+(assert (output-port? output))
+(assert-eq (get-output-string output) "")
+(display "sum: " output)
+(write (+ 1 2) output)
+(write-char #\space output)
+(write "quoted" output)
+(newline output)
+(write-string "done" output)
+(assert-eq (get-output-string output) "sum: 3 \"quoted\"\ndone")
+
+;; Written data reads back.
+(define round-trip (open-output-string))
+(write '(a "b" #\c 1.5) round-trip)
+(assert-eq (read (open-input-string (get-output-string round-trip))) '(a "b" #\c 1.5))
@@ -0,0 +1,63 @@
+;; =======
+;; Vectors
+;; =======
+
+(define letters #(a b c))
+(assert (vector? letters))
+(assert (not (vector? '(a b c))))
+(assert (= (vector-length letters) 3))
+(assert-eq (vector-ref letters 1) 'b)
+(assert (= (vector-length #()) 0))
+
+(define built (vector 1 2 3))
+(assert (equal? built #(1 2 3)))
+(assert (equal? (make-vector 2 'x) #(x x)))
+(assert (= (vector-length (make-vector 4)) 4))
+
+;; Mutation is visible through every reference.
+(define alias built)
+(vector-set! alias 0 42)
+(assert (= (vector-ref built 0) 42))
+
+(assert (equal? (vector->list #(1 2 3)) '(1 2 3)))
+(assert (equal? (vector->list #(1 2 3) 1) '(2 3)))
+(assert (equal? (list->vector '(1 2)) #(1 2)))
+
+;; Several vectors are walked together, stopping at the shortest.
+(assert (equal? (vector-map (lambda (x) (* x x)) #(1 2 3)) #(1 4 9)))
+(assert (equal? (vector-map + #(1 2 3) #(10 20)) #(11 22)))
+(assert (equal? (vector-map + #() #(1)) #()))
+
+(define total 0)
+(vector-for-each (lambda (x y) (set! total (+ total (* x y)))) #(1 2 3) #(4 5))
+(assert (= total 14))
+
+(define filled (make-vector 5 0))
+(vector-fill! filled 'x 1 3)
+(assert (equal? filled #(0 x x 0 0)))
+(vector-fill! filled 'y 4)
+(assert (equal? filled #(0 x x 0 y)))
+(vector-fill! filled 'z)
+(assert (equal? filled #(z z z z z)))
+
+;; Copies are not aliases.
+(define copy (vector-copy built))
+(vector-set! copy 0 1)
+(assert (= (vector-ref built 0) 42))
+(assert (equal? (vector-copy #(1 2 3 4) 1 3) #(2 3)))
+
+(define target (vector 1 2 3 4 5))
+(vector-copy! target 1 #(a b))
+(assert (equal? target #(1 a b 4 5)))
+(vector-copy! target 3 #(x y z) 1)
+(assert (equal? target #(1 a b y z)))
+
+;; Overlapping ranges of the same vector.
+(define shifted (vector 1 2 3 4 5))
+(vector-copy! shifted 1 shifted 0 4)
+(assert (equal? shifted #(1 1 2 3 4)))
+(vector-copy! shifted 0 shifted 1)
+(assert (equal? shifted #(1 2 3 4 4)))
+
+(assert (equal? (vector-append #(1) #() #(2 3)) #(1 2 3)))
+(assert (equal? (vector-append) #()))
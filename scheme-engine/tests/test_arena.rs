@@ -0,0 +1,135 @@
+//! Parsing into an arena, compared to a regular parse.
+use scheme_engine::{parse_program, parse_program_in, ArenaExpr, Expr, ParseArena};
+
+/// Whether the trees have the same shape and contents. Unlike `==`, forms
+/// like vectors and quotes compare by their contents.
+fn same_tree(left: &Expr, right: &Expr) -> bool {
+    let same_elements = |left: &[Expr], right: &[Expr]| {
+        left.len() == right.len() && left.iter().zip(right).all(|(l, r)| same_tree(l, r))
+    };
+    match (left, right) {
+        (Expr::Sequence(left), Expr::Sequence(right)) => same_elements(left, right),
+        (Expr::List(left), Expr::List(right)) => same_elements(left, right),
+        (Expr::Vector(left), Expr::Vector(right)) => same_elements(&left.borrow(), &right.borrow()),
+        (Expr::Quote(left), Expr::Quote(right)) => same_tree(left, right),
+        (left, right) => left.is_equal(right),
+    }
+}
+
+fn assert_same_parse(arena: &ParseArena, source: &str) {
+    let expected = parse_program(source).unwrap();
+    let tree = parse_program_in(arena, source).unwrap();
+    let owned = tree.to_owned();
+    assert!(
+        same_tree(&owned, &expected),
+        "{source}\narena: {owned:?}\nregular: {expected:?}"
+    );
+}
+
+#[test]
+fn test_same_as_regular_parse() {
+    let sources = [
+        "",
+        "(define (f x) (* x 2)) (f 21)",
+        "(a (b (c (d))) () e)",
+        "'(1 . 2) '(a b . (c))",
+        r#""plain" "with \"escapes\"\n" "" #\a #\space"#,
+        "#(1 #(2) \"three\") #u8(0 255) #u8()",
+        "#t #f #true #false #void 1.5 -2 #x1F #b101",
+        "|bar symbol| |a\\|b| ->string +",
+        "; comment\n(a #| block |# b) c",
+        "#!fold-case (DEFINE Shout 1) #!no-fold-case Quiet",
+        "#0=(a b . #0#) (x #1=(y) #1#)",
+        "#2=#(1 2)",
+    ];
+
+    let arena = ParseArena::new();
+    for source in sources {
+        assert_same_parse(&arena, source);
+    }
+}
+
+#[test]
+fn test_same_as_regular_parse_of_scripts() {
+    let scripts = [
+        include_str!("language/conditionals.scm"),
+        include_str!("language/define.scm"),
+        include_str!("language/list.scm"),
+        include_str!("language/numeric_edges.scm"),
+        include_str!("language/string_port.scm"),
+        include_str!("language/vector.scm"),
+        include_str!("test_fibonacci.scm"),
+    ];
+
+    let mut arena = ParseArena::new();
+    for source in scripts {
+        arena.clear();
+        assert_same_parse(&arena, source);
+    }
+}
+
+#[test]
+fn test_arena_tree() {
+    let arena = ParseArena::new();
+    let program = parse_program_in(&arena, "(define x 'y) #(1 \"two\")").unwrap();
+    let ArenaExpr::Sequence([define, vector]) = program else {
+        panic!("expected two forms, found {program:?}");
+    };
+    assert_eq!(
+        define,
+        &ArenaExpr::List(&[
+            ArenaExpr::Ident("define"),
+            ArenaExpr::Ident("x"),
+            ArenaExpr::Quote(&ArenaExpr::Ident("y")),
+        ])
+    );
+    assert_eq!(
+        vector,
+        &ArenaExpr::Vector(&[ArenaExpr::Number(1.0), ArenaExpr::String("two")])
+    );
+}
+
+#[test]
+fn test_same_errors() {
+    let sources = [
+        "(a b",
+        "(a))",
+        "\"unclosed",
+        "|unclosed",
+        "(. a)",
+        "(a . )",
+        "(a . b c)",
+        "#u8(256)",
+        "1.2.3",
+        "#1#",
+        "#| unclosed",
+    ];
+
+    let arena = ParseArena::new();
+    for source in sources {
+        let expected = parse_program(source).unwrap_err();
+        let err = parse_program_in(&arena, source).unwrap_err();
+        assert_eq!(err.to_string(), expected.to_string(), "{source}");
+    }
+}
+
+#[test]
+fn test_clear_reuses_blocks() {
+    let source: String = (0..500)
+        .map(|index| format!("(define (f{index} x) (+ x {index}))\n"))
+        .collect();
+
+    let mut arena = ParseArena::new();
+    let capacities: Vec<usize> = (0..4)
+        .map(|_| {
+            arena.clear();
+            assert_same_parse(&arena, &source);
+            arena.capacity_bytes()
+        })
+        .collect();
+
+    // Only the biggest block is kept, which soon has room for the whole
+    // program, so parsing it again takes no more memory.
+    assert!(capacities[0] > 0);
+    assert_eq!(capacities[2], capacities[3], "{capacities:?}");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::eval;
+use scheme_engine::Expr;
+
+#[test]
+fn test_reader_syntax() {
+    let value = eval("#u8(0 255 16)").unwrap();
+    assert_eq!(value.as_bytes().as_deref(), Some(&[0, 255, 16][..]));
+    assert_eq!(value.write_repr().to_string(), "#u8(0 255 16)");
+    assert_eq!(eval("#u8()").unwrap().repr().to_string(), "#u8()");
+}
+
+#[test]
+fn test_identity() {
+    let bytes = Expr::from(&b"abc"[..]);
+    assert_eq!(bytes, bytes.clone());
+    assert_ne!(bytes, Expr::from(&b"abc"[..]));
+}
+
+#[test]
+fn test_alias_mutation() {
+    let value =
+        eval("(define a (bytevector 1 2)) (define b a) (bytevector-u8-set! b 1 9) a").unwrap();
+    assert_eq!(value.as_bytes().as_deref(), Some(&[1, 9][..]));
+}
+
+#[test]
+fn test_utf8_round_trip() {
+    let value = eval(r#"(utf8->string (string->utf8 "héllo λ"))"#).unwrap();
+    assert_eq!(value, Expr::from("héllo λ"));
+
+    let value = eval(r#"(string->utf8 "λx")"#).unwrap();
+    assert_eq!(value.as_bytes().as_deref(), Some("λx".as_bytes()));
+
+    // Ranges count characters in strings, and bytes in bytevectors.
+    let value = eval(r#"(string->utf8 "aλb" 1 2)"#).unwrap();
+    assert_eq!(value.as_bytes().as_deref(), Some("λ".as_bytes()));
+    let value = eval(r#"(utf8->string #u8(97 206 187 98) 1 3)"#).unwrap();
+    assert_eq!(value, Expr::from("λ"));
+}
+
+#[test]
+fn test_errors() {
+    let table = [
+        (
+            "(utf8->string #u8(97 255 98))",
+            "utf8->string: invalid UTF-8 at byte 1",
+        ),
+        (
+            "(utf8->string #u8(97 206 187 98) 2)",
+            "utf8->string: invalid UTF-8 at byte 2",
+        ),
+        (
+            "(bytevector 1 256)",
+            "bytevector: expected byte as argument 2, got 256",
+        ),
+        (
+            "(make-bytevector 2 1.5)",
+            "make-bytevector: expected byte as argument 2, got 1.5",
+        ),
+        (
+            "(make-bytevector 1e20)",
+            "make-bytevector: expected index as argument 1, got 100000000000000000000.0",
+        ),
+        (
+            "(bytevector-u8-ref #u8(1 2) 2)",
+            "bytevector-u8-ref: index 2 is out of range for length 2",
+        ),
+        (
+            "(bytevector-u8-set! #u8(1 2) 5 0)",
+            "bytevector-u8-set!: index 5 is out of range for length 2",
+        ),
+        (
+            "(bytevector-u8-ref #u8(1 2) (- 0 1))",
+            "bytevector-u8-ref: expected index as argument 2, got -1",
+        ),
+        (
+            "(bytevector-copy #u8(1 2) 2 1)",
+            "bytevector-copy: range 2 to 1 is out of bounds for length 2",
+        ),
+        (
+            r#"(bytevector-length "abc")"#,
+            "bytevector-length: expected bytevector as argument 1, got \"abc\"",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
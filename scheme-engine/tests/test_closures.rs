@@ -0,0 +1,296 @@
+//! Capture semantics of closures.
+//!
+//! A captured local is an open up-value while its frame is running, and is
+//! closed over when the frame returns. Closures that capture the same local
+//! share one up-value, so they keep seeing each other's assignments.
+mod common;
+
+use common::eval;
+use scheme_engine::{Expr, Handle, HandleCell};
+
+fn eval_repr(source: &str) -> String {
+    eval(source).unwrap().write_repr().to_string()
+}
+
+#[test]
+fn test_read_after_return() {
+    let source = r"
+    (define make-adder (lambda (x) (lambda (y) (+ x y))))
+    (define add5 (make-adder 5))
+    (make-adder 100)
+    (add5 10)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(15.0));
+}
+
+#[test]
+fn test_assignment_before_return() {
+    // The closure sees the local's value at the time the frame returned,
+    // not when the closure was created.
+    let source = r"
+    (define make-getter
+      (lambda ()
+        (define n 1)
+        (define get (lambda () n))
+        (set! n 2)
+        get))
+    ((make-getter))
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(2.0));
+}
+
+#[test]
+fn test_shared_capture() {
+    let source = r"
+    (define make-cell
+      (lambda ()
+        (define n 0)
+        (define get (lambda () n))
+        (define put (lambda (value) (set! n value)))
+        ; While open, the local itself is assigned.
+        (put 1)
+        (assert-eq (get) 1)
+        (assert-eq n 1)
+        (cons get put)))
+    (define cell (make-cell))
+    ; Once closed, both closures must still share the same up-value.
+    ((cdr cell) 42)
+    ((car cell))
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(42.0));
+}
+
+#[test]
+fn test_independent_counters() {
+    let source = r"
+    (define make-counter
+      (lambda ()
+        (define n 0)
+        (lambda () (set! n (+ n 1)) n)))
+    (define a (make-counter))
+    (define b (make-counter))
+    (a)
+    (a)
+    (b)
+    (cons (a) (b))
+    ";
+    assert_eq!(eval_repr(source), "(3 . 2)");
+}
+
+#[test]
+fn test_three_levels() {
+    // The innermost closure captures `x` from its parent's up-values,
+    // which captured it from the outermost frame.
+    let source = r"
+    (define outer (lambda (x) (lambda (y) (lambda (z) (+ x y z)))))
+    (define middle (outer 1))
+    (define inner (middle 2))
+    (outer 100)
+    (inner 3)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(6.0));
+
+    // Assignments through the outer up-value reach the original local.
+    let source = r"
+    (define count-up
+      (lambda (x)
+        (define bump (lambda () (lambda () (set! x (+ x 1)) x)))
+        ((bump))
+        ((bump))
+        x))
+    (count-up 10)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(12.0));
+}
+
+#[test]
+fn test_set_errors() {
+    let table = [
+        ("(set! undefined 1)", "unbound variable \"undefined\""),
+        ("(set! 1 2)", "ill-formed special form \"set!\""),
+        (
+            "(define x 1) (set! x)",
+            "in form 2 (set! x): ill-formed special form \"set!\"",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_assignment_after_capture() {
+    // The parameter is assigned after the closure captured it, with other
+    // frames pushed and popped in between, so the up-value must be closed
+    // with the local's last value, read from its own frame's slot.
+    let source = r"
+    (define identity (lambda (x) x))
+    (define make-getter
+      (lambda (a b)
+        (define get (lambda () (cons a b)))
+        (set! a (identity 10))
+        (identity (identity 0))
+        (set! b (+ a 1))
+        (set! a (+ b 1))
+        get))
+    (define get (make-getter 1 2))
+    (identity (make-getter 3 4))
+    (get)
+    ";
+    assert_eq!(eval_repr(source), "(12 . 11)");
+}
+
+/// Evaluate the source in a fresh environment where `payload` is bound
+/// to a pair, and return a weak reference to the pair once the
+/// environment and everything evaluated in it are dropped.
+fn payload_after_drop(source: &str) -> std::rc::Weak<HandleCell<(Expr, Expr)>> {
+    let env = scheme_engine::new_env().unwrap();
+    let payload = Handle::new((Expr::Number(1.0), Expr::Nil));
+    let weak = payload.downgrade();
+    env.clone()
+        .borrow_mut()
+        .define("payload", Expr::Pair(payload))
+        .unwrap();
+
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let value = scheme_engine::eval(closure).unwrap();
+    assert!(weak.upgrade().is_some(), "payload freed too early");
+
+    drop(value);
+    drop(env);
+    weak
+}
+
+#[test]
+fn test_captured_values_are_freed() {
+    // A closure that keeps the payload alive until it's dropped.
+    let source = r"
+    (define make (lambda (big) (lambda () (car big))))
+    (define keep (make payload))
+    (set! payload #f)
+    (keep)
+    keep
+    ";
+    let weak = payload_after_drop(source);
+    assert!(weak.upgrade().is_none(), "captured value leaked");
+
+    // A local procedure calling itself. Mutually recursive local
+    // procedures still capture each other, and without a cycle
+    // collector they are never freed.
+    let source = r"
+    (define make
+      (lambda (big)
+        (define loop (lambda (n) (if (<= n 0) (car big) (loop (- n 1)))))
+        loop))
+    (define keep (make payload))
+    (set! payload #f)
+    (keep 3)
+    keep
+    ";
+    let weak = payload_after_drop(source);
+    assert!(weak.upgrade().is_none(), "self-recursive closure leaked");
+}
+
+#[test]
+fn test_local_procedure_refers_to_itself() {
+    // Calls by the procedure's own name reach the closure, unless
+    // the local is assigned or the name is hidden by a parameter.
+    let source = r"
+    (define count-down
+      (lambda (start)
+        (define loop (lambda (n) (if (<= n 0) 'done (loop (- n 1)))))
+        (loop start)))
+    (define reassigned
+      (lambda ()
+        (define loop (lambda (n) (if (<= n 0) 'first (loop (- n 1)))))
+        (define first loop)
+        (set! loop (lambda (n) 'second))
+        (first 1)))
+    (define hidden
+      (lambda ()
+        (define loop (lambda (loop) (+ loop 1)))
+        (loop 1)))
+    (cons (count-down 5) (cons (reassigned) (hidden)))
+    ";
+    assert_eq!(eval_repr(source), "(done second . 2)");
+}
+
+/// A lambda capturing `count` variables, bound by two nested lets, that
+/// sums them when called.
+fn capturing_source(count: usize) -> String {
+    let outer = count.min(200);
+    let binding = |index: usize| format!("(v{index} {index})");
+    let outer_bindings: Vec<String> = (0..outer).map(binding).collect();
+    let inner_bindings: Vec<String> = (outer..count).map(binding).collect();
+    // Calls take fewer arguments than a procedure can capture.
+    let names: Vec<String> = (0..count).map(|index| format!("v{index}")).collect();
+    let sums: Vec<String> = names
+        .chunks(100)
+        .map(|chunk| format!("(+ {})", chunk.join(" ")))
+        .collect();
+    format!(
+        "(let ({})
+           (let ({})
+             ((lambda () (+ {})))))",
+        outer_bindings.join(" "),
+        inner_bindings.join(" "),
+        sums.join(" ")
+    )
+}
+
+#[test]
+fn test_up_value_limit() {
+    // Up-values are numbered apart from locals, so a procedure can capture
+    // as many variables as it can declare.
+    let expected = (0..256).sum::<usize>() as f64;
+    assert_eq!(
+        eval(&capturing_source(256)).unwrap(),
+        Expr::Number(expected)
+    );
+
+    for count in [257, 300] {
+        match eval(&capturing_source(count)) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "number of variables captured by a procedure exceeds maximum of 256",
+                "{count}"
+            ),
+            Ok(value) => panic!("expected capture limit error for {count}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_named_let_captures() {
+    // The loop reads a parameter of the enclosing lambda, and each
+    // iteration makes a closure over its own loop variable.
+    let source = r"
+    (define (scaled-adders factor count)
+      (let loop ((i 0) (adders '()))
+        (if (= i count)
+          adders
+          (loop (+ i 1) (cons (lambda (x) (+ x (* i factor))) adders)))))
+    (define adders (scaled-adders 10 3))
+    (cons ((car adders) 1) ((car (cdr (cdr adders))) 1))
+    ";
+    assert_eq!(eval_repr(source), "(21 . 1)");
+
+    // The loop can assign a variable of the enclosing lambda.
+    let source = r"
+    (define (count-evens numbers)
+      (define evens 0)
+      (let loop ((rest numbers))
+        (if (null? rest)
+          evens
+          (let ((even (= (remainder (car rest) 2) 0)))
+            (if even (set! evens (+ evens 1)))
+            (loop (cdr rest))))))
+    (count-evens '(1 2 3 4 6))
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(3.0));
+}
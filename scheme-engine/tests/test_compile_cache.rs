@@ -0,0 +1,84 @@
+use scheme_engine::{CompilationCache, CompileOptions, Expr, Redefinition};
+
+const PRELUDE: &str = r"
+(define counter 0)
+(define (tick) (set! counter (+ counter 1)) counter)
+(tick)
+";
+
+#[test]
+fn test_cache_hit() {
+    let env = scheme_engine::new_env().unwrap();
+    let mut cache = CompilationCache::new();
+
+    assert_eq!(cache.load(&env, PRELUDE).unwrap(), Expr::Number(1.0));
+    assert_eq!((cache.misses(), cache.hits()), (1, 0));
+
+    // Reloading runs the same program again, without compiling it.
+    assert_eq!(cache.load(&env, PRELUDE).unwrap(), Expr::Number(1.0));
+    assert_eq!((cache.misses(), cache.hits()), (1, 1));
+    assert_eq!(cache.len(), 1);
+
+    let first = cache.compile(&env, PRELUDE).unwrap();
+    let second = cache.compile(&env, PRELUDE).unwrap();
+    assert_eq!(
+        scheme_engine::disassemble(first.borrow().procedure(), Some(&env.borrow())),
+        scheme_engine::disassemble(second.borrow().procedure(), Some(&env.borrow())),
+    );
+}
+
+#[test]
+fn test_cache_miss_on_edit() {
+    let env = scheme_engine::new_env().unwrap();
+    let mut cache = CompilationCache::new();
+
+    cache.load(&env, PRELUDE).unwrap();
+    let edited = PRELUDE.replace("(+ counter 1)", "(+ counter 10)");
+    assert_eq!(cache.load(&env, &edited).unwrap(), Expr::Number(10.0));
+    assert_eq!((cache.misses(), cache.hits()), (2, 0));
+
+    // Both versions stay cached.
+    assert_eq!(cache.load(&env, PRELUDE).unwrap(), Expr::Number(1.0));
+    assert_eq!((cache.misses(), cache.hits()), (2, 1));
+}
+
+#[test]
+fn test_cache_per_env() {
+    let mut cache = CompilationCache::new();
+    let first = scheme_engine::new_env().unwrap();
+    let second = scheme_engine::new_env().unwrap();
+
+    cache.load(&first, PRELUDE).unwrap();
+    cache.load(&second, PRELUDE).unwrap();
+    cache.load(&second, PRELUDE).unwrap();
+    assert_eq!((cache.misses(), cache.hits()), (2, 1));
+    assert_eq!(cache.len(), 2);
+
+    // The entries of a dropped environment go with the next miss.
+    drop(first);
+    let third = scheme_engine::new_env().unwrap();
+    cache.load(&third, PRELUDE).unwrap();
+    assert_eq!((cache.misses(), cache.hits()), (3, 1));
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn test_cache_errors_not_cached() {
+    let env = scheme_engine::new_env().unwrap();
+    let options = CompileOptions {
+        redefinition: Redefinition::Forbid,
+        ..CompileOptions::default()
+    };
+    let mut cache = CompilationCache::with_options(options);
+
+    assert!(cache.load(&env, "(car").is_err());
+    assert!(cache.load(&env, "(define z 1) (define z 2)").is_err());
+    assert!(cache.is_empty());
+    assert_eq!((cache.misses(), cache.hits()), (2, 0));
+
+    cache.load(&env, "(define x 1)").unwrap();
+    cache.clear();
+    assert!(cache.is_empty());
+    cache.load(&env, "(define y 1)").unwrap();
+    assert_eq!((cache.misses(), cache.hits()), (4, 0));
+}
@@ -0,0 +1,173 @@
+//! Tests of conditionals calling the core `not`, `and` and `or`, compiled into jumps.
+use scheme_engine::{error::Error, CompileOptions, Expr};
+
+fn assume_core() -> CompileOptions {
+    CompileOptions {
+        assume_core_procedures: true,
+        ..CompileOptions::default()
+    }
+}
+
+fn compile_listing(source: &str, options: &CompileOptions) -> Result<(String, Expr), Error> {
+    let env = scheme_engine::new_env()?;
+    let expr = scheme_engine::parse_program(source)?;
+    let closure = scheme_engine::compile_with_options(env.clone(), &expr, options)?;
+    let listing = scheme_engine::disassemble(closure.borrow().procedure(), Some(&env.borrow()));
+    Ok((listing, scheme_engine::eval(closure)?))
+}
+
+fn instruction_count(source: &str) -> usize {
+    compile_listing(source, &assume_core())
+        .unwrap()
+        .0
+        .lines()
+        .count()
+}
+
+#[test]
+fn test_inverted_jump() {
+    // Negating the test costs nothing over swapping the branches.
+    let table = [
+        ("(if (not x) 1 2)", "(if x 2 1)"),
+        ("(if (not (not x)) 1 2)", "(if x 1 2)"),
+        ("(cond ((not x) 1) (else 2))", "(cond (x 2) (else 1))"),
+    ];
+
+    for (negated, swapped) in table {
+        let negated = format!("(define x #t) {negated}");
+        let swapped = format!("(define x #t) {swapped}");
+        assert_eq!(
+            instruction_count(&negated),
+            instruction_count(&swapped),
+            "{negated}"
+        );
+
+        let (listing, _) = compile_listing(&negated, &assume_core()).unwrap();
+        assert!(!listing.contains("; not"), "{listing}");
+    }
+}
+
+#[test]
+fn test_same_results() {
+    let tests = [
+        "x",
+        "(not x)",
+        "(not (not x))",
+        "(and x y)",
+        "(or x y)",
+        "(not (and x y))",
+        "(not (or x y))",
+        "(and x (or y (not x)))",
+        "(or (and x y) (not y))",
+        "(and (or x y) (or (not x) (not y)))",
+    ];
+    let values = ["#t", "#f", "0", "'()"];
+
+    for test in tests {
+        for x in values {
+            for y in values {
+                let source = format!(
+                    "(define x {x}) (define y {y}) \
+                    (cons (if {test} 'yes 'no) (cond ({test} 'yes) (else 'no)))"
+                );
+                let (_, expected) = compile_listing(&source, &CompileOptions::default()).unwrap();
+                let (listing, value) = compile_listing(&source, &assume_core()).unwrap();
+                assert!(value.is_equal(&expected), "{source}");
+                assert!(!listing.contains("; and"), "{listing}");
+                assert!(!listing.contains("; or"), "{listing}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_short_circuit() {
+    let source = r"
+    (define calls 0)
+    (define (count value) (set! calls (+ calls 1)) value)
+    (if (and (count #f) (count #t)) 'yes 'no)
+    (if (or (count 1) (count #f)) 'yes 'no)
+    (cond ((not (or (count #f) (count #f) (count #t))) 'no) (else 'yes))
+    calls
+    ";
+    let (_, value) = compile_listing(source, &assume_core()).unwrap();
+    assert_eq!(value, Expr::Number(5.0));
+
+    // Called as procedures, every argument is evaluated.
+    let (_, value) = compile_listing(source, &CompileOptions::default()).unwrap();
+    assert_eq!(value, Expr::Number(7.0));
+}
+
+#[test]
+fn test_rebound_names() {
+    // Redefined before compiling.
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse_program("(define (not x) x)").unwrap();
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap();
+    let expr = scheme_engine::parse_program("(if (not #t) 'yes 'no)").unwrap();
+    let closure = scheme_engine::compile_with_options(env.clone(), &expr, &assume_core());
+    let value = scheme_engine::eval(closure.unwrap()).unwrap();
+    assert_eq!(value.write_repr().to_string(), "yes");
+
+    let table = [
+        // Bound by a local variable.
+        (
+            "((lambda (not) (if (not #t) 'yes 'no)) (lambda (x) x))",
+            assume_core(),
+            "yes",
+        ),
+        (
+            "(((lambda (or) (lambda () (if (or #f) 'yes 'no))) (lambda (x) #t)))",
+            assume_core(),
+            "yes",
+        ),
+        // Without the option, calls see redefinitions made while running.
+        (
+            "(define (test) (if (not #t) 'yes 'no)) (define (not x) x) (test)",
+            CompileOptions::default(),
+            "yes",
+        ),
+    ];
+
+    for (source, options, expected) in table {
+        let (_, value) = compile_listing(source, &options).unwrap();
+        assert_eq!(value.write_repr().to_string(), expected, "{source}");
+    }
+}
+
+#[test]
+fn test_ill_formed_cond() {
+    let table = ["(cond (else))", "(cond (#t => car cdr))", "(cond (#t =>))"];
+
+    for source in table {
+        match compile_listing(source, &CompileOptions::default()) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "ill-formed special form \"cond\"",
+                "{source}"
+            ),
+            Ok((_, value)) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_ill_formed_case() {
+    let table = [
+        ("(case)", "ill-formed special form \"case\""),
+        ("(case 1 ((1)))", "ill-formed special form \"case\""),
+        ("(case 1 (1 'one))", "ill-formed special form \"case\""),
+        (
+            "(case 1 ((1) => car cdr))",
+            "ill-formed special form \"case\"",
+        ),
+        ("(case 1 (else 1) ((1) 2))", "else clause must be last"),
+    ];
+
+    for (source, expected) in table {
+        match compile_listing(source, &CompileOptions::default()) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok((_, value)) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
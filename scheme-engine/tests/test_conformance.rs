@@ -0,0 +1,284 @@
+//! Conformance suite of small sample programs.
+//!
+//! Each `.scm` file in [`./conformance`] has a sibling `.expected` file.
+//! Its first line selects what is compared:
+//!
+//! - `mode: value` compares the written result of the program, or
+//!   `error: <message>` when it fails.
+//! - `mode: output` compares the text the program printed.
+//!
+//! Cases for unimplemented features are listed in `conformance/skip.txt`.
+//! They still run, so a listed case that starts passing is reported
+//! and must be removed from the list.
+//!
+//! Each case runs on a budget of [`DEFAULT_FUEL`] instructions, so a case
+//! stuck in a loop fails instead of hanging the suite. A case that needs
+//! more sets its own budget with a comment line at the top of its source:
+//!
+//! ```scheme
+//! ;; fuel: 100000000
+//! ```
+//!
+//! The number of instructions each case executed is printed, which
+//! `cargo test -- --nocapture` shows, to spot a slower compiler or VM.
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use scheme_engine::{Error, EvalOptions, Expr, Limit, Vm};
+
+/// Instructions a case may execute, unless its source sets a budget.
+const DEFAULT_FUEL: u64 = 50_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Value,
+    Output,
+}
+
+fn conformance_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance")
+}
+
+/// The case names in the skip list, without comments.
+fn read_skip_list(dir: &Path) -> BTreeSet<String> {
+    let text = fs::read_to_string(dir.join("skip.txt")).expect("read skip list");
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_expected(text: &str) -> Result<(Mode, &str), String> {
+    let (header, body) = text.split_once('\n').unwrap_or((text, ""));
+    match header.trim() {
+        "mode: value" => Ok((Mode::Value, body.trim_end())),
+        "mode: output" => Ok((Mode::Output, body)),
+        header => Err(format!("unknown header {header:?}")),
+    }
+}
+
+/// The budget set by a `;; fuel: N` line in the comments the source starts with.
+fn fuel_budget(source: &str) -> Result<u64, String> {
+    for line in source.lines().map(str::trim) {
+        if !line.starts_with(';') {
+            break;
+        }
+        if let Some(fuel) = line.trim_start_matches(';').trim().strip_prefix("fuel:") {
+            return fuel
+                .trim()
+                .parse()
+                .map_err(|err| format!("invalid fuel {:?}: {err}", fuel.trim()));
+        }
+    }
+    Ok(DEFAULT_FUEL)
+}
+
+/// The result of running a case's program.
+struct Run {
+    /// The written result, or `error: <message>`.
+    value: String,
+    /// The text the program printed.
+    output: String,
+    instructions: u64,
+}
+
+/// Run the program, or return the budget it ran out of.
+fn run(source: &str, fuel: u64) -> Result<Run, u64> {
+    let output = Rc::new(RefCell::new(String::new()));
+    let mut vm = Vm::new();
+
+    let result = (|| -> Result<Expr, Error> {
+        let mut env = scheme_engine::new_env()?;
+        let sink = output.clone();
+        env.borrow_mut()
+            .set_printer(move |text| sink.borrow_mut().push_str(text));
+
+        let expr = scheme_engine::parse(source, true)?;
+        let closure = scheme_engine::compile(env.clone(), &expr)?;
+        let options = EvalOptions {
+            fuel: Some(fuel),
+            ..EvalOptions::default()
+        };
+        vm.eval_with_options(closure, &options)
+    })();
+
+    let value = match result {
+        Ok(value) => value.write_repr().to_string(),
+        Err(Error::Limit(Limit::Fuel(fuel))) => return Err(fuel),
+        Err(err) => format!("error: {err}"),
+    };
+    Ok(Run {
+        value,
+        output: output.take(),
+        instructions: vm.stats().instructions,
+    })
+}
+
+/// Line by line differences between the expected and actual text.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let mut text = String::new();
+    for index in 0..expected.len().max(actual.len()) {
+        match (expected.get(index), actual.get(index)) {
+            (Some(e), Some(a)) if e == a => text.push_str(&format!("  {e}\n")),
+            (e, a) => {
+                if let Some(e) = e {
+                    text.push_str(&format!("- {e}\n"));
+                }
+                if let Some(a) = a {
+                    text.push_str(&format!("+ {a}\n"));
+                }
+            }
+        }
+    }
+    text
+}
+
+/// How a case compared to its expected result.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Passed,
+    /// Failed, as expected of a case in the skip list.
+    Skipped,
+    Failed(String),
+}
+
+/// Run the case in the directory, returning its outcome and the number
+/// of instructions it executed.
+fn check_case(dir: &Path, name: &str, skipped: bool) -> (Outcome, u64) {
+    let source = fs::read_to_string(dir.join(format!("{name}.scm"))).expect("read source");
+    let expected_text = match fs::read_to_string(dir.join(format!("{name}.expected"))) {
+        Ok(text) => text,
+        Err(err) => {
+            let failure = format!("{name}: missing .expected file: {err}");
+            return (Outcome::Failed(failure), 0);
+        }
+    };
+    let (mode, expected) = match parse_expected(&expected_text) {
+        Ok(expected) => expected,
+        Err(err) => return (Outcome::Failed(format!("{name}.expected: {err}")), 0),
+    };
+    let fuel = match fuel_budget(&source) {
+        Ok(fuel) => fuel,
+        Err(err) => return (Outcome::Failed(format!("{name}.scm: {err}")), 0),
+    };
+
+    // Running out fails even a skipped case, since a loop that never ends
+    // is a bug of its own.
+    let run = match run(&source, fuel) {
+        Ok(run) => run,
+        Err(fuel) => {
+            let failure = format!("{name}: fuel exhausted after {fuel} instructions in {name}.scm");
+            return (Outcome::Failed(failure), fuel);
+        }
+    };
+    let actual = match mode {
+        Mode::Value => &run.value,
+        Mode::Output => &run.output,
+    };
+    let matches = actual == expected;
+
+    let outcome = match (skipped, matches) {
+        (false, true) => Outcome::Passed,
+        (false, false) => {
+            let mut failure = format!("{name}: ({mode:?} mode)\n{}", diff(expected, actual));
+            if mode == Mode::Output && run.value.starts_with("error: ") {
+                failure.push_str(&format!("  {}\n", run.value));
+            }
+            Outcome::Failed(failure)
+        }
+        (true, false) => Outcome::Skipped,
+        (true, true) => Outcome::Failed(format!("{name}: passes, remove it from the skip list")),
+    };
+    (outcome, run.instructions)
+}
+
+#[test]
+fn test_conformance() {
+    let dir = conformance_dir();
+    let skip_list = read_skip_list(&dir);
+
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .expect("read conformance directory")
+        .filter_map(|entry| {
+            let path = entry.expect("directory entry").path();
+            match path.extension() {
+                Some(extension) if extension == "scm" => {
+                    Some(path.file_stem()?.to_string_lossy().into_owned())
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    names.sort();
+
+    let mut failures = Vec::new();
+    let mut passed = 0;
+    let mut skipped = 0;
+
+    for name in &names {
+        let (outcome, instructions) = check_case(&dir, name, skip_list.contains(name));
+        println!("{name}: {instructions} instructions");
+        match outcome {
+            Outcome::Passed => passed += 1,
+            Outcome::Skipped => skipped += 1,
+            Outcome::Failed(failure) => failures.push(failure),
+        }
+    }
+
+    for name in &skip_list {
+        if !names.contains(name) {
+            failures.push(format!(
+                "{name}: listed in the skip list, but there is no such case"
+            ));
+        }
+    }
+
+    println!(
+        "conformance: {passed} passed, {skipped} skipped, {} failed, of {} cases",
+        failures.len(),
+        names.len()
+    );
+    assert!(
+        failures.is_empty(),
+        "{} conformance failures:\n\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_fuel_budget() {
+    assert_eq!(fuel_budget("(+ 1 2)"), Ok(DEFAULT_FUEL));
+    assert_eq!(fuel_budget(";; Loops.\n;; fuel: 500\n(+ 1 2)"), Ok(500));
+    // Only the comments at the start set the budget.
+    assert_eq!(fuel_budget("(+ 1 2)\n;; fuel: 500"), Ok(DEFAULT_FUEL));
+    assert!(fuel_budget(";; fuel: lots").is_err());
+}
+
+#[test]
+fn test_case_out_of_fuel() {
+    // Not in the conformance directory, since it's meant to fail.
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/harness");
+    assert_eq!(
+        check_case(&dir, "infinite_loop", false),
+        (
+            Outcome::Failed(
+                "infinite_loop: fuel exhausted after 1000 instructions in infinite_loop.scm"
+                    .to_string()
+            ),
+            1000
+        )
+    );
+    // Even when it's expected to fail.
+    assert!(matches!(
+        check_case(&dir, "infinite_loop", true).0,
+        Outcome::Failed(_)
+    ));
+}
@@ -0,0 +1,331 @@
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+use scheme_engine::{error::Error, CompileOptions, Env, Expr, Handle};
+
+fn run(source: &str) -> Result<Expr, Error> {
+    let env = scheme_engine::new_env()?;
+    let expr = scheme_engine::parse(source, true)?;
+    let closure = scheme_engine::compile(env.clone(), &expr)?;
+    scheme_engine::eval(closure)
+}
+
+fn definition_error(source: &str) -> String {
+    match run(source) {
+        Err(Error::Reason(message)) => message,
+        other => panic!("expected definition error, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_define_top_level() {
+    let value = run("(define x 2) (+ x 1)").expect("evaluation");
+    assert_eq!(value, Expr::Number(3.0));
+}
+
+#[test]
+fn test_define_internal() {
+    let source = r"
+    (define f (lambda (a)
+      (define b (+ a 1))
+      (define c (+ b 1))
+      (+ a b c)))
+    (f 1)";
+    let value = run(source).expect("evaluation");
+    assert_eq!(value, Expr::Number(6.0));
+}
+
+#[test]
+fn test_define_in_call_argument() {
+    let message = definition_error("(+ 1 (define x 2))");
+    assert!(
+        message.starts_with("definitions are not allowed in expression context"),
+        "{message}"
+    );
+    assert!(message.contains("(define x 2)"), "{message}");
+}
+
+#[test]
+fn test_define_in_if_branch() {
+    let message = definition_error("(if #t (define x 2) 3)");
+    assert!(
+        message.starts_with("definitions are not allowed in expression context"),
+        "{message}"
+    );
+}
+
+#[test]
+fn test_define_in_cond_clause() {
+    let message = definition_error("(cond (#t (define x 2)))");
+    assert!(
+        message.starts_with("definitions are not allowed in expression context"),
+        "{message}"
+    );
+}
+
+#[test]
+fn test_define_in_top_level_begin() {
+    // The definitions are spliced into the program, defining globals.
+    let env = scheme_engine::new_env().unwrap();
+    let source = "(begin (define x 1) (define (y) (+ x 1))) (begin) (+ x (y))";
+    assert_eq!(run_in(&env, source).unwrap(), Expr::Number(3.0));
+    assert_eq!(env.borrow().lookup_var("x"), Some(&Expr::Number(1.0)));
+    assert!(matches!(
+        env.borrow().lookup_var("y"),
+        Some(Expr::Closure(_))
+    ));
+}
+
+#[test]
+fn test_define_in_nested_begin() {
+    let message = definition_error("(if #t (begin (define x 2) x) 3)");
+    assert!(
+        message.starts_with("definitions are not allowed in expression context"),
+        "{message}"
+    );
+
+    // Only a top-level `begin` may be empty.
+    assert_eq!(
+        definition_error("(if #t (begin) 3)"),
+        "ill-formed special form \"begin\""
+    );
+}
+
+#[test]
+fn test_error_names_enclosing_definitions() {
+    let source = r"
+    (define square (lambda (n) (* n n)))
+
+    (define outer
+      (lambda (n)
+        (define inner
+          (lambda (n1)
+            (+ n1 n2)))
+        (inner n)))
+    ";
+    let err = run(source).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"in form 2 (define outer (lambda (n) (define inner (lambda (n1) (+ n1 n..., definition of 'outer' > 'inner': unbound variable "n2""#
+    );
+    match err {
+        Error::Compile { definitions, .. } => assert_eq!(definitions, ["'outer'", "'inner'"]),
+        other => panic!("expected compile error, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_error_locates_definition_in_named_source() {
+    let source = "\
+(define (inner) 'unrelated)
+
+(define (outer n)
+  (define (inner n1)
+    (+ n1 n2))
+  (inner n))
+";
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse_program_named(
+        env.clone().borrow_mut().sources_mut(),
+        "nested.scm",
+        source,
+    )
+    .unwrap();
+    let options = CompileOptions {
+        source_name: Some("nested.scm".to_string()),
+        ..CompileOptions::default()
+    };
+    let err = scheme_engine::compile_with_options(env, &expr, &options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"in form 2 (define outer (lambda (n) (define inner (lambda (n1) (+ n1 n..., definition of 'outer' > 'inner' at 4:3: unbound variable "n2""#
+    );
+    match err {
+        Error::Compile { location, .. } => assert_eq!(location, Some((4, 3))),
+        other => panic!("expected compile error, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_error_in_anonymous_lambda() {
+    let table = [
+        (
+            "(define twice (lambda (f) (f (f 1)))) (define run (twice (lambda (x x) x)))",
+            r#"in form 2 (define run (twice (lambda (x x) x))), definition of 'run' > lambda: duplicate definition of local variable "x""#,
+        ),
+        (
+            "(define limit (+ 1 max-limit))",
+            r#"in definition of 'limit': unbound variable "max-limit""#,
+        ),
+        (
+            "((lambda () (undefined-procedure)))",
+            r#"unbound variable "undefined-procedure""#,
+        ),
+    ];
+
+    for (source, expected) in table {
+        match run(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_define_procedure() {
+    let source = r"
+    (define (square n) (* n n))
+    (define (sum . numbers) (apply + numbers))
+    (define (fact n)
+      (if (<= n 1)
+        1
+        (* n (fact (- n 1)))))
+    (define (thunk) 7)
+    (+ (square 3) (sum 1 2 3) (fact 5) (thunk))
+    ";
+    assert_eq!(run(source).unwrap(), Expr::Number(9.0 + 6.0 + 120.0 + 7.0));
+
+    // The name of a curried definition is a procedure returning a procedure.
+    let source = r"
+    (define ((adder n) m) (+ n m))
+    (define add5 (adder 5))
+    (define (((digits a) b) c) (+ (* 100 a) (* 10 b) c))
+    (cons (add5 10) (cons ((adder 1) 2) (((digits 1) 2) 3)))
+    ";
+    assert_eq!(
+        run(source).unwrap().write_repr().to_string(),
+        "(15 3 . 123)"
+    );
+}
+
+#[test]
+fn test_malformed_formals() {
+    let table = [
+        (
+            "(define (5) 1)",
+            "expected identifier as procedure name, found number 5",
+        ),
+        (
+            r#"(define ("f" x) 1)"#,
+            r#"expected identifier as procedure name, found string "f""#,
+        ),
+        (
+            "(define 5 1)",
+            "expected identifier or list as define target, found number 5",
+        ),
+        (
+            "(define () 1)",
+            "expected procedure name in define, found ()",
+        ),
+        (
+            "(lambda (1 x) x)",
+            "expected identifier in formals list, found number 1",
+        ),
+        (
+            r#"(lambda ("a") a)"#,
+            r#"expected identifier in formals list, found string "a""#,
+        ),
+        (
+            "(lambda 5 x)",
+            "expected formals list or identifier, found number 5",
+        ),
+        (
+            "(define (f 1) 1)",
+            "in definition of 'f': expected identifier in formals list, found number 1",
+        ),
+        (
+            "(define ((f a) (b)) 1)",
+            "in definition of 'f' > lambda: expected identifier in formals list, found list (b)",
+        ),
+        (
+            "(define ((5 a) b) 1)",
+            "expected identifier as procedure name, found number 5",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match run(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+/// Evaluate the program, collecting the name and kind of value of each
+/// definition seen by the define hook.
+fn run_with_define_hook(source: &str) -> (Result<Expr, Error>, Vec<(String, String)>) {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut env = scheme_engine::new_env().unwrap();
+    let sink = seen.clone();
+    env.borrow_mut().set_define_hook(move |name, value| {
+        sink.borrow_mut()
+            .push((name.to_string(), value.kind().name().to_string()));
+    });
+
+    let result = run_in(&env, source);
+    let seen = seen.take();
+    (result, seen)
+}
+
+fn run_in(env: &Handle<Env>, source: &str) -> Result<Expr, Error> {
+    let expr = scheme_engine::parse(source, true)?;
+    let closure = scheme_engine::compile(env.clone(), &expr)?;
+    scheme_engine::eval(closure)
+}
+
+#[test]
+fn test_define_hook() {
+    let source = r#"
+    (define counter 0)
+    (define (on-tick) (set! counter (+ counter 1)))
+    (define-values (label . rest) (values "tick" 1 2))
+    (set! counter 10)
+    (on-tick)
+    (define (f) (define local 1) local)
+    (f)
+    (set! label "tock")
+    counter
+    "#;
+    let (result, seen) = run_with_define_hook(source);
+    assert_eq!(result.unwrap(), Expr::Number(11.0));
+
+    let expected = [
+        ("counter", "number"),
+        ("on-tick", "procedure"),
+        // The values are taken off the stack, last one first.
+        ("rest", "list"),
+        ("label", "string"),
+        ("f", "procedure"),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(name, kind)| (name.to_string(), kind.to_string()))
+        .collect();
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn test_define_hook_panic() {
+    let mut env = scheme_engine::new_env().unwrap();
+    env.borrow_mut().set_define_hook(|name, _| {
+        if name == "bad" {
+            panic!("rejected {name}");
+        }
+    });
+
+    let panicked = panic::catch_unwind(AssertUnwindSafe(|| run_in(&env, "(define bad 1) 2")));
+    assert!(panicked.is_err());
+
+    // The definition was stored before the hook ran, and evaluation goes on.
+    assert_eq!(
+        run_in(&env, "(define good 2) (+ bad good)").unwrap(),
+        Expr::Number(3.0)
+    );
+
+    env.borrow_mut().clear_define_hook();
+    assert_eq!(
+        run_in(&env, "(define bad 3) bad").unwrap(),
+        Expr::Number(3.0)
+    );
+}
@@ -0,0 +1,79 @@
+use scheme_engine::Expr;
+
+#[test]
+fn test_define_procedure() {
+    let env = scheme_engine::new_env().unwrap();
+    let square = scheme_engine::define_procedure(&env, "square", "(lambda (x) (* x x))").unwrap();
+    let factorial = scheme_engine::define_procedure(
+        &env,
+        "factorial",
+        "(define (factorial n) (if (= n 0) 1 (* n (factorial (- n 1)))))",
+    )
+    .unwrap();
+
+    // Called from the host.
+    assert_eq!(
+        scheme_engine::call(square, &[Expr::Number(7.0)]).unwrap(),
+        Expr::Number(49.0)
+    );
+    assert_eq!(
+        scheme_engine::call(factorial, &[Expr::Number(5.0)]).unwrap(),
+        Expr::Number(120.0)
+    );
+
+    // Called from Scheme evaluated later.
+    let expr = scheme_engine::parse("(square (factorial 3))", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    assert_eq!(scheme_engine::eval(closure).unwrap(), Expr::Number(36.0));
+}
+
+#[test]
+fn test_define_procedure_errors() {
+    let table = [
+        (
+            "f",
+            "42",
+            "define-procedure: expected a lambda expression or define, found 42",
+        ),
+        (
+            "f",
+            "(lambda (x) x) (lambda (y) y)",
+            "define-procedure: expected a single lambda expression or define, found 2 forms",
+        ),
+        (
+            "f",
+            "(define (g x) x)",
+            "define-procedure: source defines 'g', expected 'f'",
+        ),
+        (
+            "f",
+            "(define f 1)",
+            "define-procedure: expected a procedure definition, found (define f 1)",
+        ),
+        (
+            "f",
+            "(+ 1 2)",
+            "define-procedure: expected a lambda expression or define, found (+ 1 2)",
+        ),
+        (
+            "f",
+            "(lambda (x)",
+            "expected ')' but found end-of-file at 1:12",
+        ),
+        (
+            "f",
+            "(lambda (1) 1)",
+            "in definition of 'f': expected identifier in formals list, found number 1",
+        ),
+    ];
+
+    for (name, source, expected) in table {
+        let env = scheme_engine::new_env().unwrap();
+        let err = scheme_engine::define_procedure(&env, name, source).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{source}");
+        assert!(
+            !matches!(env.borrow().lookup_var(name), Some(Expr::Closure(_))),
+            "{source}"
+        );
+    }
+}
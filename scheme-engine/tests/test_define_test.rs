@@ -0,0 +1,63 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use scheme_engine::Expr;
+
+/// Evaluate a program, capturing what it printed.
+fn eval_captured(source: &str) -> (scheme_engine::Result<Expr>, String) {
+    let env = scheme_engine::new_env().unwrap();
+    let output = Rc::new(RefCell::new(String::new()));
+    let sink = output.clone();
+    env.clone()
+        .borrow_mut()
+        .set_printer(move |text| sink.borrow_mut().push_str(text));
+
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let result = scheme_engine::eval(closure);
+
+    let output = output.take();
+    (result, output)
+}
+
+#[test]
+fn test_failure_does_not_stop_later_tests() {
+    let source = r#"
+    (define-test "addition" (assert-eq (+ 1 2) 3))
+    (define-test "broken" (assert-eq (+ 1 1) 3))
+    (define-test "comparison"
+      (assert (< 1 2))
+      (assert (> 2 1)))
+    (run-tests)
+    "#;
+    let (result, output) = eval_captured(source);
+
+    assert_eq!(result.unwrap(), Expr::Bool(false));
+    assert!(output.contains("test addition ... ok"), "{output}");
+    assert!(output.contains("test broken ... FAILED: "), "{output}");
+    assert!(output.contains("test comparison ... ok"), "{output}");
+    assert!(output.ends_with("test result: 2/3 passed\n"), "{output}");
+}
+
+#[test]
+fn test_all_passed() {
+    let source = r#"
+    (define square (lambda (x) (* x x)))
+    (define-test "square" (assert-eq (square 3) 9))
+    (run-tests)
+    "#;
+    let (result, output) = eval_captured(source);
+
+    assert_eq!(result.unwrap(), Expr::Bool(true));
+    assert!(output.ends_with("test result: 1/1 passed\n"), "{output}");
+}
+
+#[test]
+fn test_ill_formed() {
+    let env = scheme_engine::new_env().unwrap();
+    for source in [r#"(define-test "empty")"#, "(define-test name (+ 1 2))"] {
+        let expr = scheme_engine::parse(source, true).unwrap();
+        let err = scheme_engine::compile(env.clone(), &expr).unwrap_err();
+        assert!(err.to_string().contains("define-test"), "{err}");
+    }
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::eval;
+use scheme_engine::Expr;
+
+#[test]
+fn test_do_loop() {
+    let table = [
+        ("(do ((i 0 (+ i 1))) ((= i 5) i))", Expr::Number(5.0)),
+        // Steps see the variables of the previous iteration.
+        (
+            "(do ((a 1 b) (b 2 a) (n 0 (+ n 1))) ((= n 3) (+ (* a 10) b)))",
+            Expr::Number(21.0),
+        ),
+        // Variables without a step keep their value.
+        (
+            "(do ((i 0 (+ i 1)) (limit 3)) ((= i limit) (* i limit)))",
+            Expr::Number(9.0),
+        ),
+        // Commands run for effect, before the variables are stepped.
+        (
+            "(define count 0) (do ((i 0 (+ i 1))) ((= i 4)) (set! count (+ count i))) count",
+            Expr::Number(6.0),
+        ),
+        ("(do ((i 0 (+ i 1))) (#t))", Expr::Void),
+        // The test runs before the first iteration.
+        (
+            "(do ((i 10 (car i))) ((> i 0) 'done))",
+            Expr::Ident("done".into()),
+        ),
+    ];
+
+    for (source, expected) in table {
+        assert_eq!(eval(source).unwrap(), expected, "{source}");
+    }
+}
+
+#[test]
+fn test_do_in_procedure() {
+    let source = r"
+    (define sum-to
+      (lambda (n)
+        (do ((i 1 (+ i 1))
+             (sum 0 (+ sum i)))
+            ((> i n) sum))))
+    (assert-eq (sum-to 10) 55)
+    (sum-to 100)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(5050.0));
+}
+
+#[test]
+fn test_ill_formed_do() {
+    for source in ["(do)", "(do ((i)) (#t))", "(do ((1 2)) (#t))", "(do () ())"] {
+        match eval(source) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "ill-formed special form \"do\"",
+                "{source}"
+            ),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::rc::Rc;
+
+use scheme_engine::{Env, EnvLimits, Expr, Handle};
+
+fn run(env: &Handle<Env>, source: &str) -> Expr {
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    scheme_engine::eval(closure).unwrap()
+}
+
+#[test]
+fn test_envs_are_independent() {
+    let first = scheme_engine::new_env().unwrap();
+    let second = scheme_engine::new_env().unwrap();
+
+    // Definitions stay in their own environment.
+    run(&first, "(define answer 42)");
+    assert_eq!(run(&first, "answer"), Expr::Number(42.0));
+    assert!(second.borrow().lookup_var("answer").is_none());
+
+    // Rebinding a core native doesn't reach the other environment,
+    // or environments created later.
+    run(&first, "(set! + -)");
+    assert_eq!(run(&first, "(+ 5 3)"), Expr::Number(2.0));
+    assert_eq!(run(&second, "(+ 5 3)"), Expr::Number(8.0));
+    let third = scheme_engine::new_env().unwrap();
+    assert_eq!(run(&third, "(+ 5 3)"), Expr::Number(8.0));
+
+    // Host bindings too.
+    fn seven(_env: &mut Env, _args: &[Expr]) -> scheme_engine::Result<Expr> {
+        Ok(Expr::Number(7.0))
+    }
+    second
+        .clone()
+        .borrow_mut()
+        .bind_native_func("seven", seven)
+        .unwrap();
+    assert_eq!(run(&second, "(seven)"), Expr::Number(7.0));
+    assert!(first.borrow().lookup_var("seven").is_none());
+    assert!(third.borrow().lookup_var("seven").is_none());
+}
+
+#[test]
+fn test_template_matches_core() {
+    let mut bound = Env::new();
+    scheme_engine::init_core(&mut bound).unwrap();
+    let env = scheme_engine::new_env().unwrap();
+    let env = env.borrow();
+
+    for name in ["+", "display", "car", "map", "vm-stats", "run-tests"] {
+        let symbol = bound.resolve_var(name).unwrap();
+        assert_eq!(env.resolve_var(name), Some(symbol), "{name}");
+        assert_eq!(
+            env.is_effectful(symbol),
+            bound.is_effectful(symbol),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn test_host_state_in_variables() {
+    // Counts the calls from the program, keeping the count in the environment.
+    fn tick(env: &mut Env, _args: &[Expr]) -> scheme_engine::Result<Expr> {
+        let count = env.get_or_define("tick-count", || Expr::Number(0.0))?;
+        *count = Expr::Number(count.as_number().unwrap_or(0.0) + 1.0);
+        Ok(count.clone())
+    }
+
+    let env = scheme_engine::new_env().unwrap();
+    env.clone()
+        .borrow_mut()
+        .bind_native_func("tick", tick)
+        .unwrap();
+
+    run(&env, "(tick) (tick)");
+    assert_eq!(run(&env, "(tick)"), Expr::Number(3.0));
+
+    // The host resets the count, taking the old value.
+    let mut env_mut = env.clone();
+    let mut env_mut = env_mut.borrow_mut();
+    let symbol = env_mut.resolve_var("tick-count").unwrap();
+    assert_eq!(env_mut.take_var(symbol), Some(Expr::Number(3.0)));
+    env_mut.update(symbol, |_| Expr::Number(10.0)).unwrap();
+    drop(env_mut);
+    assert_eq!(run(&env, "(tick)"), Expr::Number(11.0));
+}
+
+#[test]
+fn test_symbol_limit() {
+    let env = scheme_engine::new_env().unwrap();
+    let core_count = env.borrow().iter_vars_sorted().count();
+    env.clone().borrow_mut().set_limits(EnvLimits {
+        max_symbols: Some(core_count + 100),
+        ..EnvLimits::default()
+    });
+
+    // Distinct definitions, one program each, until one fails.
+    let mut defined = 0;
+    let err = loop {
+        let form = Expr::List(Rc::from([
+            Expr::Ident("define".into()),
+            Expr::Ident(format!("var-{defined}").into()),
+            Expr::Number(defined as f64),
+        ]));
+        match scheme_engine::compile(env.clone(), &form) {
+            Ok(closure) => scheme_engine::eval(closure).unwrap(),
+            Err(err) => break err,
+        };
+        defined += 1;
+    };
+    assert_eq!(defined, 100);
+    assert_eq!(
+        err.to_string(),
+        format!("environment symbol limit exceeded ({})", core_count + 100)
+    );
+
+    // What was defined can still be read, and assigned.
+    assert_eq!(run(&env, "(+ var-0 var-99)"), Expr::Number(99.0));
+    run(&env, "(set! var-0 10)");
+    assert_eq!(run(&env, "var-0"), Expr::Number(10.0));
+
+    // Natives are bound within the same limit.
+    let bound = env
+        .clone()
+        .borrow_mut()
+        .bind_native_func("extra", |_, _| Ok(Expr::Void));
+    assert!(bound.is_err());
+}
+
+#[test]
+fn test_procedure_limit() {
+    let env = scheme_engine::new_env().unwrap();
+    env.clone().borrow_mut().set_limits(EnvLimits {
+        max_procedures: Some(2),
+        ..EnvLimits::default()
+    });
+
+    run(
+        &env,
+        "(define (double x) (* x 2)) (define (triple x) (* x 3))",
+    );
+    let program = scheme_engine::parse("(define (square x) (* x x))", true).unwrap();
+    let err = scheme_engine::compile(env.clone(), &program).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "in definition of 'square': environment procedure limit exceeded (2)"
+    );
+
+    assert_eq!(run(&env, "(triple (double 1))"), Expr::Number(6.0));
+}
@@ -0,0 +1,62 @@
+use scheme_engine::{Env, Expr, Handle};
+
+fn env_with(source: &str) -> Handle<Env> {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(source, true).unwrap();
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap();
+    env
+}
+
+fn names<'a>(vars: impl Iterator<Item = (&'a str, &'a Expr)>) -> Vec<&'a str> {
+    vars.map(|(name, _)| name).collect()
+}
+
+#[test]
+fn test_sorted_listing() {
+    let env = env_with("(define c 3) (define a 1) (define b 2)");
+    let env = env.borrow();
+    assert_eq!(names(env.iter_user_vars()), ["a", "b", "c"]);
+
+    let all = names(env.iter_vars_sorted());
+    assert!(all.is_sorted(), "{all:?}");
+    assert!(all.contains(&"car") && all.contains(&"c"));
+}
+
+#[test]
+fn test_user_and_core_partition() {
+    let env = env_with("(define total 0) (define reverse (lambda (x) x))");
+    let env = env.borrow();
+
+    // Redefining a core procedure is the user's definition.
+    assert_eq!(names(env.iter_user_vars()), ["reverse", "total"]);
+
+    let core = names(env.iter_core_vars());
+    assert!(core.contains(&"car") && core.contains(&"reverse"));
+    assert!(!core.contains(&"total"));
+}
+
+#[test]
+fn test_environment_alist() {
+    let env = env_with("(define c 3) (define a 1)");
+    let eval = |source: &str| {
+        let expr = scheme_engine::parse(source, true).unwrap();
+        scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap()
+    };
+
+    let user = eval("(environment->alist 'user)");
+    assert_eq!(user.repr().to_string(), "((a . 1) (c . 3))");
+
+    let all = eval("(environment->alist)");
+    assert!(all.as_slice().unwrap().len() > 2);
+}
+
+#[test]
+fn test_environment_alist_errors() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("(environment->alist 'mine)", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    assert_eq!(
+        scheme_engine::eval(closure).unwrap_err().to_string(),
+        "environment->alist: expected all, user or core as argument 1, got mine"
+    );
+}
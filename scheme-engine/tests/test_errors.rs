@@ -0,0 +1,76 @@
+//! Pinned error messages from the core library.
+mod common;
+
+use common::eval;
+use scheme_engine::{Expr, ExprKind};
+
+#[test]
+fn test_native_error_messages() {
+    let table = [
+        (
+            "(< 1 '(1 2))",
+            "<: expected number as argument 2, got (1 2)",
+        ),
+        ("(= 1 2 #f)", "=: expected number as argument 3, got #f"),
+        (
+            r#"(number->string 10 "2")"#,
+            r#"number->string: expected number as argument 2, got "2""#,
+        ),
+        (
+            r#"(format "out" "~a" 1)"#,
+            r#"format: expected boolean as argument 1, got "out""#,
+        ),
+        (
+            "(format #f 'x)",
+            "format: expected string as argument 2, got x",
+        ),
+        (
+            "(assert #f 42)",
+            "assert: expected string as argument 2, got 42",
+        ),
+        (
+            "(disassemble +)",
+            "disassemble: expected closure as argument 1, got <native-function>",
+        ),
+        (
+            "(not)",
+            "not: wrong number of arguments, expected 1 but got 0",
+        ),
+        (
+            "(display 1 2 3)",
+            "display: wrong number of arguments, expected 1 or 2 but got 3",
+        ),
+        (
+            "(display 1 2)",
+            "display: expected output port as argument 2, got 2",
+        ),
+        (
+            "(vm-stats 1)",
+            "vm-stats: wrong number of arguments, expected 0 but got 1",
+        ),
+        ("(1 2)", "expected procedure to call, got number 1"),
+        (
+            "(map \"f\" '(1))",
+            "map: expected procedure as argument 1, got \"f\"",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_type_name() {
+    assert_eq!(Expr::Number(1.0).type_name(), "number");
+    assert_eq!(Expr::String("a".into()).type_name(), "string");
+    assert_eq!(Expr::Char('a').type_name(), "char");
+    assert_eq!(Expr::Nil.type_name(), "null");
+    assert_eq!(Expr::Void.type_name(), "void");
+    assert_eq!(Expr::Ident("a".into()).type_name(), "symbol");
+    assert_eq!(Expr::Ident("a".into()).kind(), ExprKind::Symbol);
+    assert_eq!(ExprKind::NativeFunc.name(), "procedure");
+}
@@ -0,0 +1,17 @@
+#[test]
+fn test_eval_to_string() {
+    assert_eq!(scheme_engine::eval_to_string("(+ 1 2)"), "3");
+    assert_eq!(
+        scheme_engine::eval_to_string(r#"(display "hello") (newline) "done""#),
+        "hello\n\"done\""
+    );
+    assert_eq!(scheme_engine::eval_to_string("(display 1)"), "1");
+    assert_eq!(
+        scheme_engine::eval_to_string("(+ 1 #t)"),
+        "error: +: expected number as argument 2, got #t"
+    );
+    assert_eq!(
+        scheme_engine::eval_to_string("(+ 1"),
+        "error: expected ')' but found end-of-file at 1:5"
+    );
+}
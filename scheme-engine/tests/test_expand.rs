@@ -0,0 +1,214 @@
+//! The compiler's stages, run separately.
+use scheme_engine::{Closure, CompileOptions, Env, Expr, Handle};
+
+const SCRIPTS: [&str; 15] = [
+    include_str!("language/boolean.scm"),
+    include_str!("language/bytevector.scm"),
+    include_str!("language/conditionals.scm"),
+    include_str!("language/define.scm"),
+    include_str!("language/evaluation_order.scm"),
+    include_str!("language/format.scm"),
+    include_str!("language/lambda.scm"),
+    include_str!("language/list.scm"),
+    include_str!("language/macros.scm"),
+    include_str!("language/memoize.scm"),
+    include_str!("language/number.scm"),
+    include_str!("language/numeric_edges.scm"),
+    include_str!("language/numeric_tower.scm"),
+    include_str!("language/string_port.scm"),
+    include_str!("language/vector.scm"),
+];
+
+/// Bytecode listing of the program's top-level procedure.
+fn listing(env: &Handle<Env>, closure: &Handle<Closure>) -> String {
+    scheme_engine::disassemble(closure.borrow().procedure(), Some(&env.borrow()))
+}
+
+#[test]
+fn test_stages_match_compile() {
+    let options = CompileOptions::default();
+
+    for source in SCRIPTS {
+        let expr = scheme_engine::parse_program(source).unwrap();
+
+        let env = scheme_engine::new_env().unwrap();
+        let direct = scheme_engine::compile(env.clone(), &expr).unwrap();
+        let direct_listing = listing(&env, &direct);
+        scheme_engine::eval(direct).unwrap();
+
+        let env = scheme_engine::new_env().unwrap();
+        let expanded = scheme_engine::expand(&env.borrow(), &expr).unwrap();
+        let (staged, _) = scheme_engine::compile_core(env.clone(), &expanded, &options).unwrap();
+        assert_eq!(listing(&env, &staged), direct_listing);
+        scheme_engine::eval(staged).unwrap();
+    }
+}
+
+#[test]
+fn test_compile_deterministic() {
+    for source in SCRIPTS {
+        let expr = scheme_engine::parse_program(source).unwrap();
+        let listings: Vec<String> = (0..2)
+            .map(|_| {
+                let env = scheme_engine::new_env().unwrap();
+                let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+                listing(&env, &closure)
+            })
+            .collect();
+        assert_eq!(listings[0], listings[1]);
+    }
+}
+
+#[test]
+fn test_derived_forms() {
+    let source = r"
+    (define (sum-to n)
+      (let loop ((i 0) (total 0))
+        (if (> i n)
+          total
+          (loop (+ i 1) (+ total i)))))
+    (define (swap pair)
+      (let ((a (car pair)) (b (cdr pair)))
+        (cons b a)))
+    (define (chain x)
+      (let* ((y (* x 2)) (z (+ y 1)))
+        z))
+    (define (count-down n)
+      (letrec ((loop (lambda (n) (if (= n 0) 'done (loop (- n 1))))))
+        (loop n)))
+    (cons (sum-to 10) (cons (swap (cons 1 2)) (cons (chain 3) (count-down 7))))
+    ";
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse_program(source).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let value = scheme_engine::eval(closure).unwrap();
+    assert_eq!(value.write_repr().to_string(), "(55 (2 . 1) 7 . done)");
+}
+
+#[test]
+fn test_let_star_shadowing() {
+    // Each binding of let* sees the ones before it, shadowing the outer let
+    // and the global, which are left as they were once the body is done.
+    let source = r"
+    (define x 10)
+    (let ((y 20))
+      (cons (+ 100 (let* ((x 1) (y (+ x y)) (x (+ x y)))
+                     (* x y)))
+            (cons x y)))
+    ";
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse_program(source).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let value = scheme_engine::eval(closure).unwrap();
+    assert_eq!(value.write_repr().to_string(), "(562 10 . 20)");
+}
+
+#[test]
+fn test_core_compiler_rejects_derived_forms() {
+    let table = [
+        (
+            "(let ((a 1)) a)",
+            "let is a derived form, expand the program before compiling it",
+        ),
+        (
+            "(define (f) 1)",
+            "procedure definition is a derived form, expand the program before compiling it",
+        ),
+        (
+            "(let-syntax ((one (syntax-rules () ((one) 1)))) (one))",
+            "let-syntax is a derived form, expand the program before compiling it",
+        ),
+    ];
+
+    for (source, expected) in table {
+        let env = scheme_engine::new_env().unwrap();
+        let expr = scheme_engine::parse_program(source).unwrap();
+        match scheme_engine::compile_core(env, &expr, &CompileOptions::default()) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(_) => panic!("expected error for {source}"),
+        }
+    }
+
+    // Once expanded, they compile.
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse_program("(let ((a 1)) a)").unwrap();
+    let expanded = scheme_engine::expand(&env.borrow(), &expr).unwrap();
+    let (closure, _) =
+        scheme_engine::compile_core(env.clone(), &expanded, &CompileOptions::default()).unwrap();
+    assert_eq!(scheme_engine::eval(closure).unwrap(), Expr::Number(1.0));
+}
+
+#[test]
+fn test_macros_not_implemented() {
+    // Macro definitions are reported as such, rather than as calls to
+    // unbound variables, until define-syntax is implemented.
+    let table = [
+        (
+            "(define-syntax swap! (syntax-rules () ((swap! a b) (set! a b))))",
+            "define-syntax is not implemented yet",
+        ),
+        (
+            "(define (f) (define-syntax one (syntax-rules () ((one) 1))) (one))",
+            "define-syntax is not implemented yet",
+        ),
+    ];
+
+    for (source, expected) in table {
+        let env = scheme_engine::new_env().unwrap();
+        let expr = scheme_engine::parse_program(source).unwrap();
+        match scheme_engine::compile(env, &expr) {
+            Err(err) => assert!(err.to_string().ends_with(expected), "{source}: {err}"),
+            Ok(_) => panic!("expected error for {source}"),
+        }
+    }
+}
+
+#[test]
+fn test_scoped_macros() {
+    // The macros are applied by the expander, leaving the body in a thunk.
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(
+        "(let-syntax ((swap (syntax-rules () ((_ a b) (cons b a))))) (swap 1 2))",
+        false,
+    )
+    .unwrap();
+    let expanded = scheme_engine::expand(&env.borrow(), &expr).unwrap();
+    assert_eq!(
+        expanded.write_repr().to_string(),
+        "((lambda () (cons 2 1)))"
+    );
+
+    let table = [
+        // Only the body sees the macro.
+        (
+            "(let-syntax ((one (syntax-rules () ((_) 1)))) (one)) (one)",
+            r#"in form 2 (one): unbound variable "one""#,
+        ),
+        // The output of a let-syntax macro is outside its scope.
+        (
+            "(let-syntax ((m (syntax-rules () ((_) (m))))) (m))",
+            r#"unbound variable "m""#,
+        ),
+        (
+            "(letrec-syntax ((m (syntax-rules () ((_) (m))))) (m))",
+            "uses of macro m are nested more than 64 deep",
+        ),
+        (
+            "(let-syntax ((one (syntax-rules () ((_) 1)))) (one 2))",
+            "no syntax-rules pattern of one matches (one 2)",
+        ),
+        (
+            "(let-syntax ((one 1)) (one))",
+            "expected syntax-rules transformer for one, found 1",
+        ),
+    ];
+
+    for (source, expected) in table {
+        let env = scheme_engine::new_env().unwrap();
+        let expr = scheme_engine::parse_program(source).unwrap();
+        match scheme_engine::compile(env, &expr) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(_) => panic!("expected error for {source}"),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use scheme_engine::{error::Error, Env, Expr};
+
 #[test]
 fn test_fibonacci_sequence() {
     let source = include_str!("test_fibonacci.scm");
@@ -7,3 +9,39 @@ fn test_fibonacci_sequence() {
 
     scheme_engine::eval(closure).expect("fibonacci sequence failed");
 }
+
+fn zero(_env: &mut Env, _args: &[Expr]) -> Result<Expr, Error> {
+    Ok(Expr::Number(0.0))
+}
+
+fn redefine_fib(env: &mut Env, _args: &[Expr]) -> Result<Expr, Error> {
+    env.define("fib", Expr::NativeFunc(zero))?;
+    Ok(Expr::Void)
+}
+
+/// Calls load the procedure from its variable each time, so a native
+/// that redefines it in the middle of a recursion is seen by the calls
+/// that follow, including those of frames already running.
+#[test]
+fn test_fibonacci_redefined_mid_run() {
+    let source = r"
+    (define (fib n)
+      (if (= n 8) (redefine-fib))
+      (if (<= n 1)
+        n
+        (+ (fib (- n 1)) (fib (- n 2)))))
+    (cons (fib 7) (cons (fib 9) (fib 9)))
+    ";
+    let mut env = scheme_engine::new_env().unwrap();
+    env.borrow_mut()
+        .bind_native_func("redefine-fib", redefine_fib)
+        .unwrap();
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+
+    // (fib 9) calls (fib 8), which swaps in the native before recursing.
+    // Every call after that gets the native, including the (fib 7) made
+    // by the (fib 9) frame that was already running.
+    let value = scheme_engine::eval(closure).unwrap();
+    assert_eq!(value.write_repr().to_string(), "(13 0 . 0)");
+}
@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+use std::{fs, process};
+
+use scheme_engine::{error::Error, Expr};
+
+fn eval_with_files(source: &str) -> Result<Expr, Error> {
+    let env = scheme_engine::new_env()?;
+    scheme_engine::init_file_io(&mut env.clone().borrow_mut())?;
+    let expr = scheme_engine::parse(source, true)?;
+    let closure = scheme_engine::compile(env.clone(), &expr)?;
+    scheme_engine::eval(closure)
+}
+
+/// A path in the temporary directory that no other test uses,
+/// removed again when dropped.
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("scheme-{}-{name}", process::id()));
+        let _ = fs::remove_file(&path);
+        Self(path)
+    }
+
+    /// The path as a Scheme string literal.
+    fn literal(&self) -> String {
+        format!("{:?}", self.0.to_str().unwrap())
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn test_string_round_trip() {
+    let temp = TempPath::new("round-trip.txt");
+    let path = temp.literal();
+    let source = format!(
+        r#"
+        (assert (not (file-exists? {path})))
+        (write-file! {path} "first line\nsecond line")
+        (assert (file-exists? {path}))
+        (read-file->string {path})
+        "#
+    );
+    assert_eq!(
+        eval_with_files(&source).unwrap(),
+        Expr::from("first line\nsecond line")
+    );
+
+    let source = format!("(delete-file {path}) (file-exists? {path})");
+    assert_eq!(eval_with_files(&source).unwrap(), Expr::Bool(false));
+}
+
+#[test]
+fn test_port_round_trip() {
+    let temp = TempPath::new("ports.txt");
+    let path = temp.literal();
+    let source = format!(
+        r#"
+        (define out (open-output-file {path}))
+        (assert (output-port? out))
+        (display "hello" out)
+        (newline out)
+        (display 42 out)
+        (write-char #\space out)
+        (write-string "Ünïcode strings" out 0 7)
+        (write-string "Ünïcode strings" out 13)
+        (newline out 2)
+        (close-port out)
+
+        (define in (open-input-file {path}))
+        (assert (input-port? in))
+        (assert-eq (read-line in) "hello")
+        (assert-eq (read-char in) #\4)
+        (assert-eq (read-line in) "2 Ünïcodegs")
+        (assert-eq (read-line in) "")
+        (assert (eof-object? (read-line in)))
+        (close-input-port in)
+        (port? in)
+        "#
+    );
+    assert_eq!(eval_with_files(&source).unwrap(), Expr::Bool(true));
+    assert_eq!(
+        fs::read_to_string(&temp.0).unwrap(),
+        "hello\n42 Ünïcodegs\n\n"
+    );
+}
+
+#[test]
+fn test_with_input_from_file() {
+    let temp = TempPath::new("with-input.txt");
+    fs::write(&temp.0, "one\ntwo\n").unwrap();
+    let path = temp.literal();
+    let source = format!(
+        r#"
+        (with-input-from-file {path}
+          (lambda ()
+            (define first (read-line))
+            (define second (read-line))
+            (assert (eof-object? (read-line)))
+            (format #f "~a,~a" first second)))
+        "#
+    );
+    assert_eq!(eval_with_files(&source).unwrap(), Expr::from("one,two"));
+}
+
+#[test]
+fn test_errors() {
+    let temp = TempPath::new("closed.txt");
+    let path = temp.literal();
+    let table = [
+        (
+            format!("(define out (open-output-file {path})) (close-port out) (display 1 out)"),
+            format!("in form 3: port is closed: {}", temp.0.display()),
+        ),
+        (
+            format!("(write-file! {path} \"x\") (display 1 (open-input-file {path}))"),
+            format!(
+                "in form 2: display: expected output port as argument 2, got #<input-port {}>",
+                temp.0.display()
+            ),
+        ),
+        (
+            r#"(read-file->string "/nonexistent/scheme/file")"#.to_string(),
+            r#"read-file->string: "/nonexistent/scheme/file": No such file or directory (os error 2)"#
+                .to_string(),
+        ),
+        (
+            format!("(define out (open-output-file {path})) (write-string \"abc\" out 2 4)"),
+            "in form 2: write-string: range 2 to 4 is out of bounds for length 3".to_string(),
+        ),
+        (
+            "(read-line 42)".to_string(),
+            "read-line: expected input port as argument 1, got 42".to_string(),
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval_with_files(&source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_sandboxed_env() {
+    // The core library alone has no access to files.
+    let env = scheme_engine::new_env().unwrap();
+    for name in [
+        "read-file->string",
+        "write-file!",
+        "file-exists?",
+        "delete-file",
+        "open-input-file",
+        "open-output-file",
+        "with-input-from-file",
+    ] {
+        assert!(env.borrow().lookup_var(name).is_none(), "{name}");
+    }
+    assert!(env.borrow().lookup_var("read-line").is_some());
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{eval, eval_with};
+use scheme_engine::{EvalOptions, Expr, ParseOptions};
+
+#[test]
+fn test_fold_case_option() {
+    let folded = ParseOptions { fold_case: true };
+    assert_eq!(
+        eval_with(
+            "(DEFINE X 3) (Display x) (+ X 1)",
+            &folded,
+            &EvalOptions::default()
+        )
+        .unwrap(),
+        Expr::Number(4.0)
+    );
+
+    let err = eval("(DEFINE X 3)").unwrap_err();
+    assert!(err.to_string().contains("unbound variable"), "{err}");
+}
+
+#[test]
+fn test_fold_case_directive() {
+    assert_eq!(
+        eval("#!fold-case (DEFINE X 3) X").unwrap(),
+        Expr::Number(3.0)
+    );
+
+    // Folding stops for the rest of the file.
+    let err = eval("#!fold-case (DEFINE X 3) #!no-fold-case X").unwrap_err();
+    assert!(err.to_string().contains("unbound variable"), "{err}");
+
+    let folded = ParseOptions { fold_case: true };
+    let err = eval_with(
+        "#!no-fold-case (DEFINE X 3)",
+        &folded,
+        &EvalOptions::default(),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("unbound variable"), "{err}");
+}
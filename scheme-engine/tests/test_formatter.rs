@@ -0,0 +1,121 @@
+//! Formatter snapshots, and properties checked over every Scheme file in the repository.
+//!
+//! Each `.scm` file in [`./formatter`] is formatted and compared
+//! with its sibling `.expected` file.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use scheme_engine::{format_source, parse, Expr, FormatOptions};
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn scheme_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("read {}: {err}", dir.display()))
+        .map(|entry| entry.expect("directory entry").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "scm"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// The written forms of a program, which leave out source locations.
+fn written_forms(source: &str) -> Vec<String> {
+    match &parse(source, true).expect("parse") {
+        Expr::Sequence(forms) => forms
+            .iter()
+            .map(|form| form.write_repr().to_string())
+            .collect(),
+        expr => vec![expr.write_repr().to_string()],
+    }
+}
+
+#[test]
+fn test_format_snapshots() {
+    let paths = scheme_files(&manifest_dir().join("tests/formatter"));
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let source = fs::read_to_string(&path).unwrap();
+        let expected = fs::read_to_string(path.with_extension("expected")).unwrap();
+        let formatted = format_source(&source, FormatOptions::default()).unwrap();
+        assert_eq!(formatted, expected, "{}", path.display());
+    }
+}
+
+/// Formatting keeps the program, and formatting again changes nothing.
+#[test]
+fn test_format_corpus() {
+    let dirs = [
+        "tests",
+        "tests/language",
+        "tests/conformance",
+        "tests/formatter",
+        "benches",
+    ];
+    let paths: Vec<PathBuf> = dirs
+        .iter()
+        .flat_map(|dir| scheme_files(&manifest_dir().join(dir)))
+        .collect();
+    assert!(paths.len() > 20);
+
+    for options in [
+        FormatOptions::default(),
+        FormatOptions {
+            width: 30,
+            indent: 4,
+        },
+    ] {
+        for path in &paths {
+            let source = fs::read_to_string(path).unwrap();
+            // Conformance cases for unimplemented syntax don't parse yet.
+            if parse(&source, true).is_err() {
+                continue;
+            }
+            let formatted = format_source(&source, options.clone())
+                .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            assert_eq!(
+                written_forms(&formatted),
+                written_forms(&source),
+                "{}",
+                path.display()
+            );
+
+            let again = format_source(&formatted, options.clone()).unwrap();
+            assert_eq!(again, formatted, "{}", path.display());
+        }
+    }
+}
+
+#[test]
+fn test_format_options() {
+    let source = "(define (f x) (g x) (h x))";
+    let options = FormatOptions {
+        width: 20,
+        indent: 4,
+    };
+    assert_eq!(
+        format_source(source, options).unwrap(),
+        "(define (f x)\n    (g x)\n    (h x))\n"
+    );
+    assert_eq!(
+        format_source(source, FormatOptions::default()).unwrap(),
+        "(define (f x) (g x) (h x))\n"
+    );
+}
+
+#[test]
+fn test_format_errors() {
+    let table = [
+        ("(define x", "expected ')' but found end-of-file at 1:10"),
+        ("(a))", "expected expression but found ')' at 1:4"),
+    ];
+
+    for (source, expected) in table {
+        let err = format_source(source, FormatOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{source}");
+    }
+    assert_eq!(format_source("", FormatOptions::default()).unwrap(), "");
+}
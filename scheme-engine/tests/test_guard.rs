@@ -0,0 +1,201 @@
+//! Raising and catching errors with `raise`, `error` and `guard`.
+mod common;
+
+use common::eval;
+use scheme_engine::{error::Error, Datum, Env, ErrorKind, Expr};
+
+fn eval_written(source: &str) -> String {
+    match eval(source) {
+        Ok(value) => value.write_repr().to_string(),
+        Err(err) => panic!("{source}: {err}"),
+    }
+}
+
+#[test]
+fn test_guard_arity_error() {
+    let source = r#"
+    (define (f x) x)
+    (define (describe e)
+      (vector (error-object? e) (error-kind e) (error-object-message e) (error-object-irritants e)))
+    (vector
+      (guard (e ((eq? (error-kind e) 'arity-error) (describe e)))
+        (f 1 2))
+      (guard (e ((eq? (error-kind e) 'arity-error) (describe e)))
+        (car '(1) '(2))))
+    "#;
+    assert_eq!(
+        eval_written(source),
+        r##"#(#(#t arity-error "wrong number of arguments, expected 1 but got 2" ()) #(#t arity-error "car: wrong number of arguments, expected 1 but got 2" ()))"##
+    );
+}
+
+#[test]
+fn test_guard_dispatch_on_kind() {
+    let source = r#"
+    (define (kind-of thunk)
+      (guard (e ((symbol? e) (cons 'raised e))
+                ((eq? (error-kind e) 'type-error) 'type)
+                ((eq? (error-kind e) 'arity-error) 'arity)
+                (else (error-object-message e)))
+        (thunk)))
+    (vector (kind-of (lambda () (+ 1 "2")))
+            (kind-of (lambda () (vector-ref (vector) 0 1)))
+            (kind-of (lambda () (error "custom" 1)))
+            (kind-of (lambda () (raise 'oops)))
+            (kind-of (lambda () 'fine)))
+    "#;
+    assert_eq!(
+        eval_written(source),
+        r##"#(type arity "custom" (raised . oops) fine)"##
+    );
+}
+
+#[test]
+fn test_error_object_irritants() {
+    let source = r#"
+    (define irritant (vector 1 2))
+    (guard (e ((error-object? e)
+               (vector (error-object-message e)
+                       (error-object-irritants e)
+                       (eq? (car (cdr (error-object-irritants e))) irritant))))
+      (error "out of range" 'x irritant))
+    "#;
+    assert_eq!(eval_written(source), r##"#("out of range" (x #(1 2)) #t)"##);
+
+    let source = r#"(guard (e (else e)) (error "bad \"input\"" 1 'x))"#;
+    assert_eq!(eval_written(source), r#"#<error "bad \"input\"" 1 x>"#);
+}
+
+#[test]
+fn test_guard_reraises_unhandled() {
+    let source = r#"
+    (guard (outer ((symbol? outer) (cons 'outer outer)))
+      (guard (inner ((number? inner) 'inner))
+        (raise 'up)))
+    "#;
+    assert_eq!(eval_written(source), "(outer . up)");
+
+    // The body's value is returned when nothing is raised.
+    assert_eq!(
+        eval_written("(guard (e (else 0)) (define x 2) (* x 21))"),
+        "42"
+    );
+}
+
+#[test]
+fn test_uncaught_error_object() {
+    let err = eval(r#"(guard (e ((number? e) e)) (error "out of range" 5 '(a "b")))"#).unwrap_err();
+    match &err {
+        Error::Raised {
+            kind,
+            message,
+            irritants,
+        } => {
+            assert_eq!(*kind, ErrorKind::Error);
+            assert_eq!(message, "out of range");
+            assert_eq!(
+                irritants,
+                &[
+                    Datum::Number(5.0),
+                    Datum::List(vec![
+                        Datum::Symbol("a".to_string()),
+                        Datum::String("b".to_string())
+                    ])
+                ]
+            );
+        }
+        other => panic!("expected a raised error, found {other:?}"),
+    }
+    assert_eq!(err.to_string(), r#"out of range 5 (a "b")"#);
+
+    let err = eval("(raise (vector 1 2))").unwrap_err();
+    assert_eq!(err.to_string(), "raised non-error object #(1 2)");
+    match &err {
+        Error::Raised {
+            kind, irritants, ..
+        } => {
+            assert_eq!(*kind, ErrorKind::Raise);
+            assert_eq!(
+                irritants,
+                &[Datum::Vector(vec![Datum::Number(1.0), Datum::Number(2.0)])]
+            );
+        }
+        other => panic!("expected a raised error, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_host_error_keeps_irritants() {
+    // A native function fails with a structured error, which a `guard`
+    // catches with its irritants as values of their own types.
+    fn fail(_env: &mut Env, _args: &[Expr]) -> scheme_engine::Result<Expr> {
+        Err(Error::Raised {
+            kind: ErrorKind::Error,
+            message: "host failure".to_string(),
+            irritants: vec![
+                Datum::Number(7.0),
+                Datum::Symbol("key".to_string()),
+                Datum::DottedList(vec![Datum::Char('x')], Box::new(Datum::Inexact(1.0))),
+            ],
+        })
+    }
+
+    let env = scheme_engine::new_env().unwrap();
+    env.clone()
+        .borrow_mut()
+        .bind_native_func("host-fail", fail)
+        .unwrap();
+    let source = r#"
+    (guard (e (else (let ((irritants (error-object-irritants e)))
+                      (vector (error-kind e) (number? (car irritants)) irritants))))
+      (host-fail))
+    "#;
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let value = scheme_engine::eval(closure).unwrap();
+    assert_eq!(
+        value.write_repr().to_string(),
+        r"#(error #t (7 key (#\x . 1.0)))"
+    );
+}
+
+#[test]
+fn test_error_message_must_be_string() {
+    let err = eval("(error 'not-a-string)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error: expected string as argument 1, got not-a-string"
+    );
+}
+
+#[test]
+fn test_guard_unwinds_frames() {
+    // The body and handler see the procedure's locals, and a closure made
+    // in an unwound frame keeps the value of its up-value.
+    let source = r#"
+    (define (attempt n)
+      (define steps 0)
+      (guard (e (else (cons steps (e))))
+        (set! steps (+ steps 1))
+        (let ((local (* n 10)))
+          (if (> n 1) (raise (lambda () local)) n))))
+    (define (loop i total)
+      (if (= i 0) total (loop (- i 1) (cons (attempt i) total))))
+    (loop 3 '())
+    "#;
+    assert_eq!(eval_written(source), "(1 (1 . 20) (1 . 30))");
+}
+
+#[test]
+fn test_limits_are_not_caught() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr =
+        scheme_engine::parse("(guard (e (else 'caught)) (let loop () (loop)))", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let options = scheme_engine::EvalOptions {
+        fuel: Some(1_000),
+        ..Default::default()
+    };
+    let err = scheme_engine::eval_with_options(closure, &options).unwrap_err();
+    assert!(matches!(err, Error::Limit(_)), "{err:?}");
+}
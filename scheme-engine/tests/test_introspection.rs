@@ -0,0 +1,136 @@
+use scheme_engine::{Expr, Vm, VmConfig};
+
+/// Look up a counter in the association list returned by `vm-stats`.
+fn stat(stats: &Expr, name: &str) -> f64 {
+    for entry in stats.as_slice().expect("vm-stats returns a list") {
+        if let Expr::Pair(pair) = entry {
+            let (key, value) = &*pair.borrow();
+            if key.as_ident() == Some(name) {
+                return value.as_number().expect("counter is a number");
+            }
+        }
+    }
+    panic!("vm-stats has no counter named {name:?}")
+}
+
+#[test]
+fn test_disassemble_fib() {
+    let source = include_str!("test_fibonacci.scm");
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    scheme_engine::eval(closure).expect("fibonacci sequence failed");
+
+    let fib = env.borrow().lookup_var("fib").cloned().unwrap();
+    let fib = fib.as_closure().expect("fib is a closure");
+    let text = scheme_engine::disassemble(fib.borrow().procedure(), Some(&env.borrow()));
+
+    assert!(text.contains("Call"), "{text}");
+    assert!(text.contains("LoadEnvVar"), "{text}");
+    assert!(text.contains("; fib"), "{text}");
+
+    // The native prints the same text.
+    let expr = scheme_engine::parse("(disassemble fib)", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    assert_eq!(scheme_engine::eval(closure).unwrap(), Expr::Void);
+}
+
+#[test]
+fn test_vm_stats() {
+    let source = r"
+    (define count-down (lambda (n) (if (<= n 0) (vm-stats) (count-down (- n 1)))))
+    (define first (vm-stats))
+    (define second (count-down 10))
+    (define third (vm-stats))
+    (define applied (apply vm-stats '()))
+    (define nested (car (map (lambda (x) (count-down 2)) '(1))))
+    ";
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    scheme_engine::eval(closure).expect("evaluation");
+
+    let env = env.borrow();
+    let first = env.lookup_var("first").unwrap();
+    let second = env.lookup_var("second").unwrap();
+    let third = env.lookup_var("third").unwrap();
+    let applied = env.lookup_var("applied").unwrap();
+    let nested = env.lookup_var("nested").unwrap();
+
+    assert!(stat(first, "instructions") < stat(second, "instructions"));
+    assert!(stat(second, "instructions") < stat(third, "instructions"));
+
+    // Top-level frame, plus eleven calls to count-down without tail calls.
+    assert_eq!(stat(first, "call-depth"), 1.0);
+    assert_eq!(stat(second, "call-depth"), 12.0);
+    assert_eq!(stat(third, "peak-call-depth"), 12.0);
+    assert!(stat(second, "operand-depth") > stat(first, "operand-depth"));
+
+    // Called through other natives, it sees the same machine.
+    assert!(stat(third, "instructions") < stat(applied, "instructions"));
+    assert_eq!(stat(applied, "call-depth"), 1.0);
+    // Top-level frame, the lambda `map` called back, and three calls to count-down.
+    assert_eq!(stat(nested, "call-depth"), 5.0);
+}
+
+#[test]
+fn test_vm_high_water() {
+    let env = scheme_engine::new_env().unwrap();
+    let fib = "(define fib (lambda (n) (if (<= n 1) n (+ (fib (- n 1)) (fib (- n 2))))))";
+    let closure = scheme_engine::compile(env.clone(), &scheme_engine::parse(fib, true).unwrap());
+    scheme_engine::eval(closure.unwrap()).unwrap();
+
+    let mut vm = Vm::with_config(VmConfig {
+        initial_operand_capacity: 256,
+        initial_frame_capacity: 32,
+    });
+    assert_eq!(vm.capacity().initial_operand_capacity, 256);
+    assert_eq!(vm.capacity().initial_frame_capacity, 32);
+
+    for n in [15, 5, 10] {
+        let program = scheme_engine::parse(&format!("(fib {n})"), true).unwrap();
+        let closure = scheme_engine::compile(env.clone(), &program).unwrap();
+        vm.eval(closure).unwrap();
+
+        // The top-level frame, and one frame each for (fib n) down to (fib 1).
+        let stats = vm.stats();
+        assert_eq!(stats.peak_call_depth, n + 1, "(fib {n})");
+        assert!(
+            stats.peak_operand_depth > stats.peak_call_depth,
+            "(fib {n})"
+        );
+        assert_eq!(stats.call_depth, 0);
+        assert_eq!(stats.operand_depth, 0);
+    }
+
+    // The stacks were big enough, so they kept their first allocation.
+    assert_eq!(vm.capacity().initial_operand_capacity, 256);
+    assert_eq!(vm.capacity().initial_frame_capacity, 32);
+}
+
+#[test]
+fn test_loop_stack_is_bounded() {
+    let source = r"
+    (define total
+      (do ((i 0 (+ i 1))
+           (sum 0 (+ sum i)))
+          ((= i 1000000) sum)
+        (if (> i sum) 'never i)))
+    (define stats (vm-stats))
+    ";
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    scheme_engine::eval(closure).expect("evaluation");
+
+    let env = env.borrow();
+    assert_eq!(
+        env.lookup_var("total"),
+        Some(&Expr::Number(499_999_500_000.0))
+    );
+
+    // Every iteration pops what it pushed, so the stack never grows
+    // beyond the values of a single iteration.
+    let stats = env.lookup_var("stats").unwrap();
+    assert!(stat(stats, "peak-operand-depth") < 16.0);
+}
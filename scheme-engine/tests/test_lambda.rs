@@ -11,6 +11,7 @@ fn test_lambda_call() {
     let expr = scheme_engine::parse(source, true).expect("parse");
     let closure = scheme_engine::compile(env.clone(), &expr).expect("compile");
     println!("Top-level Closure: {closure:?}");
+    assert!(format!("{closure:?}").contains("arity: 0"), "{closure:?}");
 
     let value = scheme_engine::eval(closure).expect("evaluation");
     assert_eq!(value, Expr::Number(14.0));
@@ -21,3 +22,138 @@ fn test_lambda_call() {
         "lambda local leaked to global env"
     );
 }
+
+/// Internal definitions get their own slots in the call frame,
+/// counted per procedure.
+#[test]
+fn test_local_count() {
+    let source = r"
+    (define compute
+      (lambda (x)
+        (define a (+ x 1))
+        (define b (* a 2))
+        (define c (lambda (y)
+          (define d (+ y b))
+          (- d 1)))
+        (c (+ a b))))
+    (compute 4)
+    ";
+
+    let env = scheme_engine::new_env().expect("create core environment");
+    let expr = scheme_engine::parse(source, true).expect("parse");
+    let closure = scheme_engine::compile(env.clone(), &expr).expect("compile");
+    assert_eq!(closure.borrow().procedure().local_count(), 0);
+
+    let value = scheme_engine::eval(closure).expect("evaluation");
+    assert_eq!(value, Expr::Number(24.0));
+
+    let compute = env.borrow().lookup_var("compute").cloned().unwrap();
+    let compute = compute.as_closure().unwrap().clone();
+    // The parameter and three internal definitions.
+    assert_eq!(compute.borrow().procedure().local_count(), 4);
+
+    // Repeated calls don't leave locals behind on the stack.
+    for _ in 0..3 {
+        let value = scheme_engine::call(compute.clone(), &[Expr::Number(4.0)]).unwrap();
+        assert_eq!(value, Expr::Number(24.0));
+    }
+}
+
+/// Arguments past the fixed parameters are collected into the rest parameter.
+#[test]
+fn test_rest_parameter() {
+    let eval = |source: &str| {
+        let env = scheme_engine::new_env().expect("create core environment");
+        let expr = scheme_engine::parse(source, true).expect("parse");
+        let closure = scheme_engine::compile(env.clone(), &expr).expect("compile");
+        scheme_engine::eval(closure)
+    };
+
+    let value = eval("((lambda (a . rest) rest) 1 2 3)").unwrap();
+    assert_eq!(value.write_repr().to_string(), "(2 3)");
+    assert_eq!(eval("((lambda all all))").unwrap(), Expr::Nil);
+
+    // The fixed parameters are still required.
+    let err = eval("((lambda (a b . rest) a) 1)").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("wrong number of arguments, expected at least 2 but got 1"),
+        "{err}"
+    );
+}
+
+/// The arity is a byte in the bytecode, so longer calls and parameter
+/// lists are rejected instead of wrapping around.
+#[test]
+fn test_arity_limit() {
+    let run = |source: &str| {
+        let env = scheme_engine::new_env()?;
+        let expr = scheme_engine::parse(source, true)?;
+        // The closure only holds a weak reference to the environment.
+        let closure = scheme_engine::compile(env.clone(), &expr)?;
+        scheme_engine::eval(closure)
+    };
+    let ones = |count: usize| vec!["1"; count].join(" ");
+    let params = |count: usize| {
+        (0..count)
+            .map(|index| format!("p{index}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    assert_eq!(
+        run(&format!("(+ {})", ones(255))).unwrap(),
+        Expr::Number(255.0)
+    );
+    assert_eq!(
+        run(&format!("((lambda ({}) p254) {})", params(255), ones(255))).unwrap(),
+        Expr::Number(1.0)
+    );
+
+    let table = [
+        (
+            format!("(+ {})", ones(256)),
+            "number of arguments in a call exceeds maximum of 255",
+        ),
+        (
+            format!("(lambda ({}) 1)", params(256)),
+            "number of parameters of a procedure exceeds maximum of 255",
+        ),
+    ];
+    for (source, expected) in table {
+        match run(&source) {
+            Err(err) => assert_eq!(err.to_string(), expected),
+            Ok(value) => panic!("expected arity error, found {value:?}"),
+        }
+    }
+}
+
+/// Debug output of a closure shows its signature, without printing the
+/// values it captured, which can refer back to the closure.
+#[test]
+fn test_closure_debug() {
+    let source = r"
+    (define make-adder
+      (lambda (n)
+        (define self (vector #f))
+        (define add (lambda (x y . rest) (vector-ref self 0) (+ n x y)))
+        (vector-set! self 0 add)
+        add))
+    (define add-one (make-adder 1))
+    ";
+
+    let env = scheme_engine::new_env().expect("create core environment");
+    let expr = scheme_engine::parse(source, true).expect("parse");
+    let closure = scheme_engine::compile(env.clone(), &expr).expect("compile");
+    scheme_engine::eval(closure).expect("evaluation");
+
+    let add_one = env
+        .borrow()
+        .lookup_var("add-one")
+        .cloned()
+        .expect("defined");
+    let debug = format!("{add_one:?}");
+    assert!(debug.contains("arity: 2"), "{debug}");
+    assert!(debug.contains("variadic: true"), "{debug}");
+    assert!(debug.contains("up_values: 2"), "{debug}");
+}
@@ -1,7 +1,7 @@
 //! Aggregated tests for language features, in Scheme files.
 //!
 //! See scripts in [`./language`]
-use scheme_engine::{error::Error, Closure, Env, Expr, Handle};
+use scheme_engine::{error::Error, Closure, CompileOptions, Env, Expr, Handle};
 
 fn compile_closure_env(source: &str) -> Result<(Handle<Env>, Handle<Closure>), Error> {
     let env = scheme_engine::new_env()?;
@@ -18,6 +18,13 @@ fn test_booleans() {
     println!("Result value: {:?}", value);
 }
 
+#[test]
+fn test_bytevector() {
+    let (_env, closure) = compile_closure_env(include_str!("language/bytevector.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
 #[test]
 fn test_conditionals() {
     let (_env, closure) = compile_closure_env(include_str!("language/conditionals.scm"))
@@ -47,7 +54,106 @@ fn test_define() {
 
 #[test]
 fn test_lambda() {
-    let (env, closure) = compile_closure_env(include_str!("language/lambda.scm"))
+    let (_env, closure) = compile_closure_env(include_str!("language/lambda.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+/// Left to right evaluation is a guarantee, so optimizations must keep it.
+#[test]
+fn test_evaluation_order() {
+    let (_env, closure) = compile_closure_env(include_str!("language/evaluation_order.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+#[test]
+fn test_format() {
+    let (_env, closure) = compile_closure_env(include_str!("language/format.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+#[test]
+fn test_list() {
+    let (_env, closure) = compile_closure_env(include_str!("language/list.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+#[test]
+fn test_numeric_tower() {
+    let (_env, closure) = compile_closure_env(include_str!("language/numeric_tower.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+#[test]
+fn test_numeric_edges() {
+    let (_env, closure) = compile_closure_env(include_str!("language/numeric_edges.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+#[test]
+fn test_string_port() {
+    let (_env, closure) = compile_closure_env(include_str!("language/string_port.scm"))
         .expect("compiling closure and environment");
     let _ = scheme_engine::eval(closure).expect("evaluation");
 }
+
+#[test]
+fn test_vector() {
+    let (_env, closure) = compile_closure_env(include_str!("language/vector.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+#[test]
+fn test_memoize() {
+    let (_env, closure) = compile_closure_env(include_str!("language/memoize.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+#[test]
+fn test_macros() {
+    let (_env, closure) = compile_closure_env(include_str!("language/macros.scm"))
+        .expect("compiling closure and environment");
+    let _ = scheme_engine::eval(closure).expect("evaluation");
+}
+
+/// All language scripts must behave the same when constants
+/// are stored in the environment's shared pool.
+#[test]
+fn test_shared_constants() {
+    let scripts = [
+        include_str!("language/boolean.scm"),
+        include_str!("language/bytevector.scm"),
+        include_str!("language/conditionals.scm"),
+        include_str!("language/define.scm"),
+        include_str!("language/evaluation_order.scm"),
+        include_str!("language/format.scm"),
+        include_str!("language/lambda.scm"),
+        include_str!("language/list.scm"),
+        include_str!("language/macros.scm"),
+        include_str!("language/memoize.scm"),
+        include_str!("language/number.scm"),
+        include_str!("language/numeric_edges.scm"),
+        include_str!("language/numeric_tower.scm"),
+        include_str!("language/string_port.scm"),
+        include_str!("language/vector.scm"),
+    ];
+    let options = CompileOptions {
+        shared_constants: true,
+        ..CompileOptions::default()
+    };
+
+    for source in scripts {
+        let env = scheme_engine::new_env().expect("create core environment");
+        let expr = scheme_engine::parse(source, true).expect("parse");
+        let closure =
+            scheme_engine::compile_with_options(env.clone(), &expr, &options).expect("compile");
+        let _ = scheme_engine::eval(closure).expect("evaluation");
+    }
+}
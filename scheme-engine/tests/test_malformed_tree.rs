@@ -0,0 +1,146 @@
+//! Expressions built by hand, in shapes the parser never produces.
+use std::rc::Rc;
+
+use scheme_engine::{error::Error, CompileOptions, Expr, Handle, Keyword};
+
+fn ident(name: &str) -> Expr {
+    Expr::Ident(name.into())
+}
+
+fn list<const N: usize>(elements: [Expr; N]) -> Expr {
+    Expr::List(Rc::new(elements))
+}
+
+fn compile_error(expr: &Expr) -> Error {
+    let env = scheme_engine::new_env().unwrap();
+    match scheme_engine::compile(env, expr) {
+        Err(err) => err,
+        Ok(_) => panic!("expected {expr:?} to be rejected"),
+    }
+}
+
+#[test]
+fn test_malformed_trees() {
+    let sequence = || Expr::Sequence(vec![Expr::Number(1.0), Expr::Number(2.0)]);
+    let dot = || Expr::Keyword(Keyword::Dot);
+    let car: scheme_engine::NativeFunc = |_env, args| Ok(args[0].clone());
+
+    let table = [
+        (
+            list([ident("+"), Expr::Number(1.0), sequence()]),
+            "sequence nested at list position 2",
+        ),
+        (Expr::Quote(Box::new(sequence())), "sequence nested"),
+        (
+            list([
+                ident("display"),
+                Expr::Quote(Box::new(list([ident("a"), sequence()]))),
+            ]),
+            "sequence nested at list position 1 > 1",
+        ),
+        (
+            Expr::from(vec![Expr::Number(1.0), sequence()]),
+            "sequence nested at list position 1",
+        ),
+        (dot(), "'.' outside of a list"),
+        (
+            list([ident("f"), dot()]),
+            "'.' misplaced in a dotted list at list position 1",
+        ),
+        (
+            list([dot(), ident("f")]),
+            "'.' misplaced in a dotted list at list position 0",
+        ),
+        (
+            list([ident("f"), dot(), ident("a"), ident("b")]),
+            "'.' misplaced in a dotted list at list position 1",
+        ),
+        (
+            list([ident("quote"), list([ident("a"), dot(), dot(), ident("b")])]),
+            "'.' misplaced in a dotted list at list position 1 > 1",
+        ),
+        (
+            list([ident("car"), Expr::NativeFunc(car)]),
+            "procedure value embedded at list position 1",
+        ),
+        (
+            Expr::Quote(Box::new(list([Expr::Eof]))),
+            "eof-object value embedded at list position 0",
+        ),
+        (
+            list([ident("list"), Expr::Values(Rc::new([]))]),
+            "values value embedded at list position 1",
+        ),
+        (
+            Expr::Pair(Handle::new((ident("f"), Expr::Sequence(vec![])))),
+            "sequence nested at list position 1",
+        ),
+    ];
+
+    for (expr, expected) in table {
+        let err = compile_error(&expr);
+        assert!(
+            matches!(err, Error::MalformedTree { .. }),
+            "{expected}: {err:?}"
+        );
+        assert_eq!(
+            err.to_string(),
+            format!("malformed syntax tree: {expected}")
+        );
+    }
+}
+
+#[test]
+fn test_form_number() {
+    let program = Expr::Sequence(vec![
+        list([ident("define"), ident("x"), Expr::Number(1.0)]),
+        list([ident("+"), ident("x"), Expr::Number(1.0)]),
+        list([
+            ident("if"),
+            ident("x"),
+            list([ident("+"), ident("x"), Expr::Sequence(vec![])]),
+        ]),
+    ]);
+    match compile_error(&program) {
+        Error::MalformedTree {
+            violation,
+            path,
+            form,
+        } => {
+            assert_eq!(violation, "sequence nested");
+            assert_eq!(path, [2, 2]);
+            assert_eq!(form, Some(3));
+        }
+        err => panic!("expected a malformed tree, got {err:?}"),
+    }
+
+    let program = Expr::Sequence(vec![Expr::Number(1.0), Expr::Eof]);
+    assert_eq!(
+        compile_error(&program).to_string(),
+        "malformed syntax tree: eof-object value embedded in form 2"
+    );
+}
+
+#[test]
+fn test_cyclic_trees() {
+    // Walked once per value, so the check finishes.
+    let vector = Handle::new(vec![Expr::Number(1.0)]);
+    let mut handle = vector.clone();
+    handle.borrow_mut().push(Expr::Vector(vector.clone()));
+    handle.borrow_mut().push(Expr::Eof);
+    let expr = Expr::Quote(Box::new(Expr::Vector(vector)));
+    assert_eq!(
+        compile_error(&expr).to_string(),
+        "malformed syntax tree: eof-object value embedded at list position 2"
+    );
+    // Break the cycle, so the vector is freed.
+    handle.borrow_mut().clear();
+}
+
+#[test]
+fn test_compile_core_checks() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = list([ident("car"), Expr::Sequence(vec![])]);
+    let err = scheme_engine::compile_core(env, &expr, &CompileOptions::default()).unwrap_err();
+    assert!(matches!(err, Error::MalformedTree { .. }), "{err:?}");
+}
@@ -1,5 +1,8 @@
 //! Tests for numbers.
-use scheme_engine::{Env, Handle};
+mod common;
+
+use common::eval;
+use scheme_engine::{Env, Expr, Handle};
 
 #[test]
 fn test_add() {
@@ -9,3 +12,186 @@ fn test_add() {
     scheme_engine::init_core(&mut env.borrow_mut()).expect("init core");
     scheme_engine::compile(env, &expr).expect("compile failed");
 }
+
+#[test]
+fn test_arithmetic_arity() {
+    let table = [
+        ("(+)", 0.0),
+        ("(+ 5)", 5.0),
+        ("(+ 5 2)", 7.0),
+        ("(+ 5 2 1)", 8.0),
+        ("(*)", 1.0),
+        ("(* 5)", 5.0),
+        ("(* 5 2)", 10.0),
+        ("(* 5 2 3)", 30.0),
+        ("(- 5)", -5.0),
+        ("(- 5 2)", 3.0),
+        ("(- 5 2 1)", 2.0),
+        ("(/ 4)", 0.25),
+        ("(/ 8 2)", 4.0),
+        ("(/ 8 2 2)", 2.0),
+    ];
+
+    for (source, expected) in table {
+        let value = eval(source).expect(source);
+        assert_eq!(value, Expr::Number(expected), "{source}");
+    }
+}
+
+#[test]
+fn test_arithmetic_errors() {
+    let table = [
+        (
+            "(-)",
+            "-: wrong number of arguments, expected at least 1 but got 0",
+        ),
+        (
+            "(/)",
+            "/: wrong number of arguments, expected at least 1 but got 0",
+        ),
+        ("(+ 1 #t)", "+: expected number as argument 2, got #t"),
+        (r#"(- "a")"#, "-: expected number as argument 1, got \"a\""),
+        (
+            "(inexact->exact 0.5)",
+            "exact: 0.5 has no exact representation",
+        ),
+        (
+            "(exact (/ 1.0 0))",
+            "exact: +inf.0 has no exact representation",
+        ),
+        ("(/ 1 0)", "/: division by zero"),
+        ("(/ 0)", "/: division by zero"),
+        ("(/ 6 2 0)", "/: division by zero"),
+        (
+            "(exact? #t)",
+            "exact?: expected number as argument 1, got #t",
+        ),
+        ("(sqrt -1)", "sqrt: -1 has no real square root"),
+        ("(expt -8 0.5)", "expt: -8 raised to 0.5 has no real result"),
+        ("(expt 0 -1)", "expt: 0 raised to the negative power -1"),
+        ("(modulo 7 0)", "modulo: division by zero"),
+        ("(quotient 7 0)", "quotient: division by zero"),
+        (
+            "(remainder 7.5 2)",
+            "remainder: expected integer as argument 1, got 7.5",
+        ),
+        (
+            "(max)",
+            "max: wrong number of arguments, expected at least 1 but got 0",
+        ),
+        (
+            "(assert-close 1 2)",
+            "assertion failed: 1 is not within 0.000000001 of 2",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+/// The inexact number written and read back, still inexact.
+fn round_trip(number: f64) -> f64 {
+    let text = Expr::Inexact(number).write_repr().to_string();
+    match scheme_engine::parse(&text, false) {
+        Ok(Expr::Inexact(read)) => read,
+        other => panic!("{number:e} written as {text:?} read as {other:?}"),
+    }
+}
+
+#[test]
+fn test_number_round_trip() {
+    let third = 1.0 / 3.0;
+    let adversarial = [
+        0.0,
+        -0.0,
+        0.1,
+        third,
+        -third,
+        f64::MAX,
+        f64::MIN,
+        f64::MIN_POSITIVE,
+        f64::EPSILON,
+        f64::from_bits(1),
+        f64::from_bits(0x000f_ffff_ffff_ffff),
+        -f64::from_bits(1),
+        9_007_199_254_740_991.0,
+        9_007_199_254_740_992.0,
+        9_007_199_254_740_993.0,
+        1e21,
+        1e-7,
+        123_456_789.123_456_78,
+    ];
+
+    // Bit patterns from a xorshift generator, with a fixed seed.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let random = std::iter::repeat_with(move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        f64::from_bits(state)
+    })
+    .filter(|number| number.is_finite())
+    .take(5000);
+
+    for number in adversarial.into_iter().chain(random) {
+        assert_eq!(round_trip(number).to_bits(), number.to_bits(), "{number:e}");
+    }
+}
+
+#[test]
+fn test_non_finite_numbers() {
+    let table = [
+        (f64::INFINITY, "+inf.0"),
+        (f64::NEG_INFINITY, "-inf.0"),
+        (f64::NAN, "+nan.0"),
+        (-f64::NAN, "+nan.0"),
+    ];
+    for (number, expected) in table {
+        assert_eq!(Expr::Number(number).write_repr().to_string(), expected);
+    }
+
+    assert_eq!(round_trip(f64::INFINITY), f64::INFINITY);
+    assert_eq!(round_trip(f64::NEG_INFINITY), f64::NEG_INFINITY);
+    assert!(round_trip(f64::NAN).is_nan());
+    assert!(eval("-nan.0").unwrap().as_number().unwrap().is_nan());
+
+    // Zero and negative zero are separate constants.
+    assert_eq!(
+        eval("(cons (/ 1 0.0) (/ 1 -0.0))")
+            .unwrap()
+            .repr()
+            .to_string(),
+        "(+inf.0 . -inf.0)"
+    );
+
+    let source = r#"
+    (assert-eq (/ 1.0 0) +inf.0)
+    (assert-eq (/ -1 0.0) -inf.0)
+    (assert-eq (number->string (/ 1.0 0)) "+inf.0")
+    (number->string (- +inf.0 +inf.0))
+    "#;
+    assert_eq!(eval(source).unwrap(), Expr::String("+nan.0".into()));
+}
+
+#[test]
+fn test_print_precision() {
+    let table = [
+        (1.0 / 3.0, 4, "0.3333"),
+        (2.5, 4, "2.5"),
+        (2.0, 4, "2"),
+        (1234.5678, 0, "1235"),
+        (0.125, 2, "0.12"),
+        (f64::INFINITY, 2, "+inf.0"),
+    ];
+    for (number, digits, expected) in table {
+        let expr = Expr::Number(number);
+        assert_eq!(expr.repr().with_precision(digits).to_string(), expected);
+    }
+
+    let list = eval("(cons (/ 1 3) (cons (/ 2 3) '()))").unwrap();
+    assert_eq!(list.repr().with_precision(2).to_string(), "(0.33 0.67)");
+}
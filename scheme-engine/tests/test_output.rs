@@ -0,0 +1,90 @@
+mod common;
+
+use common::eval;
+use scheme_engine::Expr;
+
+/// The text printed by the body of a thunk.
+fn output_of(body: &str) -> String {
+    let source = format!("(with-output-to-string (lambda () {body}))");
+    match &eval(&source) {
+        Ok(Expr::String(text)) => text.to_string(),
+        other => panic!("expected output string for {body}, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_with_output_to_string() {
+    let table = [
+        (r#"(display "a") (display 1) #t"#, "a1"),
+        (
+            r#"(write-char #\x) (write-char #\λ) (write-char #\space)"#,
+            "xλ ",
+        ),
+        (r#"(write-string "héllo")"#, "héllo"),
+        ("(newline) (newline 3) (newline 0)", "\n\n\n\n"),
+        // Nested captures don't leak into the outer one.
+        (
+            r#"(display "outer") (with-output-to-string (lambda () (display "inner")))"#,
+            "outer",
+        ),
+    ];
+
+    for (body, expected) in table {
+        assert_eq!(output_of(body), expected, "{body}");
+    }
+}
+
+#[test]
+fn test_compose_output() {
+    let body = r#"
+    (write-char #\[)
+    (display 42)
+    (write-char #\])
+    (newline)
+    (for-each (lambda (c) (write-char c)) '(#\≈ #\space #\😀))
+    (newline 2)
+    (display "done")
+    "#;
+    assert_eq!(output_of(body), "[42]\n≈ 😀\n\ndone");
+}
+
+#[test]
+fn test_output_errors() {
+    let table = [
+        (
+            "(write-char \"a\")",
+            "write-char: expected char as argument 1, got \"a\"",
+        ),
+        (
+            "(write-string \"abc\" 1)",
+            "write-string: expected output port as argument 2, got 1",
+        ),
+        (
+            "(newline -1)",
+            "newline: expected index as argument 1, got -1",
+        ),
+        (
+            "(newline 1e19)",
+            "newline: expected index as argument 1, got 10000000000000000000.0",
+        ),
+        (
+            "(newline 100000)",
+            "newline: count 100000 is more than the limit of 65536",
+        ),
+        (
+            "(with-output-to-string 1)",
+            "with-output-to-string: expected procedure as argument 1, got 1",
+        ),
+        (
+            "(write-string \"abc\" 1 2 3 4)",
+            "write-string: wrong number of arguments, expected 1 to 4 but got 5",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
@@ -0,0 +1,558 @@
+mod common;
+
+use common::eval;
+use scheme_engine::Expr;
+use std::rc::Rc;
+
+fn eval_repr(source: &str) -> String {
+    eval(source).unwrap().write_repr().to_string()
+}
+
+/// A three element list whose last pair points back at the first.
+const CYCLE: &str = "
+(define cycle (cons 1 (cons 2 (cons 3 '()))))
+(set-cdr! (cdr (cdr cycle)) cycle)
+";
+
+#[test]
+fn test_repr() {
+    assert_eq!(eval_repr("(cons 1 2)"), "(1 . 2)");
+    assert_eq!(eval_repr("(cons 1 (cons 2 '()))"), "(1 2)");
+    assert_eq!(eval_repr("(cons 1 (cons 2 3))"), "(1 2 . 3)");
+    assert_eq!(eval_repr("(cons 1 '(2 3))"), "(1 2 3)");
+}
+
+#[test]
+fn test_proper_lists() {
+    assert_eq!(eval("(list? '(1 2 3))").unwrap(), Expr::Bool(true));
+    assert_eq!(eval("(list? '())").unwrap(), Expr::Bool(true));
+    assert_eq!(
+        eval("(list? (cons 1 (cons 2 '())))").unwrap(),
+        Expr::Bool(true)
+    );
+    assert_eq!(
+        eval("(proper-list? (cons 1 '(2)))").unwrap(),
+        Expr::Bool(true)
+    );
+    assert_eq!(eval("(length (cons 1 '(2 3)))").unwrap(), Expr::Number(3.0));
+    assert_eq!(eval("(length '())").unwrap(), Expr::Number(0.0));
+    assert_eq!(eval_repr("(last-pair (cons 1 (cons 2 '())))"), "(2)");
+    assert_eq!(eval_repr("(last-pair '(1 2 3))"), "(3)");
+    assert_eq!(eval_repr("(append '(1) (cons 2 '()) '(3))"), "(1 2 3)");
+    assert_eq!(eval_repr("(reverse (cons 1 '(2 3)))"), "(3 2 1)");
+    assert_eq!(
+        eval_repr("(map (lambda (x) (* x 2)) (cons 1 (cons 2 '())))"),
+        "(2 4)"
+    );
+    assert_eq!(
+        eval_repr("(filter (lambda (x) (< x 3)) (cons 1 '(2 3)))"),
+        "(1 2)"
+    );
+}
+
+#[test]
+fn test_dotted_lists() {
+    assert_eq!(eval("(list? (cons 1 2))").unwrap(), Expr::Bool(false));
+    assert_eq!(eval("(list? 42)").unwrap(), Expr::Bool(false));
+    assert_eq!(eval_repr("(last-pair (cons 1 (cons 2 3)))"), "(2 . 3)");
+    assert_eq!(eval_repr("(append '(1) 2)"), "(1 . 2)");
+    assert_eq!(eval_repr("(list-copy (cons 1 (cons 2 3)))"), "(1 2 . 3)");
+}
+
+#[test]
+fn test_list_copy() {
+    // The copy has new pairs, so mutating it leaves the original alone.
+    let source = "
+    (define original (cons 1 (cons 2 3)))
+    (define copy (list-copy original))
+    (set-car! copy 9)
+    original
+    ";
+    assert_eq!(eval_repr(source), "(1 2 . 3)");
+    assert_eq!(eval("(list-copy 42)").unwrap(), Expr::Number(42.0));
+}
+
+#[test]
+fn test_cyclic_lists() {
+    assert_eq!(
+        eval(&format!("{CYCLE} (list? cycle)")).unwrap(),
+        Expr::Bool(false)
+    );
+    assert_eq!(
+        eval(&format!("{CYCLE} (proper-list? (cdr cycle))")).unwrap(),
+        Expr::Bool(false)
+    );
+    assert_eq!(
+        eval(&format!("{CYCLE} (pair? cycle)")).unwrap(),
+        Expr::Bool(true)
+    );
+
+    for who in ["length", "list-copy", "last-pair", "reverse", "reverse!"] {
+        match eval(&format!("{CYCLE} ({who} cycle)")) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                format!(
+                    "in form 3: {who}: expected proper list as argument 1, got a circular list"
+                )
+            ),
+            Ok(_) => panic!("expected error for {who} of a cyclic list"),
+        }
+    }
+}
+
+#[test]
+fn test_errors() {
+    let table = [
+        (
+            "(length (cons 1 2))",
+            "length: expected proper list as argument 1, got (1 . 2)",
+        ),
+        (
+            "(reverse (cons 1 (cons 2 3)))",
+            "reverse: expected proper list as argument 1, got (1 2 . 3)",
+        ),
+        (
+            "(append (cons 1 2) '())",
+            "append: expected proper list as argument 1, got (1 . 2)",
+        ),
+        (
+            "(map car (cons '(1) 2))",
+            "map: expected proper list as argument 2, got ((1) . 2)",
+        ),
+        ("(car '())", "car: expected pair as argument 1, got ()"),
+        (
+            "(last-pair 42)",
+            "last-pair: expected pair as argument 1, got 42",
+        ),
+        (
+            "(set-cdr! '(1 2) 3)",
+            "set-cdr!: expected pair as argument 1, got (1 2)",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_append_in_place() {
+    // The result is the first list, ending in the pairs of the second.
+    let source = "
+    (define a (cons 1 (cons 2 '())))
+    (define b (cons 3 (cons 4 '())))
+    (define c (append! a b))
+    (set-car! b 30)
+    (vector c (eq? a c) (eq? (cdr (cdr c)) b))
+    ";
+    assert_eq!(eval_repr(source), "#((1 2 30 4) #t #t)");
+
+    // The first list can be appended to again.
+    let source = "
+    (define a (cons 1 '()))
+    (append! a (cons 2 '()))
+    (append! a (cons 3 '()) '() (cons 4 '()))
+    a
+    ";
+    assert_eq!(eval_repr(source), "(1 2 3 4)");
+
+    // Empty lists are skipped, so the result isn't the first argument.
+    let source = "
+    (define a '())
+    (define b (cons 1 '()))
+    (define c (append! a '() b))
+    (vector c a (eq? b c))
+    ";
+    assert_eq!(eval_repr(source), "#((1) () #t)");
+
+    let table = [
+        ("(append!)", "()"),
+        ("(append! 1)", "1"),
+        ("(append! (cons 1 '()) 2)", "(1 . 2)"),
+        // Literal lists and tails can't be changed, so they're copied.
+        ("(append! '(1 2) '(3))", "(1 2 3)"),
+        ("(append! (cons 1 '(2)) (cons 3 '()))", "(1 2 3)"),
+    ];
+    for (source, expected) in table {
+        assert_eq!(eval_repr(source), expected, "{source}");
+    }
+}
+
+#[test]
+fn test_reverse_in_place() {
+    // The first pair becomes the last.
+    let source = "
+    (define a (cons 1 (cons 2 (cons 3 '()))))
+    (define r (reverse! a))
+    (vector r a)
+    ";
+    assert_eq!(eval_repr(source), "#((3 2 1) (1))");
+
+    let table = [
+        ("(reverse! '())", "()"),
+        ("(reverse! '(1 2 3))", "(3 2 1)"),
+        ("(reverse! (cons 1 (cons 2 '(3 4))))", "(4 3 2 1)"),
+    ];
+    for (source, expected) in table {
+        assert_eq!(eval_repr(source), expected, "{source}");
+    }
+}
+
+#[test]
+fn test_list_tail() {
+    // The tail shares its pairs with the list.
+    let source = "
+    (define a (cons 1 (cons 2 (cons 3 '()))))
+    (define t (list-tail a 1))
+    (set-car! t 20)
+    (vector a (eq? t (cdr a)))
+    ";
+    assert_eq!(eval_repr(source), "#((1 20 3) #t)");
+
+    let table = [
+        ("(list-tail '(1 2 3) 0)", "(1 2 3)"),
+        ("(list-tail '(1 2 3) 2)", "(3)"),
+        ("(list-tail '(1 2 3) 3)", "()"),
+        ("(list-tail (cons 1 '(2 3)) 2)", "(3)"),
+        ("(list-tail (cons 1 (cons 2 3)) 2)", "3"),
+    ];
+    for (source, expected) in table {
+        assert_eq!(eval_repr(source), expected, "{source}");
+    }
+
+    // Only the first k elements are walked.
+    assert_eq!(
+        eval(&format!("{CYCLE} (car (list-tail cycle 7))")).unwrap(),
+        Expr::Number(2.0)
+    );
+}
+
+#[test]
+fn test_linear_update_errors() {
+    let table = [
+        (
+            "(append! (cons 1 2) '())",
+            "append!: expected proper list as argument 1, got (1 . 2)",
+        ),
+        (
+            "(reverse! (cons 1 (cons 2 3)))",
+            "reverse!: expected proper list as argument 1, got (1 2 . 3)",
+        ),
+        (
+            "(list-tail '(1 2) 3)",
+            "list-tail: index 3 is out of range for length 2",
+        ),
+        (
+            "(list-tail (cons 1 (cons 2 3)) 3)",
+            "list-tail: index 3 is out of range for length 2",
+        ),
+        (
+            "(list-tail '(1 2) -1)",
+            "list-tail: expected index as argument 2, got -1",
+        ),
+    ];
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+
+    // A cyclic argument is an error, and the lists before it are unchanged.
+    let source = format!(
+        "{CYCLE}
+        (define a (cons 1 '()))
+        (append! a cycle '())"
+    );
+    match eval(&source) {
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "in form 4: append!: expected proper list as argument 2, got a circular list"
+        ),
+        Ok(value) => panic!("expected error, found {value:?}"),
+    }
+    let source = format!(
+        "{CYCLE}
+        (define a (cons 1 '()))
+        (guard (e (else a)) (append! a cycle '()))"
+    );
+    assert_eq!(eval_repr(&source), "(1)");
+}
+
+#[test]
+fn test_cyclic_repr() {
+    let value = eval(&format!("{CYCLE} cycle")).unwrap();
+    assert_eq!(value.write_repr().to_string(), "#0=(1 2 3 . #0#)");
+}
+
+#[test]
+fn test_write_datum_labels() {
+    let shared = "(define x (cons 1 (cons 2 '()))) (define y (cons x x))";
+    let table = [
+        (shared, "write", "y", "((1 2) 1 2)"),
+        (shared, "write-shared", "y", "(#0=(1 2) . #0#)"),
+        (shared, "write-simple", "y", "((1 2) 1 2)"),
+        (CYCLE, "write", "cycle", "#0=(1 2 3 . #0#)"),
+        (CYCLE, "write-shared", "cycle", "#0=(1 2 3 . #0#)"),
+        // Only the pairs on the cycle are labelled by write.
+        (
+            CYCLE,
+            "write",
+            "(cons cycle (cons cycle '()))",
+            "(#0=(1 2 3 . #0#) #0#)",
+        ),
+        (CYCLE, "write", "(cons 0 cycle)", "(0 . #0=(1 2 3 . #0#))"),
+        ("", "write", "'#0=(a #0# b)", "#0=(a #0# b)"),
+        // Shared structure read from labels stays shared.
+        ("", "write-shared", "'(#0=(a) #0#)", "(#0=(a) #0#)"),
+    ];
+
+    for (definitions, procedure, obj, expected) in table {
+        let source =
+            format!("{definitions} (with-output-to-string (lambda () ({procedure} {obj})))");
+        assert_eq!(
+            eval(&source).unwrap(),
+            Expr::String(expected.into()),
+            "({procedure} {obj})"
+        );
+    }
+
+    match eval(&format!("{CYCLE} (write-simple cycle)")) {
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "in form 3: write-simple: cannot write a circular structure"
+        ),
+        Ok(value) => panic!("expected error, found {value:?}"),
+    }
+}
+
+#[test]
+fn test_read_datum_labels() {
+    // Written cycles read back as equal structures.
+    let cycle = eval(&format!("{CYCLE} cycle")).unwrap();
+    let written = cycle.write_repr().to_string();
+    let read = scheme_engine::parse(&written, false).unwrap();
+    assert!(read.is_cyclic());
+    assert!(read.is_equal(&cycle), "{written}");
+
+    assert_eq!(
+        eval("(equal? '#0=(1 2 . #0#) '#1=(1 2 1 2 . #1#))").unwrap(),
+        Expr::Bool(true)
+    );
+    assert_eq!(eval_repr("'#0=()"), "()");
+    assert_eq!(eval_repr("#0=(+ 1 2)"), "3");
+
+    let table = [
+        ("'#0#", "undefined datum label: #0#"),
+        // Labels are only visible within their top-level datum.
+        ("'#0=(1) '#0#", "undefined datum label: #0#"),
+        (
+            "#0=(car #0#)",
+            "cannot compile a circular form: #0=(car #0#)",
+        ),
+    ];
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert!(err.to_string().contains(expected), "{source}: {err}"),
+            Ok(value) => panic!("{source}: expected error, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_literal_lists() {
+    // Lists are shared rather than copied when passed around,
+    // which must not be observable.
+    let source = "
+    (define numbers '(1 2 3))
+    (define same (lambda (list) list))
+    (assert-eq (same numbers) '(1 2 3))
+    (assert-eq (cdr numbers) '(2 3))
+    (assert-eq numbers '(1 2 3))
+    (same numbers)
+    ";
+    assert_eq!(eval_repr(source), "(1 2 3)");
+
+    // Literals can't be mutated through the procedures for pairs.
+    match eval("(define numbers '(1 2 3)) (set-car! numbers 9)") {
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "in form 2: set-car!: expected pair as argument 1, got (1 2 3)"
+        ),
+        Ok(value) => panic!("expected error, found {value:?}"),
+    }
+}
+
+#[test]
+fn test_list_from_iter() {
+    let numbers: Vec<Expr> = (0..5).map(|n| Expr::Number(n as f64)).collect();
+    let list: Expr = numbers.iter().cloned().collect();
+    assert!(matches!(list, Expr::Pair(_)));
+    assert_eq!(list.write_repr().to_string(), "(0 1 2 3 4)");
+    assert!(list.is_equal(&Expr::List(Rc::from(numbers.clone()))));
+
+    let elements = list.iter_list().unwrap().collect::<Result<Vec<_>, _>>();
+    assert_eq!(elements.unwrap(), numbers);
+    assert_eq!(Expr::list_from_iter([]), Expr::Nil);
+
+    // Pairs ending in a literal list.
+    let mixed = eval("(cons 0 (cons 1 '(2 3 4)))").unwrap();
+    let elements = mixed.iter_list().unwrap().collect::<Result<Vec<_>, _>>();
+    assert_eq!(elements.unwrap(), numbers);
+}
+
+#[test]
+fn test_long_lists() {
+    // Built, walked and dropped without recursing on the length.
+    let list = Expr::list_from_iter((0..100_000).map(|n| Expr::Number(n as f64)));
+    let mut count = 0;
+    for (index, element) in list.iter_list().unwrap().enumerate() {
+        assert_eq!(element.unwrap(), Expr::Number(index as f64));
+        count += 1;
+    }
+    assert_eq!(count, 100_000);
+
+    let copy: Expr = list.iter_list().unwrap().map(Result::unwrap).collect();
+    assert!(copy.is_equal(&list));
+    drop(list);
+    drop(copy);
+}
+
+#[test]
+fn test_deep_nesting() {
+    // Nested in the heads of pairs, returned to Rust and dropped.
+    let source = "
+    (do ((i 0 (+ i 1))
+         (nested '() (cons nested i)))
+        ((= i 100000) nested))
+    ";
+    let nested = eval(source).unwrap();
+    assert_eq!(nested.as_pair().unwrap().borrow().1, Expr::Number(99_999.0));
+    drop(nested);
+}
+
+#[test]
+fn test_iter_list_errors() {
+    let improper = eval("(cons 1 (cons 2 3))").unwrap();
+    assert_eq!(
+        improper.iter_list().err().unwrap().to_string(),
+        "expected proper list, got (1 2 . 3)"
+    );
+
+    let cycle = eval(&format!("{CYCLE} cycle")).unwrap();
+    assert_eq!(
+        cycle.iter_list().err().unwrap().to_string(),
+        "expected proper list, got a circular list"
+    );
+    // Nothing else refers to the pairs, so break the cycle for them to be freed.
+    cycle.as_pair().unwrap().clone().borrow_mut().1 = Expr::Nil;
+
+    assert_eq!(Expr::Nil.iter_list().unwrap().count(), 0);
+    assert!(Expr::Number(1.0).iter_list().is_err());
+}
+
+#[test]
+fn test_sort() {
+    let table = [
+        ("(sort '() <)", "()"),
+        ("(sort '(1) <)", "(1)"),
+        ("(sort '(3 1 2) <)", "(1 2 3)"),
+        ("(sort (cons 3 (cons 1 '(2))) >)", "(3 2 1)"),
+        // Sorted by the car only, equal cars keep their order.
+        (
+            "(sort '((2 . a) (1 . b) (2 . c) (1 . d) (2 . e) (0 . f)) \
+                   (lambda (x y) (< (car x) (car y))))",
+            "((0 . f) (1 . b) (1 . d) (2 . a) (2 . c) (2 . e))",
+        ),
+        // Nothing is less than anything else, so nothing moves.
+        ("(sort '(5 3 4 1 2) (lambda (x y) #f))", "(5 3 4 1 2)"),
+    ];
+
+    for (source, expected) in table {
+        assert_eq!(eval_repr(source), expected, "{source}");
+    }
+
+    // An inconsistent comparator gives an odd order, but all the elements.
+    let source = "(length (sort '(5 3 4 1 2 9 8 7 6) (lambda (x y) #t)))";
+    assert_eq!(eval(source).unwrap(), Expr::Number(9.0));
+
+    // Long enough for many passes.
+    let source = "
+    (define (count-down n) (do ((i 0 (+ i 1)) (acc '() (cons i acc))) ((= i n) acc)))
+    (sort (count-down 1000) (lambda (a b) (< a b)))
+    ";
+    let sorted = eval(source).unwrap();
+    let expected = Expr::list_from_iter((0..1000).map(|n| Expr::Number(n as f64)));
+    assert!(sorted.is_equal(&expected));
+}
+
+#[test]
+fn test_sort_in_place() {
+    // The pairs of the list are reused.
+    let source = "
+    (define numbers (cons 3 (cons 1 (cons 2 '()))))
+    (sort! numbers <)
+    numbers
+    ";
+    assert_eq!(eval_repr(source), "(1 2 3)");
+
+    // Pairs ending in a literal list.
+    let source = "
+    (define numbers (cons 4 (cons 3 '(1 2))))
+    (sort! numbers <)
+    numbers
+    ";
+    assert_eq!(eval_repr(source), "(1 2 3 4)");
+
+    // Literal lists are sorted into a new list.
+    assert_eq!(eval_repr("(sort! '(2 1) <)"), "(1 2)");
+    assert_eq!(eval_repr("(sort! '() <)"), "()");
+}
+
+#[test]
+fn test_sort_errors() {
+    let table = [
+        (
+            "(sort (cons 1 2) <)",
+            "sort: expected proper list as argument 1, got (1 . 2)",
+        ),
+        (
+            "(sort! '(1 2) 3)",
+            "sort!: expected procedure as argument 2, got 3",
+        ),
+        ("(sort '(1 a) <)", "<: expected number as argument 1, got a"),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+
+    match eval(&format!("{CYCLE} (sort cycle <)")) {
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "in form 3: sort: expected proper list as argument 1, got a circular list"
+        ),
+        Ok(value) => panic!("expected error, found {value:?}"),
+    }
+
+    // The first error from the comparator ends the sort.
+    let env = scheme_engine::new_env().unwrap();
+    let source = "
+    (define calls 0)
+    (sort '(4 3 2 1) (lambda (a b) (set! calls (+ calls 1)) (car a)))
+    ";
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let err = scheme_engine::eval(closure).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "in form 2: car: expected pair as argument 1, got 3"
+    );
+    assert_eq!(env.borrow().lookup_var("calls"), Some(&Expr::Number(1.0)));
+}
@@ -0,0 +1,235 @@
+//! The lexer, parser and formatter must return an error for malformed source, never panic.
+//!
+//! Inputs that used to panic are kept as regression cases. A seeded fuzzer
+//! generates more from random bytes and from fragments of Scheme syntax.
+use std::panic::{self, AssertUnwindSafe};
+
+use scheme_engine::{parse_program_in, tokens_to_source, FormatOptions, Lexer, ParseArena};
+
+/// Parse and lex the source, returning the panic message if any step panicked.
+fn check(source: &str) -> Result<(), String> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let _ = scheme_engine::parse_program(source);
+        let _ = scheme_engine::parse(source, false);
+        let _ = scheme_engine::format_source(source, FormatOptions::default());
+
+        // Trivia tokens cover the source exactly.
+        let tokens: Vec<_> = Lexer::with_trivia(source).into_iter().collect();
+        assert_eq!(tokens_to_source(&tokens, source), source);
+    }))
+    .map_err(|payload| {
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|text| text.to_string()))
+            .unwrap_or_default()
+    })
+}
+
+/// Small deterministic generator, so failures can be reproduced from the seed.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+const FRAGMENTS: &[&str] = &[
+    "(",
+    ")",
+    "(",
+    ")",
+    "'",
+    "#(",
+    "#u8(",
+    ".",
+    " . ",
+    "#",
+    "#t",
+    "#f",
+    "#\\",
+    "#\\a",
+    "#\\space",
+    "#\\x41",
+    "#|",
+    "|#",
+    ";",
+    "\n",
+    " ",
+    "\t",
+    "\"",
+    "\\",
+    "\"a\"",
+    "|",
+    "|a b|",
+    "1",
+    "-1",
+    "1.5",
+    "+",
+    "-",
+    "...",
+    "abc",
+    "λ",
+    "é",
+    "😀",
+    "\u{0}",
+    "define",
+    "lambda",
+    "quote",
+    "#!",
+    "#;",
+    "#e",
+    "#x",
+    "1/2",
+    "\r\n",
+    "#!fold-case",
+    "#!no-fold-case",
+    "#b",
+    "#void",
+    "1e",
+    ".5",
+    "#0=",
+    "#0#",
+    "#1=(",
+];
+
+fn random_fragments(rng: &mut XorShift) -> String {
+    let length = rng.below(40);
+    (0..length)
+        .map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())])
+        .collect()
+}
+
+fn random_bytes(rng: &mut XorShift) -> String {
+    let length = rng.below(40);
+    let bytes: Vec<u8> = (0..length)
+        .map(|_| {
+            // Mostly the bytes of Scheme syntax, to get past the first token.
+            let syntax = b"()'#\\\";|. \nab1";
+            if rng.below(4) == 0 {
+                rng.next() as u8
+            } else {
+                syntax[rng.below(syntax.len())]
+            }
+        })
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[test]
+fn test_regressions() {
+    let table = [
+        // Unknown `#` atoms.
+        "#",
+        "#x",
+        "#abc",
+        "(#)",
+        "#!eof",
+        "#b",
+        "#x-",
+        "#x+-1",
+        "#b102",
+        // A quote without a datum.
+        "'",
+        "(')",
+        "''",
+        "'; comment",
+        // Multibyte characters next to other tokens.
+        "λ",
+        "(é",
+        "#\\",
+        "#\\λ",
+        "\"😀",
+        "#|😀",
+        "|😀",
+        "#u8(é)",
+        // Unbalanced parentheses.
+        ")",
+        ")(",
+        "(()",
+        "#(",
+        "#u8(1",
+        // Dots out of place.
+        ".",
+        "(.)",
+        "(. 1)",
+        "(1 .)",
+        "(1 . 2 3)",
+        "'.",
+        "(1 . . 2)",
+        // Unterminated literals.
+        "\"abc",
+        "\"\\",
+        "|abc",
+        "#|",
+        "#| #| |#",
+        "\u{0}",
+        "(\u{0})",
+        // Datum labels without a datum, or referring to nothing.
+        "#0=",
+        "(#0=)",
+        "#0=#0#",
+        "#0=(#0# . #0#)",
+        "'#0=(1 . #0#)",
+        "#0=(car #0#)",
+        "#99999999999999999999999=1",
+    ];
+
+    for source in table {
+        if let Err(message) = check(source) {
+            panic!("{source:?} panicked: {message}");
+        }
+    }
+}
+
+#[test]
+fn test_deep_nesting() {
+    // Each level of nesting is a recursive call, so the depth is limited
+    // before the stack overflows.
+    let deep = format!("'{}", "(".repeat(20_000));
+    if let Err(message) = check(&deep) {
+        panic!("deep nesting panicked: {message}");
+    }
+    let expected = "expression at 1:257 is nested more than 256 deep";
+    let err = scheme_engine::parse_program(&deep).unwrap_err();
+    assert_eq!(err.to_string(), expected);
+    let err = parse_program_in(&ParseArena::new(), &deep).unwrap_err();
+    assert_eq!(err.to_string(), expected);
+
+    // Up to the limit is fine.
+    let nested = format!("{}{}", "(".repeat(256), ")".repeat(256));
+    assert!(scheme_engine::parse_program(&nested).is_ok());
+    let quoted = format!("{}{}", "'".repeat(254), "#(1)");
+    assert!(scheme_engine::parse_program(&quoted).is_ok());
+}
+
+#[test]
+fn test_fuzz() {
+    let mut rng = XorShift(0x5eed_f00d_cafe_beef);
+
+    // Set SCHEME_FUZZ_ITERATIONS for a longer run.
+    let iterations = std::env::var("SCHEME_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .unwrap_or(20_000);
+
+    for iteration in 0..iterations {
+        let source = if iteration % 2 == 0 {
+            random_fragments(&mut rng)
+        } else {
+            random_bytes(&mut rng)
+        };
+
+        if let Err(message) = check(&source) {
+            panic!("{source:?} panicked: {message}");
+        }
+    }
+}
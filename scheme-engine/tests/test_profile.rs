@@ -0,0 +1,59 @@
+use scheme_engine::{Expr, ProcProfile};
+
+fn profile(source: &str) -> (Expr, Vec<ProcProfile>) {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    scheme_engine::eval_with_profile(closure).unwrap()
+}
+
+/// Calls made by naive `(fib n)`, counting itself.
+fn fib_calls(n: u64) -> u64 {
+    let (mut a, mut b) = (0, 1);
+    for _ in 0..n + 1 {
+        (a, b) = (b, a + b);
+    }
+    2 * a - 1
+}
+
+#[test]
+fn test_profile_fibonacci() {
+    let (_, profile) = profile(include_str!("test_fibonacci.scm"));
+
+    // The script calls fib with 0, 1, 2, 3 and 5.
+    let fib = &profile[0];
+    assert_eq!(fib.name, "fib");
+    let calls: u64 = [0, 1, 2, 3, 5].into_iter().map(fib_calls).sum();
+    assert_eq!(fib.calls, calls);
+
+    let top_level = profile.iter().find(|entry| entry.name == "top-level");
+    assert_eq!(top_level.unwrap().calls, 1);
+}
+
+#[test]
+fn test_profile_recursion() {
+    let source = r"
+    (define fib (lambda (n)
+                  (if (<= n 1)
+                    n
+                    (+ (fib (- n 1)) (fib (- n 2))))))
+    (fib 15)
+    ";
+    let (value, profile) = profile(source);
+    assert_eq!(value, Expr::Number(610.0));
+
+    assert_eq!(profile[0].name, "fib");
+    assert_eq!(profile[0].calls, fib_calls(15));
+    assert!(profile[0].instructions > 10 * profile[1].instructions);
+}
+
+#[test]
+fn test_profile_anonymous_procedures() {
+    let (value, profile) = profile("((lambda (x) (* x x)) 7)");
+    assert_eq!(value, Expr::Number(49.0));
+
+    let names: Vec<&str> = profile.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"lambda"), "{names:?}");
+    assert!(names.contains(&"top-level"), "{names:?}");
+}
@@ -0,0 +1,58 @@
+use scheme_engine::{Env, Error, Expr, Handle};
+
+fn panicking_env() -> Handle<Env> {
+    let env = scheme_engine::new_env().unwrap();
+    let mut env_mut = env.clone();
+    let mut env_mut = env_mut.borrow_mut();
+    env_mut
+        .bind_native_func("explode", |_, _| panic!("explode was called"))
+        .unwrap();
+    env_mut
+        .bind_native_func("explode-with", |_, args| {
+            panic!("explode-with {}", args[0].repr())
+        })
+        .unwrap();
+    env
+}
+
+fn compile(env: &Handle<Env>, source: &str) -> Handle<scheme_engine::Closure> {
+    let expr = scheme_engine::parse(source, true).unwrap();
+    scheme_engine::compile(env.clone(), &expr).unwrap()
+}
+
+#[test]
+fn test_panic_becomes_error() {
+    let env = panicking_env();
+
+    let err = scheme_engine::eval_protected(compile(&env, "(define a 1) (explode)")).unwrap_err();
+    assert!(
+        matches!(&err, Error::Reason(message) if message == "internal interpreter error: explode was called"),
+        "{err}"
+    );
+
+    // Formatted panic messages are carried over too.
+    let err = scheme_engine::eval_protected(compile(&env, "(explode-with 7)")).unwrap_err();
+    assert!(err.to_string().contains("explode-with 7"), "{err}");
+
+    // The environment is still usable, with the definition made before the panic.
+    let value = scheme_engine::eval_protected(compile(&env, "(+ a 2)")).unwrap();
+    assert_eq!(value, Expr::Number(3.0));
+}
+
+#[test]
+fn test_call_protected() {
+    let env = panicking_env();
+    let procedure = scheme_engine::eval(compile(&env, "(lambda (x) (if x (explode) 1))")).unwrap();
+    let Expr::Closure(closure) = &procedure else {
+        panic!("expected a procedure");
+    };
+
+    let err = scheme_engine::call_protected(closure.clone(), &[Expr::Bool(true)]).unwrap_err();
+    assert!(
+        err.to_string().starts_with("internal interpreter error"),
+        "{err}"
+    );
+
+    let value = scheme_engine::call_protected(closure.clone(), &[Expr::Bool(false)]).unwrap();
+    assert_eq!(value, Expr::Number(1.0));
+}
@@ -0,0 +1,31 @@
+//! Guards the public surface that embedders rely on.
+//!
+//! Only the prelude may be imported here, so removing anything
+//! from it breaks this test.
+use scheme_engine::prelude::*;
+
+fn double(_env: &mut Env, args: &[Expr]) -> Result<Expr> {
+    match args {
+        [Expr::Number(number)] => Ok(Expr::Number(number * 2.0)),
+        _ => Err(Error::Reason("double: expected one number".to_string())),
+    }
+}
+
+#[test]
+fn test_prelude_end_to_end() -> Result<()> {
+    let env: Handle<Env> = new_env()?;
+    let native: NativeFunc = double;
+    env.clone()
+        .borrow_mut()
+        .bind_native_func("double", native)?;
+
+    let program = parse_program("(define add (lambda (a b) (+ a b))) (double (add 1 2))")?;
+    let closure: Handle<Closure> = compile(env.clone(), &program)?;
+    assert_eq!(eval(closure)?, Expr::Number(6.0));
+
+    let add = env.borrow().lookup_var("add").cloned().unwrap();
+    let add = add.as_closure().unwrap().clone();
+    assert_eq!(call(add, &[2.0.into(), 3.0.into()])?, Expr::from(5.0));
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use scheme_engine::{error::Error, CompileOptions, Expr};
+
+fn eval_pure(setup: &str, formula: &str) -> Result<Expr, Error> {
+    let env = scheme_engine::new_env()?;
+    let expr = scheme_engine::parse(setup, true)?;
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr)?)?;
+
+    let options = CompileOptions {
+        pure: true,
+        ..CompileOptions::default()
+    };
+    let expr = scheme_engine::parse(formula, true)?;
+    let closure = scheme_engine::compile_with_options(env.clone(), &expr, &options)?;
+    scheme_engine::eval(closure)
+}
+
+#[test]
+fn test_pure_formula() {
+    let setup = r"
+    (define rate 0.5)
+    (define clamp (lambda (x limit) (if (> x limit) limit x)))
+    ";
+    let table = [
+        // The rate is inexact, and so is what's computed from it.
+        ("(if (> 3 2) (* 10 rate) 0)", Expr::Inexact(5.0)),
+        ("(clamp (+ 40 20) 50)", Expr::Number(50.0)),
+        // Loops only update their own variables.
+        (
+            "(do ((i 0 (+ i 1)) (sum 0 (+ sum rate))) ((= i 4) sum))",
+            Expr::Inexact(2.0),
+        ),
+        (
+            "(length (map (lambda (x) (* x x)) '(1 2 3)))",
+            Expr::Number(3.0),
+        ),
+    ];
+
+    for (formula, expected) in table {
+        assert_eq!(eval_pure(setup, formula).unwrap(), expected, "{formula}");
+    }
+}
+
+#[test]
+fn test_pure_rejects_effects() {
+    let setup = r"
+    (define x 1)
+    (define log (lambda (value) (display value) value))
+    (define make-logger (lambda () (lambda (value) (newline))))
+    (define counter 0)
+    (define bump (lambda () (set! counter (+ counter 1))))
+    ";
+    let table = [
+        ("(set! x 1)", "set!"),
+        ("(display x)", "display"),
+        ("(define y 2)", "define"),
+        ("(define-test \"formula\" (assert #t))", "define-test"),
+        ("(map display '(1 2))", "display"),
+        ("(log 10)", "log"),
+        ("((make-logger) 10)", "make-logger"),
+        ("(if #f (bump) 0)", "bump"),
+        ("(set-car! (cons 1 2) 3)", "set-car!"),
+    ];
+
+    for (formula, name) in table {
+        match eval_pure(setup, formula) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                format!("{name:?} is not allowed in pure expression mode"),
+                "{formula}"
+            ),
+            Ok(value) => panic!("expected error for {formula}, found {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_effect_flags() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse(
+        "(define square (lambda (x) (* x x))) (define shout (lambda (x) (display x)))",
+        true,
+    )
+    .unwrap();
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap();
+
+    let env = env.borrow();
+    for name in ["display", "newline", "shout", "read-line", "set-cdr!"] {
+        let symbol = env.resolve_var(name).unwrap();
+        assert!(env.is_effectful(symbol), "{name}");
+    }
+    for name in ["+", "if-missing", "not", "car", "square", "map"] {
+        if let Some(symbol) = env.resolve_var(name) {
+            assert!(!env.is_effectful(symbol), "{name}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::eval;
+use scheme_engine::{Expr, Handle};
+
+/// Small deterministic generator, so failures can be reproduced.
+struct Datums {
+    state: u64,
+}
+
+impl Datums {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.state >> 33) % bound
+    }
+
+    /// Source text of a quoted datum mixing lists, dotted pairs,
+    /// vectors, bytevectors, strings and symbols.
+    fn datum(&mut self, depth: usize) -> String {
+        let kinds = if depth == 0 { 4 } else { 7 };
+        match self.next(kinds) {
+            0 => self.next(100).to_string(),
+            1 => format!("\"s{}\"", self.next(10)),
+            2 => format!("sym{}", self.next(10)),
+            3 => format!("#u8({} {})", self.next(256), self.next(256)),
+            4 => format!("({})", self.elements(depth)),
+            5 => format!("({} . {})", self.elements(depth), self.datum(depth - 1)),
+            _ => format!("#({})", self.elements(depth)),
+        }
+    }
+
+    fn elements(&mut self, depth: usize) -> String {
+        let count = 1 + self.next(3);
+        (0..count)
+            .map(|_| self.datum(depth - 1))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Change every pair, vector and bytevector reachable from the value.
+fn mutate(value: &Expr) {
+    match value {
+        Expr::Pair(pair) => {
+            let (head, tail) = pair.borrow().clone();
+            mutate(&head);
+            mutate(&tail);
+            let mut pair: Handle<(Expr, Expr)> = pair.clone();
+            pair.borrow_mut().0 = Expr::Ident("changed".into());
+        }
+        Expr::Bytevector(bytes) => {
+            let mut bytes = bytes.clone();
+            bytes.borrow_mut().fill(0);
+        }
+        Expr::List(elements) => elements.iter().for_each(mutate),
+        Expr::Vector(vector) => {
+            let elements = vector.borrow().clone();
+            elements.iter().for_each(mutate);
+            let mut vector: Handle<Vec<Expr>> = vector.clone();
+            vector.borrow_mut().fill(Expr::Ident("changed".into()));
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn test_quoted_literals_are_protected() {
+    let mut datums = Datums { state: 955 };
+
+    for _ in 0..50 {
+        let datum = datums.datum(3);
+        let env = scheme_engine::new_env().unwrap();
+        let expr = scheme_engine::parse(&format!("(lambda () '{datum})"), true).unwrap();
+        let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+        let procedure = scheme_engine::eval(closure).unwrap();
+        let Expr::Closure(literal) = &procedure else {
+            panic!("expected a procedure");
+        };
+
+        let first = scheme_engine::call(literal.clone(), &[]).unwrap();
+        let written = first.write_repr().to_string();
+        mutate(&first);
+
+        let second = scheme_engine::call(literal.clone(), &[]).unwrap();
+        assert_eq!(second.write_repr().to_string(), written, "{datum}");
+
+        // Written text reads back as the same datum.
+        let read_back = eval(&format!("'{written}")).unwrap();
+        assert_eq!(read_back.write_repr().to_string(), written, "{datum}");
+    }
+}
+
+#[test]
+fn test_quoted_mixed_data() {
+    let value = eval(r#"'#(1 (2 . 3) "x" sym)"#).unwrap();
+    assert_eq!(value.write_repr().to_string(), r#"#(1 (2 . 3) "x" sym)"#);
+
+    let elements = value.as_vector().unwrap();
+    assert!(elements[1].as_pair().is_some());
+    assert_eq!(elements[3], Expr::Ident("sym".into()));
+
+    assert_eq!(
+        eval("(cdr (car (cdr '(1 (2 . 3)))))").unwrap(),
+        Expr::Number(3.0)
+    );
+    assert_eq!(
+        eval("'(a 'b)").unwrap().write_repr().to_string(),
+        "(a (quote b))"
+    );
+}
+
+#[test]
+fn test_bytevector_literal_is_copied() {
+    let source = r"
+    (define fresh (lambda () #u8(1 2 3)))
+    (bytevector-u8-set! (fresh) 0 99)
+    (bytevector-u8-ref (fresh) 0)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(1.0));
+}
@@ -0,0 +1,86 @@
+use scheme_engine::{CompileOptions, Env, Expr, Handle, Redefinition, Result};
+
+const PRELUDE: &str = "(define greeting \"hi\")";
+const USER: &str = "(define greeting \"hello\") greeting";
+
+/// Compile and evaluate a source into the environment, with its warnings.
+fn load(
+    env: &Handle<Env>,
+    source: &str,
+    name: &str,
+    redefinition: Redefinition,
+) -> Result<(Expr, Vec<String>)> {
+    let options = CompileOptions {
+        redefinition,
+        source_name: Some(name.to_string()),
+        ..CompileOptions::default()
+    };
+    let expr = scheme_engine::parse(source, true)?;
+    let (closure, warnings) = scheme_engine::compile_with_warnings(env.clone(), &expr, &options)?;
+    let value = scheme_engine::eval(closure)?;
+    Ok((value, warnings.iter().map(ToString::to_string).collect()))
+}
+
+#[test]
+fn test_redefinition_allowed() {
+    let env = scheme_engine::new_env().unwrap();
+    load(&env, PRELUDE, "prelude.scm", Redefinition::Allow).unwrap();
+    let (value, warnings) = load(&env, USER, "user.scm", Redefinition::Allow).unwrap();
+    assert_eq!(value, Expr::String("hello".into()));
+    assert!(warnings.is_empty(), "{warnings:?}");
+
+    let symbol = env.borrow().resolve_var("greeting").unwrap();
+    assert_eq!(
+        env.borrow().def_site(symbol).unwrap().source.as_deref(),
+        Some("user.scm")
+    );
+}
+
+#[test]
+fn test_redefinition_warns() {
+    let env = scheme_engine::new_env().unwrap();
+    load(&env, PRELUDE, "prelude.scm", Redefinition::Warn).unwrap();
+    let (value, warnings) = load(&env, USER, "user.scm", Redefinition::Warn).unwrap();
+    assert_eq!(value, Expr::String("hello".into()));
+    assert_eq!(
+        warnings,
+        vec!["'greeting' is already defined (previously in prelude.scm)".to_string()]
+    );
+}
+
+#[test]
+fn test_redefinition_forbidden() {
+    let env = scheme_engine::new_env().unwrap();
+    load(&env, PRELUDE, "prelude.scm", Redefinition::Forbid).unwrap();
+    let err = load(&env, USER, "user.scm", Redefinition::Forbid).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"in form 1 (define greeting "hello"): 'greeting' is already defined (previously in prelude.scm)"#
+    );
+
+    // The prelude's definition is kept.
+    let symbol = env.borrow().resolve_var("greeting").unwrap();
+    assert_eq!(
+        env.borrow().get_var(symbol),
+        Some(&Expr::String("hi".into()))
+    );
+}
+
+#[test]
+fn test_redefinition_ignores_natives() {
+    let env = scheme_engine::new_env().unwrap();
+    let (_, warnings) = load(&env, "(define reverse 0)", "user.scm", Redefinition::Forbid).unwrap();
+    assert_eq!(
+        warnings,
+        vec!["definition of 'reverse' shadows a core procedure".to_string()]
+    );
+
+    // Natives are still bound once per name, even over a definition.
+    load(&env, PRELUDE, "prelude.scm", Redefinition::Allow).unwrap();
+    let err = env
+        .clone()
+        .borrow_mut()
+        .bind_native_func("greeting", |_, _| Ok(Expr::Void))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "variable already bound \"greeting\"");
+}
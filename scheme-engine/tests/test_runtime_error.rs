@@ -0,0 +1,194 @@
+//! Runtime errors carry a snapshot of the virtual machine.
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use common::{eval, eval_with};
+use scheme_engine::{error::Error, EvalOptions, Expr, ParseOptions};
+
+#[test]
+fn test_stack_preview() {
+    let env = scheme_engine::new_env().unwrap();
+    let output = Rc::new(RefCell::new(String::new()));
+    let sink = output.clone();
+    env.clone()
+        .borrow_mut()
+        .set_printer(move |text| sink.borrow_mut().push_str(text));
+
+    let expr = scheme_engine::parse(r#"(+ 1 (+ 2 "three"))"#, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let err = scheme_engine::eval(closure).unwrap_err();
+
+    // The state is returned in the error, not printed.
+    assert_eq!(output.take(), "");
+
+    match err {
+        Error::Runtime {
+            message,
+            stack_preview,
+            trace,
+        } => {
+            assert_eq!(message, r#"+: expected number as argument 2, got "three""#);
+            assert_eq!(
+                &stack_preview[..5],
+                &[
+                    r#""three""#,
+                    "2",
+                    "<native-function>",
+                    "1",
+                    "<native-function>"
+                ]
+            );
+            assert_eq!(trace.len(), 1);
+            assert!(trace[0].starts_with("CallNative"), "{trace:?}");
+        }
+        other => panic!("expected runtime error, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_stack_preview_limit() {
+    let options = EvalOptions {
+        stack_preview: 2,
+        ..EvalOptions::default()
+    };
+    let err = eval_with(r#"(+ 1 (+ 2 "three"))"#, &ParseOptions::default(), &options).unwrap_err();
+
+    match err {
+        Error::Runtime { stack_preview, .. } => {
+            assert_eq!(stack_preview, vec![r#""three""#, "2"]);
+        }
+        other => panic!("expected runtime error, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_trace() {
+    let source = r#"
+    (define f (lambda (x) (+ x "a")))
+    (define g (lambda (x) (f x)))
+    (g 1)
+    "#;
+    let err = eval(source).unwrap_err();
+
+    match err {
+        Error::Runtime { trace, .. } => {
+            // Innermost frame first, ending at the top-level program.
+            assert_eq!(trace.len(), 3, "{trace:?}");
+            for frame in &trace {
+                assert!(frame.starts_with("CallNative"), "{trace:?}");
+            }
+        }
+        other => panic!("expected runtime error, found {other:?}"),
+    }
+}
+
+#[test]
+fn test_form_number() {
+    // A runtime error names the top-level form the failing procedure
+    // was defined in, rather than the form that called it.
+    let source = r"
+    (define first (lambda (list) (car list)))
+    (define numbers '(1 2 3))
+    (first numbers)
+    (first 42)
+    ";
+    let err = eval(source).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "in form 1: car: expected pair as argument 1, got 42"
+    );
+
+    // Errors in the top-level code name their own form.
+    let err = eval("(define x 1) (+ x #t)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "in form 2: +: expected number as argument 2, got #t"
+    );
+
+    // Compile errors show the start of the form.
+    let source = r"
+    (define add1 (lambda (n) (+ n 1)))
+    (define make-adder (lambda (n) (lambda (x) (+ x n z))))
+    ";
+    let err = eval(source).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"in form 2 (define make-adder (lambda (n) (lambda (x) (+ x n z)))), definition of 'make-adder' > lambda: unbound variable "z""#
+    );
+}
+
+#[test]
+fn test_strict_unspecified() {
+    let strict = EvalOptions {
+        strict_unspecified: true,
+        ..EvalOptions::default()
+    };
+    let table = [
+        ("(define x 1) (+ 1 (set! x 2))", "in form 2: use of unspecified value (result of set!/define/one-armed if) as argument 2"),
+        ("(define x 1) (cons (set! x 2) 1)", "in form 2: use of unspecified value (result of set!/define/one-armed if) as argument 1"),
+        ("(define (f x) x) (f (if #f #f))", "in form 2: use of unspecified value (result of set!/define/one-armed if) as argument 1"),
+        ("((if #f #f) 1)", "use of unspecified value (result of set!/define/one-armed if) as the procedure to call"),
+        ("(define x 1) (if (set! x 2) 'yes 'no)", "in form 2: use of unspecified value (result of set!/define/one-armed if) as a test"),
+    ];
+    for (source, expected) in table {
+        let err = eval_with(source, &ParseOptions::default(), &strict).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{source}");
+    }
+
+    // By default the value flows on, and only fails where its type is checked.
+    let default = EvalOptions::default();
+    let value = eval_with(
+        "(define x 1) (cons 1 (set! x 2))",
+        &ParseOptions::default(),
+        &default,
+    )
+    .unwrap();
+    assert_eq!(value.write_repr().to_string(), "(1 . #!void)");
+    let value = eval_with(
+        "(define x 1) (if (set! x 2) 'yes 'no)",
+        &ParseOptions::default(),
+        &default,
+    )
+    .unwrap();
+    assert_eq!(value.write_repr().to_string(), "yes");
+    let err = eval_with(
+        "(define x 1) (+ 1 (set! x 2))",
+        &ParseOptions::default(),
+        &default,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "in form 2: +: expected number as argument 2, got #!void"
+    );
+
+    // Unspecified values that are discarded are fine.
+    let source = "
+    (define x 1)
+    (define (f) (set! x 2) (if #f #f) x)
+    ((lambda () (set! x 3) (f)))
+    ";
+    assert_eq!(
+        eval_with(source, &ParseOptions::default(), &strict).unwrap(),
+        Expr::Number(2.0)
+    );
+}
+
+#[test]
+fn test_nesting_limit() {
+    // Recursion through the procedures natives call back ends in an
+    // error, instead of overflowing the stack.
+    let err = eval("(define (f x) (map f '(1))) (f 1)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "in form 1: procedure calls through native functions are nested more than 64 deep"
+    );
+
+    let source = "
+    (define (f n) (if (= n 0) 0 (car (map (lambda (k) (+ k (f (- n 1)))) '(1)))))
+    (f 60)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(60.0));
+}
@@ -0,0 +1,143 @@
+//! Scripts run one top-level form at a time, each with its own budget.
+use std::time::Duration;
+
+use scheme_engine::{run_script, Expr, FormResult, Limit, RunLimits};
+
+const SCRIPT: &str = "
+(define greeting \"hello\")
+(define forever (do () (#f)))
+(+ forever 1)
+greeting
+";
+
+fn describe(results: &[FormResult]) -> Vec<String> {
+    results
+        .iter()
+        .map(|result| match result {
+            FormResult::Value(value) => format!("value {}", value.write_repr()),
+            FormResult::Error(err) => format!("error {err}"),
+            FormResult::Limited(limit) => format!("limited {limit}"),
+            FormResult::Skipped => "skipped".to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_fuel_per_form() {
+    let env = scheme_engine::new_env().unwrap();
+    let limits = RunLimits {
+        fuel_per_form: Some(5_000),
+        ..RunLimits::default()
+    };
+    let results = run_script(&env, SCRIPT, &limits).unwrap();
+    assert_eq!(
+        describe(&results),
+        [
+            "value #!void",
+            "limited ran out of fuel after 5000 instructions",
+            "error +: expected number as argument 1, got #!void",
+            "value \"hello\"",
+        ]
+    );
+    assert!(matches!(
+        results[1],
+        FormResult::Limited(Limit::Fuel(5_000))
+    ));
+
+    // Every form gets the whole budget.
+    let source = "(do ((i 0 (+ i 1))) ((= i 200) i)) (do ((i 0 (+ i 1))) ((= i 200) i))";
+    let results = run_script(&env, source, &limits).unwrap();
+    assert_eq!(describe(&results), ["value 200", "value 200"]);
+}
+
+#[test]
+fn test_wall_clock_per_form() {
+    let env = scheme_engine::new_env().unwrap();
+    let limits = RunLimits {
+        wall_clock_per_form: Some(Duration::from_millis(50)),
+        ..RunLimits::default()
+    };
+    let results = run_script(&env, SCRIPT, &limits).unwrap();
+    assert!(matches!(results[0], FormResult::Value(Expr::Void)));
+    assert!(matches!(results[1], FormResult::Limited(Limit::Deadline)));
+    assert!(matches!(results[2], FormResult::Error(_)));
+    assert!(matches!(&results[3], FormResult::Value(Expr::String(text)) if &**text == "hello"));
+}
+
+#[test]
+fn test_limits_in_callbacks() {
+    // Procedures called back by natives run on the form's budget.
+    let env = scheme_engine::new_env().unwrap();
+    let limits = RunLimits {
+        fuel_per_form: Some(10_000),
+        ..RunLimits::default()
+    };
+    let results = run_script(&env, "(define (f x) (f x)) (for-each f '(1))", &limits).unwrap();
+    assert_eq!(
+        describe(&results),
+        [
+            "value #!void",
+            "limited ran out of fuel after 10000 instructions"
+        ]
+    );
+
+    let limits = RunLimits {
+        wall_clock_per_form: Some(Duration::from_millis(50)),
+        ..RunLimits::default()
+    };
+    let source = "(define (f x) (do () (#f))) (map f '(1))";
+    let results = run_script(&env, source, &limits).unwrap();
+    assert!(matches!(results[1], FormResult::Limited(Limit::Deadline)));
+
+    // A test that runs out stops the run, rather than failing.
+    let source = "(define-test \"forever\" (do () (#f))) (run-tests)";
+    let results = run_script(&env, source, &limits).unwrap();
+    assert!(matches!(results[1], FormResult::Limited(Limit::Deadline)));
+}
+
+#[test]
+fn test_abort_on_limit() {
+    let env = scheme_engine::new_env().unwrap();
+    let limits = RunLimits {
+        fuel_per_form: Some(5_000),
+        abort_on_limit: true,
+        ..RunLimits::default()
+    };
+    let results = run_script(&env, SCRIPT, &limits).unwrap();
+    assert_eq!(
+        describe(&results),
+        [
+            "value #!void",
+            "limited ran out of fuel after 5000 instructions",
+            "skipped",
+            "skipped",
+        ]
+    );
+}
+
+#[test]
+fn test_max_total_forms() {
+    let env = scheme_engine::new_env().unwrap();
+    let limits = RunLimits {
+        max_total_forms: Some(2),
+        ..RunLimits::default()
+    };
+    let results = run_script(&env, "1 2 3 4", &limits).unwrap();
+    assert_eq!(
+        describe(&results),
+        ["value 1", "value 2", "skipped", "skipped"]
+    );
+}
+
+#[test]
+fn test_compile_errors() {
+    let env = scheme_engine::new_env().unwrap();
+    let source = "(define x 1) (lambda) (+ x 1)";
+    let results = run_script(&env, source, &RunLimits::default()).unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(matches!(results[1], FormResult::Error(_)));
+    assert_eq!(describe(&results)[2], "value 2");
+
+    // A source that doesn't parse has no forms to run.
+    assert!(run_script(&env, "(define x", &RunLimits::default()).is_err());
+}
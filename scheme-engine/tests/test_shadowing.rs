@@ -0,0 +1,64 @@
+use scheme_engine::{error::Error, CompileOptions, Expr};
+
+fn compile_warnings(source: &str, options: &CompileOptions) -> Result<Vec<String>, Error> {
+    let env = scheme_engine::new_env()?;
+    let expr = scheme_engine::parse(source, true)?;
+    let (_closure, warnings) = scheme_engine::compile_with_warnings(env.clone(), &expr, options)?;
+    Ok(warnings.iter().map(ToString::to_string).collect())
+}
+
+#[test]
+fn test_shadowing_warns() {
+    let table = [
+        (
+            "(define car 1)",
+            "definition of 'car' shadows a core procedure",
+        ),
+        (
+            "(define f (lambda (length) length))",
+            "parameter 'length' shadows a core procedure",
+        ),
+        (
+            "(define f (lambda () (define display 0) display))",
+            "definition of 'display' shadows a core procedure",
+        ),
+        (
+            "(do ((cons 0 (+ cons 1))) ((= cons 3) cons))",
+            "loop variable 'cons' shadows a core procedure",
+        ),
+    ];
+
+    for (source, expected) in table {
+        let warnings = compile_warnings(source, &CompileOptions::default()).unwrap();
+        assert_eq!(warnings, vec![expected.to_string()], "{source}");
+    }
+}
+
+#[test]
+fn test_shadowing_ignores_other_names() {
+    let source = r"
+    (define counter 0)
+    (define f (lambda (x rest) (+ x rest)))
+    (do ((i 0 (+ i 1))) ((= i 3) i))
+    ";
+    let warnings = compile_warnings(source, &CompileOptions::default()).unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn test_shadowing_allowed() {
+    let options = CompileOptions {
+        allow_shadowing: true,
+        ..CompileOptions::default()
+    };
+    let warnings = compile_warnings("(define f (lambda (car) car))", &options).unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn test_shadowing_still_compiles() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("((lambda (length) (* length 2)) 21)", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    assert_eq!(scheme_engine::eval(closure).unwrap(), Expr::Number(42.0));
+}
@@ -0,0 +1,53 @@
+use scheme_engine::{Error, SourceMap};
+
+#[test]
+fn test_error_names_its_file() {
+    let env = scheme_engine::new_env().unwrap();
+    let mut env_mut = env.clone();
+
+    let first = "(define a 1)\n(define b 2)\n";
+    let expr =
+        scheme_engine::parse_program_named(env_mut.borrow_mut().sources_mut(), "first.scm", first)
+            .unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    scheme_engine::eval(closure).unwrap();
+
+    let second = "(display a)\n\n  (car b))\n";
+    let err = scheme_engine::parse_program_named(
+        env_mut.borrow_mut().sources_mut(),
+        "second.scm",
+        second,
+    )
+    .unwrap_err();
+
+    let env = env.borrow();
+    let Error::InSource { source_id, .. } = &err else {
+        panic!("expected an error located in a source, got {err:?}");
+    };
+    assert_eq!(env.sources().name(*source_id), Some("second.scm"));
+    assert_eq!(env.sources().text(*source_id), Some(second));
+    assert!(
+        env.sources()
+            .render_error(&err)
+            .starts_with("second.scm:3:10: expected expression but found ')'"),
+        "{}",
+        env.sources().render_error(&err)
+    );
+}
+
+#[test]
+fn test_incomplete_error_at_end() {
+    let mut sources = SourceMap::new();
+    scheme_engine::parse_program_named(&mut sources, "ok.scm", "'ok").unwrap();
+
+    let err = scheme_engine::parse_program_named(&mut sources, "open.scm", "(list\n  1\n  2")
+        .unwrap_err();
+    assert_eq!(
+        sources.render_error(&err),
+        "open.scm:3:4: expected ')' but found end-of-file at 3:4"
+    );
+
+    // Errors from unregistered sources render as before.
+    let err = scheme_engine::parse("(", false).unwrap_err();
+    assert_eq!(sources.render_error(&err), err.to_string());
+}
@@ -0,0 +1,97 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use scheme_engine::{Expr, StepControl};
+
+/// Run a program with a step hook, returning the names of the
+/// instructions the hook saw and the evaluation result.
+fn run_stepped(
+    source: &str,
+    mut control: impl FnMut(&scheme_engine::StepEvent) -> StepControl + 'static,
+) -> (Vec<String>, scheme_engine::Result<Expr>) {
+    let env = scheme_engine::new_env().unwrap();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+
+    let sink = seen.clone();
+    env.clone().borrow_mut().set_step_hook(move |event| {
+        sink.borrow_mut().push(event.op_name().to_string());
+        control(event)
+    });
+
+    let expr = scheme_engine::parse(source, true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let result = scheme_engine::eval(closure);
+
+    let seen = seen.take();
+    (seen, result)
+}
+
+#[test]
+fn test_step_sequence() {
+    let (seen, result) = run_stepped("(+ 1 2)", |_| StepControl::Step);
+    assert_eq!(result.unwrap(), Expr::Number(3.0));
+    assert_eq!(
+        seen,
+        [
+            "LoadEnvVar",
+            "PushConstant",
+            "PushConstant",
+            "CallNative",
+            "Return",
+        ]
+    );
+}
+
+#[test]
+fn test_step_into_closure() {
+    let source = "(define f (lambda (x) x)) (f 1)";
+    let depths = Rc::new(RefCell::new(Vec::new()));
+    let sink = depths.clone();
+    let (seen, result) = run_stepped(source, move |event| {
+        sink.borrow_mut().push(event.depth());
+        StepControl::Step
+    });
+
+    assert_eq!(result.unwrap(), Expr::Number(1.0));
+    assert!(seen.contains(&"LoadLocalVar".to_string()), "{seen:?}");
+    assert_eq!(depths.borrow().iter().max(), Some(&2));
+}
+
+#[test]
+fn test_breakpoint() {
+    // Continue right away, so only the breakpoint brings the hook back.
+    let (seen, result) = run_stepped("(define x 1) (breakpoint) (+ x 1)", |event| {
+        if event.is_breakpoint() {
+            StepControl::Step
+        } else if event.op_name() == "CallNative" {
+            // Top of the stack is the last argument.
+            assert_eq!(event.stack_top(0), Some(&Expr::Number(1.0)));
+            StepControl::Step
+        } else if event.pc() == 0 {
+            StepControl::Continue
+        } else {
+            StepControl::Step
+        }
+    });
+
+    assert_eq!(result.unwrap(), Expr::Number(2.0));
+    // The first instruction, then everything after the breakpoint call.
+    assert_eq!(seen.first().map(String::as_str), Some("PushConstant"));
+    assert!(seen.len() > 1, "{seen:?}");
+    assert_eq!(seen.last().map(String::as_str), Some("Return"));
+    assert!(!seen.contains(&"DefineEnvVar".to_string()), "{seen:?}");
+}
+
+#[test]
+fn test_step_abort() {
+    let (seen, result) = run_stepped("(display 1)", |_| StepControl::Abort);
+    assert_eq!(seen.len(), 1);
+    let err = result.unwrap_err();
+    assert!(err.to_string().contains("aborted"), "{err}");
+}
+
+#[test]
+fn test_breakpoint_without_hook() {
+    let output = scheme_engine::eval_to_string("(breakpoint) 42");
+    assert_eq!(output, "42");
+}
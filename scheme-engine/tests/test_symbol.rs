@@ -0,0 +1,177 @@
+mod common;
+
+use common::eval;
+use scheme_engine::Expr;
+
+/// The text `write` produces for the symbol named by `name`.
+fn write_symbol(name: &str) -> String {
+    let source = format!(
+        r#"
+        (define port (open-output-string))
+        (write (string->symbol {}) port)
+        (get-output-string port)
+        "#,
+        Expr::String(name.into()).write_repr()
+    );
+    match &eval(&source).unwrap() {
+        Expr::String(written) => written.to_string(),
+        other => panic!("expected a string, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_symbols_needing_bars() {
+    assert_eq!(write_symbol("hello world"), "|hello world|");
+    assert_eq!(write_symbol(""), "||");
+    assert_eq!(write_symbol("a|b"), r"|a\|b|");
+    assert_eq!(write_symbol("12"), "|12|");
+
+    // Plain names are written as they are.
+    assert_eq!(write_symbol("hello"), "hello");
+    assert_eq!(write_symbol("string->symbol"), "string->symbol");
+    assert_eq!(write_symbol("-"), "-");
+
+    // Display never adds bars.
+    assert_eq!(
+        eval(r#"(with-output-to-string (lambda () (display (string->symbol "a b"))))"#).unwrap(),
+        Expr::String("a b".into())
+    );
+}
+
+#[test]
+fn test_written_symbols_read_back() {
+    let names = [
+        "",
+        "hello world",
+        "a|b",
+        r"back\slash",
+        "tab\there",
+        "line\nbreak",
+        "(paren",
+        "close)",
+        "semi;colon",
+        "quote'd",
+        "\"quoted\"",
+        "#t",
+        "#hash",
+        "12",
+        "+5",
+        "-1.5e3",
+        ".",
+        "1.2.3",
+        "lambda",
+        "UPPER",
+        "λ",
+        "\u{1}",
+        "+",
+        "...",
+    ];
+
+    for name in names {
+        let written = write_symbol(name);
+        let source = format!("(symbol->string '{written})");
+        assert_eq!(
+            eval(&source).unwrap(),
+            Expr::String(name.into()),
+            "{name:?} was written as {written}"
+        );
+    }
+}
+
+#[test]
+fn test_symbol_conversions() {
+    assert_eq!(eval("(symbol? 'a)").unwrap(), Expr::Bool(true));
+    assert_eq!(eval(r#"(symbol? "a")"#).unwrap(), Expr::Bool(false));
+    assert_eq!(
+        eval("(symbol->string '|a b|)").unwrap(),
+        Expr::String("a b".into())
+    );
+    assert_eq!(
+        eval(r#"(string->symbol "abc")"#).unwrap(),
+        Expr::Ident("abc".into())
+    );
+
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("(symbol->string \"a\")", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    let err = scheme_engine::eval(closure).unwrap_err();
+    assert!(err.to_string().contains("expected symbol"), "{err}");
+}
+
+#[test]
+fn test_quoted_symbols() {
+    // Quoted identifiers are symbols, not variables to look up.
+    assert_eq!(
+        eval("(symbol? 'undefined-variable)").unwrap(),
+        Expr::Bool(true)
+    );
+    assert_eq!(eval("(symbol? '())").unwrap(), Expr::Bool(false));
+    assert!(eval("'()").unwrap().is_null());
+    assert_eq!(
+        eval("(symbol->string (quote abc))").unwrap(),
+        Expr::from("abc")
+    );
+
+    // The shorthand and the special form read the same structure.
+    assert_eq!(
+        eval("(equal? (quote (a b 1)) '(a b 1))").unwrap(),
+        Expr::Bool(true)
+    );
+    assert_eq!(
+        eval("(cdr '(a b 1))").unwrap().write_repr().to_string(),
+        "(b 1)"
+    );
+
+    // Symbols with the same name are the same object.
+    let table = [
+        ("(eq? 'a 'a)", true),
+        ("(eq? 'a (string->symbol \"a\"))", true),
+        ("(eqv? 'a (car '(a)))", true),
+        ("(eq? 'a 'b)", false),
+        ("(eq? 'a \"a\")", false),
+        ("(eq? '() '())", true),
+        ("(let ((pair (cons 1 2))) (eq? pair pair))", true),
+        ("(eq? (cons 1 2) (cons 1 2))", false),
+        ("(eqv? 0.0 -0.0)", false),
+        ("(eqv? car car)", true),
+    ];
+    for (source, expected) in table {
+        assert_eq!(eval(source).unwrap(), Expr::Bool(expected), "{source}");
+    }
+}
+
+#[test]
+fn test_symbols_from_rust() {
+    let env = scheme_engine::new_env().unwrap();
+    let source = r#"
+    (define (same-as-click? event) (eq? event 'click))
+    (define (handle event)
+      (cond ((eq? event 'click) "clicked")
+            ((eq? event (string->symbol "key press")) "pressed")
+            (else (symbol->string event))))
+    "#;
+    let expr = scheme_engine::parse(source, true).unwrap();
+    scheme_engine::eval(scheme_engine::compile(env.clone(), &expr).unwrap()).unwrap();
+
+    let procedure = |name: &str| match env.borrow().lookup_var(name) {
+        Some(Expr::Closure(closure)) => closure.clone(),
+        other => panic!("expected {name} closure, found {other:?}"),
+    };
+    let call = |name: &str, event: &str| {
+        scheme_engine::call(procedure(name), &[Expr::symbol(event)]).unwrap()
+    };
+
+    // Symbols made in Rust are the same as those written in source.
+    assert_eq!(call("same-as-click?", "click"), Expr::Bool(true));
+    assert_eq!(call("same-as-click?", "Click"), Expr::Bool(false));
+
+    assert_eq!(call("handle", "click"), Expr::from("clicked"));
+    assert_eq!(call("handle", "key press"), Expr::from("pressed"));
+    assert_eq!(call("handle", "scroll"), Expr::from("scroll"));
+
+    // Symbols from Scheme read back in Rust, bars and all when written.
+    let symbol = eval(r#"(string->symbol "key press")"#).unwrap();
+    assert_eq!(symbol.as_symbol(), Some("key press"));
+    assert!(symbol.is_eqv(&Expr::symbol("key press")));
+    assert_eq!(symbol.write_repr().to_string(), "|key press|");
+}
@@ -0,0 +1,95 @@
+use scheme_engine::{Lexer, TextEdit, Token, TokenCache, TokenKind};
+
+/// Small deterministic generator, so failures can be reproduced.
+struct Edits {
+    state: u64,
+}
+
+impl Edits {
+    fn next(&mut self, bound: usize) -> usize {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.state >> 33) % bound as u64) as usize
+    }
+
+    /// A character boundary in the source, at most `max` bytes past `from`.
+    fn boundary(&mut self, source: &str, from: usize, max: usize) -> usize {
+        let mut pos = (from + self.next(max + 1)).min(source.len());
+        while !source.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        pos.max(from)
+    }
+
+    fn edit(&mut self, source: &str) -> TextEdit {
+        const FRAGMENTS: &[&str] = &[
+            "", "(", ")", " ", "\n", "\"", "\\", ";", "#|", "|#", "#", "u8(", "#\\", "'", "abc",
+            "12", "(f x)",
+        ];
+        let start = self.boundary(source, 0, source.len());
+        let end = self.boundary(source, start, 6);
+        TextEdit {
+            range: start..end,
+            replacement: FRAGMENTS[self.next(FRAGMENTS.len())].to_string(),
+        }
+    }
+}
+
+fn summary(tokens: &[Token]) -> Vec<(TokenKind, usize, usize)> {
+    tokens
+        .iter()
+        .map(|token| (token.kind, token.span.low(), token.span.high()))
+        .collect()
+}
+
+fn lex(source: &str) -> Vec<(TokenKind, usize, usize)> {
+    summary(&Lexer::with_trivia(source).into_iter().collect::<Vec<_>>())
+}
+
+#[test]
+fn test_incremental_matches_full_lex() {
+    let corpus = [
+        include_str!("test_fibonacci.scm"),
+        include_str!("test_number.scm"),
+        include_str!("language/list.scm"),
+        include_str!("language/string_port.scm"),
+        include_str!("language/bytevector.scm"),
+    ];
+
+    let mut edits = Edits { state: 963 };
+    for text in corpus {
+        let mut cache = TokenCache::new(text);
+        for _ in 0..200 {
+            let edit = edits.edit(cache.source());
+            let description = format!("{edit:?}");
+            let tokens = summary(cache.update(edit));
+            assert_eq!(tokens, lex(cache.source()), "after {description}");
+        }
+    }
+}
+
+#[test]
+fn test_edits_in_strings_and_comments() {
+    let mut cache = TokenCache::new("(a \"b\" c) #| d |# e");
+
+    // Opening a string swallows the rest of the source.
+    cache.update(TextEdit {
+        range: 1..1,
+        replacement: "\"".to_string(),
+    });
+    assert_eq!(cache.source(), "(\"a \"b\" c) #| d |# e");
+    assert_eq!(summary(cache.tokens()), lex(cache.source()));
+
+    // Typing inside a comment keeps it one token.
+    let mut cache = TokenCache::new("x #| d |# e");
+    cache.update(TextEdit {
+        range: 5..6,
+        replacement: "long".to_string(),
+    });
+    let tokens = cache.tokens();
+    assert_eq!(tokens[2].kind, TokenKind::BlockComment);
+    assert_eq!(tokens[2].fragment(cache.source()), "#| long |#");
+    assert_eq!(tokens[4].fragment(cache.source()), "e");
+}
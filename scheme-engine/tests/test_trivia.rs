@@ -0,0 +1,65 @@
+//! The tokens of a lexer in trivia mode must cover the source exactly,
+//! so a formatter can reproduce everything it doesn't change.
+use std::fs;
+use std::path::Path;
+
+use scheme_engine::{tokens_to_source, Lexer, Token};
+
+fn assert_round_trip(source: &str) {
+    let tokens = Lexer::with_trivia(source)
+        .into_iter()
+        .collect::<Vec<Token>>();
+
+    // Tokens are contiguous, each starting where the previous ended.
+    let mut pos = 0;
+    for token in &tokens {
+        let range = token.span.as_range();
+        assert_eq!(range.start, pos, "gap before {token:?} in {source:?}");
+        pos = range.end;
+    }
+    assert_eq!(pos, source.len());
+
+    assert_eq!(tokens_to_source(&tokens, source), source);
+}
+
+#[test]
+fn test_snippets() {
+    let snippets = [
+        "",
+        "   ",
+        "; only a comment",
+        "(define x 1) ; trailing\n",
+        "#| block |#(display \"a ; not a comment\")",
+        "'(1 . 2)\t\r\n#\\space #\\( #u8(1 2)",
+        "(λ (x) x) ; ünïcödé\n",
+        "#!fold-case\n(DISPLAY 1)",
+    ];
+    for source in snippets {
+        assert_round_trip(source);
+    }
+}
+
+#[test]
+fn test_language_sources() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/language");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "scm") {
+            assert_round_trip(&fs::read_to_string(&path).unwrap());
+            count += 1;
+        }
+    }
+    assert!(count > 0, "no test sources found");
+}
+
+#[test]
+fn test_parse_ignores_block_comments() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("#| (display 1) |# (+ 1 #| 2 |# 3)", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    assert_eq!(
+        scheme_engine::eval(closure).unwrap(),
+        scheme_engine::Expr::Number(4.0)
+    );
+}
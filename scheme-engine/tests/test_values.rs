@@ -0,0 +1,134 @@
+use scheme_engine::{error::Error, Expr};
+
+fn eval(source: &str) -> Result<Expr, Error> {
+    let env = scheme_engine::new_env()?;
+    let expr = scheme_engine::parse(source, true)?;
+    let closure = scheme_engine::compile(env.clone(), &expr)?;
+    scheme_engine::eval(closure)
+}
+
+#[test]
+fn test_values() {
+    let table = [
+        ("(values 1)", Expr::Number(1.0)),
+        (
+            "(call-with-values (lambda () (values 1 2)) +)",
+            Expr::Number(3.0),
+        ),
+        ("(call-with-values (lambda () 5) -)", Expr::Number(-5.0)),
+        (
+            "(call-with-values (lambda () (values)) (lambda args args))",
+            Expr::Nil,
+        ),
+    ];
+
+    for (source, expected) in table {
+        assert_eq!(eval(source).unwrap(), expected, "{source}");
+    }
+}
+
+#[test]
+fn test_let_values() {
+    let table = [
+        (
+            "(let-values (((a b) (values 1 2)) ((c) (values 3))) (+ (* a 100) (* b 10) c))",
+            Expr::Number(123.0),
+        ),
+        // Extra values are collected into the rest formal.
+        (
+            "(let-values (((x . rest) (values 3 4 5))) (+ x (car rest) (car (cdr rest))))",
+            Expr::Number(12.0),
+        ),
+        ("(let-values (((x . rest) (values 3))) rest)", Expr::Nil),
+        (
+            "(let-values ((all (values 1 2))) (car (cdr all)))",
+            Expr::Number(2.0),
+        ),
+        // The inits don't see the bindings.
+        (
+            "(define a 10) (let-values (((a) (values 1)) ((b) (values a))) b)",
+            Expr::Number(10.0),
+        ),
+    ];
+
+    for (source, expected) in table {
+        assert_eq!(eval(source).unwrap(), expected, "{source}");
+    }
+}
+
+#[test]
+fn test_define_values_in_body() {
+    let source = r"
+    (define (split n)
+      (define-values (q r) (values (quotient n 10) (remainder n 10)))
+      (+ (* r 10) q))
+    (split 47)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(74.0));
+}
+
+#[test]
+fn test_define_values_at_top_level() {
+    let env = scheme_engine::new_env().unwrap();
+    let expr = scheme_engine::parse("(define-values (a b . rest) (values 1 2 3))", true).unwrap();
+    let closure = scheme_engine::compile(env.clone(), &expr).unwrap();
+    assert_eq!(scheme_engine::eval(closure).unwrap(), Expr::Void);
+
+    let env = env.borrow();
+    assert_eq!(env.lookup_var("a"), Some(&Expr::Number(1.0)));
+    assert_eq!(env.lookup_var("b"), Some(&Expr::Number(2.0)));
+    assert_eq!(
+        env.lookup_var("rest"),
+        Some(&Expr::List(vec![Expr::Number(3.0)].into()))
+    );
+}
+
+#[test]
+fn test_value_count_mismatch() {
+    let table = [
+        (
+            "(let-values (((a b) (values 1))) a)",
+            "define-values: expected 2 values for (a b) but got 1",
+        ),
+        (
+            "(let-values (((a b) (values 1 2 3))) a)",
+            "define-values: expected 2 values for (a b) but got 3",
+        ),
+        (
+            "(define-values (a b . rest) (values 1))",
+            "define-values: expected at least 2 values for (a b . rest) but got 1",
+        ),
+        (
+            "(define-values () (values 1))",
+            "define-values: expected 0 values for () but got 1",
+        ),
+    ];
+
+    for (source, expected) in table {
+        let err = eval(source).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{source}");
+    }
+}
+
+#[test]
+fn test_ill_formed_define_values() {
+    let table = [
+        (
+            "(define-values (a))",
+            "ill-formed special form \"define-values\"",
+        ),
+        (
+            "(define-values (a 1) (values 1 2))",
+            "expected identifier in formals list, found number 1",
+        ),
+        (
+            "(if #t (define-values (a) 1))",
+            "definitions are not allowed in expression context: (define-values (a) 1)",
+        ),
+    ];
+
+    for (source, expected) in table {
+        let err = eval(source).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{source}");
+    }
+}
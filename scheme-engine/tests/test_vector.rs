@@ -0,0 +1,159 @@
+use scheme_engine::{error::Error, DatumLabels, Expr};
+
+fn eval(source: &str) -> Result<Expr, Error> {
+    let env = scheme_engine::new_env()?;
+    let expr = scheme_engine::parse(source, true)?;
+    let closure = scheme_engine::compile(env.clone(), &expr)?;
+    scheme_engine::eval(closure)
+}
+
+fn eval_repr(source: &str) -> String {
+    eval(source).unwrap().write_repr().to_string()
+}
+
+#[test]
+fn test_identity() {
+    let vector = Expr::from(vec![Expr::Number(1.0)]);
+    assert_eq!(vector, vector.clone());
+    assert_ne!(vector, Expr::from(vec![Expr::Number(1.0)]));
+    assert!(vector.is_equal(&Expr::from(vec![Expr::Number(1.0)])));
+}
+
+#[test]
+fn test_literal_is_copied() {
+    let source = r"
+    (define fresh (lambda () #(1 2 3)))
+    (vector-set! (fresh) 0 99)
+    (vector-ref (fresh) 0)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Number(1.0));
+}
+
+#[test]
+fn test_overlapping_copy() {
+    // Each copy starts from (0 1 2 3 4 5 6 7 8 9).
+    let table = [
+        // Towards the back, overlapping.
+        ("1 v 0 5", "#(0 0 1 2 3 4 6 7 8 9)"),
+        ("2 v 0 8", "#(0 1 0 1 2 3 4 5 6 7)"),
+        ("5 v 3 8", "#(0 1 2 3 4 3 4 5 6 7)"),
+        // Towards the front, overlapping.
+        ("0 v 1 6", "#(1 2 3 4 5 5 6 7 8 9)"),
+        ("0 v 2", "#(2 3 4 5 6 7 8 9 8 9)"),
+        ("3 v 5 9", "#(0 1 2 5 6 7 8 7 8 9)"),
+        // Onto itself, apart, and empty.
+        ("0 v", "#(0 1 2 3 4 5 6 7 8 9)"),
+        ("4 v 4 7", "#(0 1 2 3 4 5 6 7 8 9)"),
+        ("6 v 0 3", "#(0 1 2 3 4 5 0 1 2 9)"),
+        ("7 v 2 2", "#(0 1 2 3 4 5 6 7 8 9)"),
+        ("10 v 3 3", "#(0 1 2 3 4 5 6 7 8 9)"),
+    ];
+
+    for (args, expected) in table {
+        let source = format!(
+            "(define v (vector 0 1 2 3 4 5 6 7 8 9)) \
+            (vector-copy! v {args}) \
+            v"
+        );
+        assert_eq!(eval_repr(&source), expected, "{args}");
+    }
+
+    // The same as copying through a temporary vector, for every range.
+    for len in 0..=6 {
+        for start in 0..=len {
+            for end in start..=len {
+                for at in 0..=len - (end - start) {
+                    let source = format!(
+                        "(define v (list->vector (vector->list #(0 1 2 3 4 5) 0 {len}))) \
+                        (define temporary (vector-copy v {start} {end})) \
+                        (define expected (vector-copy v)) \
+                        (vector-copy! expected {at} temporary) \
+                        (vector-copy! v {at} v {start} {end}) \
+                        (equal? v expected)"
+                    );
+                    assert_eq!(eval(&source).unwrap(), Expr::Bool(true), "{source}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_cyclic_repr() {
+    let source = "(define v (vector 1 2)) (vector-set! v 1 v) v";
+    assert_eq!(eval_repr(source), "#0=#(1 #0#)");
+
+    // Only values in a cycle get labels, unless sharing is written too.
+    let source = "(define v (vector 1)) (define p (cons v v)) (vector-set! v 0 p) p";
+    let value = eval(source).unwrap();
+    assert_eq!(value.write_repr().to_string(), "#0=(#(#0#) . #(#0#))");
+    assert_eq!(
+        value
+            .write_repr()
+            .with_labels(DatumLabels::Shared)
+            .to_string(),
+        "#0=(#1=#(#0#) . #1#)"
+    );
+
+    let source = "
+    (define a (vector 1))
+    (define b (vector 1))
+    (vector-set! a 0 a)
+    (vector-set! b 0 b)
+    (equal? a b)
+    ";
+    assert_eq!(eval(source).unwrap(), Expr::Bool(true));
+}
+
+#[test]
+fn test_errors() {
+    let table = [
+        (
+            "(vector-ref #(1 2) 2)",
+            "vector-ref: index 2 is out of range for length 2",
+        ),
+        (
+            "(make-vector 1e18 0)",
+            "make-vector: expected index as argument 1, got 1000000000000000000.0",
+        ),
+        (
+            "(vector-set! '(1 2) 0 1)",
+            "vector-set!: expected vector as argument 1, got (1 2)",
+        ),
+        (
+            "(vector-fill! (vector 1 2) 0 2 1)",
+            "vector-fill!: range 2 to 1 is out of bounds for length 2",
+        ),
+        (
+            "(vector-copy #(1 2) 1 3)",
+            "vector-copy: range 1 to 3 is out of bounds for length 2",
+        ),
+        (
+            "(vector-copy! (vector 1 2) 1 #(1 2))",
+            "vector-copy!: range 1 to 3 is out of bounds for length 2",
+        ),
+        (
+            "(vector-copy! (vector 1 2) 0 #(1 2 3) 2 1)",
+            "vector-copy!: range 2 to 1 is out of bounds for length 3",
+        ),
+        (
+            "(vector-map car #(1) '(2))",
+            "vector-map: expected vector as argument 3, got (2)",
+        ),
+        (
+            "(vector-for-each car)",
+            "vector-for-each: wrong number of arguments, expected at least 2 but got 1",
+        ),
+        (
+            "(vector-append #(1) 2)",
+            "vector-append: expected vector as argument 2, got 2",
+        ),
+    ];
+
+    for (source, expected) in table {
+        match eval(source) {
+            Err(err) => assert_eq!(err.to_string(), expected, "{source}"),
+            Ok(value) => panic!("expected error for {source}, found {value:?}"),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Startup file, loaded into the console environment before the first prompt.
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use scheme_engine::{error::Error, Env, Handle};
+
+/// Environment variable naming the init file, instead of the default location.
+pub const INIT_VAR: &str = "SCHEME_INIT";
+
+/// What happened to the init file.
+#[derive(Debug)]
+pub enum InitReport {
+    /// No init file was named, and there's none at the default location.
+    NotFound,
+    /// The named init file doesn't exist.
+    Missing(PathBuf),
+    /// The init file was evaluated.
+    Loaded(PathBuf),
+    /// The init file failed to read, compile or evaluate. The definitions
+    /// made before the error are kept.
+    Failed { path: PathBuf, error: Error },
+}
+
+impl InitReport {
+    /// A line telling the user about the init file, if there's anything to tell.
+    pub fn notice(&self, env: &Env) -> Option<String> {
+        match self {
+            Self::NotFound => None,
+            Self::Missing(path) => Some(format!("init file not found: {}", path.display())),
+            Self::Loaded(path) => Some(format!("loaded {}", path.display())),
+            Self::Failed { path, error } => Some(format!(
+                "error in init file {}: {}",
+                path.display(),
+                env.sources().render_error(error)
+            )),
+        }
+    }
+}
+
+/// Evaluate the init file into the environment.
+///
+/// The file is the explicit path if given, otherwise the one named by
+/// `$SCHEME_INIT`, otherwise `init.scm` in the `scheme` directory of the
+/// user's configuration directory, like `~/.config/scheme/init.scm`.
+pub fn load_init_file(env: &Handle<Env>, explicit_path: Option<&Path>) -> InitReport {
+    let named = explicit_path
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(INIT_VAR).map(PathBuf::from));
+    let path = match named {
+        Some(path) if !path.exists() => return InitReport::Missing(path),
+        Some(path) => path,
+        None => match default_init_path(|name| std::env::var_os(name)) {
+            Some(path) if path.exists() => path,
+            _ => return InitReport::NotFound,
+        },
+    };
+
+    match crate::load_file(env, &path.to_string_lossy()) {
+        Ok(()) => InitReport::Loaded(path),
+        Err(error) => InitReport::Failed { path, error },
+    }
+}
+
+/// The default location of the init file, under `$XDG_CONFIG_HOME`,
+/// or `~/.config` without it.
+fn default_init_path(var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let config_dir = var("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("scheme").join("init.scm"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, process};
+
+    use scheme_engine::Expr;
+
+    use super::*;
+
+    /// A file in the temporary directory, removed again when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn with_contents(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("scheme-{}-{name}", process::id()));
+            fs::write(&path, contents).expect("write temporary file");
+            Self(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_load_init_file() {
+        let env = crate::new_script_env().unwrap();
+        let init = TempPath::with_contents("init.scm", "(define (square x) (* x x))");
+
+        let report = load_init_file(&env, Some(&init.0));
+        assert!(matches!(&report, InitReport::Loaded(path) if *path == init.0));
+
+        let program = scheme_engine::parse_program("(square 4)").unwrap();
+        let closure = scheme_engine::compile(env.clone(), &program).unwrap();
+        assert_eq!(scheme_engine::eval(closure).unwrap(), Expr::Number(16.0));
+    }
+
+    #[test]
+    fn test_broken_init_file() {
+        let env = crate::new_script_env().unwrap();
+        let init = TempPath::with_contents("broken-init.scm", "(define kept 1)\n(car '())");
+
+        let report = load_init_file(&env, Some(&init.0));
+        match &report {
+            InitReport::Failed { path, error } => {
+                assert_eq!(*path, init.0);
+                assert!(error.to_string().contains("car"), "{error}");
+            }
+            other => panic!("expected failure, found {other:?}"),
+        }
+        let notice = report.notice(&env.borrow()).unwrap();
+        assert!(notice.starts_with("error in init file"), "{notice}");
+
+        // Definitions before the error are kept.
+        assert_eq!(env.borrow().lookup_var("kept"), Some(&Expr::Number(1.0)));
+    }
+
+    #[test]
+    fn test_missing_init_file() {
+        let env = crate::new_script_env().unwrap();
+        let path = std::env::temp_dir().join(format!("scheme-{}-missing.scm", process::id()));
+
+        let report = load_init_file(&env, Some(&path));
+        assert!(matches!(&report, InitReport::Missing(missing) if *missing == path));
+        assert!(report.notice(&env.borrow()).is_some());
+        assert!(InitReport::NotFound.notice(&env.borrow()).is_none());
+    }
+
+    #[test]
+    fn test_default_init_path() {
+        let table = [
+            (
+                Some("/config"),
+                Some("/home/user"),
+                Some("/config/scheme/init.scm"),
+            ),
+            (
+                Some(""),
+                Some("/home/user"),
+                Some("/home/user/.config/scheme/init.scm"),
+            ),
+            (
+                None,
+                Some("/home/user"),
+                Some("/home/user/.config/scheme/init.scm"),
+            ),
+            (None, None, None),
+        ];
+
+        for (config_home, home, expected) in table {
+            let var = |name: &str| match name {
+                "XDG_CONFIG_HOME" => config_home.map(OsString::from),
+                "HOME" => home.map(OsString::from),
+                _ => None,
+            };
+            assert_eq!(
+                default_init_path(var),
+                expected.map(PathBuf::from),
+                "{config_home:?} {home:?}"
+            );
+        }
+    }
+}
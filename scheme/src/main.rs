@@ -1,86 +1,271 @@
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+use std::path::Path;
 use std::{env, fs};
 
-use scheme_engine::{self, Expr};
+use scheme_engine::{self, error::Error, CompileOptions, Env, FormatOptions, Handle, Redefinition};
+
+use self::init::load_init_file;
+use self::repl::Repl;
+
+mod init;
+mod repl;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let args: Vec<&str> = args.iter().skip(1).map(String::as_str).collect();
+
+    match args[..] {
+        ["--fmt", ..] => run_fmt(&args[1..]),
+        [] => run_repl(InitFile::Default),
+        ["--no-init"] => run_repl(InitFile::Skip),
+        ["--init", path] => run_repl(InitFile::Path(Path::new(path))),
+        _ => run_files(&args),
+    }
+}
+
+/// Which init file the console loads before the first prompt.
+enum InitFile<'a> {
+    /// The one named by `$SCHEME_INIT`, or the one at the default location.
+    Default,
+    Path(&'a Path),
+    Skip,
+}
+
+/// Evaluate each file in order, into one global environment, so a
+/// prelude can define procedures for the files after it.
+fn run_files(file_paths: &[&str]) {
+    // Global environment
+    let env = new_script_env().expect("failed creating new core environment");
+
+    for file_path in file_paths {
+        match load_file(&env, file_path) {
+            Ok(()) => {}
+            Err(Error::Io(err)) => {
+                eprintln!("failed to open file: {err}");
+                return;
+            }
+            Err(err @ Error::InSource { .. }) => {
+                eprintln!("error: {}", env.borrow().sources().render_error(&err));
+                std::process::exit(1);
+            }
+            Err(err) => {
+                report_error(&err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Read, compile and evaluate a file into the environment, printing
+/// warnings about redefinitions.
+///
+/// The file's source is registered with the environment, so parse
+/// errors can be rendered with their location.
+fn load_file(env: &Handle<Env>, file_path: &str) -> Result<(), Error> {
+    let script = fs::read_to_string(file_path)?;
+    let expr = scheme_engine::parse_program_named(
+        env.clone().borrow_mut().sources_mut(),
+        file_path,
+        script,
+    )?;
 
-    match args.get(1) {
-        Some(file_path) => run_file(file_path),
-        None => run_repl(),
+    let options = CompileOptions {
+        redefinition: Redefinition::Warn,
+        source_name: Some(file_path.to_string()),
+        ..CompileOptions::default()
+    };
+    let (closure, warnings) = scheme_engine::compile_with_warnings(env.clone(), &expr, &options)?;
+    for warning in warnings {
+        eprintln!("warning: {warning}");
     }
+
+    scheme_engine::eval(closure)?;
+    Ok(())
 }
 
-fn run_file(file_path: &str) {
-    match fs::read_to_string(file_path) {
-        Ok(script) => {
-            // Global environment
-            let env = scheme_engine::new_env().expect("failed creating new core environment");
+/// Format each file in place, or with `--check` print how each would change.
+///
+/// Without files, standard input is formatted to standard output. Exits with
+/// an error status when a file fails to parse, or is unformatted under `--check`.
+fn run_fmt(args: &[&str]) {
+    let check = args.contains(&"--check");
+    let file_paths: Vec<&str> = args
+        .iter()
+        .copied()
+        .filter(|arg| *arg != "--check")
+        .collect();
 
-            let expr =
-                scheme_engine::parse(script.as_str(), true).expect("failed to parse program");
+    if file_paths.is_empty() {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).expect("read stdin");
+        match scheme_engine::format_source(&source, FormatOptions::default()) {
+            Ok(formatted) if check && formatted != source => {
+                print_diff("<stdin>", &source, &formatted);
+                std::process::exit(1);
+            }
+            Ok(_) if check => {}
+            Ok(formatted) => print!("{formatted}"),
+            Err(err) => {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-            let closure =
-                scheme_engine::compile(env.clone(), &expr).expect("failed to compile program");
+    let mut failed = false;
+    for file_path in file_paths {
+        let source = match fs::read_to_string(file_path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("failed to open file: {err}");
+                failed = true;
+                continue;
+            }
+        };
+        let formatted = match scheme_engine::format_source(&source, FormatOptions::default()) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                eprintln!("error: {file_path}: {err}");
+                failed = true;
+                continue;
+            }
+        };
 
-            let _value0 = scheme_engine::eval(closure).expect("runtime error");
+        if formatted == source {
+            continue;
         }
-        Err(err) => {
-            eprintln!("failed to open file: {err}");
+        if check {
+            print_diff(file_path, &source, &formatted);
+            failed = true;
+        } else if let Err(err) = fs::write(file_path, formatted) {
+            eprintln!("failed to write file: {err}");
+            failed = true;
         }
     }
+
+    if failed {
+        std::process::exit(1);
+    }
 }
 
-fn run_repl() {
+/// Print the lines removed and added by formatting, after the line numbers they start at.
+fn print_diff(file_path: &str, source: &str, formatted: &str) {
+    let old: Vec<&str> = source.lines().collect();
+    let new: Vec<&str> = formatted.lines().collect();
+
+    // Length of the longest common subsequence of the lines after each pair of positions.
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    println!("--- {file_path}");
+    println!("+++ {file_path} (formatted)");
+    let (mut i, mut j) = (0, 0);
+    let mut in_change = false;
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            in_change = false;
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if !in_change {
+            println!("@@ line {} @@", i + 1);
+            in_change = true;
+        }
+        if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            println!("-{}", old[i]);
+            i += 1;
+        } else {
+            println!("+{}", new[j]);
+            j += 1;
+        }
+    }
+}
+
+/// Run the console, after loading the init file into its environment.
+///
+/// An init file that fails to load is reported, and the console starts anyway.
+/// The console exits at the end of its input.
+fn run_repl(init_file: InitFile) {
     let mut buf = String::new();
     let stdin = io::stdin();
     let mut count = 0;
 
-    // Console environment.
-    let env = scheme_engine::new_env().expect("failed creating new core environment");
+    let mut repl = Repl::new().expect("failed creating new core environment");
+
+    let report = match init_file {
+        InitFile::Default => Some(load_init_file(repl.env(), None)),
+        InitFile::Path(path) => Some(load_init_file(repl.env(), Some(path))),
+        InitFile::Skip => None,
+    };
+    if let Some(notice) = report.and_then(|report| report.notice(&repl.env().borrow())) {
+        println!("{notice}");
+    }
 
     loop {
-        count += 1;
-        buf.clear();
-        print!("{count} > ");
+        // Unfinished input is kept, and the next line continues it.
+        if buf.is_empty() {
+            count += 1;
+            print!("{count} > ");
+        } else {
+            print!("{} ", ".".repeat(count.to_string().len() + 2));
+        }
         let _ = io::stdout().flush();
-        stdin.read_line(&mut buf).expect("read stdin");
-
-        match scheme_engine::parse(buf.as_str(), true) {
-            Ok(expr) => {
-                println!("parse:\n\t{:#?}", expr);
-
-                match scheme_engine::compile(env.clone(), &expr) {
-                    Ok(closure) => {
-                        println!("bytecode:");
-                        for (index, op) in
-                            closure.borrow().procedure().bytecode().iter().enumerate()
-                        {
-                            println!("  {index:>6} : {op:?}");
-                        }
-
-                        // Run closure in VM
-                        match scheme_engine::eval(closure) {
-                            Ok(Expr::Void) => {
-                                // Don't print a #!void, it's the "nothing" value
-                            }
-                            Ok(value) => {
-                                println!("{}", value.repr());
-                            }
-                            Err(err) => {
-                                eprintln!("error: {err}");
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        eprintln!("error: {err}");
-                    }
-                }
-            }
-            Err(err) => {
-                eprintln!("error: {err}");
+        if stdin.read_line(&mut buf).expect("read stdin") == 0 {
+            // End of input, which leaves any unfinished expression unfinished.
+            if let Err(err) = repl.run_line(&buf) {
+                report_error(&err);
             }
+            println!();
+            break;
+        }
+
+        match repl.run_line(&buf) {
+            Ok(Some(text)) => println!("{text}"),
+            Ok(None) => {}
+            Err(Error::Incomplete { .. }) => continue,
+            Err(err) => report_error(&err),
+        }
+        buf.clear();
+    }
+}
+
+/// The environment for scripts and the console, which may access files.
+fn new_script_env() -> Result<Handle<Env>, Error> {
+    let mut env = scheme_engine::new_env()?;
+    scheme_engine::init_file_io(&mut env.borrow_mut())?;
+    Ok(env)
+}
+
+/// Print an error, including the machine state captured by runtime errors.
+fn report_error(err: &Error) {
+    eprintln!("error: {err}");
+
+    if let Error::Runtime {
+        stack_preview,
+        trace,
+        ..
+    } = err
+    {
+        eprintln!("evaluation stack");
+        eprintln!("---");
+        for value in stack_preview {
+            eprintln!("  {value}");
+        }
+        eprintln!("---");
+
+        eprintln!("trace");
+        for frame in trace {
+            eprintln!("  {frame}");
         }
     }
 }
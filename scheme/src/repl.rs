@@ -0,0 +1,486 @@
+//! Interactive prompt.
+use scheme_engine::{
+    self, error::Error, Closure, CompileOptions, Env, Expr, FormatOptions, Handle, StepControl,
+};
+
+/// Default number of nested list levels printed for a result.
+const PRINT_DEPTH: usize = 8;
+
+/// Default number of elements printed per list in a result.
+const PRINT_LENGTH: usize = 64;
+
+/// State of a console session, kept between the lines typed at the prompt.
+pub struct Repl {
+    /// Console environment.
+    env: Handle<Env>,
+
+    /// Print the parse tree and bytecode of each line before evaluating it.
+    pub verbose: bool,
+
+    /// How many levels of nested lists are printed in results.
+    pub print_depth: usize,
+
+    /// How many elements of each list are printed in results.
+    pub print_length: usize,
+
+    /// How many digits after the decimal point are printed in results,
+    /// or as many as it takes to read the number back without a limit.
+    pub print_precision: Option<usize>,
+
+    /// The program being expanded by `,expand-step`, as of its last step.
+    expand_step: Option<Expr>,
+}
+
+impl Repl {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            env: crate::new_script_env()?,
+            verbose: true,
+            print_depth: PRINT_DEPTH,
+            print_length: PRINT_LENGTH,
+            print_precision: None,
+            expand_step: None,
+        })
+    }
+
+    /// The console environment.
+    pub fn env(&self) -> &Handle<Env> {
+        &self.env
+    }
+
+    /// Run a line of input, returning the text to print for its result.
+    ///
+    /// Lines starting with a comma are meta-commands:
+    ///
+    /// - `,step <expr>` single-steps the expression, printing each instruction.
+    /// - `,set print-depth <n>` and `,set print-length <n>` limit how much
+    ///   of large results is printed.
+    /// - `,set print-precision <n>` rounds numbers in results to `n` digits
+    ///   after the decimal point, and `,set print-precision off` stops it.
+    /// - `,env` lists the variables defined at the prompt, sorted by name,
+    ///   and `,env all` lists every variable, including the core library.
+    /// - `,profile <expr>` evaluates the expression, then prints the calls
+    ///   and instructions executed per procedure.
+    /// - `,expand <expr>` prints the expression with its derived forms
+    ///   expanded into core forms, without evaluating it.
+    /// - `,expand-step <expr>` prints the expression with only its first
+    ///   derived form rewritten, and `,expand-step` on its own takes the
+    ///   next step from there.
+    ///
+    /// Input that ends inside an expression fails with [`Error::Incomplete`],
+    /// and can be run again once the next line is appended.
+    pub fn run_line(&mut self, line: &str) -> Result<Option<String>, Error> {
+        if let Some(rest) = line.strip_prefix(",set") {
+            self.set(rest)?;
+            return Ok(None);
+        }
+
+        if let Some(rest) = line.strip_prefix(",env") {
+            return self.list_vars(rest.trim());
+        }
+
+        if let Some(rest) = line.strip_prefix(",profile") {
+            return self.profile(rest);
+        }
+
+        if let Some(rest) = line.strip_prefix(",expand-step") {
+            return self.expand_step(rest);
+        }
+
+        if let Some(rest) = line.strip_prefix(",expand") {
+            return self.expand(rest);
+        }
+
+        // Single-step the expression, printing each instruction.
+        let (stepping, source) = match line.strip_prefix(",step") {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        // Blank lines and comments have nothing to run, and print nothing.
+        let expr = scheme_engine::parse(source, true)?;
+        if matches!(&expr, Expr::Sequence(forms) if forms.is_empty()) {
+            return Ok(None);
+        }
+
+        if stepping {
+            self.env.borrow_mut().set_step_hook(|event| {
+                println!(
+                    "step: {:>2} {:>6} : {}",
+                    event.depth(),
+                    event.pc(),
+                    event.instruction()
+                );
+                StepControl::Step
+            });
+        }
+
+        let result = self.eval(&expr);
+
+        if stepping {
+            self.env.borrow_mut().clear_step_hook();
+        }
+
+        match result? {
+            // Don't print a #!void, it's the "nothing" value
+            Expr::Void => Ok(None),
+            value => Ok(Some(self.print_value(&value))),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Expr, Error> {
+        let closure = self.compile(expr)?;
+        scheme_engine::eval(closure)
+    }
+
+    fn compile(&mut self, expr: &Expr) -> Result<Handle<Closure>, Error> {
+        if self.verbose {
+            println!("parse:\n\t{:#?}", expr);
+        }
+
+        let (closure, warnings) = scheme_engine::compile_with_warnings(
+            self.env.clone(),
+            expr,
+            &CompileOptions::default(),
+        )?;
+        for warning in warnings {
+            eprintln!("warning: {warning}");
+        }
+        if self.verbose {
+            println!("bytecode:");
+            print!(
+                "{}",
+                scheme_engine::disassemble(closure.borrow().procedure(), Some(&self.env.borrow()))
+            );
+        }
+
+        Ok(closure)
+    }
+
+    /// Evaluate the expression, returning a table of the procedures it
+    /// called followed by its result.
+    fn profile(&mut self, source: &str) -> Result<Option<String>, Error> {
+        let closure = self.compile(&scheme_engine::parse(source, true)?)?;
+        let (value, profile) = scheme_engine::eval_with_profile(closure)?;
+
+        let mut lines = vec![format!(
+            "{:<24} {:>10} {:>14}",
+            "procedure", "calls", "instructions"
+        )];
+        for entry in profile {
+            lines.push(format!(
+                "{:<24} {:>10} {:>14}",
+                entry.name, entry.calls, entry.instructions
+            ));
+        }
+        if value != Expr::Void {
+            lines.push(self.print_value(&value));
+        }
+        Ok(Some(lines.join("\n")))
+    }
+
+    /// The top-level forms of the source after expansion, one per line.
+    fn expand(&self, source: &str) -> Result<Option<String>, Error> {
+        let expr = scheme_engine::parse(source, true)?;
+        let expanded = scheme_engine::expand(&self.env.borrow(), &expr)?;
+
+        let forms = expanded.as_sequence().unwrap_or_default();
+        let lines: Vec<String> = forms
+            .iter()
+            .map(|form| {
+                form.write_repr()
+                    .with_limits(self.print_depth, self.print_length)
+                    .to_string()
+            })
+            .collect();
+        Ok(Some(lines.join("\n")))
+    }
+
+    /// The source, or the program of the last `,expand-step` when there's
+    /// none, after one more step of expansion, formatted.
+    fn expand_step(&mut self, source: &str) -> Result<Option<String>, Error> {
+        let expr = match (source.trim(), self.expand_step.take()) {
+            ("", Some(expr)) => expr,
+            ("", None) => return Err(Error::Reason("expected ,expand-step <expr>".to_string())),
+            (source, _) => scheme_engine::parse(source, true)?,
+        };
+
+        let Some(step) = scheme_engine::expand_once(&self.env.borrow(), &expr)? else {
+            return Ok(Some("no derived forms left to expand".to_string()));
+        };
+        let forms = step.as_sequence().unwrap_or_default();
+        let text: Vec<String> = forms
+            .iter()
+            .map(|form| form.write_repr().to_string())
+            .collect();
+        let formatted = scheme_engine::format_source(&text.join("\n"), FormatOptions::default())?;
+        self.expand_step = Some(step);
+        Ok(Some(formatted.trim_end().to_string()))
+    }
+
+    /// A result value, abbreviated to the print limits.
+    pub fn print_value(&self, value: &Expr) -> String {
+        let repr = value
+            .repr()
+            .with_limits(self.print_depth, self.print_length);
+        match self.print_precision {
+            Some(digits) => repr.with_precision(digits).to_string(),
+            None => repr.to_string(),
+        }
+    }
+
+    /// The variables of the console environment, one per line.
+    fn list_vars(&self, which: &str) -> Result<Option<String>, Error> {
+        let env = self.env.borrow();
+        let vars: Vec<(&str, &Expr)> = match which {
+            "" => env.iter_user_vars().collect(),
+            "all" => env.iter_vars_sorted().collect(),
+            _ => return Err(Error::Reason("expected ,env or ,env all".to_string())),
+        };
+
+        let lines: Vec<String> = vars
+            .into_iter()
+            .map(|(name, value)| format!("{name} = {}", self.print_value(value)))
+            .collect();
+        Ok((!lines.is_empty()).then(|| lines.join("\n")))
+    }
+
+    /// Change a setting, given as `<name> <value>`.
+    fn set(&mut self, setting: &str) -> Result<(), Error> {
+        let (name, value) = match setting.split_whitespace().collect::<Vec<_>>()[..] {
+            [name, value] => (name, value),
+            _ => return Err(Error::Reason("expected ,set <name> <value>".to_string())),
+        };
+
+        if (name, value) == ("print-precision", "off") {
+            self.print_precision = None;
+            return Ok(());
+        }
+
+        let limit = value
+            .parse::<usize>()
+            .map_err(|_| Error::Reason(format!("{name}: expected a count, got {value}")))?;
+
+        match name {
+            "print-depth" => self.print_depth = limit,
+            "print-length" => self.print_length = limit,
+            "print-precision" => self.print_precision = Some(limit),
+            _ => return Err(Error::Reason(format!("unknown setting: {name}"))),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn quiet_repl() -> Repl {
+        let mut repl = Repl::new().expect("create repl");
+        repl.verbose = false;
+        repl
+    }
+
+    #[test]
+    fn test_print_length_setting() {
+        let mut repl = quiet_repl();
+        let source =
+            "(define range (lambda (n) (if (= n 0) '() (cons n (range (- n 1)))))) (range 100)";
+
+        let value = repl.run_line(source).unwrap().unwrap();
+        assert!(value.ends_with("37 ...)"), "{value}");
+
+        repl.run_line(",set print-length 3").unwrap();
+        assert_eq!(repl.print_length, 3);
+        assert_eq!(
+            repl.run_line("(range 100)").unwrap().unwrap(),
+            "(100 99 98 ...)"
+        );
+    }
+
+    #[test]
+    fn test_print_depth_setting() {
+        let mut repl = quiet_repl();
+        repl.run_line(",set print-depth 2").unwrap();
+        assert_eq!(
+            repl.run_line("'(1 (2 (3 (4))))").unwrap().unwrap(),
+            "(1 (2 #))"
+        );
+    }
+
+    #[test]
+    fn test_print_precision_setting() {
+        let mut repl = quiet_repl();
+        repl.run_line(",set print-precision 3").unwrap();
+        assert_eq!(repl.run_line("(/ 2 3)").unwrap().unwrap(), "0.667");
+        assert_eq!(repl.run_line("(/ 1.0 0)").unwrap().unwrap(), "+inf.0");
+
+        repl.run_line(",set print-precision off").unwrap();
+        assert_eq!(
+            repl.run_line("(/ 2 3)").unwrap().unwrap(),
+            "0.6666666666666666"
+        );
+    }
+
+    #[test]
+    fn test_incomplete_line() {
+        let mut repl = quiet_repl();
+        assert!(matches!(
+            repl.run_line("(+ 1\n"),
+            Err(Error::Incomplete { .. })
+        ));
+        assert_eq!(repl.run_line("(+ 1\n  2)\n").unwrap().unwrap(), "3");
+
+        // A malformed line is an error right away.
+        assert!(matches!(repl.run_line("(+ 1))\n"), Err(Error::Reason(_))));
+    }
+
+    #[test]
+    fn test_env_listing() {
+        let mut repl = quiet_repl();
+        assert_eq!(repl.run_line(",env").unwrap(), None);
+
+        repl.run_line("(define c 3) (define a 1) (define b '(2))")
+            .unwrap();
+        assert_eq!(
+            repl.run_line(",env").unwrap().unwrap(),
+            "a = 1\nb = (2)\nc = 3"
+        );
+
+        let all = repl.run_line(",env all").unwrap().unwrap();
+        assert!(all.contains("car = <native-function>"), "{all}");
+        assert!(all.contains("c = 3"), "{all}");
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut repl = quiet_repl();
+        repl.run_line("(define square (lambda (x) (* x x)))")
+            .unwrap();
+
+        let table = repl
+            .run_line(",profile (square (square 3))")
+            .unwrap()
+            .unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("procedure"), "{table}");
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("square")
+                    && line.split_whitespace().nth(1) == Some("2")),
+            "{table}"
+        );
+        assert_eq!(lines.last(), Some(&"81"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut repl = quiet_repl();
+        assert_eq!(
+            repl.run_line(",expand (define (f x) (let ((y 'a)) y)) (f 1)")
+                .unwrap()
+                .unwrap(),
+            "(define f (lambda (x) ((lambda (y) y) 'a)))\n(f 1)"
+        );
+
+        // Nothing is evaluated.
+        assert!(repl.run_line("f").is_err());
+    }
+
+    #[test]
+    fn test_expand_step() {
+        let mut repl = quiet_repl();
+        let steps = [
+            (
+                ",expand-step (define (f) (let* ((a 1)) a)) (f)",
+                "(define f (lambda () (let* ((a 1)) a)))\n\n(f)",
+            ),
+            (
+                ",expand-step",
+                "(define f (lambda () (let ((a 1)) a)))\n\n(f)",
+            ),
+            (
+                ",expand-step",
+                "(define f (lambda () ((lambda (a) a) 1)))\n\n(f)",
+            ),
+            (",expand-step", "no derived forms left to expand"),
+        ];
+        for (line, expected) in steps {
+            assert_eq!(repl.run_line(line).unwrap().unwrap(), expected, "{line}");
+        }
+
+        // A finished expansion isn't remembered.
+        assert!(repl.run_line(",expand-step").is_err());
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let mut repl = quiet_repl();
+        let table = [
+            (",set print-width 3", "unknown setting: print-width"),
+            (
+                ",set print-length many",
+                "print-length: expected a count, got many",
+            ),
+            (",set print-length", "expected ,set <name> <value>"),
+        ];
+        for (line, expected) in table {
+            match repl.run_line(line) {
+                Err(err) => assert_eq!(err.to_string(), expected, "{line}"),
+                Ok(_) => panic!("expected error for {line}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_interactive_input() {
+        enum Outcome {
+            Nothing,
+            Value(&'static str),
+            Continue,
+            Report(&'static str),
+        }
+        use Outcome::*;
+
+        let mut repl = Repl::new().expect("create repl");
+        // Blank lines print nothing, even with the parse and bytecode output on.
+        assert!(repl.verbose);
+        assert!(matches!(repl.run_line("\n"), Ok(None)));
+        repl.verbose = false;
+
+        let table = [
+            ("", Nothing),
+            ("   \n", Nothing),
+            ("; comment\n", Nothing),
+            ("#| block |# ; and line\n", Nothing),
+            (",step ; nothing to step\n", Nothing),
+            ("(define x 1) ; comment\n", Nothing),
+            ("x ; comment\n", Value("1")),
+            ("'\n", Continue),
+            ("(+ x\n", Continue),
+            ("(+ x\n 2)\n", Value("3")),
+            ("#| unclosed\n", Continue),
+            (")\n", Report("expected expression but found ')' at 1:1")),
+            (
+                "`\n",
+                Report("expected expression but found atom \"`\" at 1:1"),
+            ),
+            (
+                "(car '())\n",
+                Report("car: expected pair as argument 1, got ()"),
+            ),
+        ];
+        for (line, expected) in table {
+            match (repl.run_line(line), expected) {
+                (Ok(None), Nothing) => {}
+                (Ok(Some(value)), Value(expected)) => assert_eq!(value, expected, "{line:?}"),
+                (Err(Error::Incomplete { .. }), Continue) => {}
+                (Err(err), Report(expected)) if !matches!(err, Error::Incomplete { .. }) => {
+                    assert_eq!(err.to_string(), expected, "{line:?}")
+                }
+                (other, _) => panic!("unexpected outcome for {line:?}: {other:?}"),
+            }
+        }
+    }
+}